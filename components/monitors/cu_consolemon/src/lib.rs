@@ -3,11 +3,14 @@ pub mod sysinfo;
 use ansi_to_tui::IntoText;
 use color_eyre::config::HookBuilder;
 use compact_str::{CompactString, ToCompactString};
-use cu29::clock::{CuDuration, RobotClock};
+use cu29::bincode::config::standard;
+use cu29::bincode::decode_from_std_read;
+use cu29::clock::{CuDuration, CuTime, RobotClock};
 use cu29::config::{CuConfig, Node};
+use cu29::copperlist::CopperList;
 use cu29::cutask::CuMsgMetadata;
 use cu29::monitoring::{CuDurationStatistics, CuMonitor, CuTaskState, Decision};
-use cu29::{CuError, CuResult};
+use cu29::{CopperListTuple, CuError, CuResult};
 use gag::Gag;
 use ratatui::backend::CrosstermBackend;
 use ratatui::buffer::Buffer;
@@ -23,10 +26,11 @@ use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Text};
 use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, StatefulWidget, Table};
 use ratatui::{Frame, Terminal};
-use std::fmt::{Display, Formatter};
-use std::io::stdout;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{stdout, Read};
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{io, thread};
@@ -38,8 +42,39 @@ enum Screen {
     Neofetch,
     Dag,
     Latency,
+    Errors,
+    Inspector,
 }
 
+/// How many process_error events are kept for the error screen.
+const MAX_ERROR_LOG: usize = 200;
+
+/// No message is inspected.
+const NOT_INSPECTED: usize = usize::MAX;
+
+#[derive(Clone)]
+struct ErrorEvent {
+    time: CuTime,
+    taskid: usize,
+    step: CuTaskState,
+    error: CompactString,
+}
+
+/// The last payload of the inspected message, formatted on the runtime side.
+#[derive(Default)]
+struct Inspected {
+    payload: Option<String>,
+    copperlists: u64,
+}
+
+/// The shared state between the monitor and the UI to inspect the messages.
+struct Inspection {
+    /// The index of the message in the copperlist, NOT_INSPECTED if none.
+    selected: AtomicUsize,
+    inspected: Mutex<Inspected>,
+}
+
+#[derive(Clone)]
 struct TaskStats {
     stats: Vec<CuDurationStatistics>,
     end2end: CuDurationStatistics,
@@ -130,6 +165,8 @@ struct NodesScrollableWidgetState {
     node_types: Vec<NodeType>,
     connections: Vec<Connection>,
     statuses: Arc<Mutex<Vec<TaskStatus>>>,
+    /// The statuses displayed while the view is frozen.
+    frozen_statuses: Option<Vec<TaskStatus>>,
     nodes_scrollable_state: ScrollViewState,
 }
 
@@ -168,6 +205,7 @@ impl NodesScrollableWidgetState {
             connections,
             nodes_scrollable_state: ScrollViewState::default(),
             statuses: errors,
+            frozen_statuses: None,
         }
     }
 }
@@ -227,7 +265,9 @@ impl StatefulWidget for NodesScrollableWidget<'_> {
         let zones = graph.split(scroll_view.area());
 
         {
-            let mut statuses = state.statuses.lock().unwrap();
+            let mut live_statuses = state.statuses.lock().unwrap();
+            let frozen = state.frozen_statuses.is_some();
+            let statuses = state.frozen_statuses.as_mut().unwrap_or(&mut live_statuses);
             for (idx, ea_zone) in zones.into_iter().enumerate() {
                 let s = state.config_nodes[idx].get_type();
                 let status = &mut statuses[idx];
@@ -249,7 +289,9 @@ impl StatefulWidget for NodesScrollableWidget<'_> {
                 } else {
                     paragraph.green()
                 };
-                status.is_error = false; // reset if it was displayed
+                if !frozen {
+                    status.is_error = false; // reset if it was displayed
+                }
                 scroll_view.render_widget(paragraph, ea_zone);
             }
         }
@@ -271,17 +313,40 @@ impl StatefulWidget for NodesScrollableWidget<'_> {
 pub struct CuConsoleMon {
     config: CuConfig,
     taskids: &'static [&'static str],
+    clock: RobotClock,
     task_stats: Arc<Mutex<TaskStats>>,
     task_statuses: Arc<Mutex<Vec<TaskStatus>>>,
+    error_log: Arc<Mutex<VecDeque<ErrorEvent>>>,
+    inspection: Arc<Inspection>,
+    /// When frozen, the display keeps the last state while the stats, statuses and errors keep being recorded.
+    frozen: Arc<AtomicBool>,
     quitting: Arc<AtomicBool>,
 }
 
+/// A connection between 2 tasks as shown by the inspector.
+struct InspectedConnection {
+    src: String,
+    dst: String,
+    msg: String,
+    /// The index of the message of src in the copperlist.
+    msg_index: Option<usize>,
+}
+
 struct UI {
     task_ids: &'static [&'static str],
     active_screen: Screen,
     sysinfo: String,
     task_stats: Arc<Mutex<TaskStats>>,
+    /// The latencies displayed while the view is frozen.
+    frozen_task_stats: Option<TaskStats>,
     nodes_scrollable_widget_state: NodesScrollableWidgetState,
+    error_log: Arc<Mutex<VecDeque<ErrorEvent>>>,
+    /// The errors displayed while the view is frozen.
+    frozen_error_log: Option<VecDeque<ErrorEvent>>,
+    connections: Vec<InspectedConnection>,
+    selected_connection: usize,
+    inspection: Arc<Inspection>,
+    frozen: Arc<AtomicBool>,
 }
 
 impl UI {
@@ -290,19 +355,156 @@ impl UI {
         task_ids: &'static [&'static str],
         task_stats: Arc<Mutex<TaskStats>>,
         task_statuses: Arc<Mutex<Vec<TaskStatus>>>,
+        error_log: Arc<Mutex<VecDeque<ErrorEvent>>>,
+        inspection: Arc<Inspection>,
+        frozen: Arc<AtomicBool>,
     ) -> UI {
         init_error_hooks();
         let nodes_scrollable_widget_state =
             NodesScrollableWidgetState::new(&config, task_statuses.clone());
+        let connections = config
+            .graph
+            .edge_indices()
+            .filter_map(|edge| {
+                let (src, dst) = config.graph.edge_endpoints(edge)?;
+                let src = config.graph[src].get_id();
+                Some(InspectedConnection {
+                    msg_index: task_ids.iter().position(|id| *id == src),
+                    src,
+                    dst: config.graph[dst].get_id(),
+                    msg: config.graph[edge].msg.clone(),
+                })
+            })
+            .collect();
         Self {
             task_ids,
             active_screen: Screen::Neofetch,
             sysinfo: sysinfo::pfetch_info(),
             task_stats,
+            frozen_task_stats: None,
             nodes_scrollable_widget_state,
+            error_log,
+            frozen_error_log: None,
+            connections,
+            selected_connection: 0,
+            inspection,
+            frozen,
+        }
+    }
+
+    /// Tells the monitor which message to format for the inspector.
+    fn select_connection(&mut self, index: usize) {
+        self.selected_connection = index;
+        let msg_index = match self.active_screen {
+            Screen::Inspector => self
+                .connections
+                .get(index)
+                .and_then(|c| c.msg_index)
+                .unwrap_or(NOT_INSPECTED),
+            _ => NOT_INSPECTED, // don't pay for the formatting if nobody looks at it.
+        };
+        if self.inspection.selected.swap(msg_index, Ordering::Relaxed) != msg_index {
+            *self.inspection.inspected.lock().unwrap() = Inspected::default();
         }
     }
 
+    /// Freezes or unfreezes the view, the latencies, the errors and the task statuses are snapshotted
+    /// when it freezes while the live ones keep being updated.
+    fn toggle_freeze(&mut self) {
+        let frozen = !self.frozen.fetch_xor(true, Ordering::Relaxed);
+        let nodes_state = &mut self.nodes_scrollable_widget_state;
+        if frozen {
+            self.frozen_task_stats = Some(self.task_stats.lock().unwrap().clone());
+            self.frozen_error_log = Some(self.error_log.lock().unwrap().clone());
+            nodes_state.frozen_statuses = Some(nodes_state.statuses.lock().unwrap().clone());
+        } else {
+            self.frozen_task_stats = None;
+            self.frozen_error_log = None;
+            nodes_state.frozen_statuses = None;
+        }
+    }
+
+    fn draw_error_log(&self, f: &mut Frame, area: Rect) {
+        let header = Row::new(["Time", "Task", "Step", "Error"].map(|h| {
+            Cell::from(h).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )
+        }))
+        .bottom_margin(1);
+        let live_error_log = self.error_log.lock().unwrap();
+        let error_log = self.frozen_error_log.as_ref().unwrap_or(&live_error_log);
+        // most recent first
+        let rows = error_log.iter().rev().map(|event| {
+            let task = self.task_ids.get(event.taskid).copied().unwrap_or("?");
+            Row::new(vec![
+                Cell::from(event.time.to_string()),
+                Cell::from(task).light_blue(),
+                Cell::from(format!("{:?}", event.step)),
+                Cell::from(event.error.to_string()).red(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            &[
+                Constraint::Length(14),
+                Constraint::Length(16),
+                Constraint::Length(12),
+                Constraint::Min(20),
+            ],
+        )
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Errors ({} last) ", error_log.len())),
+        );
+        f.render_widget(table, area);
+    }
+
+    fn draw_inspector(&self, f: &mut Frame, area: Rect) {
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(48), Constraint::Min(0)].as_ref())
+            .split(area);
+
+        let rows = self.connections.iter().enumerate().map(|(i, c)| {
+            let row = Row::new(vec![
+                Cell::from(format!("{} → {}", c.src, c.dst)),
+                Cell::from(c.msg.clone()).dark_gray(),
+            ]);
+            if i == self.selected_connection {
+                row.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        });
+        let table = Table::new(rows, &[Constraint::Length(24), Constraint::Min(10)]).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Connections "),
+        );
+        f.render_widget(table, layout[0]);
+
+        let inspected = self.inspection.inspected.lock().unwrap();
+        let content = match &inspected.payload {
+            _ if self.connections.is_empty() => "No connection.".to_string(),
+            Some(payload) => payload.clone(),
+            None if inspected.copperlists == 0 => "Waiting for a message...".to_string(),
+            None => "No payload.".to_string(),
+        };
+        let title = match self.connections.get(self.selected_connection) {
+            Some(c) => format!(
+                " {} → {} (copperlists: {}) ",
+                c.src, c.dst, inspected.copperlists
+            ),
+            None => " Payload ".to_string(),
+        };
+        let p = Paragraph::new(content).block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(p, layout[1]);
+    }
+
     fn draw_latency_table(&self, f: &mut Frame, area: Rect) {
         let header_cells = [
             "🛠 Task",
@@ -327,7 +529,8 @@ impl UI {
             .bottom_margin(1)
             .top_margin(1);
 
-        let task_stats = self.task_stats.lock().unwrap(); // Acquire lock to read task_stats
+        let live_task_stats = self.task_stats.lock().unwrap(); // Acquire lock to read task_stats
+        let task_stats = self.frozen_task_stats.as_ref().unwrap_or(&live_task_stats);
         let mut rows = task_stats
             .stats
             .iter()
@@ -443,13 +646,20 @@ impl UI {
             )
             .split(f.area());
 
-        let menu = Paragraph::new("   [1] SysInfo  [2] DAG  [3] Latencies  [q] Quit")
-            .style(
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::ITALIC),
-            )
-            .block(Block::default().borders(Borders::BOTTOM));
+        let frozen = if self.frozen.load(Ordering::Relaxed) {
+            "  ⏸ FROZEN"
+        } else {
+            ""
+        };
+        let menu = Paragraph::new(format!(
+            "   [1] SysInfo  [2] DAG  [3] Latencies  [4] Errors  [5] Messages  [p] Freeze  [q] Quit{frozen}"
+        ))
+        .style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::ITALIC),
+        )
+        .block(Block::default().borders(Borders::BOTTOM));
         f.render_widget(menu, layout[0]);
 
         match self.active_screen {
@@ -469,6 +679,8 @@ impl UI {
                 self.draw_nodes(f, layout[1]);
             }
             Screen::Latency => self.draw_latency_table(f, layout[1]),
+            Screen::Errors => self.draw_error_log(f, layout[1]),
+            Screen::Inspector => self.draw_inspector(f, layout[1]),
         };
     }
 
@@ -484,9 +696,26 @@ impl UI {
                         KeyCode::Char('1') => self.active_screen = Screen::Neofetch,
                         KeyCode::Char('2') => self.active_screen = Screen::Dag,
                         KeyCode::Char('3') => self.active_screen = Screen::Latency,
+                        KeyCode::Char('4') => self.active_screen = Screen::Errors,
+                        KeyCode::Char('5') => self.active_screen = Screen::Inspector,
+                        KeyCode::Char('p') => self.toggle_freeze(),
+                        KeyCode::Up | KeyCode::Char('k')
+                            if self.active_screen == Screen::Inspector =>
+                        {
+                            self.select_connection(self.selected_connection.saturating_sub(1));
+                        }
+                        KeyCode::Down | KeyCode::Char('j')
+                            if self.active_screen == Screen::Inspector =>
+                        {
+                            let last = self.connections.len().saturating_sub(1);
+                            self.select_connection((self.selected_connection + 1).min(last));
+                        }
                         KeyCode::Char('r') => {
                             if self.active_screen == Screen::Latency {
-                                self.task_stats.lock().unwrap().reset()
+                                self.task_stats.lock().unwrap().reset();
+                                if let Some(frozen_task_stats) = &mut self.frozen_task_stats {
+                                    frozen_task_stats.reset();
+                                }
                             }
                         }
                         KeyCode::Char('j') => {
@@ -530,6 +759,8 @@ impl UI {
                         }
                         _ => {}
                     }
+                    // starts or stops the formatting of the payloads when the screen changes.
+                    self.select_connection(self.selected_connection);
                 }
             }
         }
@@ -550,18 +781,29 @@ impl CuMonitor for CuConsoleMon {
         Ok(Self {
             config: config.clone(),
             taskids,
+            clock: RobotClock::new(), // replaced by the one of the runtime at start.
             task_stats,
             task_statuses: Arc::new(Mutex::new(vec![TaskStatus::default(); taskids.len()])),
+            error_log: Arc::new(Mutex::new(VecDeque::with_capacity(MAX_ERROR_LOG))),
+            inspection: Arc::new(Inspection {
+                selected: AtomicUsize::new(NOT_INSPECTED),
+                inspected: Mutex::new(Inspected::default()),
+            }),
+            frozen: Arc::new(AtomicBool::new(false)),
             quitting: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    fn start(&mut self, _clock: &RobotClock) -> CuResult<()> {
+    fn start(&mut self, clock: &RobotClock) -> CuResult<()> {
+        self.clock = clock.clone();
         let config_dup = self.config.clone();
         let taskids = self.taskids;
 
         let task_stats_ui = self.task_stats.clone();
         let error_states = self.task_statuses.clone();
+        let error_log = self.error_log.clone();
+        let inspection = self.inspection.clone();
+        let frozen = self.frozen.clone();
         let quitting = self.quitting.clone();
 
         // Start the main UI loop
@@ -575,7 +817,15 @@ impl CuMonitor for CuConsoleMon {
 
             let mut terminal =
                 Terminal::new(backend).expect("Failed to initialize terminal backend");
-            let mut ui = UI::new(config_dup, taskids, task_stats_ui, error_states);
+            let mut ui = UI::new(
+                config_dup,
+                taskids,
+                task_stats_ui,
+                error_states,
+                error_log,
+                inspection,
+                frozen,
+            );
             ui.run_app(&mut terminal).expect("Failed to run app");
             quitting.store(true, Ordering::SeqCst);
             // restoring the terminal
@@ -587,6 +837,9 @@ impl CuMonitor for CuConsoleMon {
     }
 
     fn process_copperlist(&self, msgs: &[&CuMsgMetadata]) -> CuResult<()> {
+        if self.quitting.load(Ordering::SeqCst) {
+            return Err("Exiting...".into());
+        }
         {
             let mut task_stats = self.task_stats.lock().unwrap();
             task_stats.update(msgs);
@@ -600,26 +853,45 @@ impl CuMonitor for CuConsoleMon {
                 }
             }
         }
+        Ok(())
+    }
 
-        if self.quitting.load(Ordering::SeqCst) {
-            return Err("Exiting...".into());
+    fn process_payloads(&self, payloads: &[Option<&dyn Debug>]) -> CuResult<()> {
+        let selected = self.inspection.selected.load(Ordering::Relaxed);
+        if selected == NOT_INSPECTED || self.frozen.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        // Only the inspected message is formatted, the others are not worth the cost.
+        let payload = payloads
+            .get(selected)
+            .and_then(|p| p.map(|p| format!("{:#?}", p)));
+        let mut inspected = self.inspection.inspected.lock().unwrap();
+        inspected.copperlists += 1;
+        if payload.is_some() {
+            inspected.payload = payload;
         }
         Ok(())
     }
 
     fn process_error(&self, taskid: usize, step: CuTaskState, error: &CuError) -> Decision {
+        // Recorded even when the view is frozen, it is displayed once unfrozen.
+        let error = error.to_compact_string();
         {
             let status = &mut self.task_statuses.lock().unwrap()[taskid];
             status.is_error = true;
-            status.error = error.to_compact_string();
+            status.error = error.clone();
         }
-        match step {
-            CuTaskState::Start => Decision::Shutdown,
-            CuTaskState::Preprocess => Decision::Abort,
-            CuTaskState::Process => Decision::Ignore,
-            CuTaskState::Postprocess => Decision::Ignore,
-            CuTaskState::Stop => Decision::Shutdown,
+        let mut error_log = self.error_log.lock().unwrap();
+        if error_log.len() == MAX_ERROR_LOG {
+            error_log.pop_front();
         }
+        error_log.push_back(ErrorEvent {
+            time: self.clock.now(),
+            taskid,
+            step,
+            error,
+        });
+        Decision::default_for(step)
     }
 
     fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
@@ -635,6 +907,30 @@ impl CuMonitor for CuConsoleMon {
     }
 }
 
+/// Runs the console monitor on copperlists coming from elsewhere than the runtime,
+/// typically a live stream from a robot with cu29::prelude::RemoteLogReader.
+/// collect_metadata is the function generated alongside the CopperList type of the application.
+/// It returns when the source ends, or when the user has quit and the next copperlist is received.
+pub fn monitor_remote<P: CopperListTuple, const N: usize>(
+    config: &CuConfig,
+    taskids: &'static [&'static str],
+    mut source: impl Read,
+    collect_metadata: fn(&CopperList<P>) -> [&CuMsgMetadata; N],
+) -> CuResult<()> {
+    let clock = RobotClock::new();
+    let mut monitor = CuConsoleMon::new(config, taskids)?;
+    monitor.start(&clock)?;
+    while let Ok(culist) = decode_from_std_read::<CopperList<P>, _, _>(&mut source, standard()) {
+        if monitor
+            .process_copperlist(&collect_metadata(&culist))
+            .is_err()
+        {
+            break; // the user quit.
+        }
+    }
+    monitor.stop(&clock)
+}
+
 fn init_error_hooks() {
     let (panic, error) = HookBuilder::default().into_hooks();
     let panic = panic.into_panic_hook();
@@ -0,0 +1,441 @@
+use cu29::clock::{CuDuration, CuTime, RobotClock};
+use cu29::config::CuConfig;
+use cu29::cutask::CuMsgMetadata;
+use cu29::monitoring::{CuDurationStatistics, CuMonitor, CuTaskState, Decision, GLOBAL};
+use cu29::{CuError, CuResult};
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The default port, the same as the OpenTelemetry Prometheus exporter.
+const DEFAULT_PORT: u32 = 9464;
+const DEFAULT_BIND: &str = "0.0.0.0";
+
+/// The upper bounds of the histogram buckets, in ns.
+const BUCKETS: [u64; 12] = [
+    10_000,
+    50_000,
+    100_000,
+    500_000,
+    1_000_000,
+    5_000_000,
+    10_000_000,
+    50_000_000,
+    100_000_000,
+    500_000_000,
+    1_000_000_000,
+    5_000_000_000,
+];
+
+/// Anything above is recorded as the max.
+const MAX_DURATION: CuDuration = CuDuration(60_000_000_000);
+
+/// Indexed by CuTaskState as usize.
+const STATES: [&str; 5] = ["Start", "Preprocess", "Process", "Postprocess", "Stop"];
+
+/// A CuDurationStatistics with the exact sum Prometheus needs for the histograms.
+struct DurationMetric {
+    stats: CuDurationStatistics,
+    sum: CuDuration,
+}
+
+impl DurationMetric {
+    fn new() -> Self {
+        Self {
+            stats: CuDurationStatistics::new(MAX_DURATION),
+            sum: CuDuration::default(),
+        }
+    }
+
+    fn record(&mut self, value: CuDuration) {
+        let value = value.min(MAX_DURATION);
+        self.stats.record(value);
+        self.sum = self.sum.saturating_add(value);
+    }
+}
+
+struct Metrics {
+    tasks: Vec<DurationMetric>,
+    end2end: DurationMetric,
+    errors: Vec<[u64; STATES.len()]>,
+    copperlists: u64,
+}
+
+impl Metrics {
+    fn new(num_tasks: usize) -> Self {
+        Self {
+            tasks: (0..num_tasks).map(|_| DurationMetric::new()).collect(),
+            end2end: DurationMetric::new(),
+            errors: vec![[0; STATES.len()]; num_tasks],
+            copperlists: 0,
+        }
+    }
+
+    fn update(&mut self, msgs: &[&CuMsgMetadata]) {
+        for (metric, msg) in self.tasks.iter_mut().zip(msgs) {
+            if let Some(duration) = process_time(msg) {
+                metric.record(duration);
+            }
+        }
+        let start: Option<CuTime> = msgs.first().and_then(|m| m.process_time.start.into());
+        let end: Option<CuTime> = msgs.last().and_then(|m| m.process_time.end.into());
+        if let (Some(start), Some(end)) = (start, end) {
+            self.end2end.record(end.saturating_sub(start));
+        }
+        self.copperlists += 1;
+    }
+}
+
+/// None if the task did not go through its process step.
+fn process_time(msg: &CuMsgMetadata) -> Option<CuDuration> {
+    let start: Option<CuTime> = msg.process_time.start.into();
+    let end: Option<CuTime> = msg.process_time.end.into();
+    Some(end?.saturating_sub(start?))
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn seconds(duration: CuDuration) -> f64 {
+    duration.0 as f64 / 1e9
+}
+
+/// Renders all the metrics in the Prometheus text format, or the OpenMetrics one.
+fn render(metrics: &Metrics, taskids: &[&str], openmetrics: bool) -> String {
+    let mut out = String::new();
+    // OpenMetrics names the counter families without their _total suffix.
+    let counter_family = |name: &str| {
+        if openmetrics {
+            name.trim_end_matches("_total").to_string()
+        } else {
+            name.to_string()
+        }
+    };
+    let histogram = |out: &mut String, name: &str, labels: &str, metric: &DurationMetric| {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for bucket in BUCKETS {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{}\"}} {}",
+                seconds(CuDuration(bucket)),
+                metric.stats.count_up_to(CuDuration(bucket))
+            );
+        }
+        let count = metric.stats.len();
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {count}"
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {}", seconds(metric.sum));
+        let _ = writeln!(out, "{name}_count{labels} {count}");
+    };
+
+    let name = "copper_task_process_time_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {name} Time spent by each task in its process step."
+    );
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    for (metric, taskid) in metrics.tasks.iter().zip(taskids) {
+        let labels = format!("task=\"{}\"", escape_label(taskid));
+        histogram(&mut out, name, &labels, metric);
+    }
+
+    let name = "copper_task_errors_total";
+    let _ = writeln!(
+        out,
+        "# HELP {} Number of errors of each task for each step.",
+        counter_family(name)
+    );
+    let _ = writeln!(out, "# TYPE {} counter", counter_family(name));
+    for (errors, taskid) in metrics.errors.iter().zip(taskids) {
+        for (count, state) in errors.iter().zip(STATES) {
+            let _ = writeln!(
+                out,
+                "{name}{{task=\"{}\",state=\"{state}\"}} {count}",
+                escape_label(taskid)
+            );
+        }
+    }
+
+    let name = "copper_end_to_end_latency_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {name} Time between the start of the first task and the end of the last task of a copperlist."
+    );
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    histogram(&mut out, name, "", &metrics.end2end);
+
+    let name = "copper_copperlists_total";
+    let _ = writeln!(
+        out,
+        "# HELP {} Number of copperlists processed.",
+        counter_family(name)
+    );
+    let _ = writeln!(out, "# TYPE {} counter", counter_family(name));
+    let _ = writeln!(out, "{name} {}", metrics.copperlists);
+
+    let allocated = GLOBAL.get_allocated();
+    let deallocated = GLOBAL.get_deallocated();
+    for (name, help, value) in [
+        (
+            "copper_allocated_bytes_total",
+            "Bytes allocated since the start.",
+            allocated,
+        ),
+        (
+            "copper_deallocated_bytes_total",
+            "Bytes deallocated since the start.",
+            deallocated,
+        ),
+    ] {
+        let _ = writeln!(out, "# HELP {} {help}", counter_family(name));
+        let _ = writeln!(out, "# TYPE {} counter", counter_family(name));
+        let _ = writeln!(out, "{name} {value}");
+    }
+    let name = "copper_heap_in_use_bytes";
+    let _ = writeln!(out, "# HELP {name} Bytes currently allocated.");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {}", allocated.saturating_sub(deallocated));
+
+    if openmetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+/// Answers one HTTP request, only GET /metrics is served.
+fn handle_connection(
+    mut stream: TcpStream,
+    metrics: &Mutex<Metrics>,
+    taskids: &[&str],
+) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, path) = (request_line.next(), request_line.next());
+    let openmetrics = request.lines().any(|l| {
+        let l = l.to_ascii_lowercase();
+        l.starts_with("accept:") && l.contains("application/openmetrics-text")
+    });
+
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = render(&metrics.lock().unwrap(), taskids, openmetrics);
+            let content_type = if openmetrics {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            } else {
+                "text/plain; version=0.0.4; charset=utf-8"
+            };
+            ("200 OK", content_type, body)
+        }
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n".to_string(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+struct MetricsServer {
+    local_addr: SocketAddr,
+    quitting: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// A monitor exposing the metrics of the tasks on an HTTP /metrics endpoint for Prometheus.
+pub struct CuPrometheusMon {
+    taskids: &'static [&'static str],
+    bind: String,
+    metrics: Arc<Mutex<Metrics>>,
+    server: Option<MetricsServer>,
+}
+
+impl CuPrometheusMon {
+    /// The address the /metrics endpoint listens on once started, useful with port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.as_ref().map(|s| s.local_addr)
+    }
+}
+
+impl CuMonitor for CuPrometheusMon {
+    fn new(config: &CuConfig, taskids: &'static [&'static str]) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let monitor_config = config.get_monitor_config().and_then(|m| m.get_config());
+        let port = monitor_config
+            .and_then(|c| c.get::<u32>("port"))
+            .unwrap_or(DEFAULT_PORT);
+        let host = monitor_config
+            .and_then(|c| c.get::<String>("bind"))
+            .unwrap_or(DEFAULT_BIND.to_string());
+        Ok(Self {
+            taskids,
+            bind: format!("{host}:{port}"),
+            metrics: Arc::new(Mutex::new(Metrics::new(taskids.len()))),
+            server: None,
+        })
+    }
+
+    fn start(&mut self, _clock: &RobotClock) -> CuResult<()> {
+        let listener = TcpListener::bind(&self.bind).map_err(|e| {
+            CuError::new_with_cause(
+                &format!("Could not bind the metrics endpoint on {}", self.bind),
+                e,
+            )
+        })?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| CuError::new_with_cause("Could not set the listener nonblocking", e))?;
+        let local_addr = listener.local_addr().map_err(|e| {
+            CuError::new_with_cause("Could not get the metrics endpoint address", e)
+        })?;
+
+        let quitting = Arc::new(AtomicBool::new(false));
+        let metrics = self.metrics.clone();
+        let taskids = self.taskids;
+        let quitting_server = quitting.clone();
+        let handle = thread::Builder::new()
+            .name("prometheus-metrics".to_string())
+            .spawn(move || {
+                while !quitting_server.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        // a misbehaving client should not bring the monitor down.
+                        Ok((stream, _)) => {
+                            let _ = handle_connection(stream, &metrics, taskids);
+                        }
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(50));
+                        }
+                        Err(_) => thread::sleep(Duration::from_millis(50)),
+                    }
+                }
+            })
+            .map_err(|e| CuError::new_with_cause("Could not start the metrics thread", e))?;
+
+        self.server = Some(MetricsServer {
+            local_addr,
+            quitting,
+            handle,
+        });
+        Ok(())
+    }
+
+    fn process_copperlist(&self, msgs: &[&CuMsgMetadata]) -> CuResult<()> {
+        self.metrics.lock().unwrap().update(msgs);
+        Ok(())
+    }
+
+    fn process_error(&self, taskid: usize, step: CuTaskState, _error: &CuError) -> Decision {
+        if let Some(errors) = self.metrics.lock().unwrap().errors.get_mut(taskid) {
+            errors[step as usize] += 1;
+        }
+        Decision::default_for(step)
+    }
+
+    fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
+        if let Some(server) = self.server.take() {
+            server.quitting.store(true, Ordering::Relaxed);
+            server
+                .handle
+                .join()
+                .map_err(|_| CuError::from("The metrics thread panicked"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cu29::test_utils::process_metadata;
+
+    fn scrape(addr: SocketAddr, path: &str, accept: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\nAccept: {accept}\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_scrape_localhost() {
+        let config = CuConfig::deserialize_ron(
+            r#"( tasks: [], cnx: [], monitor: (type: "CuPrometheusMon", config: { "port": 0, "bind": "127.0.0.1" }) )"#,
+        );
+        let mut monitor = CuPrometheusMon::new(&config, &["src", "sink"]).unwrap();
+        let (clock, _) = RobotClock::mock();
+        monitor.start(&clock).unwrap();
+        let addr = monitor.local_addr().unwrap();
+
+        let src = process_metadata(CuDuration(0), CuDuration(20_000)); // 20µs
+        let sink = process_metadata(CuDuration(30_000), CuDuration(2_030_000)); // 2ms
+        monitor.process_copperlist(&[&src, &sink]).unwrap();
+        monitor.process_error(1, CuTaskState::Process, &CuError::from("boom"));
+
+        let response = scrape(addr, "/metrics", "text/plain");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(response
+            .contains("copper_task_process_time_seconds_bucket{task=\"src\",le=\"0.00001\"} 0"));
+        assert!(response
+            .contains("copper_task_process_time_seconds_bucket{task=\"src\",le=\"0.00005\"} 1"));
+        assert!(response.contains("copper_task_process_time_seconds_count{task=\"sink\"} 1"));
+        assert!(response.contains("copper_task_errors_total{task=\"sink\",state=\"Process\"} 1"));
+        assert!(response.contains("copper_task_errors_total{task=\"src\",state=\"Process\"} 0"));
+        assert!(response.contains("copper_end_to_end_latency_seconds_sum 0.00203"));
+        assert!(response.contains("copper_copperlists_total 1"));
+        assert!(response.contains("# TYPE copper_allocated_bytes_total counter"));
+        assert!(!response.contains("# EOF"));
+
+        let response = scrape(
+            addr,
+            "/metrics",
+            "application/openmetrics-text; version=1.0.0",
+        );
+        assert!(response.contains("# TYPE copper_task_errors counter"));
+        assert!(response.ends_with("# EOF\n"));
+
+        let response = scrape(addr, "/", "text/plain");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+
+        monitor.stop(&clock).unwrap();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use cu29::clock::{CuDuration, CuTime, RobotClock};
+use cu29::config::CuConfig;
+use cu29::cutask::CuMsgMetadata;
+use cu29::monitoring::{CuDurationStatistics, CuMonitor, CuTaskState, Decision};
+use cu29::{CuError, CuResult};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const DEFAULT_REPORT: &str = "copper_report.json";
+const DEFAULT_THRESHOLD_PCT: f64 = 10.0;
+const DEFAULT_TOLERANCE: CuDuration = CuDuration(1_000);
+
+/// Anything above is recorded as the max.
+const MAX_DURATION: CuDuration = CuDuration(60_000_000_000);
+
+/// The summary of a CuDurationStatistics, all the durations are in ns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DurationReport {
+    pub samples: u64,
+    pub min: CuDuration,
+    pub max: CuDuration,
+    pub mean: CuDuration,
+    pub stddev: CuDuration,
+    pub p50: CuDuration,
+    pub p90: CuDuration,
+    pub p99: CuDuration,
+    pub jitter_mean: CuDuration,
+    pub jitter_max: CuDuration,
+    pub jitter_p99: CuDuration,
+}
+
+impl From<&CuDurationStatistics> for DurationReport {
+    fn from(stats: &CuDurationStatistics) -> Self {
+        if stats.is_empty() {
+            return DurationReport::default();
+        }
+        DurationReport {
+            samples: stats.len(),
+            min: stats.min(),
+            max: stats.max(),
+            mean: stats.mean(),
+            stddev: stats.stddev(),
+            p50: stats.percentile(0.5),
+            p90: stats.percentile(0.9),
+            p99: stats.percentile(0.99),
+            jitter_mean: stats.jitter_mean(),
+            jitter_max: stats.jitter_max(),
+            jitter_p99: stats.jitter_percentile(0.99),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskReport {
+    pub id: String,
+    pub process_time: DurationReport,
+    pub errors: u64,
+}
+
+/// The report written by the monitor when Copper stops.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub copperlists: u64,
+    pub tasks: Vec<TaskReport>,
+    pub end2end: DurationReport,
+}
+
+impl Report {
+    pub fn to_json(&self) -> CuResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| CuError::new_with_cause("Could not serialize the report", e))
+    }
+
+    pub fn from_json(json: &str) -> CuResult<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| CuError::new_with_cause("Could not deserialize the report", e))
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# Copper latency report\n");
+        let _ = writeln!(md, "Copperlists: {}\n", self.copperlists);
+        let _ = writeln!(
+            md,
+            "| Task | Samples | Errors | Min | Mean | p50 | p90 | p99 | Max | Jitter mean | Jitter p99 |"
+        );
+        let _ = writeln!(md, "|---|---|---|---|---|---|---|---|---|---|---|");
+        let rows = self
+            .tasks
+            .iter()
+            .map(|t| (t.id.as_str(), &t.process_time, t.errors.to_string()))
+            .chain([("**End to end**", &self.end2end, "".to_string())]);
+        for (id, d, errors) in rows {
+            let _ = writeln!(
+                md,
+                "| {id} | {} | {errors} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                d.samples, d.min, d.mean, d.p50, d.p90, d.p99, d.max, d.jitter_mean, d.jitter_p99
+            );
+        }
+        md
+    }
+
+    /// Lists the p50 and p99 latencies that regressed by more than threshold_pct % and tolerance
+    /// compared to the baseline. The tasks not present in the baseline are ignored.
+    pub fn regressions(
+        &self,
+        baseline: &Report,
+        threshold_pct: f64,
+        tolerance: CuDuration,
+    ) -> Vec<String> {
+        let mut regressions = Vec::new();
+        let mut check = |name: &str, current: &DurationReport, base: &DurationReport| {
+            for (metric, current, base) in [
+                ("p50", current.p50, base.p50),
+                ("p99", current.p99, base.p99),
+            ] {
+                let limit = base.0 as f64 * (1.0 + threshold_pct / 100.0);
+                if current.0 as f64 > limit && current.saturating_sub(base) > tolerance {
+                    let increase = (current.0 as f64 / base.0.max(1) as f64 - 1.0) * 100.0;
+                    regressions.push(format!(
+                        "{name} {metric} regressed from {base} to {current} (+{increase:.1}%)"
+                    ));
+                }
+            }
+        };
+        for task in &self.tasks {
+            if let Some(base) = baseline.tasks.iter().find(|t| t.id == task.id) {
+                check(
+                    &format!("Task '{}'", task.id),
+                    &task.process_time,
+                    &base.process_time,
+                );
+            }
+        }
+        check("End to end", &self.end2end, &baseline.end2end);
+        regressions
+    }
+}
+
+struct Stats {
+    tasks: Vec<CuDurationStatistics>,
+    end2end: CuDurationStatistics,
+    errors: Vec<u64>,
+    copperlists: u64,
+}
+
+/// A monitor without UI recording the latencies and writing a report on stop, see the README.
+pub struct CuReportMon {
+    taskids: &'static [&'static str],
+    report_path: PathBuf,
+    baseline_path: Option<PathBuf>,
+    threshold_pct: f64,
+    tolerance: CuDuration,
+    stats: Mutex<Stats>,
+}
+
+impl CuReportMon {
+    /// The report of what has been recorded so far.
+    pub fn report(&self) -> Report {
+        let stats = self.stats.lock().unwrap();
+        Report {
+            copperlists: stats.copperlists,
+            tasks: self
+                .taskids
+                .iter()
+                .zip(&stats.tasks)
+                .zip(&stats.errors)
+                .map(|((id, task), errors)| TaskReport {
+                    id: id.to_string(),
+                    process_time: task.into(),
+                    errors: *errors,
+                })
+                .collect(),
+            end2end: (&stats.end2end).into(),
+        }
+    }
+}
+
+fn is_markdown(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("md") | Some("markdown")
+    )
+}
+
+impl CuMonitor for CuReportMon {
+    fn new(config: &CuConfig, taskids: &'static [&'static str]) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let monitor_config = config.get_monitor_config().and_then(|m| m.get_config());
+        let report_path = monitor_config
+            .and_then(|c| c.get::<String>("report"))
+            .unwrap_or(DEFAULT_REPORT.to_string())
+            .into();
+        let baseline_path = monitor_config
+            .and_then(|c| c.get::<String>("baseline"))
+            .map(PathBuf::from);
+        let threshold_pct = monitor_config
+            .and_then(|c| c.get::<f64>("threshold"))
+            .unwrap_or(DEFAULT_THRESHOLD_PCT);
+        let tolerance = monitor_config
+            .and_then(|c| c.get::<CuDuration>("tolerance"))
+            .unwrap_or(DEFAULT_TOLERANCE);
+
+        Ok(Self {
+            taskids,
+            report_path,
+            baseline_path,
+            threshold_pct,
+            tolerance,
+            stats: Mutex::new(Stats {
+                tasks: vec![CuDurationStatistics::new(MAX_DURATION); taskids.len()],
+                end2end: CuDurationStatistics::new(MAX_DURATION),
+                errors: vec![0; taskids.len()],
+                copperlists: 0,
+            }),
+        })
+    }
+
+    fn process_copperlist(&self, msgs: &[&CuMsgMetadata]) -> CuResult<()> {
+        let mut stats = self.stats.lock().unwrap();
+        for (task, msg) in stats.tasks.iter_mut().zip(msgs) {
+            let start: Option<CuTime> = msg.process_time.start.into();
+            let end: Option<CuTime> = msg.process_time.end.into();
+            if let (Some(start), Some(end)) = (start, end) {
+                task.record(end.saturating_sub(start).min(MAX_DURATION));
+            }
+        }
+        let start: Option<CuTime> = msgs.first().and_then(|m| m.process_time.start.into());
+        let end: Option<CuTime> = msgs.last().and_then(|m| m.process_time.end.into());
+        if let (Some(start), Some(end)) = (start, end) {
+            stats
+                .end2end
+                .record(end.saturating_sub(start).min(MAX_DURATION));
+        }
+        stats.copperlists += 1;
+        Ok(())
+    }
+
+    fn process_error(&self, taskid: usize, step: CuTaskState, _error: &CuError) -> Decision {
+        if let Some(errors) = self.stats.lock().unwrap().errors.get_mut(taskid) {
+            *errors += 1;
+        }
+        Decision::default_for(step)
+    }
+
+    /// Writes the report and checks it against the baseline if any.
+    /// The baseline is read first so a run can update the baseline it is checked against.
+    fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
+        let baseline = match &self.baseline_path {
+            Some(baseline_path) => {
+                let baseline = fs::read_to_string(baseline_path).map_err(|e| {
+                    CuError::new_with_cause(
+                        &format!("Could not read the baseline {}", baseline_path.display()),
+                        e,
+                    )
+                })?;
+                Some((baseline_path, Report::from_json(&baseline)?))
+            }
+            None => None,
+        };
+
+        let report = self.report();
+        let content = if is_markdown(&self.report_path) {
+            report.to_markdown()
+        } else {
+            report.to_json()?
+        };
+        fs::write(&self.report_path, content).map_err(|e| {
+            CuError::new_with_cause(
+                &format!("Could not write the report {}", self.report_path.display()),
+                e,
+            )
+        })?;
+
+        let Some((baseline_path, baseline)) = baseline else {
+            return Ok(());
+        };
+        let regressions = report.regressions(&baseline, self.threshold_pct, self.tolerance);
+        if regressions.is_empty() {
+            Ok(())
+        } else {
+            Err(CuError::from(format!(
+                "Latency regressions against {}:\n{}",
+                baseline_path.display(),
+                regressions.join("\n")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cu29::test_utils::process_metadata;
+    use tempfile::tempdir;
+
+    fn run(config: &str, sink_time: u64) -> CuResult<CuReportMon> {
+        let config = CuConfig::deserialize_ron(config);
+        let mut monitor = CuReportMon::new(&config, &["src", "sink"])?;
+        for i in 0..100 {
+            let src = process_metadata(CuDuration(0), CuDuration(10_000 + i));
+            let sink = process_metadata(CuDuration(10_000 + i), CuDuration(10_000 + i + sink_time));
+            monitor.process_copperlist(&[&src, &sink])?;
+        }
+        let (clock, _) = RobotClock::mock();
+        monitor.stop(&clock)?;
+        Ok(monitor)
+    }
+
+    #[test]
+    fn test_report_and_baseline() {
+        let dir = tempdir().unwrap();
+        let baseline = dir.path().join("baseline.json");
+        let config = format!(
+            r#"( tasks: [], cnx: [], monitor: (type: "CuReportMon", config: {{ "report": "{}" }}) )"#,
+            baseline.display()
+        );
+        let monitor = run(&config, 100_000).unwrap();
+        let report = Report::from_json(&fs::read_to_string(&baseline).unwrap()).unwrap();
+        assert_eq!(report, monitor.report());
+        assert_eq!(report.copperlists, 100);
+        assert_eq!(report.tasks[1].id, "sink");
+        assert_eq!(report.tasks[1].process_time.samples, 100);
+        assert!(report.end2end.p99 > CuDuration(100_000));
+
+        // 5% slower is within the 10% threshold
+        let markdown = dir.path().join("report.md");
+        let config = format!(
+            r#"( tasks: [], cnx: [], monitor: (type: "CuReportMon", config: {{ "report": "{}", "baseline": "{}", "threshold": 10.0 }}) )"#,
+            markdown.display(),
+            baseline.display()
+        );
+        run(&config, 105_000).unwrap();
+        let md = fs::read_to_string(&markdown).unwrap();
+        assert!(md.contains("| sink | 100 | 0 |"));
+
+        // 50% slower is not
+        let error = run(&config, 150_000).err().unwrap();
+        assert!(error.to_string().contains("Task 'sink' p50 regressed"));
+        assert!(!error.to_string().contains("Task 'src'"));
+    }
+
+    #[test]
+    fn test_report_over_its_baseline() {
+        let dir = tempdir().unwrap();
+        let baseline = dir.path().join("baseline.json");
+        let config = format!(
+            r#"( tasks: [], cnx: [], monitor: (type: "CuReportMon", config: {{ "report": "{0}", "baseline": "{0}" }}) )"#,
+            baseline.display()
+        );
+        // no baseline yet
+        assert!(run(&config, 100_000).is_err());
+
+        let config_first = format!(
+            r#"( tasks: [], cnx: [], monitor: (type: "CuReportMon", config: {{ "report": "{}" }}) )"#,
+            baseline.display()
+        );
+        run(&config_first, 100_000).unwrap();
+        // checked against the previous run, then replaces it
+        let monitor = run(&config, 105_000).unwrap();
+        let report = Report::from_json(&fs::read_to_string(&baseline).unwrap()).unwrap();
+        assert_eq!(report, monitor.report());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{decode_quantity, encode_quantity, quantity};
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use uom::si::f32::{ElectricCurrent, ElectricPotential, Ratio, ThermodynamicTemperature};
+
+#[derive(Default, PartialEq, Debug, Copy, Clone, Encode, Decode)]
+pub enum BatteryStatus {
+    #[default]
+    Unknown,
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+}
+
+/// Standardized state of a battery as reported by a BMS or a power monitor.
+#[derive(Default, PartialEq, Debug, Copy, Clone)]
+pub struct BatteryState {
+    pub voltage: ElectricPotential,
+    /// Negative when discharging.
+    pub current: ElectricCurrent,
+    /// The remaining charge, from 0 to 1.
+    pub state_of_charge: Ratio,
+    pub temperature: Option<ThermodynamicTemperature>,
+    pub status: BatteryStatus,
+}
+
+/// Encodes the quantities as f32 in V, A, ratio and K.
+impl Encode for BatteryState {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        encode_quantity(&self.voltage, encoder)?;
+        encode_quantity(&self.current, encoder)?;
+        encode_quantity(&self.state_of_charge, encoder)?;
+        self.temperature.map(|t| t.value).encode(encoder)?;
+        self.status.encode(encoder)
+    }
+}
+
+impl Decode for BatteryState {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(BatteryState {
+            voltage: decode_quantity(decoder)?,
+            current: decode_quantity(decoder)?,
+            state_of_charge: decode_quantity(decoder)?,
+            temperature: Option::<f32>::decode(decoder)?.map(quantity),
+            status: Decode::decode(decoder)?,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(BatteryState);
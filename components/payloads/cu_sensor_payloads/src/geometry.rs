@@ -0,0 +1,390 @@
+use crate::{decode_quantity, encode_quantity, quantity};
+use bincode::de::{BorrowDecoder, Decoder};
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{BorrowDecode, Decode, Encode};
+use derive_more::{Add, Sub};
+use std::ops::Mul;
+use uom::si::angle::radian;
+use uom::si::f32::{Angle, AngularVelocity, Length, Velocity};
+use uom::si::{Dimension, Quantity, Units};
+
+/// A 3D vector of a physical quantity, for example a position (Length) or an acceleration.
+/// Encoded as 3 f32 in the base SI unit of the quantity.
+#[derive(Default, PartialEq, Debug, Copy, Clone, Add, Sub)]
+pub struct Vector3<Q> {
+    pub x: Q,
+    pub y: Q,
+    pub z: Q,
+}
+
+impl<Q> Vector3<Q> {
+    pub fn new(x: Q, y: Q, z: Q) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl<D, U> Vector3<Quantity<D, U, f32>>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+{
+    /// From the values in the base SI unit of the quantity.
+    pub fn from_values(values: [f32; 3]) -> Self {
+        Self::new(
+            quantity(values[0]),
+            quantity(values[1]),
+            quantity(values[2]),
+        )
+    }
+
+    /// The values in the base SI unit of the quantity.
+    pub fn values(&self) -> [f32; 3] {
+        [self.x.value, self.y.value, self.z.value]
+    }
+
+    pub fn norm(&self) -> Quantity<D, U, f32> {
+        let [x, y, z] = self.values();
+        quantity((x * x + y * y + z * z).sqrt())
+    }
+}
+
+impl<D, U> Encode for Vector3<Quantity<D, U, f32>>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+{
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        encode_quantity(&self.x, encoder)?;
+        encode_quantity(&self.y, encoder)?;
+        encode_quantity(&self.z, encoder)
+    }
+}
+
+impl<D, U> Decode for Vector3<Quantity<D, U, f32>>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+{
+    fn decode<De: Decoder>(decoder: &mut De) -> Result<Self, DecodeError> {
+        Ok(Self::new(
+            decode_quantity(decoder)?,
+            decode_quantity(decoder)?,
+            decode_quantity(decoder)?,
+        ))
+    }
+}
+
+impl<'de, D, U> BorrowDecode<'de> for Vector3<Quantity<D, U, f32>>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+{
+    fn borrow_decode<BD: BorrowDecoder<'de>>(decoder: &mut BD) -> Result<Self, DecodeError> {
+        Decode::decode(decoder)
+    }
+}
+
+/// A unit quaternion representing an orientation or a rotation.
+/// The default is the identity.
+#[derive(PartialEq, Debug, Copy, Clone, Encode, Decode)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// From the roll (x), pitch (y) and yaw (z) angles, applied in the yaw, pitch, roll order.
+    pub fn from_euler(roll: Angle, pitch: Angle, yaw: Angle) -> Self {
+        let (sr, cr) = (roll.get::<radian>() / 2.0).sin_cos();
+        let (sp, cp) = (pitch.get::<radian>() / 2.0).sin_cos();
+        let (sy, cy) = (yaw.get::<radian>() / 2.0).sin_cos();
+        Self {
+            w: cr * cp * cy + sr * sp * sy,
+            x: sr * cp * cy - cr * sp * sy,
+            y: cr * sp * cy + sr * cp * sy,
+            z: cr * cp * sy - sr * sp * cy,
+        }
+    }
+
+    /// The roll, pitch and yaw angles, see from_euler.
+    pub fn to_euler(&self) -> (Angle, Angle, Angle) {
+        let Self { w, x, y, z } = *self;
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+        (
+            Angle::new::<radian>(roll),
+            Angle::new::<radian>(pitch),
+            Angle::new::<radian>(yaw),
+        )
+    }
+
+    pub fn norm(&self) -> f32 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    /// Back to a unit quaternion, for example after accumulating rounding errors.
+    pub fn normalize(&self) -> Self {
+        let n = self.norm();
+        if n == 0.0 {
+            return Self::IDENTITY;
+        }
+        Self::new(self.w / n, self.x / n, self.y / n, self.z / n)
+    }
+
+    /// The inverse rotation.
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// From a rotation vector in rad: its direction is the axis, its norm the angle (exponential map).
+    pub fn from_rotation_vector(v: [f32; 3]) -> Self {
+        let angle = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if angle < 1e-9 {
+            // first order to stay well defined around 0.
+            return Self::new(1.0, v[0] / 2.0, v[1] / 2.0, v[2] / 2.0).normalize();
+        }
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        let k = sin / angle;
+        Self::new(cos, v[0] * k, v[1] * k, v[2] * k)
+    }
+
+    /// The rotation vector in rad of this rotation, the shortest one (logarithmic map).
+    pub fn to_rotation_vector(&self) -> [f32; 3] {
+        // q and -q are the same rotation, pick the one with the smallest angle.
+        let q = if self.w < 0.0 {
+            Self::new(-self.w, -self.x, -self.y, -self.z)
+        } else {
+            *self
+        };
+        let sin = (q.x * q.x + q.y * q.y + q.z * q.z).sqrt();
+        if sin < 1e-9 {
+            return [2.0 * q.x, 2.0 * q.y, 2.0 * q.z];
+        }
+        let angle = 2.0 * sin.atan2(q.w);
+        let k = angle / sin;
+        [q.x * k, q.y * k, q.z * k]
+    }
+
+    /// Spherical linear interpolation, t = 0 is self and t = 1 is other.
+    /// t out of [0, 1] extrapolates at the same angular velocity.
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Quaternion {
+        let delta = (self.conjugate() * *other).to_rotation_vector();
+        (*self * Quaternion::from_rotation_vector(delta.map(|d| d * t))).normalize()
+    }
+
+    /// Rotates a vector of any quantity.
+    pub fn rotate<D, U>(&self, v: &Vector3<Quantity<D, U, f32>>) -> Vector3<Quantity<D, U, f32>>
+    where
+        D: Dimension + ?Sized,
+        U: Units<f32> + ?Sized,
+    {
+        // v' = v + w t + q x t with t = 2 q x v
+        let q = [self.x, self.y, self.z];
+        let v = v.values();
+        let t = cross(q, v).map(|c| 2.0 * c);
+        let qt = cross(q, t);
+        Vector3::from_values([
+            v[0] + self.w * t[0] + qt[0],
+            v[1] + self.w * t[1] + qt[1],
+            v[2] + self.w * t[2] + qt[2],
+        ])
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// The Hamilton product: self * rhs rotates by rhs first, then by self.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        let (a, b) = (self, rhs);
+        Quaternion {
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        }
+    }
+}
+
+/// A rigid transform: a rotation followed by a translation.
+/// The transform from a frame A to a frame B is also the pose of B in A.
+#[derive(Default, PartialEq, Debug, Copy, Clone, Encode, Decode)]
+pub struct Transform {
+    pub translation: Vector3<Length>,
+    pub rotation: Quaternion,
+}
+
+/// A position and an orientation in a reference frame.
+pub type Pose = Transform;
+
+impl Transform {
+    pub fn new(translation: Vector3<Length>, rotation: Quaternion) -> Self {
+        Self {
+            translation,
+            rotation,
+        }
+    }
+
+    /// Transforms a point.
+    pub fn apply(&self, point: &Vector3<Length>) -> Vector3<Length> {
+        self.rotation.rotate(point) + self.translation
+    }
+
+    /// self ∘ other: the transform applying other first, then self.
+    pub fn compose(&self, other: &Transform) -> Transform {
+        Transform {
+            translation: self.apply(&other.translation),
+            rotation: (self.rotation * other.rotation).normalize(),
+        }
+    }
+
+    /// Interpolates linearly the translation and spherically the rotation, t = 0 is self and t = 1 is other.
+    /// t out of [0, 1] extrapolates at constant velocity.
+    pub fn interpolate(&self, other: &Transform, t: f32) -> Transform {
+        let (a, b) = (self.translation.values(), other.translation.values());
+        Transform {
+            translation: Vector3::from_values([0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)),
+            rotation: self.rotation.slerp(&other.rotation, t),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.conjugate();
+        let t = rotation.rotate(&self.translation).values();
+        Transform {
+            translation: Vector3::from_values([-t[0], -t[1], -t[2]]),
+            rotation,
+        }
+    }
+}
+
+/// A linear and angular velocity.
+#[derive(Default, PartialEq, Debug, Copy, Clone, Encode, Decode)]
+pub struct Twist {
+    pub linear: Vector3<Velocity>,
+    pub angular: Vector3<AngularVelocity>,
+}
+
+/// An estimation of the pose of a robot in its odometry frame,
+/// with its velocity expressed in the body frame of the robot.
+#[derive(Default, PartialEq, Debug, Copy, Clone, Encode, Decode)]
+pub struct Odometry {
+    pub pose: Pose,
+    pub twist: Twist,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+    use uom::si::length::meter;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_euler_round_trip() {
+        let q = Quaternion::from_euler(
+            Angle::new::<radian>(0.1),
+            Angle::new::<radian>(-0.2),
+            Angle::new::<radian>(0.3),
+        );
+        let (roll, pitch, yaw) = q.to_euler();
+        assert_close([roll.value, pitch.value, yaw.value], [0.1, -0.2, 0.3]);
+    }
+
+    #[test]
+    fn test_transform() {
+        // 90 degrees around z then 1m along x.
+        let t = Transform::new(
+            Vector3::new(
+                Length::new::<meter>(1.0),
+                Length::new::<meter>(0.0),
+                Length::new::<meter>(0.0),
+            ),
+            Quaternion::from_euler(
+                Angle::new::<radian>(0.0),
+                Angle::new::<radian>(0.0),
+                Angle::new::<radian>(FRAC_PI_2),
+            ),
+        );
+        let p = Vector3::<Length>::from_values([1.0, 0.0, 0.0]);
+        assert_close(t.apply(&p).values(), [1.0, 1.0, 0.0]);
+        let back = t.inverse().apply(&t.apply(&p));
+        assert_close(back.values(), p.values());
+        let twice = t.compose(&t);
+        assert_close(twice.apply(&p).values(), [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let a = Transform::default();
+        let b = Transform::new(
+            Vector3::from_values([2.0, 0.0, 0.0]),
+            Quaternion::from_rotation_vector([0.0, 0.0, FRAC_PI_2]),
+        );
+        let half = a.interpolate(&b, 0.5);
+        assert_close(half.translation.values(), [1.0, 0.0, 0.0]);
+        assert_close(
+            half.rotation.to_rotation_vector(),
+            [0.0, 0.0, FRAC_PI_2 / 2.0],
+        );
+        let extrapolated = a.interpolate(&b, 1.5);
+        assert_close(extrapolated.translation.values(), [3.0, 0.0, 0.0]);
+        assert_close(
+            extrapolated.rotation.to_rotation_vector(),
+            [0.0, 0.0, FRAC_PI_2 * 1.5],
+        );
+    }
+
+    #[test]
+    fn test_odometry_encoding() {
+        let odometry = Odometry {
+            pose: Transform::new(
+                Vector3::from_values([1.0, 2.0, 3.0]),
+                Quaternion::new(0.0, 1.0, 0.0, 0.0),
+            ),
+            twist: Twist {
+                linear: Vector3::from_values([0.5, 0.0, 0.0]),
+                angular: Vector3::from_values([0.0, 0.0, 0.1]),
+            },
+        };
+        let config = bincode::config::standard();
+        let encoded = bincode::encode_to_vec(odometry, config).unwrap();
+        assert_eq!(encoded.len(), 13 * 4);
+        let (decoded, _): (Odometry, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded, odometry);
+    }
+}
@@ -0,0 +1,228 @@
+use bincode::de::read::Reader;
+use bincode::de::{BorrowDecoder, Decoder};
+use bincode::enc::write::Writer;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{BorrowDecode, Decode, Encode};
+use cu29::CuResult;
+use std::fmt::{Debug, Formatter};
+
+/// The layout of the pixels of an Image.
+#[derive(Default, PartialEq, Eq, Debug, Copy, Clone, Encode, Decode)]
+pub enum ImageFormat {
+    #[default]
+    Gray8,
+    Gray16,
+    Rgb8,
+    Bgr8,
+    Rgba8,
+    /// YUV 4:2:2, 2 pixels packed in 4 bytes.
+    Yuyv,
+}
+
+impl ImageFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            ImageFormat::Gray8 => 1,
+            ImageFormat::Gray16 | ImageFormat::Yuyv => 2,
+            ImageFormat::Rgb8 | ImageFormat::Bgr8 => 3,
+            ImageFormat::Rgba8 => 4,
+        }
+    }
+}
+
+/// Standardized image with a fixed capacity of N bytes for its pixels so it can live in a copperlist.
+/// The rows are contiguous, without padding.
+/// Only the bytes actually used by the image are encoded.
+#[derive(Clone)]
+pub struct Image<const N: usize> {
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+    data: [u8; N],
+}
+
+impl<const N: usize> Default for Image<N> {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            format: ImageFormat::default(),
+            data: [0; N],
+        }
+    }
+}
+
+impl<const N: usize> Image<N> {
+    /// A black image, it fails if it doesn't fit in N bytes.
+    pub fn new(width: u32, height: u32, format: ImageFormat) -> CuResult<Self> {
+        let mut image = Self::default();
+        image.reshape(width, height, format)?;
+        Ok(image)
+    }
+
+    /// An image copied from packed pixels.
+    pub fn from_bytes(
+        width: u32,
+        height: u32,
+        format: ImageFormat,
+        bytes: &[u8],
+    ) -> CuResult<Self> {
+        let mut image = Self::new(width, height, format)?;
+        if bytes.len() != image.len() {
+            return Err(format!(
+                "Expected {} bytes for a {}x{} {:?} image, got {}",
+                image.len(),
+                width,
+                height,
+                format,
+                bytes.len()
+            )
+            .into());
+        }
+        image.pixels_mut().copy_from_slice(bytes);
+        Ok(image)
+    }
+
+    /// Changes the dimensions of the image in place, the content of the pixels is unspecified after that.
+    pub fn reshape(&mut self, width: u32, height: u32, format: ImageFormat) -> CuResult<()> {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(format.bytes_per_pixel()))
+            .ok_or_else(|| format!("A {width}x{height} {format:?} image is too large"))?;
+        if len > N {
+            return Err(format!(
+                "A {}x{} {:?} image needs {} bytes, the capacity is {}",
+                width, height, format, len, N
+            )
+            .into());
+        }
+        self.width = width;
+        self.height = height;
+        self.format = format;
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    /// The number of bytes of a row.
+    pub fn stride(&self) -> usize {
+        self.width as usize * self.format.bytes_per_pixel()
+    }
+
+    /// The number of bytes used by the pixels.
+    pub fn len(&self) -> usize {
+        self.stride() * self.height as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.data[..self.len()]
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        let len = self.len();
+        &mut self.data[..len]
+    }
+
+    /// The bytes of the pixel at (x, y), None if it is out of the image.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let bpp = self.format.bytes_per_pixel();
+        let start = y as usize * self.stride() + x as usize * bpp;
+        Some(&self.data[start..start + bpp])
+    }
+}
+
+/// Don't dump the pixels.
+impl<const N: usize> Debug for Image<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("format", &self.format)
+            .field("bytes", &format_args!("{}/{}", self.len(), N))
+            .finish()
+    }
+}
+
+impl<const N: usize> PartialEq for Image<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.format == other.format
+            && self.pixels() == other.pixels()
+    }
+}
+
+impl<const N: usize> Encode for Image<N> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.width.encode(encoder)?;
+        self.height.encode(encoder)?;
+        self.format.encode(encoder)?;
+        encoder.writer().write(self.pixels())
+    }
+}
+
+impl<const N: usize> Decode for Image<N> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let width = u32::decode(decoder)?;
+        let height = u32::decode(decoder)?;
+        let format = ImageFormat::decode(decoder)?;
+        let mut image = Self::default();
+        image
+            .reshape(width, height, format)
+            .map_err(|e| DecodeError::OtherString(e.to_string()))?;
+        let len = image.len();
+        decoder.claim_bytes_read(len)?;
+        decoder.reader().read(&mut image.data[..len])?;
+        Ok(image)
+    }
+}
+
+impl<'de, const N: usize> BorrowDecode<'de> for Image<N> {
+    fn borrow_decode<BD: BorrowDecoder<'de>>(decoder: &mut BD) -> Result<Self, DecodeError> {
+        Decode::decode(decoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_encoding() {
+        let bytes: Vec<u8> = (0..2 * 3 * 3).collect();
+        let image = Image::<1024>::from_bytes(2, 3, ImageFormat::Rgb8, &bytes).unwrap();
+        assert_eq!(image.pixel(1, 2), Some(&bytes[15..18]));
+        assert_eq!(image.pixel(2, 0), None);
+
+        let config = bincode::config::standard();
+        let encoded = bincode::encode_to_vec(&image, config).unwrap();
+        // only the used bytes are encoded.
+        assert!(encoded.len() < 32);
+        let (decoded, _): (Image<1024>, usize) =
+            bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded, image);
+
+        // it doesn't fit.
+        assert!(bincode::decode_from_slice::<Image<16>, _>(&encoded, config).is_err());
+        assert!(Image::<16>::new(4, 4, ImageFormat::Gray16).is_err());
+        assert!(Image::<16>::new(u32::MAX, u32::MAX, ImageFormat::Rgba8).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+use crate::{Quaternion, Vector3};
+use bincode::{Decode, Encode};
+use uom::si::f32::{Acceleration, AngularVelocity, MagneticFluxDensity};
+
+/// Standardized IMU reading, expressed in the frame of the sensor.
+/// The fields a sensor doesn't provide are left to None.
+#[derive(Default, PartialEq, Debug, Copy, Clone, Encode, Decode)]
+pub struct ImuPayload {
+    /// Specific force, it includes the gravity.
+    pub acceleration: Vector3<Acceleration>,
+    pub angular_velocity: Vector3<AngularVelocity>,
+    pub magnetic_field: Option<Vector3<MagneticFluxDensity>>,
+    /// The orientation estimated by the sensor itself if it has a fusion engine.
+    pub orientation: Option<Quaternion>,
+}
@@ -0,0 +1,68 @@
+use crate::{decode_quantity, encode_quantity};
+use bincode::de::{BorrowDecoder, Decoder};
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{BorrowDecode, Decode, Encode};
+use uom::si::f32::{Angle, AngularVelocity, Torque};
+
+/// The state of a revolute joint (a servo, a wheel, an arm joint...).
+#[derive(Default, PartialEq, Debug, Copy, Clone)]
+pub struct Joint {
+    pub position: Angle,
+    pub velocity: AngularVelocity,
+    pub effort: Torque,
+}
+
+impl Encode for Joint {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        encode_quantity(&self.position, encoder)?;
+        encode_quantity(&self.velocity, encoder)?;
+        encode_quantity(&self.effort, encoder)
+    }
+}
+
+impl Decode for Joint {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Joint {
+            position: decode_quantity(decoder)?,
+            velocity: decode_quantity(decoder)?,
+            effort: decode_quantity(decoder)?,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(Joint);
+
+/// The state of the N joints of a mechanism, in the order of their ids.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct JointState<const N: usize> {
+    pub joints: [Joint; N],
+}
+
+impl<const N: usize> Default for JointState<N> {
+    fn default() -> Self {
+        Self {
+            joints: [Joint::default(); N],
+        }
+    }
+}
+
+impl<const N: usize> Encode for JointState<N> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.joints.encode(encoder)
+    }
+}
+
+impl<const N: usize> Decode for JointState<N> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            joints: Decode::decode(decoder)?,
+        })
+    }
+}
+
+impl<'de, const N: usize> BorrowDecode<'de> for JointState<N> {
+    fn borrow_decode<BD: BorrowDecoder<'de>>(decoder: &mut BD) -> Result<Self, DecodeError> {
+        Decode::decode(decoder)
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{decode_quantity, encode_quantity, Distance, Reflectivity, Vector3};
+use bincode::de::{BorrowDecoder, Decoder};
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{BorrowDecode, Decode, Encode};
+use cu29::CuResult;
+use std::fmt::{Debug, Formatter};
+use uom::si::angle::radian;
+use uom::si::f32::{Angle, Length};
+
+/// Standardized planar scan of a 2D lidar with up to N beams, counterclockwise from angle_min.
+/// Only the beams actually used are encoded.
+#[derive(Clone)]
+pub struct LaserScan<const N: usize> {
+    pub angle_min: Angle,
+    pub angle_increment: Angle,
+    /// The ranges out of [range_min, range_max] are invalid measurements.
+    pub range_min: Length,
+    pub range_max: Length,
+    len: usize,
+    ranges: [Distance; N],
+    intensities: [Reflectivity; N],
+}
+
+impl<const N: usize> Default for LaserScan<N> {
+    fn default() -> Self {
+        Self {
+            angle_min: Angle::default(),
+            angle_increment: Angle::default(),
+            range_min: Length::default(),
+            range_max: Length::default(),
+            len: 0,
+            ranges: [Distance::default(); N],
+            intensities: [Reflectivity::default(); N],
+        }
+    }
+}
+
+impl<const N: usize> LaserScan<N> {
+    pub fn new(
+        angle_min: Angle,
+        angle_increment: Angle,
+        range_min: Length,
+        range_max: Length,
+    ) -> Self {
+        Self {
+            angle_min,
+            angle_increment,
+            range_min,
+            range_max,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Adds the next beam, it fails if the scan is full.
+    pub fn push(&mut self, range: Distance, intensity: Reflectivity) -> CuResult<()> {
+        if self.len == N {
+            return Err(format!("LaserScan is full ({} beams)", N).into());
+        }
+        self.ranges[self.len] = range;
+        self.intensities[self.len] = intensity;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn ranges(&self) -> &[Distance] {
+        &self.ranges[..self.len]
+    }
+
+    pub fn intensities(&self) -> &[Reflectivity] {
+        &self.intensities[..self.len]
+    }
+
+    /// The angle of the beam i.
+    pub fn angle(&self, i: usize) -> Angle {
+        self.angle_min + self.angle_increment * i as f32
+    }
+
+    pub fn is_valid(&self, i: usize) -> bool {
+        i < self.len && self.ranges[i].0 >= self.range_min && self.ranges[i].0 <= self.range_max
+    }
+
+    /// The beam i as a point in the frame of the sensor (z = 0), None if the measurement is invalid.
+    pub fn point(&self, i: usize) -> Option<Vector3<Length>> {
+        if !self.is_valid(i) {
+            return None;
+        }
+        let (sin, cos) = self.angle(i).get::<radian>().sin_cos();
+        let r = self.ranges[i].0;
+        Some(Vector3::new(r * cos, r * sin, Length::default()))
+    }
+}
+
+/// Only the beams in use.
+impl<const N: usize> Debug for LaserScan<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LaserScan")
+            .field("angle_min", &self.angle_min)
+            .field("angle_increment", &self.angle_increment)
+            .field("range_min", &self.range_min)
+            .field("range_max", &self.range_max)
+            .field("ranges", &self.ranges())
+            .field("intensities", &self.intensities())
+            .finish()
+    }
+}
+
+impl<const N: usize> PartialEq for LaserScan<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.angle_min == other.angle_min
+            && self.angle_increment == other.angle_increment
+            && self.range_min == other.range_min
+            && self.range_max == other.range_max
+            && self.ranges() == other.ranges()
+            && self.intensities() == other.intensities()
+    }
+}
+
+impl<const N: usize> Encode for LaserScan<N> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        encode_quantity(&self.angle_min, encoder)?;
+        encode_quantity(&self.angle_increment, encoder)?;
+        encode_quantity(&self.range_min, encoder)?;
+        encode_quantity(&self.range_max, encoder)?;
+        self.len.encode(encoder)?;
+        for i in 0..self.len {
+            self.ranges[i].encode(encoder)?;
+            self.intensities[i].encode(encoder)?;
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Decode for LaserScan<N> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let mut scan = Self::new(
+            decode_quantity(decoder)?,
+            decode_quantity(decoder)?,
+            decode_quantity(decoder)?,
+            decode_quantity(decoder)?,
+        );
+        let len = usize::decode(decoder)?;
+        if len > N {
+            return Err(DecodeError::ArrayLengthMismatch {
+                required: N,
+                found: len,
+            });
+        }
+        for i in 0..len {
+            scan.ranges[i] = Decode::decode(decoder)?;
+            scan.intensities[i] = Decode::decode(decoder)?;
+        }
+        scan.len = len;
+        Ok(scan)
+    }
+}
+
+impl<'de, const N: usize> BorrowDecode<'de> for LaserScan<N> {
+    fn borrow_decode<BD: BorrowDecoder<'de>>(decoder: &mut BD) -> Result<Self, DecodeError> {
+        Decode::decode(decoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+    use uom::si::length::meter;
+
+    #[test]
+    fn test_laserscan() {
+        let mut scan = LaserScan::<360>::new(
+            Angle::new::<radian>(0.0),
+            Angle::new::<radian>(FRAC_PI_2),
+            Length::new::<meter>(0.1),
+            Length::new::<meter>(10.0),
+        );
+        scan.push(2.0.into(), 50.0.into()).unwrap();
+        scan.push(3.0.into(), 50.0.into()).unwrap();
+        scan.push(20.0.into(), 0.0.into()).unwrap(); // out of range
+
+        let p = scan.point(1).unwrap();
+        assert!(p.x.value.abs() < 1e-5);
+        assert!((p.y.value - 3.0).abs() < 1e-5);
+        assert!(scan.point(2).is_none());
+
+        let config = bincode::config::standard();
+        let encoded = bincode::encode_to_vec(&scan, config).unwrap();
+        assert_eq!(encoded.len(), 4 * 4 + 1 + 3 * 2 * 4);
+        let (decoded, _): (LaserScan<360>, usize) =
+            bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded, scan);
+    }
+}
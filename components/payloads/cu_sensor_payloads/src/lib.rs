@@ -1,3 +1,17 @@
+mod battery;
+mod geometry;
+mod image;
+mod imu;
+mod joint;
+mod laserscan;
+
+pub use battery::*;
+pub use geometry::*;
+pub use image::*;
+pub use imu::*;
+pub use joint::*;
+pub use laserscan::*;
+
 use bincode::de::{BorrowDecoder, Decoder};
 use bincode::enc::Encoder;
 use bincode::error::{DecodeError, EncodeError};
@@ -7,7 +21,46 @@ use cu29_soa_derive::Soa;
 use derive_more::{Add, Deref, Div, From, Mul, Sub};
 use uom::si::f32::{Length, Ratio};
 use uom::si::length::meter;
-use uom::si::ratio::percent;
+use uom::si::ratio::{percent, ratio};
+use uom::si::{Dimension, Quantity, Units};
+
+/// Builds a uom quantity from its value in the base SI unit of its dimension (m, rad, m/s...).
+pub(crate) fn quantity<D, U>(value: f32) -> Quantity<D, U, f32>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+{
+    Quantity {
+        dimension: Default::default(),
+        units: Default::default(),
+        value,
+    }
+}
+
+/// Encodes a uom quantity as a f32 in the base SI unit of its dimension.
+pub(crate) fn encode_quantity<D, U, E>(
+    quantity: &Quantity<D, U, f32>,
+    encoder: &mut E,
+) -> Result<(), EncodeError>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+    E: Encoder,
+{
+    Encode::encode(&quantity.value, encoder)
+}
+
+/// Decodes a uom quantity encoded by encode_quantity.
+pub(crate) fn decode_quantity<D, U, De>(
+    decoder: &mut De,
+) -> Result<Quantity<D, U, f32>, DecodeError>
+where
+    D: Dimension + ?Sized,
+    U: Units<f32> + ?Sized,
+    De: Decoder,
+{
+    Ok(quantity(Decode::decode(decoder)?))
+}
 
 #[derive(Default, PartialEq, Debug, Copy, Clone, Add, Deref, Sub, From, Mul, Div)]
 pub struct Reflectivity(Ratio);
@@ -25,19 +78,19 @@ impl Encode for Reflectivity {
     }
 }
 
-/// Decode as f32
+/// Decode as f32, the ratio itself as it is encoded, not in percent
 impl Decode for Reflectivity {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
         let value: f32 = Decode::decode(decoder)?;
-        Ok(Reflectivity(Ratio::new::<percent>(value)))
+        Ok(Reflectivity(Ratio::new::<ratio>(value)))
     }
 }
 
-/// Decode as f32
+/// Decode as f32, the ratio itself as it is encoded, not in percent
 impl<'de> BorrowDecode<'de> for Reflectivity {
     fn borrow_decode<D: BorrowDecoder<'de>>(decoder: &mut D) -> Result<Self, DecodeError> {
         let value: f32 = Decode::decode(decoder)?;
-        Ok(Reflectivity(Ratio::new::<percent>(value)))
+        Ok(Reflectivity(Ratio::new::<ratio>(value)))
     }
 }
 
@@ -119,43 +172,9 @@ impl PointCloud {
 }
 
 impl<const N: usize> PointCloudSoa<N> {
-    /// Sort in place the point cloud so it can be ready for merge sorts for example
+    /// Sort in place the point cloud by tov so it can be ready for merge sorts for example
     pub fn sort(&mut self) {
-        self.quicksort(0, N - 1);
-    }
-
-    /// Implementation of the sort
-    fn quicksort(&mut self, low: usize, high: usize) {
-        if low < high {
-            let pivot_index = self.partition(low, high);
-            if pivot_index > 0 {
-                self.quicksort(low, pivot_index - 1);
-            }
-            self.quicksort(pivot_index + 1, high);
-        }
-    }
-
-    /// Used by quicksort.
-    fn partition(&mut self, low: usize, high: usize) -> usize {
-        let pivot = self.tov[high];
-        let mut i = low;
-        for j in low..high {
-            if self.tov[j] <= pivot {
-                self.swap(i, j);
-                i += 1;
-            }
-        }
-        self.swap(i, high);
-        i
-    }
-
-    /// swap the elements at index a and b
-    pub fn swap(&mut self, a: usize, b: usize) {
-        self.tov.swap(a, b);
-        self.x.swap(a, b);
-        self.y.swap(a, b);
-        self.z.swap(a, b);
-        self.i.swap(a, b);
+        self.sort_by_key(|p| *p.tov);
     }
 }
 
@@ -191,4 +210,35 @@ mod tests {
             bincode::encode_into_slice(a, &mut encoded, bincode::config::standard()).unwrap();
         assert_eq!(length, 4);
     }
+
+    #[test]
+    fn test_reflectivity_roundtrip() {
+        let reflectivity = Reflectivity(Ratio::new::<percent>(42.0));
+        let encoded = bincode::encode_to_vec(reflectivity, bincode::config::standard()).unwrap();
+        let (decoded, _): (Reflectivity, usize) =
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+        assert!((decoded.get::<percent>() - 42.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut cloud = PointCloudSoa::<8>::default();
+        for (tov, x) in [(3, 3.0), (1, 1.0), (2, 2.0)] {
+            cloud.push(PointCloud::new(
+                CuDuration(tov),
+                x,
+                0.0,
+                0.0,
+                0.0,
+                Some(tov as u8),
+            ));
+        }
+        cloud.sort();
+        assert_eq!(cloud.len(), 3);
+        let points: Vec<(u64, f32, u8)> = cloud
+            .iter()
+            .map(|p| (p.tov.0, p.x.value, p.return_order))
+            .collect();
+        assert_eq!(points, vec![(1, 1.0, 1), (2, 2.0, 2), (3, 3.0, 3)]);
+    }
 }
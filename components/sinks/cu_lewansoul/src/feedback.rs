@@ -0,0 +1,278 @@
+use crate::{open_bus, servo_ids, timeout, LewansoulBus, MAX_SERVOS};
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use cu29::clock::RobotClock;
+use cu29::config::ComponentConfig;
+use cu29::cutask::{CuMsg, CuSrcTask, Freezable};
+use cu29::{output_msg, CuResult};
+use std::io;
+use uom::si::angle::radian;
+use uom::si::electric_potential::volt;
+use uom::si::f32::{Angle, ElectricPotential, ThermodynamicTemperature};
+use uom::si::thermodynamic_temperature::kelvin;
+
+/// How the last poll of a servo went.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Encode, Decode)]
+pub enum ServoStatus {
+    /// No servo is configured at this index.
+    #[default]
+    Unused,
+    Ok,
+    /// The servo did not answer in time.
+    Timeout,
+    /// The answer was corrupted or was not the one expected.
+    InvalidResponse,
+}
+
+/// The state of a servo read back from the bus.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ServoFeedback {
+    pub id: u8,
+    pub status: ServoStatus,
+    /// Only valid if the status is Ok.
+    pub position: Angle,
+    /// None if not polled or if it could not be read.
+    pub temperature: Option<ThermodynamicTemperature>,
+    pub voltage: Option<ElectricPotential>,
+    /// The number of polls in a row that failed.
+    pub consecutive_errors: u32,
+}
+
+/// Encodes the quantities as f32 in rad, K and V.
+impl Encode for ServoFeedback {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.id.encode(encoder)?;
+        self.status.encode(encoder)?;
+        self.position.value.encode(encoder)?;
+        self.temperature.map(|t| t.value).encode(encoder)?;
+        self.voltage.map(|v| v.value).encode(encoder)?;
+        self.consecutive_errors.encode(encoder)
+    }
+}
+
+impl Decode for ServoFeedback {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(ServoFeedback {
+            id: Decode::decode(decoder)?,
+            status: Decode::decode(decoder)?,
+            position: Angle::new::<radian>(Decode::decode(decoder)?),
+            temperature: Option::<f32>::decode(decoder)?
+                .map(ThermodynamicTemperature::new::<kelvin>),
+            voltage: Option::<f32>::decode(decoder)?.map(ElectricPotential::new::<volt>),
+            consecutive_errors: Decode::decode(decoder)?,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(ServoFeedback);
+
+/// The feedback of the servos in the order of the config ("servo0", "servo1"...).
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct ServoFeedbackPayload {
+    pub servos: [ServoFeedback; MAX_SERVOS],
+}
+
+/// The positions in radians, to compare them with the ServoPositionsPayload sent to the servos.
+impl From<&ServoFeedbackPayload> for [f32; MAX_SERVOS] {
+    fn from(payload: &ServoFeedbackPayload) -> Self {
+        payload.servos.map(|s| s.position.get::<radian>())
+    }
+}
+
+/// Polls the positions, temperatures and voltages of the Lewansoul servos.
+/// It can share the serial device with the Lewansoul sink.
+///
+/// A servo that does not answer is reported with its status, the task only fails if a servo
+/// misses more than "max_consecutive_errors" polls in a row when it is set.
+pub struct LewansoulFeedback {
+    bus: LewansoulBus,
+    read_temperature: bool,
+    read_voltage: bool,
+    max_consecutive_errors: Option<u32>,
+    count: usize,
+    // Internal state
+    servos: [ServoFeedback; MAX_SERVOS],
+}
+
+impl LewansoulFeedback {
+    fn with_bus(config: &ComponentConfig, mut bus: LewansoulBus) -> CuResult<Self> {
+        let (ids, count) = servo_ids(config)?;
+        bus.set_timeout(timeout(config))
+            .map_err(|e| format!("Could not set the timeout of the serial port: {e}"))?;
+        let mut servos = [ServoFeedback::default(); MAX_SERVOS];
+        for (servo, id) in servos.iter_mut().zip(&ids[..count]) {
+            servo.id = *id;
+        }
+        Ok(Self {
+            bus,
+            read_temperature: config.get::<bool>("read_temperature").unwrap_or(true),
+            read_voltage: config.get::<bool>("read_voltage").unwrap_or(true),
+            max_consecutive_errors: config.get::<u32>("max_consecutive_errors"),
+            count,
+            servos,
+        })
+    }
+
+    fn poll(&mut self, index: usize) {
+        let servo = &mut self.servos[index];
+        let id = servo.id;
+        let bus = &mut self.bus;
+        let polled = bus.pos_read(id).and_then(|position| {
+            servo.position = position;
+            servo.temperature = None;
+            servo.voltage = None;
+            if self.read_temperature {
+                servo.temperature = Some(bus.temp_read(id)?);
+            }
+            if self.read_voltage {
+                servo.voltage = Some(bus.vin_read(id)?);
+            }
+            Ok(())
+        });
+        match polled {
+            Ok(()) => {
+                servo.status = ServoStatus::Ok;
+                servo.consecutive_errors = 0;
+            }
+            Err(e) => {
+                servo.status = match e.kind() {
+                    io::ErrorKind::TimedOut => ServoStatus::Timeout,
+                    _ => ServoStatus::InvalidResponse,
+                };
+                servo.consecutive_errors += 1;
+            }
+        }
+    }
+}
+
+impl Freezable for LewansoulFeedback {
+    // The output of a source is replayed in resim, there is nothing to restore.
+}
+
+impl<'cl> CuSrcTask<'cl> for LewansoulFeedback {
+    type Output = output_msg!('cl, ServoFeedbackPayload);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config =
+            config.ok_or("LewansoulFeedback needs a config, None was passed as ComponentConfig")?;
+        Self::with_bus(config, open_bus(config)?)
+    }
+
+    fn process(&mut self, clock: &RobotClock, new_msg: Self::Output) -> CuResult<()> {
+        new_msg.metadata.tov = clock.now().into();
+        for index in 0..self.count {
+            self.poll(index);
+            let servo = &self.servos[index];
+            if let Some(max) = self.max_consecutive_errors {
+                if servo.consecutive_errors > max {
+                    return Err(format!(
+                        "The servo {} did not answer properly to {} polls in a row ({:?}).",
+                        servo.id, servo.consecutive_errors, servo.status
+                    )
+                    .into());
+                }
+            }
+        }
+        new_msg.set_payload(ServoFeedbackPayload {
+            servos: self.servos,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockBus, MockServo};
+    use uom::si::angle::degree;
+    use uom::si::thermodynamic_temperature::degree_celsius;
+
+    fn config() -> ComponentConfig {
+        let mut config = ComponentConfig::default();
+        config.set("servo0", 1u8);
+        config.set("servo1", 2u8);
+        config
+    }
+
+    #[test]
+    fn test_polls_the_servos() {
+        let mock = MockBus::with_servos(&[1, 2]);
+        mock.set_servo(
+            2,
+            MockServo {
+                position: 250,
+                temperature: 60,
+                vin: 11100,
+                ..Default::default()
+            },
+        );
+        let mut feedback =
+            LewansoulFeedback::with_bus(&config(), LewansoulBus::new(Box::new(mock.clone())))
+                .unwrap();
+        let mut msg = CuMsg::<ServoFeedbackPayload>::default();
+        feedback.process(&RobotClock::new(), &mut msg).unwrap();
+        let servos = msg.payload().unwrap().servos;
+        assert_eq!(servos[0].status, ServoStatus::Ok);
+        let servo = servos[1];
+        assert_eq!((servo.id, servo.status), (2, ServoStatus::Ok));
+        assert!((servo.position.get::<degree>() - 60.0).abs() < 1e-3);
+        assert!((servo.temperature.unwrap().get::<degree_celsius>() - 60.0).abs() < 1e-3);
+        assert!((servo.voltage.unwrap().get::<volt>() - 11.1).abs() < 1e-3);
+        assert_eq!(servos[2].status, ServoStatus::Unused);
+
+        // the payload survives the log.
+        let encoded =
+            bincode::encode_to_vec(msg.payload().unwrap(), bincode::config::standard()).unwrap();
+        let (decoded, _): (ServoFeedbackPayload, usize) =
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+        assert!(
+            (decoded.servos[1]
+                .temperature
+                .unwrap()
+                .get::<degree_celsius>()
+                - 60.0)
+                .abs()
+                < 1e-3
+        );
+    }
+
+    #[test]
+    fn test_per_servo_errors() {
+        let mock = MockBus::with_servos(&[1, 2]);
+        let mut config = config();
+        config.set("max_consecutive_errors", 1u32);
+        config.set("read_voltage", false);
+        let mut feedback =
+            LewansoulFeedback::with_bus(&config, LewansoulBus::new(Box::new(mock.clone())))
+                .unwrap();
+        let clock = RobotClock::new();
+        let mut msg = CuMsg::<ServoFeedbackPayload>::default();
+
+        // a glitch on one servo does not affect the others.
+        mock.corrupt_next_response();
+        feedback.process(&clock, &mut msg).unwrap();
+        let servos = msg.payload().unwrap().servos;
+        assert_eq!(servos[0].status, ServoStatus::InvalidResponse);
+        assert_eq!(servos[1].status, ServoStatus::Ok);
+        assert_eq!(servos[1].voltage, None);
+
+        feedback.process(&clock, &mut msg).unwrap();
+        assert_eq!(msg.payload().unwrap().servos[0].status, ServoStatus::Ok);
+        assert_eq!(msg.payload().unwrap().servos[0].consecutive_errors, 0);
+
+        // a servo that stays silent is reported then fails the task.
+        mock.remove_servo(2);
+        feedback.process(&clock, &mut msg).unwrap();
+        let servo = msg.payload().unwrap().servos[1];
+        assert_eq!(
+            (servo.status, servo.consecutive_errors),
+            (ServoStatus::Timeout, 1)
+        );
+        assert!(feedback.process(&clock, &mut msg).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! An in memory bus of emulated servos, answering the packets written to it like the real ones.
+
+use crate::protocol::{compute_checksum, servo, ServoBus};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(unix)]
+use nix::pty::openpty;
+#[cfg(unix)]
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+#[cfg(unix)]
+use nix::unistd::ttyname;
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+#[cfg(unix)]
+use std::thread;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MockServo {
+    pub position: i16,
+    pub move_time: u16,
+    pub vin: u16,
+    pub temperature: u8,
+    pub mode: u8,
+    pub speed: i16,
+    pub led: u8,
+}
+
+impl Default for MockServo {
+    fn default() -> Self {
+        Self {
+            position: 0,
+            move_time: 0,
+            vin: 7400,
+            temperature: 35,
+            mode: 0,
+            speed: 0,
+            led: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    servos: BTreeMap<u8, MockServo>,
+    written: Vec<u8>,
+    to_read: VecDeque<u8>,
+    corrupt_next: bool,
+}
+
+/// Cloning it gives another handle on the same bus.
+#[derive(Clone, Default)]
+pub struct MockBus(Arc<Mutex<Inner>>);
+
+impl MockBus {
+    pub fn with_servos(ids: &[u8]) -> Self {
+        let bus = Self::default();
+        for id in ids {
+            bus.0
+                .lock()
+                .unwrap()
+                .servos
+                .insert(*id, MockServo::default());
+        }
+        bus
+    }
+
+    pub fn servo(&self, id: u8) -> MockServo {
+        self.0.lock().unwrap().servos[&id]
+    }
+
+    pub fn set_servo(&self, id: u8, servo: MockServo) {
+        self.0.lock().unwrap().servos.insert(id, servo);
+    }
+
+    pub fn remove_servo(&self, id: u8) {
+        self.0.lock().unwrap().servos.remove(&id);
+    }
+
+    /// The checksum of the next response will be wrong.
+    pub fn corrupt_next_response(&self) {
+        self.0.lock().unwrap().corrupt_next = true;
+    }
+}
+
+impl Inner {
+    fn handle(&mut self, id: u8, command: u8, params: &[u8]) {
+        let id = if id == servo::BROADCAST_ID {
+            match self.servos.keys().next() {
+                Some(id) => *id,
+                None => return,
+            }
+        } else {
+            id
+        };
+        let Some(state) = self.servos.get_mut(&id) else {
+            return;
+        };
+        let u16_at = |i: usize| u16::from_le_bytes([params[i], params[i + 1]]);
+        let response: Option<Vec<u8>> = match command {
+            servo::SERVO_MOVE_TIME_WRITE => {
+                state.position = u16_at(0) as i16;
+                state.move_time = u16_at(2);
+                None
+            }
+            servo::SERVO_MOVE_TIME_READ => {
+                Some([state.position.to_le_bytes(), state.move_time.to_le_bytes()].concat())
+            }
+            servo::SERVO_ID_READ => Some(vec![id]),
+            servo::SERVO_TEMP_READ => Some(vec![state.temperature]),
+            servo::SERVO_VIN_READ => Some(state.vin.to_le_bytes().to_vec()),
+            servo::SERVO_POS_READ => Some(state.position.to_le_bytes().to_vec()),
+            servo::SERVO_OR_MOTOR_MODE_WRITE => {
+                state.mode = params[0];
+                state.speed = u16_at(2) as i16;
+                None
+            }
+            servo::SERVO_OR_MOTOR_MODE_READ => {
+                Some([[state.mode, 0], state.speed.to_le_bytes()].concat())
+            }
+            servo::SERVO_LED_CTRL_WRITE => {
+                state.led = params[0];
+                None
+            }
+            servo::SERVO_LED_CTRL_READ => Some(vec![state.led]),
+            _ => None,
+        };
+        if let Some(params) = response {
+            let mut packet = vec![0x55, 0x55, id, params.len() as u8 + 3, command];
+            packet.extend(params);
+            let mut checksum = compute_checksum(packet[2..].iter().cloned());
+            if self.corrupt_next {
+                self.corrupt_next = false;
+                checksum = !checksum;
+            }
+            packet.push(checksum);
+            self.to_read.extend(packet);
+        }
+    }
+}
+
+impl Write for MockBus {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.0.lock().unwrap();
+        inner.written.extend_from_slice(buf);
+        while inner.written.len() >= 6 {
+            let length = inner.written[3] as usize;
+            if inner.written.len() < length + 3 {
+                break;
+            }
+            let packet: Vec<u8> = inner.written.drain(..length + 3).collect();
+            assert_eq!(&packet[..2], &[0x55, 0x55], "Invalid header");
+            assert_eq!(
+                compute_checksum(packet[2..length + 2].iter().cloned()),
+                packet[length + 2],
+                "Invalid checksum"
+            );
+            inner.handle(packet[2], packet[4], &packet[5..length + 2]);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for MockBus {
+    /// Times out like a serial port when no servo answered.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut inner = self.0.lock().unwrap();
+        if inner.to_read.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Operation timed out",
+            ));
+        }
+        let n = buf.len().min(inner.to_read.len());
+        for (byte, read) in buf.iter_mut().zip(inner.to_read.drain(..n)) {
+            *byte = read;
+        }
+        Ok(n)
+    }
+}
+
+impl ServoBus for MockBus {
+    fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().to_read.clear();
+        Ok(())
+    }
+}
+
+/// Emulated servos behind a pseudo terminal, to go through a real serial port.
+#[cfg(unix)]
+pub struct PtyServos {
+    pub mock: MockBus,
+    /// The serial device to open.
+    pub path: String,
+    master: File,
+    _slave: OwnedFd,
+}
+
+#[cfg(unix)]
+impl PtyServos {
+    pub fn with_servos(ids: &[u8]) -> Self {
+        let pty = openpty(None, None).unwrap();
+        let mut termios = tcgetattr(&pty.slave).unwrap();
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios).unwrap();
+        let path = ttyname(&pty.slave).unwrap().to_string_lossy().into_owned();
+        let master = File::from(pty.master);
+        let mock = MockBus::with_servos(ids);
+        let (mut port, mut bus) = (master.try_clone().unwrap(), mock.clone());
+        // Answers the packets until the pty is closed.
+        thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while let Ok(n @ 1..) = port.read(&mut buf) {
+                bus.write_all(&buf[..n]).unwrap();
+                while let Ok(n) = bus.read(&mut buf) {
+                    if port.write_all(&buf[..n]).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self {
+            mock,
+            path,
+            master,
+            _slave: pty.slave,
+        }
+    }
+
+    /// Sends bytes to the serial port as if a servo did.
+    pub fn inject(&mut self, bytes: &[u8]) {
+        self.master.write_all(bytes).unwrap();
+    }
+}
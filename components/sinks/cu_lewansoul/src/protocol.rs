@@ -0,0 +1,494 @@
+use cu29::{CuError, CuResult};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use uom::si::angle::degree;
+use uom::si::electric_potential::millivolt;
+use uom::si::f32::{Angle, ElectricPotential, ThermodynamicTemperature};
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+pub mod servo {
+    // From "lx-16a LewanSoul Bus Servo Communication Protocol.pdf"
+    pub const SERVO_MOVE_TIME_WRITE: u8 = 1; // 7 bytes
+    pub const SERVO_MOVE_TIME_READ: u8 = 2; // 3 bytes
+    pub const SERVO_MOVE_TIME_WAIT_WRITE: u8 = 7; // 7 bytes
+    pub const SERVO_MOVE_TIME_WAIT_READ: u8 = 8; // 3 bytes
+    pub const SERVO_MOVE_START: u8 = 11; // 3 bytes
+    pub const SERVO_MOVE_STOP: u8 = 12; // 3 bytes
+    pub const SERVO_ID_WRITE: u8 = 13; // 4 bytes
+    pub const SERVO_ID_READ: u8 = 14; // 3 bytes
+    pub const SERVO_ANGLE_OFFSET_ADJUST: u8 = 17; // 4 bytes
+    pub const SERVO_ANGLE_OFFSET_WRITE: u8 = 18; // 3 bytes
+    pub const SERVO_ANGLE_OFFSET_READ: u8 = 19; // 3 bytes
+    pub const SERVO_ANGLE_LIMIT_WRITE: u8 = 20; // 7 bytes
+    pub const SERVO_ANGLE_LIMIT_READ: u8 = 21; // 3 bytes
+    pub const SERVO_VIN_LIMIT_WRITE: u8 = 22; // 7 bytes
+    pub const SERVO_VIN_LIMIT_READ: u8 = 23; // 3 bytes
+    pub const SERVO_TEMP_MAX_LIMIT_WRITE: u8 = 24; // 4 bytes
+    pub const SERVO_TEMP_MAX_LIMIT_READ: u8 = 25; // 3 bytes
+    pub const SERVO_TEMP_READ: u8 = 0x1A; // 26 -> 3 bytes
+    pub const SERVO_VIN_READ: u8 = 0x1B; // 27 -> 3 bytes
+    pub const SERVO_POS_READ: u8 = 28; // 3 bytes
+    pub const SERVO_OR_MOTOR_MODE_WRITE: u8 = 29; // 7 bytes
+    pub const SERVO_OR_MOTOR_MODE_READ: u8 = 30; // 3 bytes
+    pub const SERVO_LOAD_OR_UNLOAD_WRITE: u8 = 31; // 4 bytes
+    pub const SERVO_LOAD_OR_UNLOAD_READ: u8 = 32; // 3 bytes
+    pub const SERVO_LED_CTRL_WRITE: u8 = 33; // 4 bytes
+    pub const SERVO_LED_CTRL_READ: u8 = 34; // 3 bytes
+    pub const SERVO_LED_ERROR_WRITE: u8 = 35; // 4 bytes
+    pub const SERVO_LED_ERROR_READ: u8 = 36; // 3 bytes
+
+    /// The id every servo answers to, only use it with a single servo on the bus.
+    pub const BROADCAST_ID: u8 = 0xFE;
+
+    /// Bits of the LED error alarm.
+    pub const LED_ERROR_OVER_TEMPERATURE: u8 = 1;
+    pub const LED_ERROR_OVER_VOLTAGE: u8 = 2;
+    pub const LED_ERROR_LOCKED_ROTOR: u8 = 4;
+}
+
+const SERIAL_SPEED: u32 = 115200; // only this speed is supported by the servos
+const HEADER: u8 = 0x55;
+const MAX_PARAMS: usize = 7;
+
+/// The serial link to the servos: a serial port or a mock in the tests.
+pub trait ServoBus: Read + Write + Send {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()>;
+
+    /// Drops what is left of a broken response.
+    fn clear_input(&mut self) -> io::Result<()>;
+}
+
+impl ServoBus for Box<dyn SerialPort> {
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        SerialPort::set_timeout(self.as_mut(), timeout)?;
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> io::Result<()> {
+        self.clear(ClearBuffer::Input)?;
+        Ok(())
+    }
+}
+
+/// Compute the checksum for the given data.
+/// The spec is "Checksum:The calculation method is as follows:
+// Checksum=~(ID+ Length+Cmd+ Prm1+...PrmN)If the numbers in the
+// brackets are calculated and exceeded 255,Then take the lowest one byte, "~"
+// means Negation."
+#[inline]
+pub(crate) fn compute_checksum(data: impl Iterator<Item = u8>) -> u8 {
+    let mut checksum: u8 = 0;
+    for byte in data {
+        checksum = checksum.wrapping_add(byte);
+    }
+    !checksum
+}
+
+// angle in degrees, returns position in 0.24 degrees
+#[inline]
+fn angle_to_position(angle: Angle) -> i16 {
+    let angle = angle.get::<degree>();
+    (angle * 1000.0 / 240.0) as i16
+}
+
+#[inline]
+fn position_to_angle(position: i16) -> Angle {
+    Angle::new::<degree>(position as f32 * 240.0 / 1000.0)
+}
+
+/// A response of a servo without its header and checksum.
+#[derive(Debug, Clone, Copy)]
+pub struct Response {
+    pub id: u8,
+    pub command: u8,
+    params: [u8; MAX_PARAMS],
+    len: usize,
+}
+
+impl Response {
+    pub fn params(&self) -> &[u8] {
+        &self.params[..self.len]
+    }
+
+    fn u16_at(&self, index: usize) -> u16 {
+        u16::from_le_bytes([self.params[index], self.params[index + 1]])
+    }
+
+    fn i16_at(&self, index: usize) -> i16 {
+        i16::from_le_bytes([self.params[index], self.params[index + 1]])
+    }
+}
+
+/// What a servo does with the SERVO_OR_MOTOR_MODE_WRITE command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServoMode {
+    /// Position control.
+    Servo,
+    /// Continuous rotation at a speed from -1000 to 1000.
+    Motor { speed: i16 },
+}
+
+/// The LewanSoul LX-16A, LX-225 etc. bus servo protocol on a serial link.
+/// The tasks use it under the hood, it can also be used directly to make setup tools (ids, offsets, limits...).
+pub struct LewansoulBus {
+    port: Box<dyn ServoBus>,
+}
+
+impl LewansoulBus {
+    pub fn new(port: Box<dyn ServoBus>) -> Self {
+        Self { port }
+    }
+
+    /// Opens the serial device shared with the other Lewansoul tasks on the same bus.
+    pub fn open(serial_dev: &str, timeout: Duration) -> CuResult<Self> {
+        let port = serialport::new(serial_dev, SERIAL_SPEED)
+            .data_bits(DataBits::Eight)
+            .flow_control(FlowControl::None)
+            .parity(Parity::None)
+            .stop_bits(StopBits::One)
+            .timeout(timeout)
+            // The sink and the feedback source can share the bus: they run one after the other.
+            .exclusive(false)
+            .open()
+            .map_err(|e| format!("Error opening serial port: {:?}", e))?;
+        Ok(Self::new(Box::new(port)))
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.port.set_timeout(timeout)
+    }
+
+    pub fn send_packet(&mut self, id: u8, command: u8, data: &[u8]) -> io::Result<()> {
+        if data.len() > MAX_PARAMS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Too many parameters",
+            ));
+        }
+        let mut packet = [0u8; MAX_PARAMS + 6];
+        packet[..5].copy_from_slice(&[HEADER, HEADER, id, data.len() as u8 + 3, command]);
+        packet[5..5 + data.len()].copy_from_slice(data);
+        let end = 5 + data.len();
+        packet[end] = compute_checksum(packet[2..end].iter().cloned());
+        self.port.write_all(&packet[..=end])?;
+        Ok(())
+    }
+
+    pub fn read_response(&mut self) -> io::Result<Response> {
+        let mut header = [0; 5];
+        self.port.read_exact(&mut header)?;
+        if header[0] != HEADER || header[1] != HEADER {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid header"));
+        }
+        let length = header[3] as usize;
+        if !(3..=MAX_PARAMS + 3).contains(&length) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid length"));
+        }
+        // the length counts itself, the command and the checksum.
+        let len = length - 3;
+        let mut remaining = [0; MAX_PARAMS + 1];
+        self.port.read_exact(&mut remaining[..=len])?;
+        let checksum = compute_checksum(header[2..].iter().chain(remaining[..len].iter()).cloned());
+        if checksum != remaining[len] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid checksum",
+            ));
+        }
+        let mut params = [0; MAX_PARAMS];
+        params[..len].copy_from_slice(&remaining[..len]);
+        Ok(Response {
+            id: header[2],
+            command: header[4],
+            params,
+            len,
+        })
+    }
+
+    /// Sends a read command and waits for the answer of this servo.
+    /// Stale bytes are dropped before sending, and what is left of the response on error,
+    /// so a late answer to a previous query can't be taken for this one.
+    pub fn query(&mut self, id: u8, command: u8, expected_len: usize) -> io::Result<Response> {
+        let response = self
+            .port
+            .clear_input()
+            .and_then(|_| self.send_packet(id, command, &[]))
+            .and_then(|_| self.read_response())
+            .and_then(|response| {
+                if (id != servo::BROADCAST_ID && response.id != id)
+                    || response.command != command
+                    || response.len < expected_len
+                {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Unexpected response",
+                    ))
+                } else {
+                    Ok(response)
+                }
+            });
+        if response.is_err() {
+            let _ = self.port.clear_input();
+        }
+        response
+    }
+
+    /// Moves to the position in the given time (up to 30s), right away.
+    pub fn move_time_write(&mut self, id: u8, position: Angle, time: Duration) -> io::Result<()> {
+        let [p0, p1] = angle_to_position(position).clamp(0, 1000).to_le_bytes();
+        let [t0, t1] = (time.as_millis().min(30000) as u16).to_le_bytes();
+        self.send_packet(id, servo::SERVO_MOVE_TIME_WRITE, &[p0, p1, t0, t1])
+    }
+
+    /// The last position and time given with SERVO_MOVE_TIME_WRITE.
+    pub fn move_time_read(&mut self, id: u8) -> io::Result<(Angle, Duration)> {
+        let response = self.query(id, servo::SERVO_MOVE_TIME_READ, 4)?;
+        Ok((
+            position_to_angle(response.i16_at(0)),
+            Duration::from_millis(response.u16_at(2) as u64),
+        ))
+    }
+
+    /// Like move_time_write but the servo waits for SERVO_MOVE_START to move.
+    pub fn move_time_wait_write(
+        &mut self,
+        id: u8,
+        position: Angle,
+        time: Duration,
+    ) -> io::Result<()> {
+        let [p0, p1] = angle_to_position(position).clamp(0, 1000).to_le_bytes();
+        let [t0, t1] = (time.as_millis().min(30000) as u16).to_le_bytes();
+        self.send_packet(id, servo::SERVO_MOVE_TIME_WAIT_WRITE, &[p0, p1, t0, t1])
+    }
+
+    pub fn move_time_wait_read(&mut self, id: u8) -> io::Result<(Angle, Duration)> {
+        let response = self.query(id, servo::SERVO_MOVE_TIME_WAIT_READ, 4)?;
+        Ok((
+            position_to_angle(response.i16_at(0)),
+            Duration::from_millis(response.u16_at(2) as u64),
+        ))
+    }
+
+    pub fn move_start(&mut self, id: u8) -> io::Result<()> {
+        self.send_packet(id, servo::SERVO_MOVE_START, &[])
+    }
+
+    pub fn move_stop(&mut self, id: u8) -> io::Result<()> {
+        self.send_packet(id, servo::SERVO_MOVE_STOP, &[])
+    }
+
+    /// This should only be use at HW setup. I leave it there for you to make a tool around it if needed.
+    pub fn id_write(&mut self, id: u8, new_id: u8) -> io::Result<()> {
+        self.send_packet(id, servo::SERVO_ID_WRITE, &[new_id])
+    }
+
+    /// The id of the servo, use the BROADCAST_ID to find the id of a single servo on the bus.
+    pub fn id_read(&mut self, id: u8) -> io::Result<u8> {
+        Ok(self.query(id, servo::SERVO_ID_READ, 1)?.params[0])
+    }
+
+    /// Adjusts the offset of the position (-30° to 30°), it is lost at power off unless written with angle_offset_write.
+    pub fn angle_offset_adjust(&mut self, id: u8, offset: Angle) -> io::Result<()> {
+        let offset = angle_to_position(offset).clamp(-125, 125) as i8;
+        self.send_packet(id, servo::SERVO_ANGLE_OFFSET_ADJUST, &[offset as u8])
+    }
+
+    /// Saves the adjusted offset.
+    pub fn angle_offset_write(&mut self, id: u8) -> io::Result<()> {
+        self.send_packet(id, servo::SERVO_ANGLE_OFFSET_WRITE, &[])
+    }
+
+    pub fn angle_offset_read(&mut self, id: u8) -> io::Result<Angle> {
+        let response = self.query(id, servo::SERVO_ANGLE_OFFSET_READ, 1)?;
+        Ok(position_to_angle(response.params[0] as i8 as i16))
+    }
+
+    pub fn angle_limit_write(&mut self, id: u8, min: Angle, max: Angle) -> io::Result<()> {
+        let [min0, min1] = angle_to_position(min).clamp(0, 1000).to_le_bytes();
+        let [max0, max1] = angle_to_position(max).clamp(0, 1000).to_le_bytes();
+        self.send_packet(
+            id,
+            servo::SERVO_ANGLE_LIMIT_WRITE,
+            &[min0, min1, max0, max1],
+        )
+    }
+
+    pub fn angle_limit_read(&mut self, id: u8) -> io::Result<(Angle, Angle)> {
+        let response = self.query(id, servo::SERVO_ANGLE_LIMIT_READ, 4)?;
+        Ok((
+            position_to_angle(response.i16_at(0)),
+            position_to_angle(response.i16_at(2)),
+        ))
+    }
+
+    /// The input voltage range (4.5V to 12V) out of which the servo unloads and blinks its LED.
+    pub fn vin_limit_write(
+        &mut self,
+        id: u8,
+        min: ElectricPotential,
+        max: ElectricPotential,
+    ) -> io::Result<()> {
+        let [min0, min1] = (min.get::<millivolt>() as u16).to_le_bytes();
+        let [max0, max1] = (max.get::<millivolt>() as u16).to_le_bytes();
+        self.send_packet(id, servo::SERVO_VIN_LIMIT_WRITE, &[min0, min1, max0, max1])
+    }
+
+    pub fn vin_limit_read(&mut self, id: u8) -> io::Result<(ElectricPotential, ElectricPotential)> {
+        let response = self.query(id, servo::SERVO_VIN_LIMIT_READ, 4)?;
+        Ok((
+            ElectricPotential::new::<millivolt>(response.u16_at(0) as f32),
+            ElectricPotential::new::<millivolt>(response.u16_at(2) as f32),
+        ))
+    }
+
+    /// The internal temperature (50°C to 100°C) over which the servo unloads and blinks its LED.
+    pub fn temp_max_limit_write(
+        &mut self,
+        id: u8,
+        max: ThermodynamicTemperature,
+    ) -> io::Result<()> {
+        let max = max.get::<degree_celsius>() as u8;
+        self.send_packet(id, servo::SERVO_TEMP_MAX_LIMIT_WRITE, &[max])
+    }
+
+    pub fn temp_max_limit_read(&mut self, id: u8) -> io::Result<ThermodynamicTemperature> {
+        let response = self.query(id, servo::SERVO_TEMP_MAX_LIMIT_READ, 1)?;
+        Ok(ThermodynamicTemperature::new::<degree_celsius>(
+            response.params[0] as f32,
+        ))
+    }
+
+    pub fn temp_read(&mut self, id: u8) -> io::Result<ThermodynamicTemperature> {
+        let response = self.query(id, servo::SERVO_TEMP_READ, 1)?;
+        Ok(ThermodynamicTemperature::new::<degree_celsius>(
+            response.params[0] as f32,
+        ))
+    }
+
+    pub fn vin_read(&mut self, id: u8) -> io::Result<ElectricPotential> {
+        let response = self.query(id, servo::SERVO_VIN_READ, 2)?;
+        Ok(ElectricPotential::new::<millivolt>(
+            response.u16_at(0) as f32
+        ))
+    }
+
+    /// The current position, it can be out of the 0-240° range when the servo is pushed.
+    pub fn pos_read(&mut self, id: u8) -> io::Result<Angle> {
+        let response = self.query(id, servo::SERVO_POS_READ, 2)?;
+        Ok(position_to_angle(response.i16_at(0)))
+    }
+
+    pub fn servo_or_motor_mode_write(&mut self, id: u8, mode: ServoMode) -> io::Result<()> {
+        let (mode, speed) = match mode {
+            ServoMode::Servo => (0, 0),
+            ServoMode::Motor { speed } => (1, speed.clamp(-1000, 1000)),
+        };
+        let [s0, s1] = speed.to_le_bytes();
+        self.send_packet(id, servo::SERVO_OR_MOTOR_MODE_WRITE, &[mode, 0, s0, s1])
+    }
+
+    pub fn servo_or_motor_mode_read(&mut self, id: u8) -> io::Result<ServoMode> {
+        let response = self.query(id, servo::SERVO_OR_MOTOR_MODE_READ, 4)?;
+        Ok(match response.params[0] {
+            0 => ServoMode::Servo,
+            _ => ServoMode::Motor {
+                speed: response.i16_at(2),
+            },
+        })
+    }
+
+    /// Powers the motor on (loaded) or off (unloaded, it can be moved by hand).
+    pub fn load_or_unload_write(&mut self, id: u8, load: bool) -> io::Result<()> {
+        self.send_packet(id, servo::SERVO_LOAD_OR_UNLOAD_WRITE, &[load as u8])
+    }
+
+    pub fn load_or_unload_read(&mut self, id: u8) -> io::Result<bool> {
+        Ok(self.query(id, servo::SERVO_LOAD_OR_UNLOAD_READ, 1)?.params[0] != 0)
+    }
+
+    pub fn led_ctrl_write(&mut self, id: u8, on: bool) -> io::Result<()> {
+        // 0 is on, 1 is off.
+        self.send_packet(id, servo::SERVO_LED_CTRL_WRITE, &[!on as u8])
+    }
+
+    pub fn led_ctrl_read(&mut self, id: u8) -> io::Result<bool> {
+        Ok(self.query(id, servo::SERVO_LED_CTRL_READ, 1)?.params[0] == 0)
+    }
+
+    /// Which faults (LED_ERROR_* bits) blink the LED.
+    pub fn led_error_write(&mut self, id: u8, faults: u8) -> io::Result<()> {
+        self.send_packet(id, servo::SERVO_LED_ERROR_WRITE, &[faults & 0b111])
+    }
+
+    pub fn led_error_read(&mut self, id: u8) -> io::Result<u8> {
+        Ok(self.query(id, servo::SERVO_LED_ERROR_READ, 1)?.params[0])
+    }
+
+    pub fn ping(&mut self, id: u8) -> CuResult<()> {
+        let response_id = self.id_read(id).map_err(|e| {
+            CuError::new_with_cause("IO Error trying to ping the servo on the bus", e)
+        })?;
+        if response_id == id {
+            Ok(())
+        } else {
+            Err(format!(
+                "The servo ID {} did not respond to ping got {} as ID instead.",
+                id, response_id
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockBus;
+    use uom::si::electric_potential::volt;
+
+    #[test]
+    fn test_reads() {
+        let mock = MockBus::with_servos(&[1]);
+        let mut bus = LewansoulBus::new(Box::new(mock.clone()));
+        bus.move_time_write(1, Angle::new::<degree>(120.0), Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(mock.servo(1).position, 500);
+        let position = bus.pos_read(1).unwrap();
+        assert!((position.get::<degree>() - 120.0).abs() < 1e-3);
+        let (position, time) = bus.move_time_read(1).unwrap();
+        assert!((position.get::<degree>() - 120.0).abs() < 1e-3);
+        assert_eq!(time, Duration::from_millis(500));
+        assert!((bus.vin_read(1).unwrap().get::<volt>() - 7.4).abs() < 1e-3);
+        assert!((bus.temp_read(1).unwrap().get::<degree_celsius>() - 35.0).abs() < 1e-3);
+        assert_eq!(bus.id_read(servo::BROADCAST_ID).unwrap(), 1);
+        bus.ping(1).unwrap();
+
+        bus.servo_or_motor_mode_write(1, ServoMode::Motor { speed: -300 })
+            .unwrap();
+        assert_eq!(
+            bus.servo_or_motor_mode_read(1).unwrap(),
+            ServoMode::Motor { speed: -300 }
+        );
+        bus.led_ctrl_write(1, false).unwrap();
+        assert!(!bus.led_ctrl_read(1).unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mock = MockBus::with_servos(&[1]);
+        let mut bus = LewansoulBus::new(Box::new(mock.clone()));
+        // nobody answers.
+        assert_eq!(bus.pos_read(2).unwrap_err().kind(), io::ErrorKind::TimedOut);
+        mock.corrupt_next_response();
+        assert_eq!(
+            bus.pos_read(1).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        // the bus recovers.
+        bus.pos_read(1).unwrap();
+        // a late answer to a previous query is not taken for the next one.
+        bus.send_packet(1, servo::SERVO_TEMP_READ, &[]).unwrap();
+        assert!((bus.pos_read(1).unwrap().get::<degree>() - 0.0).abs() < 1e-3);
+    }
+}
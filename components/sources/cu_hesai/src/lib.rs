@@ -1,7 +1,6 @@
 pub mod parser;
 
-use crate::parser::{generate_default_elevation_calibration, RefTime};
-use chrono::Utc;
+use crate::parser::generate_default_elevation_calibration;
 use cu29::prelude::*;
 use cu_sensor_payloads::{PointCloud, PointCloudSoa};
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
@@ -28,14 +27,27 @@ fn spherical_to_cartesian(
 
 pub struct Xt32 {
     socket: Socket,
-    reftime: RefTime,
+    calibration_service: ClockCalibrationService,
+    calibration: ClockCalibration,
+    calibration_generation: u64,
     channel_elevations: [Angle; 32],
 }
 
 impl Xt32 {
-    /// This give the matching timestamps between the UTC time to the Robot time.
+    /// Picks up the latest mapping between the UTC time of the lidar and the Robot time, and logs it if it changed.
     fn sync(&mut self, robot_clock: &RobotClock) {
-        self.reftime = (Utc::now(), robot_clock.now());
+        let generation = self.calibration_service.generation();
+        if generation == self.calibration_generation && generation != 0 {
+            return;
+        }
+        self.calibration = self
+            .calibration_service
+            .calibration_or_system_utc(robot_clock);
+        self.calibration_generation = self.calibration_service.generation();
+        info!(
+            "Hesai XT32 clock calibration: {}",
+            calibration = self.calibration
+        );
     }
 }
 
@@ -52,7 +64,8 @@ pub type LidarCuMsgPayload = PointCloudSoa<MAX_POINTS>;
 // t6 + 1512ns * (i-1) + 280ns
 fn channel_time(t6: CuTime, i: u64) -> CuTime {
     if i == 0 {
-        CuDuration(t6.0 - 1512 + 280) // this is an underflow, so we just subtract the value
+        // channel 0 fires before t6.
+        t6.saturating_sub(CuDuration(1512 - 280))
     } else {
         CuDuration(t6.0 + 1512 * (i - 1) + 280)
     }
@@ -77,10 +90,12 @@ impl<'cl> CuSrcTask<'cl> for Xt32 {
         socket.set_nonblocking(true).unwrap();
 
         // just a temporary value, it will be redone at start.
-        let rt: RefTime = (Utc::now(), RobotClock::new().now());
+        let calibration = ClockCalibration::from_reference(ExternalTimeBase::Utc, CuDuration(0), 0);
         Ok(Xt32 {
             socket,
-            reftime: rt,
+            calibration_service: ClockCalibrationService::global().clone(),
+            calibration,
+            calibration_generation: 0,
             channel_elevations: generate_default_elevation_calibration(), // TODO: make the config able to override that
         })
     }
@@ -88,7 +103,8 @@ impl<'cl> CuSrcTask<'cl> for Xt32 {
         self.sync(robot_clock);
         Ok(())
     }
-    fn process(&mut self, _clock: &RobotClock, new_msg: Self::Output) -> CuResult<()> {
+    fn process(&mut self, clock: &RobotClock, new_msg: Self::Output) -> CuResult<()> {
+        self.sync(clock);
         let payload = new_msg.payload_mut().insert(LidarCuMsgPayload::default());
         let mut buf = [0u8; 1500];
         match self.socket.read(&mut buf) {
@@ -97,7 +113,7 @@ impl<'cl> CuSrcTask<'cl> for Xt32 {
                     .map_err(|e| CuError::new_with_cause("Failed to parse Hesai UDP packet", e))?;
                 // this is the reference point for the block timings
                 let t6 = lidar_packet
-                    .block_ts(&self.reftime)
+                    .block_ts(&self.calibration)
                     .map_err(|e| CuError::new_with_cause("Failed to get block timings", e))?[5]; // 0 == channel 1, 5 == channel 6
 
                 let mut min_tov = CuTime::MAX;
@@ -144,7 +160,7 @@ impl<'cl> CuSrcTask<'cl> for Xt32 {
 mod tests {
     use super::*;
     use crate::parser::Packet;
-    use chrono::DateTime;
+    use chrono::{DateTime, Utc};
     use cu29::cutask::CuMsg;
     use cu_udp_inject::PcapStreamer;
 
@@ -164,7 +180,9 @@ mod tests {
             .unwrap()
             .with_timezone(&Utc);
 
-        xt32.reftime = (datetime, clock.now());
+        xt32.calibration_service = ClockCalibrationService::new(ExternalTimeBase::Utc, 1);
+        xt32.calibration_service
+            .add_sample(clock.now(), datetime.timestamp_nanos_opt().unwrap() as u64);
 
         // 1076 is the expected payload size for Hesai XT32
         const PACKET_SIZE: usize = size_of::<Packet>();
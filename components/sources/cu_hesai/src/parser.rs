@@ -1,6 +1,6 @@
 use bytemuck::{Pod, Zeroable};
 use chrono::{DateTime, MappedLocalTime, TimeZone, Utc};
-use cu29::prelude::{CuDuration, CuTime};
+use cu29::prelude::{ClockCalibration, CuDuration, CuTime};
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
@@ -348,22 +348,18 @@ impl Tail {
     }
 
     // Lidar timestamp to monotonic time of validity.
-    // RefTime is a tuple of (DateTime<Utc>, CuTime) to convert the UTC time to a monotonic time.
-    // You can create it and update it doing DateTime<Utc>::now() and CuTime::now() respectively
-    // if the system clock is precise enough and sync'ed with the lidar.
-    fn tov(&self, reftime: &(DateTime<Utc>, CuTime)) -> Result<CuTime, HesaiError> {
+    // The calibration maps the UTC time to the robot time, see ClockCalibrationService.
+    fn tov(&self, calibration: &ClockCalibration) -> Result<CuTime, HesaiError> {
         // This hesai API is terrible and based on UTC, here we give a function to convert it to a monotonic robot time.
         // UTC is corrected to match earth rotation so it is NOT suitable for robotic applications.
-        let (ref_date, ref_cu_time) = reftime;
-        let utc_tov = self.utc_tov()?;
-
-        let elapsed = utc_tov
-            .signed_duration_since(*ref_date)
-            .num_nanoseconds()
-            .unwrap() as u64;
-
-        let cu_time = *ref_cu_time + CuTime::from(elapsed);
-        Ok(cu_time)
+        let utc_tov = self
+            .utc_tov()?
+            .timestamp_nanos_opt()
+            .ok_or_else(|| HesaiError::InvalidTimestamp("Out of range".into()))?;
+        if utc_tov < 0 {
+            return Err(HesaiError::InvalidTimestamp("Before the unix epoch".into()));
+        }
+        Ok(calibration.to_robot(utc_tov as u64))
     }
 }
 
@@ -400,9 +396,6 @@ fn u32_endianess(val: u32) -> u32 {
     }
 }
 
-// Type to map the lidar timestamp to the robot monotonic time.
-pub type RefTime = (DateTime<Utc>, CuTime);
-
 #[repr(C, packed)]
 #[derive(Copy, Clone, Zeroable, Pod, Debug)]
 pub struct Packet {
@@ -443,8 +436,8 @@ impl Packet {
     // │ Blocks 4&3  │ t₀ + 5.632 − 50 × 2                                    │
     // │ Blocks 2&1  │ t₀ + 5.632 − 50 × 3                                    │
     // └─────────────┴────────────────────────────────────────────────────────┘
-    pub fn block_ts(self, reftime: &RefTime) -> Result<[CuTime; 8], HesaiError> {
-        let t_zero = self.tail.tov(reftime)? + FIRING_OFFSET;
+    pub fn block_ts(self, calibration: &ClockCalibration) -> Result<[CuTime; 8], HesaiError> {
+        let t_zero = self.tail.tov(calibration)? + FIRING_OFFSET;
         let offsets = if self.header.is_dual_return() {
             DUAL_RETURN_OFFSETS
         } else {
@@ -503,8 +496,9 @@ pub fn generate_default_elevation_calibration() -> [Angle; 32] {
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::{parse_packet, Packet, RefTime};
+    use crate::parser::{parse_packet, Packet};
     use cu29::prelude::RobotClock;
+    use cu29::prelude::{ClockCalibration, ExternalTimeBase};
 
     #[test]
     fn test_packet() {
@@ -604,9 +598,15 @@ mod tests {
         let packet_data = &packet[udp_header_size..udp_header_size + size_of::<Packet>()];
         let packet = parse_packet(packet_data).unwrap();
 
-        let rt: RefTime = (
-            packet.tail.utc_tov().unwrap(), // emulates a packet coming in recently
+        let rt = ClockCalibration::from_reference(
+            ExternalTimeBase::Utc,
             robot_clock.now(),
+            packet
+                .tail
+                .utc_tov()
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap() as u64, // emulates a packet coming in recently
         );
         for (bid, ts) in packet.block_ts(&rt).unwrap().iter().enumerate() {
             println!("Block {} tov: {}", bid, ts);
@@ -1,22 +1,56 @@
 use velodyne_lidar::{Config, Config16};
 use velodyne_lidar::{DataPacket, Packet};
 
-use cu29::clock::RobotClock;
-use cu29::config::ComponentConfig;
-use cu29::cutask::{CuMsg, CuSrcTask, Freezable};
-use cu29::{output_msg, CuResult};
+use cu29::prelude::*;
 use cu_sensor_payloads::{PointCloud, PointCloudSoa};
 use std::net::UdpSocket;
 use std::time::Duration;
 use velodyne_lidar::iter::try_packet_to_frame_xyz;
 use velodyne_lidar::types::frame_xyz::FrameXyz;
 
+const NANOS_PER_HOUR: u64 = 3_600_000_000_000;
+
+/// The Velodyne timestamps are the time since the top of the UTC hour, this finds the matching robot time
+/// assuming the point is less than half an hour away from now.
+fn toh_to_robot_time(calibration: &ClockCalibration, now: CuTime, toh: Duration) -> CuTime {
+    let utc_now = calibration.to_external(now);
+    let top_of_hour = utc_now - utc_now % NANOS_PER_HOUR;
+    let mut utc = top_of_hour + toh.as_nanos() as u64;
+    if utc > utc_now + NANOS_PER_HOUR / 2 {
+        utc -= NANOS_PER_HOUR; // the point is from the end of the previous hour
+    } else if utc + NANOS_PER_HOUR / 2 < utc_now {
+        utc += NANOS_PER_HOUR; // the point is from the beginning of the next hour
+    }
+    calibration.to_robot(utc)
+}
+
 pub struct Vlp16 {
     velo_config: Config,
     listen_addr: String,
     #[allow(dead_code)]
     test_mode: bool,
     socket: Option<UdpSocket>,
+    calibration_service: ClockCalibrationService,
+    calibration: ClockCalibration,
+    calibration_generation: u64,
+}
+
+impl Vlp16 {
+    /// Picks up the latest mapping between the UTC time of the lidar and the Robot time, and logs it if it changed.
+    fn sync(&mut self, robot_clock: &RobotClock) {
+        let generation = self.calibration_service.generation();
+        if generation == self.calibration_generation && generation != 0 {
+            return;
+        }
+        self.calibration = self
+            .calibration_service
+            .calibration_or_system_utc(robot_clock);
+        self.calibration_generation = self.calibration_service.generation();
+        info!(
+            "VLP16 clock calibration: {}",
+            calibration = self.calibration
+        );
+    }
 }
 
 impl Freezable for Vlp16 {}
@@ -47,10 +81,15 @@ impl<'cl> CuSrcTask<'cl> for Vlp16 {
             listen_addr,
             test_mode: test_mode == "true",
             socket: None,
+            calibration_service: ClockCalibrationService::global().clone(),
+            // just a temporary value, it will be redone at start.
+            calibration: ClockCalibration::from_reference(ExternalTimeBase::Utc, CuDuration(0), 0),
+            calibration_generation: 0,
         })
     }
 
-    fn start(&mut self, _clock: &RobotClock) -> CuResult<()> {
+    fn start(&mut self, clock: &RobotClock) -> CuResult<()> {
+        self.sync(clock);
         let socket = UdpSocket::bind(&self.listen_addr).unwrap();
         socket
             .set_read_timeout(Some(Duration::from_millis(100)))
@@ -59,7 +98,9 @@ impl<'cl> CuSrcTask<'cl> for Vlp16 {
         Ok(())
     }
 
-    fn process(&mut self, _clock: &RobotClock, new_msg: Self::Output) -> CuResult<()> {
+    fn process(&mut self, clock: &RobotClock, new_msg: Self::Output) -> CuResult<()> {
+        self.sync(clock);
+        let now = clock.now();
         let socket = self.socket.as_ref().unwrap();
         let mut packet = [0u8; size_of::<DataPacket>()];
         let (read_size, _peer_addr) = socket.recv_from(&mut packet).unwrap();
@@ -77,12 +118,12 @@ impl<'cl> CuSrcTask<'cl> for Vlp16 {
         frame.firing_iter().for_each(|firing| {
             firing.point_iter().for_each(|point| {
                 let point = point.as_single().unwrap();
-                let tov = point.toh; // FIXME: Needs to sync the clocks here.
+                let tov = toh_to_robot_time(&self.calibration, now, point.toh);
                 let x = point.measurement.xyz[0].as_meters() as f32;
                 let y = point.measurement.xyz[1].as_meters() as f32;
                 let z = point.measurement.xyz[2].as_meters() as f32;
                 let intensity = point.measurement.intensity as f32 / 255.0f32;
-                output.push(PointCloud::new(tov.into(), x, y, z, intensity, None));
+                output.push(PointCloud::new(tov, x, y, z, intensity, None));
             });
         });
         new_msg.set_payload(output);
@@ -99,6 +140,31 @@ mod tests {
     use super::*;
     use cu_udp_inject::PcapStreamer;
 
+    #[test]
+    fn test_toh_to_robot_time() {
+        // robot time 10s is 12:59:50 UTC.
+        let utc_now = 1_700_000_000 * 1_000_000_000;
+        let utc_now = utc_now - utc_now % NANOS_PER_HOUR + NANOS_PER_HOUR - 10_000_000_000;
+        let calibration = ClockCalibration::from_reference(
+            ExternalTimeBase::Utc,
+            CuDuration(10_000_000_000),
+            utc_now,
+        );
+        let now = CuDuration(10_000_000_000);
+        // a point 1s ago
+        let toh = Duration::from_nanos(NANOS_PER_HOUR - 11_000_000_000);
+        assert_eq!(
+            toh_to_robot_time(&calibration, now, toh),
+            CuDuration(9_000_000_000)
+        );
+        // 13:00:01, after the top of the hour so 11s ahead
+        let toh = Duration::from_secs(1);
+        assert_eq!(
+            toh_to_robot_time(&calibration, now, toh),
+            CuDuration(21_000_000_000)
+        );
+    }
+
     #[test]
     fn vlp16_end_2_end_test() {
         let clk = RobotClock::new();
@@ -0,0 +1,393 @@
+use crate::buffers::{
+    extract_tov_time_left, extract_tov_time_right, tov_spread, TimeboundCircularBuffer,
+};
+use crate::sync::Interpolate;
+use circular_buffer::CircularBuffer;
+use cu29::clock::{CuDuration, CuTime, Tov};
+use cu29::config::ComponentConfig;
+use cu29::cutask::{CuMsg, CuMsgPayload};
+use cu29::{CuError, CuResult};
+use std::fmt::{Display, Formatter};
+
+/// Counters of the messages the aligner could not use.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlignerStats {
+    /// Messages removed from the buffers, because they went past the stale data horizon or a buffer was full.
+    pub purged: u64,
+    /// Messages refused at reception: without tov, older than the stale data horizon or than the previous message of their input.
+    pub stale: u64,
+    /// Purged messages that never were part of an output.
+    pub unmatched: u64,
+}
+
+/// Compact enough to be a status of a message: `output.metadata.set_status(aligner.stats())`.
+impl Display for AlignerStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "p:{} s:{} u:{}", self.purged, self.stale, self.unmatched)
+    }
+}
+
+/// The times of the buffered messages of one input that were part of an output, sorted.
+/// It tells the purged messages that never were used, for the [`Aligner`] and the tasks of [`crate::define_task`].
+#[doc(hidden)]
+pub struct UsedTimes<const S: usize>(CircularBuffer<S, CuTime>);
+
+impl<const S: usize> Default for UsedTimes<S> {
+    fn default() -> Self {
+        Self(CircularBuffer::new())
+    }
+}
+
+impl<const S: usize> UsedTimes<S> {
+    /// Records that the message with this tov was part of an output.
+    // is_none_or needs Rust 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn mark(&mut self, tov: &Tov) {
+        if let Some(time) = extract_tov_time_right(tov) {
+            // the outputs are always more recent or the same.
+            if self.0.back().map_or(true, |last| *last < time) {
+                self.0.push_back(time);
+            }
+        }
+    }
+
+    /// Records the messages of the buffer an interpolation at time was computed from.
+    pub fn mark_bracket<const B: usize, P: CuMsgPayload>(
+        &mut self,
+        buffer: &TimeboundCircularBuffer<B, P>,
+        time: CuTime,
+    ) {
+        if let Some((before, after)) = buffer.bracket(time) {
+            self.mark(&before.metadata.tov);
+            if extract_tov_time_left(&after.metadata.tov) == Some(time) {
+                self.mark(&after.metadata.tov);
+            }
+        }
+    }
+
+    /// Counts the message with this tov removed from its buffer.
+    pub fn dropped(&mut self, tov: &Tov, stats: &mut AlignerStats) {
+        stats.purged += 1;
+        let time = extract_tov_time_right(tov);
+        while self.0.front().is_some_and(|used| Some(*used) < time) {
+            self.0.pop_front();
+        }
+        if self.0.front().is_some() && self.0.front().copied() == time {
+            self.0.pop_front();
+        } else {
+            stats.unmatched += 1;
+        }
+    }
+}
+
+/// The buffer of one input of an [`Aligner`].
+#[doc(hidden)]
+pub struct AlignerStream<const S: usize, P: CuMsgPayload> {
+    buffer: TimeboundCircularBuffer<S, P>,
+    used: UsedTimes<S>,
+}
+
+impl<const S: usize, P: CuMsgPayload> Default for AlignerStream<S, P> {
+    fn default() -> Self {
+        Self {
+            buffer: TimeboundCircularBuffer::new(),
+            used: UsedTimes::default(),
+        }
+    }
+}
+
+impl<const S: usize, P: CuMsgPayload> AlignerStream<S, P> {
+    fn push(&mut self, msg: &CuMsg<P>, horizon: Option<CuTime>, stats: &mut AlignerStats) {
+        if msg.payload().is_none() {
+            return;
+        }
+        let Some(time) = extract_tov_time_right(&msg.metadata.tov) else {
+            stats.stale += 1;
+            return;
+        };
+        let latest = self.buffer.most_recent_time().ok().flatten();
+        if horizon.is_some_and(|horizon| time < horizon) || latest.is_some_and(|l| time < l) {
+            stats.stale += 1;
+            return;
+        }
+        if self.buffer.inner.is_full() {
+            self.drop_oldest(stats);
+        }
+        self.buffer.push(msg.clone());
+    }
+
+    fn purge(&mut self, horizon: CuTime, stats: &mut AlignerStats) {
+        while self
+            .buffer
+            .inner
+            .front()
+            .and_then(|msg| extract_tov_time_right(&msg.metadata.tov))
+            .is_some_and(|time| time < horizon)
+        {
+            self.drop_oldest(stats);
+        }
+    }
+
+    fn drop_oldest(&mut self, stats: &mut AlignerStats) {
+        if let Some(msg) = self.buffer.inner.pop_front() {
+            self.used.dropped(&msg.metadata.tov, stats);
+        }
+    }
+}
+
+/// The tuples of payloads an [`Aligner`] can align.
+#[doc(hidden)]
+pub trait AlignerInputs<const S: usize> {
+    type Streams: Default;
+}
+
+/// Aligns in time the messages of several inputs with payloads (A, B, ...), keeping the last S messages of each.
+/// A generic alternative to [`crate::define_task`] to use directly in a task:
+///
+/// ```rust,ignore
+/// // in process:
+/// self.aligner.push(input);
+/// if let Some((time, (imu, gnss))) = self.aligner.nearest() {
+///     // ...
+/// }
+/// output.metadata.set_status(self.aligner.stats());
+/// // and in preprocess:
+/// self.aligner.purge(clock.now());
+/// ```
+///
+/// Messages without payload are skipped, the messages it could not use are counted in [`AlignerStats`].
+pub struct Aligner<T: AlignerInputs<S>, const S: usize> {
+    target_alignment_window: CuDuration,
+    stale_data_horizon: CuDuration,
+    horizon: Option<CuTime>,
+    stats: AlignerStats,
+    streams: T::Streams,
+}
+
+impl<T: AlignerInputs<S>, const S: usize> Aligner<T, S> {
+    pub fn new(target_alignment_window: CuDuration, stale_data_horizon: CuDuration) -> Self {
+        Self {
+            target_alignment_window,
+            stale_data_horizon,
+            horizon: None,
+            stats: AlignerStats::default(),
+            streams: Default::default(),
+        }
+    }
+
+    /// From the same config as the tasks of [`crate::define_task`]:
+    /// "target_alignment_window_ms" and "stale_data_horizon_ms".
+    pub fn from_config(config: &ComponentConfig) -> CuResult<Self> {
+        let get_ms = |key: &str| {
+            config
+                .get::<u32>(key)
+                .map(|ms| CuDuration(ms as u64 * 1_000_000))
+                .ok_or_else(|| CuError::from(format!("Missing {key}")))
+        };
+        Ok(Self::new(
+            get_ms("target_alignment_window_ms")?,
+            get_ms("stale_data_horizon_ms")?,
+        ))
+    }
+
+    pub fn stats(&self) -> AlignerStats {
+        self.stats
+    }
+}
+
+macro_rules! impl_aligner {
+    ($(($p:ident, $i:tt)),+) => {
+        impl<const S: usize, $($p: CuMsgPayload),+> AlignerInputs<S> for ($($p),+) {
+            type Streams = ($(AlignerStream<S, $p>),+);
+        }
+
+        impl<const S: usize, $($p: CuMsgPayload),+> Aligner<($($p),+), S> {
+            /// Buffers the messages of the inputs of a task.
+            pub fn push(&mut self, input: ($(&CuMsg<$p>),+)) {
+                $( self.streams.$i.push(input.$i, self.horizon, &mut self.stats); )+
+            }
+
+            /// Discards the data older than the stale data horizon, call it in preprocess.
+            pub fn purge(&mut self, now: CuTime) {
+                let horizon = now.saturating_sub(self.stale_data_horizon);
+                self.horizon = Some(horizon);
+                $( self.streams.$i.purge(horizon, &mut self.stats); )+
+            }
+
+            /// The most recent time at which all the inputs have data.
+            pub fn most_recent_common_time(&self) -> Option<CuTime> {
+                [$( self.streams.$i.buffer.most_recent_time().ok().flatten() ),+]
+                    .into_iter()
+                    .min()
+                    .flatten()
+            }
+
+            /// All the messages of the latest alignment window.
+            pub fn window(&mut self) -> Option<($(impl Iterator<Item = &CuMsg<$p>>),+)> {
+                let end = self.most_recent_common_time()?;
+                let start = end.saturating_sub(self.target_alignment_window);
+                $(
+                    for msg in self.streams.$i.buffer.iter_window(start, end) {
+                        self.streams.$i.used.mark(&msg.metadata.tov);
+                    }
+                )+
+                Some(($( self.streams.$i.buffer.iter_window(start, end) ),+))
+            }
+
+            /// For each input the message the closest to the most recent common time, within the alignment window.
+            pub fn nearest(&mut self) -> Option<(CuTime, ($(&CuMsg<$p>),+))> {
+                let time = self.most_recent_common_time()?;
+                let reference = Tov::Time(time);
+                $(
+                    let msg = self.streams.$i.buffer.nearest(time)?;
+                    if tov_spread([&msg.metadata.tov, &reference])? > self.target_alignment_window {
+                        return None;
+                    }
+                )+
+                $(
+                    let msg = self.streams.$i.buffer.nearest(time)?;
+                    self.streams.$i.used.mark(&msg.metadata.tov);
+                )+
+                Some((time, ($( self.streams.$i.buffer.nearest(time)? ),+)))
+            }
+
+            /// Like nearest but only if all the messages are within slop of each other.
+            pub fn approximate(&mut self, slop: CuDuration) -> Option<(CuTime, ($(&CuMsg<$p>),+))> {
+                let time = self.most_recent_common_time()?;
+                let spread = tov_spread([$( &self.streams.$i.buffer.nearest(time)?.metadata.tov ),+])?;
+                if spread > slop {
+                    return None;
+                }
+                self.nearest()
+            }
+        }
+
+        impl<const S: usize, $($p: CuMsgPayload + Interpolate),+> Aligner<($($p),+), S> {
+            /// All the inputs interpolated at the time of the latest message of the reference input
+            /// that the others have data around.
+            pub fn interpolated(&mut self, reference: usize) -> Option<(CuTime, ($(CuMsg<$p>),+))> {
+                let common_time = self.most_recent_common_time()?;
+                let times = [$(
+                    self.streams.$i.buffer
+                        .latest_before(common_time)
+                        .and_then(|msg| extract_tov_time_right(&msg.metadata.tov))
+                ),+];
+                let time = (*times.get(reference)?)?;
+                let msgs = ($( self.streams.$i.buffer.interpolate(time)? ),+);
+                $( self.streams.$i.used.mark_bracket(&self.streams.$i.buffer, time); )+
+                Some((time, msgs))
+            }
+        }
+    };
+}
+
+impl_aligner!((A, 0), (B, 1));
+impl_aligner!((A, 0), (B, 1), (C, 2));
+impl_aligner!((A, 0), (B, 1), (C, 2), (D, 3));
+impl_aligner!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4));
+impl_aligner!((A, 0), (B, 1), (C, 2), (D, 3), (E, 4), (F, 5));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cu29::test_utils::msg_at;
+    use std::time::Duration;
+
+    #[test]
+    fn test_aligner() {
+        let mut config = ComponentConfig::new();
+        config.set("target_alignment_window_ms", 10);
+        config.set("stale_data_horizon_ms", 100);
+        let mut aligner = Aligner::<(u32, f32), 4>::from_config(&config).unwrap();
+        assert!(aligner.nearest().is_none());
+
+        // empty messages are skipped, messages without time are refused.
+        aligner.push((&CuMsg::new(None), &CuMsg::new(Some(1.0))));
+        assert_eq!(
+            aligner.stats(),
+            AlignerStats {
+                stale: 1,
+                ..Default::default()
+            }
+        );
+        // even with a time, an empty message is neither buffered nor counted.
+        let mut empty = CuMsg::<u32>::new(None);
+        empty.metadata.tov = Tov::Time(CuDuration(5_000_000));
+        aligner.push((&empty, &CuMsg::new(None)));
+        assert_eq!(aligner.stats().stale, 1);
+        assert!(aligner.nearest().is_none());
+
+        aligner.push((
+            &msg_at(Duration::from_millis(10).into(), 1),
+            &msg_at(Duration::from_millis(8).into(), 0.8),
+        ));
+        aligner.push((
+            &msg_at(Duration::from_millis(20).into(), 2),
+            &msg_at(Duration::from_millis(19).into(), 1.9),
+        ));
+        let (time, (a, b)) = aligner.nearest().unwrap();
+        assert_eq!(time, CuDuration(19_000_000));
+        assert_eq!((a.payload(), b.payload()), (Some(&2), Some(&1.9)));
+        assert!(aligner.approximate(CuDuration(500_000)).is_none());
+        assert!(aligner.approximate(CuDuration(1_000_000)).is_some());
+
+        // out of order.
+        aligner.push((
+            &msg_at(Duration::from_millis(15).into(), 0),
+            &CuMsg::new(None),
+        ));
+        assert_eq!(aligner.stats().stale, 2);
+
+        // the messages at 10 and 8ms were never used.
+        aligner.purge(CuDuration(115_000_000));
+        assert_eq!(
+            aligner.stats(),
+            AlignerStats {
+                purged: 2,
+                stale: 2,
+                unmatched: 2
+            }
+        );
+        aligner.purge(CuDuration(200_000_000));
+        assert_eq!(aligner.stats().purged, 4);
+        assert_eq!(aligner.stats().unmatched, 2);
+        assert_eq!(aligner.stats().to_string(), "p:4 s:2 u:2");
+
+        // older than the horizon.
+        aligner.push((
+            &msg_at(Duration::from_millis(50).into(), 5),
+            &msg_at(Duration::from_millis(150).into(), 15.0),
+        ));
+        assert_eq!(aligner.stats().stale, 3);
+    }
+
+    #[test]
+    fn test_aligner_overflow_and_interpolation() {
+        let mut aligner =
+            Aligner::<(f32, f64, f32), 2>::new(CuDuration(10_000_000), CuDuration(100_000_000));
+        for t in 0..3 {
+            aligner.push((
+                &msg_at(Duration::from_millis(t * 10).into(), t as f32),
+                &msg_at(Duration::from_millis(t * 10 + 5).into(), t as f64),
+                &msg_at(Duration::from_millis(t * 10).into(), 0.0),
+            ));
+        }
+        // the first messages didn't fit.
+        assert_eq!(aligner.stats().purged, 3);
+        assert_eq!(aligner.stats().unmatched, 3);
+
+        let (time, (a, b, _)) = aligner.interpolated(0).unwrap();
+        assert_eq!(time, CuDuration(20_000_000));
+        assert_eq!(a.payload(), Some(&2.0));
+        assert_eq!(b.payload(), Some(&1.5));
+        assert!(aligner.interpolated(3).is_none());
+
+        let window: Vec<_> = aligner
+            .window()
+            .unwrap()
+            .0
+            .map(|m| *m.payload().unwrap())
+            .collect();
+        assert_eq!(window, vec![1.0, 2.0]);
+    }
+}
@@ -1,5 +1,6 @@
+use crate::sync::Interpolate;
 use circular_buffer::CircularBuffer;
-use cu29::clock::{CuTime, Tov};
+use cu29::clock::{CuDuration, CuTime, Tov};
 use cu29::cutask::{CuMsg, CuMsgPayload};
 use cu29::{CuError, CuResult};
 
@@ -11,8 +12,7 @@ where
     pub inner: CircularBuffer<S, CuMsg<P>>,
 }
 
-#[allow(dead_code)]
-fn extract_tov_time_left(tov: &Tov) -> Option<CuTime> {
+pub(crate) fn extract_tov_time_left(tov: &Tov) -> Option<CuTime> {
     match tov {
         Tov::Time(time) => Some(*time),
         Tov::Range(range) => Some(range.start), // Use the start of the range for alignment
@@ -20,7 +20,7 @@ fn extract_tov_time_left(tov: &Tov) -> Option<CuTime> {
     }
 }
 
-fn extract_tov_time_right(tov: &Tov) -> Option<CuTime> {
+pub(crate) fn extract_tov_time_right(tov: &Tov) -> Option<CuTime> {
     match tov {
         Tov::Time(time) => Some(*time),
         Tov::Range(range) => Some(range.end), // Use the end of the range for alignment
@@ -28,6 +28,26 @@ fn extract_tov_time_right(tov: &Tov) -> Option<CuTime> {
     }
 }
 
+/// How far the tov is from the given time, 0 if it is within its range.
+fn tov_distance(tov: &Tov, time: CuTime) -> Option<CuDuration> {
+    let (left, right) = (extract_tov_time_left(tov)?, extract_tov_time_right(tov)?);
+    if time < left {
+        Some(left - time)
+    } else {
+        Some(time.saturating_sub(right))
+    }
+}
+
+/// The spread in time of a set of tovs, None if one of them has no time.
+pub fn tov_spread<'a>(tovs: impl IntoIterator<Item = &'a Tov>) -> Option<CuDuration> {
+    let mut bounds: Option<(CuTime, CuTime)> = None;
+    for tov in tovs {
+        let (left, right) = (extract_tov_time_left(tov)?, extract_tov_time_right(tov)?);
+        bounds = Some(bounds.map_or((left, right), |(min, max)| (min.min(left), max.max(right))));
+    }
+    bounds.map(|(min, max)| max.saturating_sub(min))
+}
+
 impl<const S: usize, P> Default for TimeboundCircularBuffer<S, P>
 where
     P: CuMsgPayload,
@@ -64,6 +84,11 @@ where
 
     /// Remove all the messages that are older than the given time horizon.
     pub fn purge(&mut self, time_horizon: CuTime) {
+        self.purge_with(time_horizon, |_| {});
+    }
+
+    /// Like purge, calling on_purge with each message removed.
+    pub fn purge_with(&mut self, time_horizon: CuTime, mut on_purge: impl FnMut(&CuMsg<P>)) {
         // Find the index of the first element that should be retained
         let drain_end = self
             .inner
@@ -76,7 +101,7 @@ where
             .unwrap_or(self.inner.len()); // If none match, drain the entire buffer
 
         // Drain all elements before the `drain_end` index
-        self.inner.drain(..drain_end);
+        self.inner.drain(..drain_end).for_each(|msg| on_purge(&msg));
     }
 
     /// Get the most recent time of the messages in the buffer.
@@ -94,6 +119,54 @@ where
             })
     }
 
+    /// The message with a payload closest in time to the given time, the oldest wins the ties.
+    pub fn nearest(&self, time: CuTime) -> Option<&CuMsg<P>> {
+        self.inner
+            .iter()
+            .filter(|msg| msg.payload().is_some())
+            .filter_map(|msg| Some((tov_distance(&msg.metadata.tov, time)?, msg)))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, msg)| msg)
+    }
+
+    /// The most recent message with a payload valid at or before the given time.
+    pub fn latest_before(&self, time: CuTime) -> Option<&CuMsg<P>> {
+        self.inner.iter().rev().find(|msg| {
+            msg.payload().is_some()
+                && extract_tov_time_right(&msg.metadata.tov).is_some_and(|t| t <= time)
+        })
+    }
+
+    /// The 2 consecutive messages with a payload around the given time, both are the same if one is exactly at it.
+    pub fn bracket(&self, time: CuTime) -> Option<(&CuMsg<P>, &CuMsg<P>)> {
+        let before = self.latest_before(time)?;
+        let after = self.inner.iter().find(|msg| {
+            msg.payload().is_some()
+                && extract_tov_time_left(&msg.metadata.tov).is_some_and(|t| t >= time)
+        })?;
+        Some((before, after))
+    }
+
+    /// A message linearly interpolated at the given time between the 2 messages around it.
+    pub fn interpolate(&self, time: CuTime) -> Option<CuMsg<P>>
+    where
+        P: Interpolate,
+    {
+        let (before, after) = self.bracket(time)?;
+        let t0 = extract_tov_time_right(&before.metadata.tov)?;
+        let t1 = extract_tov_time_left(&after.metadata.tov)?;
+        let (p0, p1) = (before.payload()?, after.payload()?);
+        let payload = if t1 > t0 {
+            let alpha = (time - t0).0 as f64 / (t1 - t0).0 as f64;
+            p0.interpolate(p1, alpha as f32)
+        } else {
+            p0.clone()
+        };
+        let mut msg = CuMsg::new(Some(payload));
+        msg.metadata.tov = Tov::Time(time);
+        Some(msg)
+    }
+
     /// Push a message into the buffer.
     pub fn push(&mut self, msg: CuMsg<P>) {
         self.inner.push_back(msg);
@@ -121,33 +194,116 @@ macro_rules! alignment_buffers {
             /// Call this to be sure we discard the old/ non relevant data
             #[allow(dead_code)]
             pub fn purge(&mut self, now: cu29::clock::CuTime) {
-                let horizon_time = now - self.stale_data_horizon;
+                let horizon_time = now.saturating_sub(self.stale_data_horizon);
                 // purge all the stale data from the TimeboundCircularBuffers first
                 $(self.$name.purge(horizon_time);)*
             }
 
-            /// Get the most recent set of aligned data from all the buffers matching the constraints set at construction.
+            /// The min of the max of the last time for all buffers
+            /// meaning the most recent time at which all buffers have data
             #[allow(dead_code)]
-            pub fn get_latest_aligned_data(
-                &mut self,
-            ) -> Option<($(impl Iterator<Item = &cu29::cutask::CuMsg<$payload>>),*)> {
-                // Now find the min of the max of the last time for all buffers
-                // meaning the most recent time at which all buffers have data
-                let most_recent_time = [
+            pub fn most_recent_common_time(&self) -> Option<cu29::clock::CuTime> {
+                [
                     $(self.$name.most_recent_time().unwrap_or(None)),*
                 ]
                 .iter()
                 .filter_map(|&time| time)
-                .min();
+                .min()
+            }
 
+            /// Get the most recent set of aligned data from all the buffers matching the constraints set at construction.
+            #[allow(dead_code)]
+            pub fn get_latest_aligned_data(
+                &mut self,
+            ) -> Option<($(impl Iterator<Item = &cu29::cutask::CuMsg<$payload>>),*)> {
                 // If there is no data in any of the buffers, return early
-                most_recent_time?;
+                let most_recent_time = self.most_recent_common_time()?;
 
-                let most_recent_time = most_recent_time.unwrap();
-
-                let time_to_get_complete_window = most_recent_time - self.target_alignment_window;
+                let time_to_get_complete_window = most_recent_time.saturating_sub(self.target_alignment_window);
                 Some(($(self.$name.iter_window(time_to_get_complete_window, most_recent_time)),*))
             }
+
+            /// For each buffer the message the closest to the most recent common time, within the alignment window.
+            #[allow(dead_code)]
+            pub fn get_nearest_aligned_data(
+                &self,
+            ) -> Option<(cu29::clock::CuTime, ($(&cu29::cutask::CuMsg<$payload>),*))> {
+                let time = self.most_recent_common_time()?;
+                let msgs = ($(
+                    self.$name.nearest(time).filter(|msg| {
+                        $crate::buffers::tov_spread([&msg.metadata.tov, &cu29::clock::Tov::Time(time)])
+                            .is_some_and(|distance| distance <= self.target_alignment_window)
+                    })?
+                ),*);
+                Some((time, msgs))
+            }
+
+            /// Like get_nearest_aligned_data but only if all the messages are within slop of each other.
+            #[allow(dead_code)]
+            pub fn get_approximate_aligned_data(
+                &self,
+                slop: cu29::clock::CuDuration,
+            ) -> Option<(cu29::clock::CuTime, ($(&cu29::cutask::CuMsg<$payload>),*))> {
+                let (time, msgs) = self.get_nearest_aligned_data()?;
+                let ($($name),*) = msgs;
+                let spread = $crate::buffers::tov_spread([$(&$name.metadata.tov),*])?;
+                (spread <= slop).then_some((time, ($($name),*)))
+            }
+
+            /// All the buffers interpolated at the time of the latest message of the reference buffer (by index)
+            /// that the others have data around.
+            /// It fails if one of the payloads but the reference doesn't implement Interpolate.
+            #[allow(dead_code)]
+            pub fn get_interpolated_data(
+                &self,
+                reference: usize,
+            ) -> cu29::CuResult<Option<(cu29::clock::CuTime, ($(cu29::cutask::CuMsg<$payload>),*))>> {
+                #[allow(unused_imports)]
+                use $crate::sync::{InterpolationProbe, ViaFallback, ViaInterpolate};
+                let Some(common_time) = self.most_recent_common_time() else {
+                    return Ok(None);
+                };
+                let buffers_times = [$(
+                    self.$name.latest_before(common_time).map(|msg| msg.metadata.tov.clone())
+                ),*];
+                let time = match buffers_times.get(reference) {
+                    Some(Some(cu29::clock::Tov::Time(time))) => *time,
+                    Some(Some(cu29::clock::Tov::Range(range))) => range.end,
+                    Some(_) => return Ok(None),
+                    None => return Err(format!("No input {reference} to use as the interpolation reference").into()),
+                };
+                let mut _index = 0;
+                $(
+                    let $name = if _index == reference {
+                        self.$name.latest_before(time).cloned()
+                    } else {
+                        (&&InterpolationProbe(&self.$name)).interpolate_at(time)?
+                    };
+                    _index += 1;
+                    let Some($name) = $name else {
+                        return Ok(None);
+                    };
+                )*
+                Ok(Some((time, ($($name),*))))
+            }
+
+            /// Checks that all the buffers but the reference can be interpolated.
+            #[allow(dead_code)]
+            pub fn check_interpolation(&self, reference: usize) -> cu29::CuResult<()> {
+                #[allow(unused_imports)]
+                use $crate::sync::{InterpolationProbe, ViaFallback, ViaInterpolate};
+                let mut _index = 0;
+                $(
+                    if _index != reference && !(&&InterpolationProbe(&self.$name)).can_interpolate() {
+                        return (&&InterpolationProbe(&self.$name)).interpolate_at(cu29::clock::CuDuration(0)).map(|_| ());
+                    }
+                    _index += 1;
+                )*
+                if reference >= _index {
+                    return Err(format!("No input {reference} to use as the interpolation reference").into());
+                }
+                Ok(())
+            }
         }
     };
 }
@@ -158,6 +314,7 @@ pub use alignment_buffers;
 mod tests {
     use cu29::clock::Tov;
     use cu29::cutask::CuMsg;
+    use cu29::test_utils::msg_at;
     use std::time::Duration;
 
     #[test]
@@ -208,6 +365,67 @@ mod tests {
         assert!(buffers.get_latest_aligned_data().is_none());
     }
 
+    #[test]
+    fn sync_policies_test() {
+        alignment_buffers!(
+            AlignmentBuffers,
+            buffer1: TimeboundCircularBuffer<10, CuMsg<u32>>,
+            buffer2: TimeboundCircularBuffer<12, CuMsg<f32>>
+        );
+
+        let mut buffers = AlignmentBuffers::new(
+            Duration::from_secs(2).into(), // 2-second alignment window
+            Duration::from_secs(5).into(), // 5-second stale data horizon
+        );
+        assert!(buffers.get_nearest_aligned_data().is_none());
+        assert!(buffers.get_interpolated_data(0).unwrap().is_none());
+
+        buffers
+            .buffer1
+            .push(msg_at(Duration::from_secs(2).into(), 2));
+        buffers
+            .buffer1
+            .push(msg_at(Duration::from_secs(5).into(), 5));
+        buffers
+            .buffer1
+            .push(msg_at(Duration::from_secs(9).into(), 9));
+        buffers
+            .buffer2
+            .push(msg_at(Duration::from_secs(1).into(), 10.0));
+        buffers
+            .buffer2
+            .push(msg_at(Duration::from_secs(4).into(), 40.0));
+        buffers
+            .buffer2
+            .push(msg_at(Duration::from_secs(7).into(), 70.0));
+
+        // all the buffers have data up to 7s.
+        let (time, (m1, m2)) = buffers.get_nearest_aligned_data().unwrap();
+        assert_eq!(time, Duration::from_secs(7).into());
+        assert_eq!(m1.payload(), Some(&5));
+        assert_eq!(m2.payload(), Some(&70.0));
+
+        assert!(buffers
+            .get_approximate_aligned_data(Duration::from_secs(1).into())
+            .is_none());
+        assert!(buffers
+            .get_approximate_aligned_data(Duration::from_secs(2).into())
+            .is_some());
+
+        // buffer2 interpolated at the latest message of buffer1 before 7s.
+        let (time, (m1, m2)) = buffers.get_interpolated_data(0).unwrap().unwrap();
+        assert_eq!(time, Duration::from_secs(5).into());
+        assert_eq!(m1.payload(), Some(&5));
+        assert_eq!(m2.payload(), Some(&50.0));
+        assert_eq!(m2.metadata.tov, Tov::Time(Duration::from_secs(5).into()));
+
+        // u32 can't be interpolated.
+        assert!(buffers.check_interpolation(0).is_ok());
+        assert!(buffers.check_interpolation(1).is_err());
+        assert!(buffers.check_interpolation(2).is_err());
+        assert!(buffers.get_interpolated_data(1).is_err());
+    }
+
     #[test]
     fn horizon_and_window_alignment_test() {
         alignment_buffers!(
@@ -1,6 +1,11 @@
 #![doc = include_str!("../README.md")]
 
+pub mod aligner;
 pub mod buffers;
+pub mod sync;
+
+pub use aligner::{Aligner, AlignerStats};
+pub use sync::{AlignmentPolicy, Interpolate};
 
 /// Define a task that aligns incoming messages based on their timestamps
 /// See module doc for use.
@@ -19,6 +24,18 @@ macro_rules! define_task {
 
         pub struct $name {
             aligner: AlignmentBuffers,
+            policy: $crate::AlignmentPolicy,
+            last_sync_time: Option<cu29::clock::CuTime>,
+            stats: $crate::AlignerStats,
+            used: ($($crate::aligner::UsedTimes<$mis>,)*),
+        }
+
+        impl $name {
+            /// Counters of the messages this task could not use.
+            #[allow(dead_code)]
+            pub fn stats(&self) -> $crate::AlignerStats {
+                self.stats
+            }
         }
 
         impl Freezable for $name {}
@@ -39,13 +56,28 @@ macro_rules! define_task {
                 let stale_data_horizon: u64 =
                     config.get::<u32>("stale_data_horizon_ms").ok_or_else(|| cu29::CuError::from("Missing stale_data_horizon"))?.into();
 
+                let policy = $crate::AlignmentPolicy::from_config(config)?;
+                let aligner = AlignmentBuffers::new(cu29_clock::CuDuration(target_alignment_window as u64 * 1_000_000),cu29_clock::CuDuration(stale_data_horizon as u64 * 1_000_000));
+                if let $crate::AlignmentPolicy::Interpolate { reference } = policy {
+                    aligner.check_interpolation(reference)?;
+                }
+
                 Ok(Self {
-                    aligner: AlignmentBuffers::new(cu29_clock::CuDuration(target_alignment_window as u64 * 1_000_000),cu29_clock::CuDuration(stale_data_horizon as u64 * 1_000_000)),
+                    aligner,
+                    policy,
+                    last_sync_time: None,
+                    stats: Default::default(),
+                    used: Default::default(),
                 })
             }
 
             fn preprocess(&mut self, clock: &cu29_clock::RobotClock) -> CuResult<()> {
-                self.aligner.purge(clock.now());
+                let horizon = clock.now().saturating_sub(self.aligner.stale_data_horizon);
+                paste::paste! {
+                    $(
+                        self.aligner.[<buffer $index>].purge_with(horizon, |msg| self.used.$index.dropped(&msg.metadata.tov, &mut self.stats));
+                    )*
+                }
                 Ok(())
             }
 
@@ -59,11 +91,57 @@ macro_rules! define_task {
                 // input is a tuple of &'cl CuMsg<T> for each T in the input
                 paste::paste! {
                     $(
-                        self.aligner.[<buffer $index>].push(input.$index.clone());
+                        // empty messages can't be aligned, messages without time are refused.
+                        if input.$index.payload().is_some() {
+                            let buffer = &mut self.aligner.[<buffer $index>];
+                            if matches!(input.$index.metadata.tov, cu29::clock::Tov::None) {
+                                self.stats.stale += 1;
+                            } else {
+                                if buffer.inner.is_full() {
+                                    if let Some(oldest) = buffer.inner.front() {
+                                        self.used.$index.dropped(&oldest.metadata.tov, &mut self.stats);
+                                    }
+                                }
+                                buffer.push(input.$index.clone());
+                            }
+                        }
                     )*
                 }
 
 
+                // the policies giving one message per input only output when there is a more recent synchronized set.
+                macro_rules! output_synced {
+                    ($synced:expr) => {{
+                        match $synced {
+                            Some((time, msgs)) if self.last_sync_time.map_or(true, |last| time > last) => {
+                                self.last_sync_time = Some(time);
+                                let payload = output.payload_mut().get_or_insert_with(Default::default);
+                                $(
+                                    self.used.$index.mark(&msgs.$index.metadata.tov);
+                                    payload.$index.fill_from_iter(msgs.$index.payload().cloned());
+                                )*
+                                output.metadata.tov = cu29::clock::Tov::Time(time);
+                            }
+                            _ => output.clear_payload(),
+                        }
+                        return Ok(());
+                    }};
+                }
+                match self.policy {
+                    $crate::AlignmentPolicy::Window => {}
+                    $crate::AlignmentPolicy::Nearest => output_synced!(self.aligner.get_nearest_aligned_data()),
+                    $crate::AlignmentPolicy::ApproximateTime { slop } => output_synced!(self.aligner.get_approximate_aligned_data(slop)),
+                    $crate::AlignmentPolicy::Interpolate { reference } => {
+                        let synced = self.aligner.get_interpolated_data(reference)?;
+                        if let Some((time, _)) = &synced {
+                            paste::paste! {
+                                $( self.used.$index.mark_bracket(&self.aligner.[<buffer $index>], *time); )*
+                            }
+                        }
+                        output_synced!(synced)
+                    }
+                }
+
                 let tuple_of_iters = self.aligner.get_latest_aligned_data();
                 if tuple_of_iters.is_none() {
                     return Ok(());
@@ -74,7 +152,10 @@ macro_rules! define_task {
 
                 // Populate the CuArray fields in the output message
                 $(
-                    output.payload_mut().as_mut().unwrap().$index.fill_from_iter(tuple_of_iters.$index.map(|msg| *msg.payload().unwrap()));
+                    output.payload_mut().get_or_insert_with(Default::default).$index.fill_from_iter(tuple_of_iters.$index.filter_map(|msg| {
+                        self.used.$index.mark(&msg.metadata.tov);
+                        msg.payload().cloned()
+                    }));
                 )*
                 Ok(())
             }
@@ -84,7 +165,7 @@ macro_rules! define_task {
 
 #[cfg(test)]
 mod tests {
-    use super::define_task;
+    use crate::AlignerStats;
     use cu29::config::ComponentConfig;
     use cu29::cutask::CuMsg;
     use cu29::cutask::CuTask;
@@ -92,7 +173,9 @@ mod tests {
     use cu29::input_msg;
     use cu29::output_msg;
     use cu29::payload::CuArray;
+    use cu29::test_utils::msg_at;
     use cu29::CuResult;
+    use std::time::Duration;
 
     define_task!(AlignerTask, 0 => { 10, 5, f32 }, 1 => { 5, 10, i32 });
 
@@ -111,5 +194,151 @@ mod tests {
         let clock = cu29::clock::RobotClock::new();
         let result = aligner.process(&clock, input, output);
         assert!(result.is_ok());
+
+        // a message without payload but with a tov.
+        let mut m1 = CuMsg::<f32>::new(None);
+        m1.metadata.tov = cu29::clock::Tov::Time(cu29::clock::CuDuration(1));
+        let mut m2 = CuMsg::<i32>::new(Some(2));
+        m2.metadata.tov = cu29::clock::Tov::Time(cu29::clock::CuDuration(1));
+        let mut m3 = CuMsg::<(CuArray<f32, 5>, CuArray<i32, 10>)>::default();
+        aligner.process(&clock, (&m1, &m2), &mut m3).unwrap();
+        // it is not buffered, only m2 is output.
+        assert_eq!(aligner.aligner.buffer0.inner.len(), 0);
+        assert_eq!(aligner.aligner.buffer1.inner.len(), 1);
+        let (floats, ints) = m3.payload().unwrap();
+        assert!(floats.as_slice().is_empty());
+        assert_eq!(ints.as_slice(), &[2]);
+
+        // the same message with a payload is aligned with m2.
+        let mut m1 = CuMsg::<f32>::new(Some(1.0));
+        m1.metadata.tov = cu29::clock::Tov::Time(cu29::clock::CuDuration(1));
+        aligner
+            .process(&clock, (&m1, &CuMsg::default()), &mut m3)
+            .unwrap();
+        let (floats, ints) = m3.payload().unwrap();
+        assert_eq!(floats.as_slice(), &[1.0]);
+        assert_eq!(ints.as_slice(), &[2]);
+    }
+
+    #[test]
+    fn test_aligner_stats() {
+        let mut config = ComponentConfig::default();
+        config.set("target_alignment_window_ms", 10);
+        config.set("stale_data_horizon_ms", 100);
+        config.set("policy", "nearest".to_string());
+        let mut aligner = AlignerTask::new(Some(&config)).unwrap();
+        let (clock, mock) = cu29::clock::RobotClock::mock();
+        let mut output = CuMsg::<(CuArray<f32, 5>, CuArray<i32, 10>)>::default();
+
+        // no time.
+        aligner
+            .process(
+                &clock,
+                (&CuMsg::new(Some(1.0)), &CuMsg::default()),
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(aligner.stats().stale, 1);
+
+        // the i32 buffer holds 5 messages, the first one is dropped without ever being output.
+        for t in 1..=6 {
+            let ints = msg_at(Duration::from_millis(t).into(), t as i32);
+            aligner
+                .process(&clock, (&CuMsg::default(), &ints), &mut output)
+                .unwrap();
+        }
+        assert_eq!(
+            aligner.stats(),
+            AlignerStats {
+                purged: 1,
+                stale: 1,
+                unmatched: 1
+            }
+        );
+
+        // the last 2 messages are output, the 4 others end up purged unused.
+        let floats = msg_at(Duration::from_millis(6).into(), 6.0);
+        aligner
+            .process(&clock, (&floats, &CuMsg::default()), &mut output)
+            .unwrap();
+        assert!(output.payload().is_some());
+        mock.set_value(200_000_000);
+        aligner.preprocess(&clock).unwrap();
+        assert_eq!(
+            aligner.stats(),
+            AlignerStats {
+                purged: 7,
+                stale: 1,
+                unmatched: 5
+            }
+        );
+    }
+
+    mod interpolating {
+        use super::*;
+        define_task!(InterpolatingTask, 0 => { 10, 1, i32 }, 1 => { 10, 1, f64 });
+    }
+    use interpolating::InterpolatingTask;
+
+    #[test]
+    fn test_aligner_policies() {
+        let mut config = ComponentConfig::default();
+        config.set("target_alignment_window_ms", 100);
+        config.set("stale_data_horizon_ms", 1000);
+        config.set("policy", "interpolate".to_string());
+        config.set("reference", 0);
+        let mut aligner = InterpolatingTask::new(Some(&config)).unwrap();
+        let clock = cu29::clock::RobotClock::new();
+        let mut output = CuMsg::<(CuArray<i32, 1>, CuArray<f64, 1>)>::default();
+
+        aligner
+            .process(
+                &clock,
+                (
+                    &msg_at(Duration::from_millis(10).into(), 1),
+                    &msg_at(Duration::from_millis(0).into(), 0.0),
+                ),
+                &mut output,
+            )
+            .unwrap();
+        assert!(output.payload().is_none());
+        aligner
+            .process(
+                &clock,
+                (
+                    &msg_at(Duration::from_millis(30).into(), 3),
+                    &msg_at(Duration::from_millis(20).into(), 2.0),
+                ),
+                &mut output,
+            )
+            .unwrap();
+        // the f64 stream interpolated at the 10ms of the reference.
+        let (ints, floats) = output.payload().unwrap();
+        assert_eq!(ints.as_slice(), &[1]);
+        assert_eq!(floats.as_slice(), &[1.0]);
+        // nothing new to sync.
+        aligner
+            .process(&clock, (&CuMsg::default(), &CuMsg::default()), &mut output)
+            .unwrap();
+        assert!(output.payload().is_none());
+
+        // i32 can't be interpolated.
+        config.set("reference", 1);
+        assert!(InterpolatingTask::new(Some(&config)).is_err());
+
+        config.set("policy", "nearest".to_string());
+        let mut aligner = InterpolatingTask::new(Some(&config)).unwrap();
+        aligner
+            .process(
+                &clock,
+                (
+                    &msg_at(Duration::from_millis(10).into(), 1),
+                    &msg_at(Duration::from_millis(12).into(), 1.2),
+                ),
+                &mut output,
+            )
+            .unwrap();
+        let (ints, floats) = output.payload().unwrap();
+        assert_eq!((ints.as_slice(), floats.as_slice()), (&[1][..], &[1.2][..]));
     }
 }
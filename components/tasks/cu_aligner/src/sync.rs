@@ -0,0 +1,162 @@
+use crate::buffers::TimeboundCircularBuffer;
+use cu29::clock::{CuDuration, CuTime};
+use cu29::config::ComponentConfig;
+use cu29::cutask::{CuMsg, CuMsgPayload};
+use cu29::{CuError, CuResult};
+
+/// Payloads that can be linearly interpolated between 2 messages.
+pub trait Interpolate {
+    /// The value at alpha (0 for self, 1 for other) between self and other.
+    fn interpolate(&self, other: &Self, alpha: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, alpha: f32) -> Self {
+        self + (other - self) * alpha
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, alpha: f32) -> Self {
+        self + (other - self) * alpha as f64
+    }
+}
+
+/// How the aligner synchronizes its inputs, from the "policy" key of the task config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlignmentPolicy {
+    /// "window" (default): all the messages of the latest alignment window.
+    Window,
+    /// "nearest": for each input the message the closest in time to the most recent time all inputs have data.
+    Nearest,
+    /// "interpolate": each input is interpolated at the tov of the latest message of the "reference" input (0 by default).
+    /// All the inputs but the reference need to implement [`Interpolate`].
+    Interpolate { reference: usize },
+    /// "approximate": like nearest, but only if all the messages are within "slop_ms" of each other.
+    ApproximateTime { slop: CuDuration },
+}
+
+impl AlignmentPolicy {
+    pub fn from_config(config: &ComponentConfig) -> CuResult<Self> {
+        match config.get::<String>("policy").as_deref() {
+            None | Some("window") => Ok(Self::Window),
+            Some("nearest") => Ok(Self::Nearest),
+            Some("interpolate") => Ok(Self::Interpolate {
+                reference: config.get::<u32>("reference").unwrap_or(0) as usize,
+            }),
+            Some("approximate") => {
+                let slop_ms = config
+                    .get::<u32>("slop_ms")
+                    .ok_or_else(|| CuError::from("Missing slop_ms for the approximate policy"))?;
+                Ok(Self::ApproximateTime {
+                    slop: CuDuration(slop_ms as u64 * 1_000_000),
+                })
+            }
+            Some(other) => Err(format!(
+                "Unknown alignment policy \"{other}\", expected window, nearest, interpolate or approximate"
+            )
+            .into()),
+        }
+    }
+}
+
+/// Lets the aligner macros interpolate the payloads implementing [`Interpolate`] and report an error for the others,
+/// by picking the impl at the call site: `(&&InterpolationProbe(&buffer)).interpolate_at(time)`.
+#[doc(hidden)]
+pub struct InterpolationProbe<'a, const S: usize, P: CuMsgPayload>(
+    pub &'a TimeboundCircularBuffer<S, P>,
+);
+
+#[doc(hidden)]
+pub trait ViaInterpolate<P: CuMsgPayload> {
+    fn can_interpolate(&self) -> bool;
+    fn interpolate_at(&self, time: CuTime) -> CuResult<Option<CuMsg<P>>>;
+}
+
+impl<const S: usize, P: CuMsgPayload + Interpolate> ViaInterpolate<P>
+    for &InterpolationProbe<'_, S, P>
+{
+    fn can_interpolate(&self) -> bool {
+        true
+    }
+
+    fn interpolate_at(&self, time: CuTime) -> CuResult<Option<CuMsg<P>>> {
+        Ok(self.0.interpolate(time))
+    }
+}
+
+#[doc(hidden)]
+pub trait ViaFallback<P: CuMsgPayload> {
+    fn can_interpolate(&self) -> bool;
+    fn interpolate_at(&self, time: CuTime) -> CuResult<Option<CuMsg<P>>>;
+}
+
+impl<const S: usize, P: CuMsgPayload> ViaFallback<P> for InterpolationProbe<'_, S, P> {
+    fn can_interpolate(&self) -> bool {
+        false
+    }
+
+    fn interpolate_at(&self, _time: CuTime) -> CuResult<Option<CuMsg<P>>> {
+        Err(format!(
+            "{} doesn't implement cu_aligner::Interpolate",
+            std::any::type_name::<P>()
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cu29::clock::Tov;
+
+    #[test]
+    fn test_policy_from_config() {
+        let mut config = ComponentConfig::new();
+        assert_eq!(
+            AlignmentPolicy::from_config(&config).unwrap(),
+            AlignmentPolicy::Window
+        );
+        config.set("policy", "interpolate".to_string());
+        config.set("reference", 1);
+        assert_eq!(
+            AlignmentPolicy::from_config(&config).unwrap(),
+            AlignmentPolicy::Interpolate { reference: 1 }
+        );
+        config.set("policy", "approximate".to_string());
+        assert!(AlignmentPolicy::from_config(&config).is_err());
+        config.set("slop_ms", 5);
+        assert_eq!(
+            AlignmentPolicy::from_config(&config).unwrap(),
+            AlignmentPolicy::ApproximateTime {
+                slop: CuDuration(5_000_000)
+            }
+        );
+        config.set("policy", "latest".to_string());
+        assert!(AlignmentPolicy::from_config(&config).is_err());
+    }
+
+    #[test]
+    // the probe needs the 2 references to select the impl.
+    #[allow(clippy::needless_borrow)]
+    fn test_interpolation_probe() {
+        let mut floats = TimeboundCircularBuffer::<4, f32>::new();
+        for (t, v) in [(10, 1.0), (20, 3.0)] {
+            let mut msg = CuMsg::new(Some(v));
+            msg.metadata.tov = Tov::Time(CuDuration(t));
+            floats.push(msg);
+        }
+        let probe = InterpolationProbe(&floats);
+        assert!((&&probe).can_interpolate());
+        let msg = (&&probe).interpolate_at(CuDuration(15)).unwrap().unwrap();
+        assert_eq!(msg.payload(), Some(&2.0));
+        assert_eq!(msg.metadata.tov, Tov::Time(CuDuration(15)));
+        // out of the buffer.
+        assert!((&&probe).interpolate_at(CuDuration(25)).unwrap().is_none());
+
+        let ints = TimeboundCircularBuffer::<4, i32>::new();
+        let probe = InterpolationProbe(&ints);
+        assert!(!(&&probe).can_interpolate());
+        assert!((&&probe).interpolate_at(CuDuration(15)).is_err());
+    }
+}
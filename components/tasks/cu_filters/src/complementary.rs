@@ -0,0 +1,173 @@
+use crate::{elapsed_seconds, getcfg, FilterOutputPayload};
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use cu29::prelude::*;
+use std::marker::PhantomData;
+
+/// Fuses a rate (for example from a gyroscope) with an absolute but noisy measurement of its integral
+/// (for example an angle from an accelerometer): the rate is trusted at high frequency and the measurement at low frequency.
+///
+/// Its inputs are the rate `R` and the measurement `M`, the rate is integrated over the tov of its messages.
+///
+/// Config, one of:
+/// - "time_constant": the crossover time constant in s, alpha is then computed from the tov of each rate.
+/// - "alpha": a fixed weight of the integrated rate in [0, 1).
+pub struct ComplementaryTask<R, M>
+where
+    f32: for<'a> From<&'a R>,
+    f32: for<'a> From<&'a M>,
+{
+    _marker: PhantomData<(R, M)>,
+    time_constant: Option<f32>,
+    alpha: f32,
+    // Internal state
+    estimate: Option<f32>,
+    last_tov: Option<CuTime>,
+}
+
+impl<'cl, R, M> CuTask<'cl> for ComplementaryTask<R, M>
+where
+    f32: for<'a> From<&'a R>,
+    f32: for<'a> From<&'a M>,
+    R: CuMsgPayload + 'cl,
+    M: CuMsgPayload + 'cl,
+{
+    type Input = input_msg!('cl, R, M);
+    type Output = output_msg!('cl, FilterOutputPayload);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config = config.ok_or("ComplementaryTask needs a config.")?;
+        let (time_constant, alpha) =
+            match (getcfg(config, "time_constant"), getcfg(config, "alpha")) {
+                (Some(tau), _) if tau > 0.0 => (Some(tau), 0.0),
+                (None, Some(alpha)) if (0.0..1.0).contains(&alpha) => (None, alpha),
+                _ => return Err(
+                    "ComplementaryTask needs a positive 'time_constant' or an 'alpha' in [0, 1)."
+                        .into(),
+                ),
+            };
+        Ok(Self {
+            _marker: PhantomData,
+            time_constant,
+            alpha,
+            estimate: None,
+            last_tov: None,
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let (rate_msg, measurement_msg) = input;
+        let mut predicted = self.estimate;
+        if let Some(rate) = rate_msg.payload() {
+            let rate: f32 = rate.into();
+            let dt = elapsed_seconds(&mut self.last_tov, &rate_msg.metadata.tov)?;
+            if let (Some(estimate), Some(dt)) = (self.estimate, dt) {
+                predicted = Some(estimate + rate * dt);
+                if let Some(tau) = self.time_constant {
+                    self.alpha = tau / (tau + dt);
+                }
+            }
+        }
+        let estimate = match (predicted, measurement_msg.payload()) {
+            (Some(predicted), Some(measurement)) => {
+                let measurement: f32 = measurement.into();
+                self.alpha * predicted + (1.0 - self.alpha) * measurement
+            }
+            (Some(predicted), None) => predicted,
+            // the first measurement initializes the estimate.
+            (None, Some(measurement)) => measurement.into(),
+            (None, None) => {
+                output.clear_payload();
+                return Ok(());
+            }
+        };
+        self.estimate = Some(estimate);
+        output.metadata.tov = if rate_msg.payload().is_some() {
+            rate_msg.metadata.tov.clone()
+        } else {
+            measurement_msg.metadata.tov.clone()
+        };
+        output.set_payload(FilterOutputPayload { value: estimate });
+        Ok(())
+    }
+}
+
+impl<R, M> Freezable for ComplementaryTask<R, M>
+where
+    f32: for<'a> From<&'a R>,
+    f32: for<'a> From<&'a M>,
+{
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.estimate, encoder)?;
+        Encode::encode(&self.last_tov, encoder)?;
+        Encode::encode(&self.alpha, encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.estimate = Decode::decode(decoder)?;
+        self.last_tov = Decode::decode(decoder)?;
+        self.alpha = Decode::decode(decoder)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{input, value};
+    use cu29::test_utils::refreeze;
+
+    #[test]
+    fn test_complementary() {
+        let mut config = ComponentConfig::new();
+        config.set("alpha", 0.9);
+        let mut task =
+            ComplementaryTask::<FilterOutputPayload, FilterOutputPayload>::new(Some(&config))
+                .unwrap();
+        let clock = RobotClock::new();
+        let mut output = CuMsg::<FilterOutputPayload>::default();
+        let none = CuMsg::<FilterOutputPayload>::new(None);
+
+        // nothing to estimate from yet.
+        task.process(&clock, (&input(0, 1.0), &none), &mut output)
+            .unwrap();
+        assert!(output.payload().is_none());
+        task.process(&clock, (&input(0, 1.0), &input(0, 2.0)), &mut output)
+            .unwrap();
+        assert_eq!(value(&output), 2.0);
+
+        // the rate is integrated alone between measurements.
+        task.process(&clock, (&input(500, 1.0), &none), &mut output)
+            .unwrap();
+        assert!((value(&output) - 2.5).abs() < 1e-6);
+
+        let mut restored =
+            ComplementaryTask::<FilterOutputPayload, FilterOutputPayload>::new(Some(&config))
+                .unwrap();
+        refreeze(&task, &mut restored);
+        let mut restored_output = CuMsg::<FilterOutputPayload>::default();
+
+        // a biased rate is corrected by the measurements: 0.9 * (2.5 + 0.5) + 0.1 * 2.0.
+        task.process(&clock, (&input(1000, 1.0), &input(1000, 2.0)), &mut output)
+            .unwrap();
+        assert!((value(&output) - 2.9).abs() < 1e-6);
+        restored
+            .process(
+                &clock,
+                (&input(1000, 1.0), &input(1000, 2.0)),
+                &mut restored_output,
+            )
+            .unwrap();
+        assert_eq!(value(&output), value(&restored_output));
+    }
+}
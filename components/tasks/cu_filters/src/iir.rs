@@ -0,0 +1,289 @@
+use crate::{elapsed_seconds, getcfg, FilterOutputPayload};
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use cu29::prelude::*;
+use std::f32::consts::PI;
+use std::marker::PhantomData;
+
+/// First order low-pass (exponential smoothing): y += alpha * (x - y).
+///
+/// Config, one of:
+/// - "cutoff_hz": the cutoff frequency, alpha is then computed from the tov of each input.
+/// - "alpha": a fixed smoothing factor in (0, 1].
+pub struct LowPassTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    _marker: PhantomData<I>,
+    // the time constant in s, or the fixed alpha.
+    time_constant: Option<f32>,
+    alpha: f32,
+    // Internal state
+    state: Option<f32>,
+    last_tov: Option<CuTime>,
+}
+
+impl<'cl, I> CuTask<'cl> for LowPassTask<I>
+where
+    f32: for<'a> From<&'a I>,
+    I: CuMsgPayload + 'cl,
+{
+    type Input = input_msg!('cl, I);
+    type Output = output_msg!('cl, FilterOutputPayload);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config = config.ok_or("LowPassTask needs a config.")?;
+        let (time_constant, alpha) = match (getcfg(config, "cutoff_hz"), getcfg(config, "alpha")) {
+            (Some(cutoff), _) if cutoff > 0.0 => (Some(1.0 / (2.0 * PI * cutoff)), 1.0),
+            (None, Some(alpha)) if alpha > 0.0 && alpha <= 1.0 => (None, alpha),
+            _ => {
+                return Err(
+                    "LowPassTask needs a positive 'cutoff_hz' or an 'alpha' in (0, 1].".into(),
+                )
+            }
+        };
+        Ok(Self {
+            _marker: PhantomData,
+            time_constant,
+            alpha,
+            state: None,
+            last_tov: None,
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let Some(payload) = input.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let x: f32 = payload.into();
+        let alpha = match self.time_constant {
+            Some(tau) => match elapsed_seconds(&mut self.last_tov, &input.metadata.tov)? {
+                Some(dt) => dt / (tau + dt),
+                None => 1.0,
+            },
+            None => self.alpha,
+        };
+        let y = match self.state {
+            Some(y) => y + alpha * (x - y),
+            None => x,
+        };
+        self.state = Some(y);
+        output.metadata.tov = input.metadata.tov.clone();
+        output.set_payload(FilterOutputPayload { value: y });
+        Ok(())
+    }
+}
+
+impl<I> Freezable for LowPassTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.state, encoder)?;
+        Encode::encode(&self.last_tov, encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.state = Decode::decode(decoder)?;
+        self.last_tov = Decode::decode(decoder)?;
+        Ok(())
+    }
+}
+
+/// Second order IIR filter (transposed direct form II), for a fixed sample rate.
+///
+/// Config, either the normalized coefficients "b0", "b1", "b2", "a1", "a2" or a design from the Audio EQ Cookbook:
+/// - "type": "lowpass", "highpass", "bandpass" or "notch".
+/// - "cutoff_hz": the cutoff or center frequency.
+/// - "sample_rate_hz": the rate of the input.
+/// - "q": the quality factor, 0.7071 (Butterworth) by default.
+pub struct BiquadTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    _marker: PhantomData<I>,
+    b: [f32; 3],
+    a: [f32; 2],
+    // Internal state
+    z: [f32; 2],
+}
+
+impl<I> BiquadTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    fn design(config: &ComponentConfig) -> CuResult<([f32; 3], [f32; 2])> {
+        let kind = config.get::<String>("type").ok_or(
+            "BiquadTask needs either the coefficients 'b0', 'b1', 'b2', 'a1', 'a2' or a 'type' of filter.",
+        )?;
+        let (Some(cutoff), Some(rate)) = (
+            getcfg(config, "cutoff_hz"),
+            getcfg(config, "sample_rate_hz"),
+        ) else {
+            return Err("BiquadTask needs 'cutoff_hz' and 'sample_rate_hz'.".into());
+        };
+        if cutoff <= 0.0 || cutoff >= rate / 2.0 {
+            return Err(format!(
+                "BiquadTask: 'cutoff_hz' needs to be between 0 and the Nyquist frequency {}Hz.",
+                rate / 2.0
+            )
+            .into());
+        }
+        let q = getcfg(config, "q").unwrap_or(std::f32::consts::FRAC_1_SQRT_2);
+        let w0 = 2.0 * PI * cutoff / rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let b = match kind.as_str() {
+            "lowpass" => [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            "highpass" => [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            "bandpass" => [alpha, 0.0, -alpha],
+            "notch" => [1.0, -2.0 * cos, 1.0],
+            other => {
+                return Err(format!(
+                    "BiquadTask: unknown 'type' {other}, expected lowpass, highpass, bandpass or notch."
+                )
+                .into())
+            }
+        };
+        let a0 = 1.0 + alpha;
+        Ok((b.map(|b| b / a0), [-2.0 * cos / a0, (1.0 - alpha) / a0]))
+    }
+}
+
+impl<'cl, I> CuTask<'cl> for BiquadTask<I>
+where
+    f32: for<'a> From<&'a I>,
+    I: CuMsgPayload + 'cl,
+{
+    type Input = input_msg!('cl, I);
+    type Output = output_msg!('cl, FilterOutputPayload);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config = config.ok_or("BiquadTask needs a config.")?;
+        let coefficients = ["b0", "b1", "b2", "a1", "a2"].map(|key| getcfg(config, key));
+        let (b, a) = match coefficients {
+            [Some(b0), Some(b1), Some(b2), Some(a1), Some(a2)] => ([b0, b1, b2], [a1, a2]),
+            _ => Self::design(config)?,
+        };
+        Ok(Self {
+            _marker: PhantomData,
+            b,
+            a,
+            z: [0.0; 2],
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let Some(payload) = input.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let x: f32 = payload.into();
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        output.metadata.tov = input.metadata.tov.clone();
+        output.set_payload(FilterOutputPayload { value: y });
+        Ok(())
+    }
+}
+
+impl<I> Freezable for BiquadTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.z, encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.z = Decode::decode(decoder)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{input, value};
+    use cu29::test_utils::refreeze;
+
+    #[test]
+    fn test_low_pass() {
+        let mut config = ComponentConfig::new();
+        config.set("cutoff_hz", 1.0 / (2.0 * std::f64::consts::PI)); // time constant of 1s
+        let mut task = LowPassTask::<FilterOutputPayload>::new(Some(&config)).unwrap();
+        let clock = RobotClock::new();
+        let mut output = CuMsg::<FilterOutputPayload>::default();
+        task.process(&clock, &input(0, 0.0), &mut output).unwrap();
+        assert_eq!(value(&output), 0.0);
+        task.process(&clock, &input(1000, 1.0), &mut output)
+            .unwrap();
+        assert!((value(&output) - 0.5).abs() < 1e-6);
+
+        // a copy restored from the frozen state continues the same way.
+        let mut restored = LowPassTask::<FilterOutputPayload>::new(Some(&config)).unwrap();
+        refreeze(&task, &mut restored);
+        let mut restored_output = CuMsg::<FilterOutputPayload>::default();
+        task.process(&clock, &input(2000, 1.0), &mut output)
+            .unwrap();
+        restored
+            .process(&clock, &input(2000, 1.0), &mut restored_output)
+            .unwrap();
+        assert_eq!(value(&output), value(&restored_output));
+
+        assert!(LowPassTask::<FilterOutputPayload>::new(Some(&ComponentConfig::new())).is_err());
+    }
+
+    #[test]
+    fn test_biquad() {
+        let mut config = ComponentConfig::new();
+        config.set("type", "lowpass".to_string());
+        config.set("cutoff_hz", 10.0);
+        config.set("sample_rate_hz", 1000.0);
+        let mut task = BiquadTask::<FilterOutputPayload>::new(Some(&config)).unwrap();
+        let clock = RobotClock::new();
+        let mut output = CuMsg::<FilterOutputPayload>::default();
+        // unity gain in DC.
+        for t in 0..2000 {
+            task.process(&clock, &input(t, 1.0), &mut output).unwrap();
+        }
+        assert!((value(&output) - 1.0).abs() < 1e-3);
+
+        // a notch removes its frequency.
+        config.set("type", "notch".to_string());
+        config.set("q", 2.0);
+        let mut task = BiquadTask::<FilterOutputPayload>::new(Some(&config)).unwrap();
+        let mut peak: f32 = 0.0;
+        for t in 0..3000 {
+            let x = (2.0 * PI * 10.0 * t as f32 / 1000.0).sin();
+            task.process(&clock, &input(t, x), &mut output).unwrap();
+            if t > 2000 {
+                peak = peak.max(value(&output).abs());
+            }
+        }
+        assert!(peak < 0.01, "{peak}");
+
+        config.set("cutoff_hz", 600.0);
+        assert!(BiquadTask::<FilterOutputPayload>::new(Some(&config)).is_err());
+    }
+}
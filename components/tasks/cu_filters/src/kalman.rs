@@ -0,0 +1,209 @@
+use crate::matrix::{
+    add, identity, inverse, matrix_from_config, mul, mul_vec, sub, transpose, vector_from_config,
+    Matrix,
+};
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use cu29::prelude::*;
+
+/// A measurement of M values, the input of the [`KalmanFilterTask`].
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct VectorPayload<const M: usize> {
+    pub values: [f32; M],
+}
+
+impl<const M: usize> Default for VectorPayload<M> {
+    fn default() -> Self {
+        Self { values: [0.0; M] }
+    }
+}
+
+/// The estimated state of N values and its covariance, the output of the [`KalmanFilterTask`].
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct KalmanStatePayload<const N: usize> {
+    pub state: [f32; N],
+    pub covariance: [[f32; N]; N],
+}
+
+impl<const N: usize> Default for KalmanStatePayload<N> {
+    fn default() -> Self {
+        Self {
+            state: [0.0; N],
+            covariance: [[0.0; N]; N],
+        }
+    }
+}
+
+/// Linear Kalman filter with a state of N values and measurements of M values.
+/// It predicts the state at each process and corrects it when a measurement is received.
+///
+/// Config, the matrices are lists of rows:
+/// - "f": the N x N state transition over one period of the task.
+/// - "h": the M x N observation model.
+/// - "q": the N x N process noise covariance.
+/// - "r": the M x M measurement noise covariance.
+/// - "x0": the N initial state values (0 by default).
+/// - "p0": the N x N initial covariance (identity by default).
+pub struct KalmanFilterTask<const N: usize, const M: usize> {
+    f: Matrix<N, N>,
+    h: Matrix<M, N>,
+    q: Matrix<N, N>,
+    r: Matrix<M, M>,
+    x0: [f32; N],
+    p0: Matrix<N, N>,
+    // Internal state
+    x: [f32; N],
+    p: Matrix<N, N>,
+}
+
+impl<const N: usize, const M: usize> KalmanFilterTask<N, M> {
+    fn predict(&mut self) {
+        self.x = mul_vec(&self.f, &self.x);
+        self.p = add(&mul(&mul(&self.f, &self.p), &transpose(&self.f)), &self.q);
+    }
+
+    fn update(&mut self, z: &[f32; M]) -> CuResult<()> {
+        let ht = transpose(&self.h);
+        let s = add(&mul(&mul(&self.h, &self.p), &ht), &self.r);
+        let s_inv =
+            inverse(&s).ok_or("KalmanFilterTask: the innovation covariance is singular.")?;
+        let k = mul(&mul(&self.p, &ht), &s_inv);
+        let hx = mul_vec(&self.h, &self.x);
+        let mut innovation = [0.0; M];
+        for (i, y) in innovation.iter_mut().enumerate() {
+            *y = z[i] - hx[i];
+        }
+        let correction = mul_vec(&k, &innovation);
+        for (x, c) in self.x.iter_mut().zip(correction) {
+            *x += c;
+        }
+        // Joseph form, it keeps the covariance symmetric and positive.
+        let i_kh = sub(&identity::<N>(), &mul(&k, &self.h));
+        self.p = add(
+            &mul(&mul(&i_kh, &self.p), &transpose(&i_kh)),
+            &mul(&mul(&k, &self.r), &transpose(&k)),
+        );
+        Ok(())
+    }
+}
+
+impl<'cl, const N: usize, const M: usize> CuTask<'cl> for KalmanFilterTask<N, M> {
+    type Input = input_msg!('cl, VectorPayload<M>);
+    type Output = output_msg!('cl, KalmanStatePayload<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config = config.ok_or("KalmanFilterTask needs a config.")?;
+        let missing = |key: &str| {
+            CuError::from(format!(
+                "'{key}' not found in the config of the KalmanFilterTask."
+            ))
+        };
+        let x0 = vector_from_config(config, "x0")?.unwrap_or([0.0; N]);
+        let p0 = matrix_from_config(config, "p0")?.unwrap_or_else(identity::<N>);
+        Ok(Self {
+            f: matrix_from_config(config, "f")?.ok_or_else(|| missing("f"))?,
+            h: matrix_from_config(config, "h")?.ok_or_else(|| missing("h"))?,
+            q: matrix_from_config(config, "q")?.ok_or_else(|| missing("q"))?,
+            r: matrix_from_config(config, "r")?.ok_or_else(|| missing("r"))?,
+            x0,
+            p0,
+            x: x0,
+            p: p0,
+        })
+    }
+
+    fn process(
+        &mut self,
+        clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        self.predict();
+        match input.payload() {
+            Some(measurement) => {
+                self.update(&measurement.values)?;
+                output.metadata.tov = input.metadata.tov.clone();
+            }
+            None => output.metadata.tov = clock.now().into(),
+        }
+        output.set_payload(KalmanStatePayload {
+            state: self.x,
+            covariance: self.p,
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
+        self.x = self.x0;
+        self.p = self.p0;
+        Ok(())
+    }
+}
+
+impl<const N: usize, const M: usize> Freezable for KalmanFilterTask<N, M> {
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.x, encoder)?;
+        Encode::encode(&self.p, encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.x = Decode::decode(decoder)?;
+        self.p = Decode::decode(decoder)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cu29::test_utils::refreeze;
+
+    fn measurement(t: u64, position: f32) -> CuMsg<VectorPayload<1>> {
+        let mut msg = CuMsg::new(Some(VectorPayload { values: [position] }));
+        msg.metadata.tov = Tov::Time(CuDuration(t * 1_000_000));
+        msg
+    }
+
+    #[test]
+    fn test_constant_velocity() {
+        // position and velocity with a period of 0.1s, only the position is measured.
+        let mut config = ComponentConfig::new();
+        config.set("f", vec![vec![1.0, 0.1], vec![0.0, 1.0]]);
+        config.set("h", vec![vec![1.0, 0.0]]);
+        config.set("q", vec![vec![1e-4, 0.0], vec![0.0, 1e-4]]);
+        config.set("r", vec![vec![0.01]]);
+        config.set("p0", vec![vec![10.0, 0.0], vec![0.0, 10.0]]);
+        let mut task = KalmanFilterTask::<2, 1>::new(Some(&config)).unwrap();
+        let clock = RobotClock::new();
+        let mut output = CuMsg::<KalmanStatePayload<2>>::default();
+        for i in 0..100 {
+            // moves at 2 m/s.
+            task.process(&clock, &measurement(i * 100, 0.2 * i as f32), &mut output)
+                .unwrap();
+        }
+        let state = output.payload().unwrap();
+        assert!((state.state[1] - 2.0).abs() < 0.05, "{:?}", state);
+        assert!(state.covariance[0][0] < 0.01);
+
+        // it keeps on predicting without measurements.
+        let mut restored = KalmanFilterTask::<2, 1>::new(Some(&config)).unwrap();
+        refreeze(&task, &mut restored);
+        let mut restored_output = CuMsg::<KalmanStatePayload<2>>::default();
+        let none = CuMsg::<VectorPayload<1>>::new(None);
+        task.process(&clock, &none, &mut output).unwrap();
+        restored
+            .process(&clock, &none, &mut restored_output)
+            .unwrap();
+        let state = output.payload().unwrap();
+        assert!((state.state[0] - 20.0).abs() < 0.05, "{:?}", state);
+        assert_eq!(output.payload(), restored_output.payload());
+
+        config.set("h", vec![vec![1.0, 0.0, 0.0]]);
+        assert!(KalmanFilterTask::<2, 1>::new(Some(&config)).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Signal filters and state estimators as Copper tasks.
+//! The scalar filters are generic on their input payload like `cu_pid::GenericPIDTask`:
+//! specialize them with any payload that converts to an f32, for example `pub type MyLowPass = LowPassTask<MyPayload>;`.
+//! They all output a [`FilterOutputPayload`] that can itself be the input of another filter.
+
+mod complementary;
+mod iir;
+mod kalman;
+mod matrix;
+mod median;
+
+pub use complementary::ComplementaryTask;
+pub use iir::{BiquadTask, LowPassTask};
+pub use kalman::{KalmanFilterTask, KalmanStatePayload, VectorPayload};
+pub use median::MedianTask;
+
+use bincode::{Decode, Encode};
+use cu29::prelude::*;
+
+/// Output of the scalar filters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct FilterOutputPayload {
+    pub value: f32,
+}
+
+impl From<&FilterOutputPayload> for f32 {
+    fn from(payload: &FilterOutputPayload) -> Self {
+        payload.value
+    }
+}
+
+/// Gets an f32 from the config.
+fn getcfg(config: &ComponentConfig, key: &str) -> Option<f32> {
+    config.get::<f64>(key).map(|v| v as f32)
+}
+
+/// The time in s elapsed since the last tov, updated with this one.
+fn elapsed_seconds(last_tov: &mut Option<CuTime>, tov: &Tov) -> CuResult<Option<f32>> {
+    let Tov::Time(tov) = tov else {
+        return Err("Unexpected variant for a TOV of a filter input, it needs a time.".into());
+    };
+    let dt = last_tov.map(|last| tov.saturating_sub(last).0 as f32 / 1e9);
+    *last_tov = Some(*tov);
+    Ok(dt)
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+
+    /// A filter input at t ms.
+    pub fn input(t: u64, value: f32) -> CuMsg<FilterOutputPayload> {
+        let mut msg = CuMsg::new(Some(FilterOutputPayload { value }));
+        msg.metadata.tov = Tov::Time(CuDuration(t * 1_000_000));
+        msg
+    }
+
+    pub fn value(msg: &CuMsg<FilterOutputPayload>) -> f32 {
+        msg.payload().unwrap().value
+    }
+}
@@ -0,0 +1,140 @@
+//! Just enough fixed size linear algebra for the Kalman filter, without allocations.
+
+use cu29::prelude::*;
+
+pub type Matrix<const R: usize, const C: usize> = [[f32; C]; R];
+
+pub fn identity<const N: usize>() -> Matrix<N, N> {
+    let mut m = [[0.0; N]; N];
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    m
+}
+
+pub fn mul<const R: usize, const K: usize, const C: usize>(
+    a: &Matrix<R, K>,
+    b: &Matrix<K, C>,
+) -> Matrix<R, C> {
+    let mut m = [[0.0; C]; R];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..K).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+pub fn mul_vec<const R: usize, const C: usize>(a: &Matrix<R, C>, v: &[f32; C]) -> [f32; R] {
+    a.map(|row| row.iter().zip(v).map(|(a, v)| a * v).sum())
+}
+
+pub fn transpose<const R: usize, const C: usize>(a: &Matrix<R, C>) -> Matrix<C, R> {
+    let mut m = [[0.0; R]; C];
+    for (i, row) in a.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            m[j][i] = *value;
+        }
+    }
+    m
+}
+
+pub fn add<const R: usize, const C: usize>(a: &Matrix<R, C>, b: &Matrix<R, C>) -> Matrix<R, C> {
+    let mut m = *a;
+    for (row, b) in m.iter_mut().zip(b) {
+        for (value, b) in row.iter_mut().zip(b) {
+            *value += b;
+        }
+    }
+    m
+}
+
+pub fn sub<const R: usize, const C: usize>(a: &Matrix<R, C>, b: &Matrix<R, C>) -> Matrix<R, C> {
+    let mut m = *a;
+    for (row, b) in m.iter_mut().zip(b) {
+        for (value, b) in row.iter_mut().zip(b) {
+            *value -= b;
+        }
+    }
+    m
+}
+
+/// Gauss-Jordan elimination with partial pivoting, None if the matrix is singular.
+pub fn inverse<const N: usize>(a: &Matrix<N, N>) -> Option<Matrix<N, N>> {
+    let mut a = *a;
+    let mut inv = identity::<N>();
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < f32::EPSILON {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+        let scale = 1.0 / a[col][col];
+        for j in 0..N {
+            a[col][j] *= scale;
+            inv[col][j] *= scale;
+        }
+        for i in (0..N).filter(|&i| i != col) {
+            let factor = a[i][col];
+            for j in 0..N {
+                a[i][j] -= factor * a[col][j];
+                inv[i][j] -= factor * inv[col][j];
+            }
+        }
+    }
+    Some(inv)
+}
+
+/// Reads a R x C matrix from the config as a list of rows like [[1.0, 0.0], [0.0, 1.0]].
+pub fn matrix_from_config<const R: usize, const C: usize>(
+    config: &ComponentConfig,
+    key: &str,
+) -> CuResult<Option<Matrix<R, C>>> {
+    let Some(rows) = config.get::<Vec<Vec<f64>>>(key) else {
+        return Ok(None);
+    };
+    if rows.len() != R || rows.iter().any(|row| row.len() != C) {
+        return Err(
+            format!("'{key}' needs to be a {R}x{C} matrix given as a list of {R} rows.").into(),
+        );
+    }
+    let mut m = [[0.0; C]; R];
+    for (row, values) in m.iter_mut().zip(rows) {
+        for (value, v) in row.iter_mut().zip(values) {
+            *value = v as f32;
+        }
+    }
+    Ok(Some(m))
+}
+
+/// Reads a vector of N values from the config.
+pub fn vector_from_config<const N: usize>(
+    config: &ComponentConfig,
+    key: &str,
+) -> CuResult<Option<[f32; N]>> {
+    let Some(values) = config.get::<Vec<f64>>(key) else {
+        return Ok(None);
+    };
+    if values.len() != N {
+        return Err(format!("'{key}' needs {N} values but has {}.", values.len()).into());
+    }
+    let mut v = [0.0; N];
+    for (value, x) in v.iter_mut().zip(values) {
+        *value = x as f32;
+    }
+    Ok(Some(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse() {
+        let a = [[0.0, 2.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 4.0]];
+        let inv = inverse(&a).unwrap();
+        assert_eq!(mul(&a, &inv), identity::<3>());
+        assert!(inverse(&[[1.0, 2.0], [2.0, 4.0]]).is_none());
+    }
+}
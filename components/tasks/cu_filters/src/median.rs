@@ -0,0 +1,139 @@
+use crate::FilterOutputPayload;
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use cu29::prelude::*;
+use std::marker::PhantomData;
+
+/// Median of the last "window" inputs (5 by default), to remove spikes from a signal.
+/// Until the window is full, it is the median of the inputs received so far.
+pub struct MedianTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    _marker: PhantomData<I>,
+    window: usize,
+    // Internal state, allocated once.
+    samples: Vec<f32>,
+    next: usize,
+    sorted: Vec<f32>,
+}
+
+impl<'cl, I> CuTask<'cl> for MedianTask<I>
+where
+    f32: for<'a> From<&'a I>,
+    I: CuMsgPayload + 'cl,
+{
+    type Input = input_msg!('cl, I);
+    type Output = output_msg!('cl, FilterOutputPayload);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let window = config
+            .and_then(|config| config.get::<u32>("window"))
+            .unwrap_or(5) as usize;
+        if window == 0 {
+            return Err("MedianTask needs a 'window' of at least 1 sample.".into());
+        }
+        Ok(Self {
+            _marker: PhantomData,
+            window,
+            samples: Vec::with_capacity(window),
+            next: 0,
+            sorted: Vec::with_capacity(window),
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let Some(payload) = input.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let x: f32 = payload.into();
+        if self.samples.len() < self.window {
+            self.samples.push(x);
+        } else {
+            self.samples[self.next] = x;
+        }
+        self.next = (self.next + 1) % self.window;
+
+        self.sorted.clear();
+        self.sorted.extend_from_slice(&self.samples);
+        self.sorted.sort_unstable_by(f32::total_cmp);
+        let middle = self.sorted.len() / 2;
+        let median = if self.sorted.len().is_multiple_of(2) {
+            (self.sorted[middle - 1] + self.sorted[middle]) / 2.0
+        } else {
+            self.sorted[middle]
+        };
+        output.metadata.tov = input.metadata.tov.clone();
+        output.set_payload(FilterOutputPayload { value: median });
+        Ok(())
+    }
+}
+
+impl<I> Freezable for MedianTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.samples, encoder)?;
+        Encode::encode(&(self.next as u32), encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        let samples: Vec<f32> = Decode::decode(decoder)?;
+        let next: u32 = Decode::decode(decoder)?;
+        if samples.len() > self.window || next as usize >= self.window {
+            return Err(DecodeError::Other(
+                "MedianTask: the frozen state does not fit the configured window.",
+            ));
+        }
+        self.samples.clear();
+        self.samples.extend_from_slice(&samples);
+        self.next = next as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{input, value};
+    use cu29::test_utils::refreeze;
+
+    #[test]
+    fn test_median() {
+        let mut config = ComponentConfig::new();
+        config.set("window", 3u32);
+        let mut task = MedianTask::<FilterOutputPayload>::new(Some(&config)).unwrap();
+        let clock = RobotClock::new();
+        let mut output = CuMsg::<FilterOutputPayload>::default();
+        task.process(&clock, &input(0, 1.0), &mut output).unwrap();
+        assert_eq!(value(&output), 1.0);
+        task.process(&clock, &input(1, 3.0), &mut output).unwrap();
+        assert_eq!(value(&output), 2.0);
+        // the spike is removed.
+        task.process(&clock, &input(2, 100.0), &mut output).unwrap();
+        assert_eq!(value(&output), 3.0);
+
+        let mut restored = MedianTask::<FilterOutputPayload>::new(Some(&config)).unwrap();
+        refreeze(&task, &mut restored);
+        let mut restored_output = CuMsg::<FilterOutputPayload>::default();
+        // the 1.0 goes out of the window.
+        task.process(&clock, &input(3, 4.0), &mut output).unwrap();
+        restored
+            .process(&clock, &input(3, 4.0), &mut restored_output)
+            .unwrap();
+        assert_eq!(value(&output), 4.0);
+        assert_eq!(value(&restored_output), 4.0);
+    }
+}
@@ -0,0 +1,286 @@
+//! Turns goal positions into smooth setpoints along trapezoidal or S-curve velocity profiles.
+//! `MotionProfileTask<I, N>` plans N independent axes and replans from the current setpoints
+//! whenever a new goal arrives. `I` is the goal payload, read through its `From<&I> for [f32; N]`
+//! conversion, for example `pub type ArmProfile = MotionProfileTask<ServoPositionsPayload, 8>;`.
+
+mod profile;
+
+pub use profile::{Limits, Profile, State};
+
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use cu29::prelude::*;
+use std::marker::PhantomData;
+
+/// The setpoints of each axis at the time of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct MotionSetpointPayload<const N: usize> {
+    pub positions: [f32; N],
+    pub velocities: [f32; N],
+    pub accelerations: [f32; N],
+    /// All the axes are at rest at their goal.
+    pub reached: bool,
+}
+
+impl<const N: usize> Default for MotionSetpointPayload<N> {
+    fn default() -> Self {
+        Self {
+            positions: [0.0; N],
+            velocities: [0.0; N],
+            accelerations: [0.0; N],
+            reached: true,
+        }
+    }
+}
+
+/// The position of a single axis, for example to feed it to a `cu_pid` or to a filter.
+impl From<&MotionSetpointPayload<1>> for f32 {
+    fn from(payload: &MotionSetpointPayload<1>) -> Self {
+        payload.positions[0]
+    }
+}
+
+/// Plans a motion from the current setpoints to each new goal and samples it with the clock at each process.
+/// A new goal during a motion is planned from the current position and velocity.
+///
+/// Config:
+/// - "profile": "trapezoidal" (default) or "s_curve".
+/// - "max_velocity", "max_acceleration": the limits of all the axes in units/s and units/s².
+/// - "max_jerk": the limit in units/s³, needed by the S-curve.
+/// - "initial_positions": the N positions of the axes at startup, without it the first goal is output as is.
+pub struct MotionProfileTask<I, const N: usize>
+where
+    [f32; N]: for<'a> From<&'a I>,
+{
+    _marker: PhantomData<I>,
+    limits: Limits,
+    // Internal state
+    profiles: Option<[Profile; N]>,
+    start: CuTime,
+}
+
+impl<I, const N: usize> MotionProfileTask<I, N>
+where
+    [f32; N]: for<'a> From<&'a I>,
+{
+    fn sample(&self, now: CuTime) -> Option<[State; N]> {
+        let t = now.saturating_sub(self.start).0 as f64 / 1e9;
+        self.profiles
+            .as_ref()
+            .map(|profiles| profiles.each_ref().map(|profile| profile.sample(t)))
+    }
+}
+
+impl<'cl, I, const N: usize> CuTask<'cl> for MotionProfileTask<I, N>
+where
+    [f32; N]: for<'a> From<&'a I>,
+    I: CuMsgPayload + 'cl,
+{
+    type Input = input_msg!('cl, I);
+    type Output = output_msg!('cl, MotionSetpointPayload<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config = config.ok_or("MotionProfileTask needs a config.")?;
+        let limit = |key: &str| -> CuResult<f64> {
+            match config.get::<f64>(key) {
+                Some(value) if value > 0.0 => Ok(value),
+                _ => Err(format!("MotionProfileTask needs a positive '{key}'.").into()),
+            }
+        };
+        let jerk = match config.get::<String>("profile").as_deref() {
+            None | Some("trapezoidal") => f64::INFINITY,
+            Some("s_curve") => limit("max_jerk")?,
+            Some(other) => {
+                return Err(format!(
+                    "'profile' is \"trapezoidal\" or \"s_curve\", not \"{other}\"."
+                )
+                .into())
+            }
+        };
+        let limits = Limits {
+            velocity: limit("max_velocity")?,
+            acceleration: limit("max_acceleration")?,
+            jerk,
+        };
+        let profiles = match config.get::<Vec<f64>>("initial_positions") {
+            Some(positions) if positions.len() == N => {
+                Some(std::array::from_fn(|i| Profile::at_rest(positions[i])))
+            }
+            Some(positions) => {
+                return Err(format!(
+                    "'initial_positions' needs {N} values but has {}.",
+                    positions.len()
+                )
+                .into())
+            }
+            None => None,
+        };
+        Ok(Self {
+            _marker: PhantomData,
+            limits,
+            profiles,
+            start: CuTime::default(),
+        })
+    }
+
+    fn process(
+        &mut self,
+        clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let now = clock.now();
+        if let Some(goals) = input.payload() {
+            let goals: [f32; N] = goals.into();
+            let changed = match &self.profiles {
+                Some(profiles) => profiles
+                    .iter()
+                    .zip(goals)
+                    .any(|(profile, goal)| profile.goal() != goal as f64),
+                None => true,
+            };
+            if changed {
+                self.profiles = Some(match self.sample(now) {
+                    Some(states) => std::array::from_fn(|i| {
+                        Profile::plan(
+                            states[i].position,
+                            states[i].velocity,
+                            states[i].acceleration,
+                            goals[i] as f64,
+                            &self.limits,
+                        )
+                    }),
+                    None => goals.map(|goal| Profile::at_rest(goal as f64)),
+                });
+                self.start = now;
+            }
+        }
+        let Some(states) = self.sample(now) else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let reached = self
+            .profiles
+            .iter()
+            .flatten()
+            .all(|profile| now.saturating_sub(self.start).0 as f64 / 1e9 >= profile.duration());
+        output.metadata.tov = now.into();
+        output.set_payload(MotionSetpointPayload {
+            positions: states.map(|s| s.position as f32),
+            velocities: states.map(|s| s.velocity as f32),
+            accelerations: states.map(|s| s.acceleration as f32),
+            reached,
+        });
+        Ok(())
+    }
+}
+
+impl<I, const N: usize> Freezable for MotionProfileTask<I, N>
+where
+    [f32; N]: for<'a> From<&'a I>,
+{
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.profiles, encoder)?;
+        Encode::encode(&self.start, encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.profiles = Decode::decode(decoder)?;
+        self.start = Decode::decode(decoder)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cu29::test_utils::refreeze;
+    use std::time::Duration;
+
+    #[derive(Debug, Default, Clone, Encode, Decode)]
+    struct Goals([f32; 2]);
+
+    impl From<&Goals> for [f32; 2] {
+        fn from(goals: &Goals) -> Self {
+            goals.0
+        }
+    }
+
+    fn config(profile: &str) -> ComponentConfig {
+        let mut config = ComponentConfig::new();
+        config.set("profile", profile.to_string());
+        config.set("max_velocity", 1.0);
+        config.set("max_acceleration", 2.0);
+        config.set("max_jerk", 10.0);
+        config.set("initial_positions", vec![0.0, 1.0]);
+        config
+    }
+
+    #[test]
+    fn test_follows_the_clock() {
+        let mut task = MotionProfileTask::<Goals, 2>::new(Some(&config("trapezoidal"))).unwrap();
+        let (clock, mock) = RobotClock::mock();
+        let mut output = CuMsg::<MotionSetpointPayload<2>>::default();
+        let none = CuMsg::<Goals>::new(None);
+
+        // at rest at the initial positions.
+        task.process(&clock, &none, &mut output).unwrap();
+        assert_eq!(output.payload().unwrap().positions, [0.0, 1.0]);
+
+        task.process(&clock, &CuMsg::new(Some(Goals([2.0, 1.0]))), &mut output)
+            .unwrap();
+        assert!(!output.payload().unwrap().reached);
+        mock.increment(Duration::from_millis(500));
+        task.process(&clock, &none, &mut output).unwrap();
+        let setpoint = output.payload().unwrap();
+        assert!((setpoint.velocities[0] - 1.0).abs() < 1e-6);
+        assert!((setpoint.positions[0] - 0.25).abs() < 1e-6);
+        assert_eq!(setpoint.positions[1], 1.0);
+
+        // resending the same goal does not restart the motion.
+        task.process(&clock, &CuMsg::new(Some(Goals([2.0, 1.0]))), &mut output)
+            .unwrap();
+        assert!((output.payload().unwrap().velocities[0] - 1.0).abs() < 1e-6);
+
+        let mut restored =
+            MotionProfileTask::<Goals, 2>::new(Some(&config("trapezoidal"))).unwrap();
+        let mut restored_output = CuMsg::<MotionSetpointPayload<2>>::default();
+        refreeze(&task, &mut restored);
+
+        mock.increment(Duration::from_secs(2));
+        task.process(&clock, &none, &mut output).unwrap();
+        restored
+            .process(&clock, &none, &mut restored_output)
+            .unwrap();
+        let setpoint = output.payload().unwrap();
+        assert!(setpoint.reached);
+        assert_eq!(setpoint.positions, [2.0, 1.0]);
+        assert_eq!(output.payload(), restored_output.payload());
+    }
+
+    #[test]
+    fn test_s_curve_starts_smoothly() {
+        let mut task = MotionProfileTask::<Goals, 2>::new(Some(&config("s_curve"))).unwrap();
+        let (clock, mock) = RobotClock::mock();
+        let mut output = CuMsg::<MotionSetpointPayload<2>>::default();
+        task.process(&clock, &CuMsg::new(Some(Goals([2.0, -1.0]))), &mut output)
+            .unwrap();
+        assert_eq!(output.payload().unwrap().accelerations, [0.0, 0.0]);
+        mock.increment(Duration::from_millis(100));
+        task.process(&clock, &CuMsg::new(None), &mut output)
+            .unwrap();
+        let setpoint = output.payload().unwrap();
+        // still ramping up the acceleration with the max jerk.
+        assert!((setpoint.accelerations[0] - 1.0).abs() < 1e-6);
+        assert!((setpoint.accelerations[1] + 1.0).abs() < 1e-6);
+
+        let mut config = config("s_curve");
+        config.set("initial_positions", vec![0.0]);
+        assert!(MotionProfileTask::<Goals, 2>::new(Some(&config)).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! Time optimal profiles for one axis, from any position and velocity to a rest at the goal.
+
+use bincode::{Decode, Encode};
+
+/// Kinematic limits of an axis, an infinite jerk gives a trapezoidal velocity profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub velocity: f64,
+    pub acceleration: f64,
+    pub jerk: f64,
+}
+
+/// A phase of constant jerk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Encode, Decode)]
+struct Segment {
+    duration: f64,
+    acceleration: f64, // at the start of the segment
+    jerk: f64,
+}
+
+// zero the acceleration (1) + stop (3) + speed up (3) + cruise (1) + slow down (3)
+const MAX_SEGMENTS: usize = 11;
+
+/// The kinematic state of an axis at a time of its profile.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct State {
+    pub position: f64,
+    pub velocity: f64,
+    pub acceleration: f64,
+}
+
+/// A planned motion, evaluated from the time since its start.
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
+pub struct Profile {
+    position: f64,
+    velocity: f64,
+    goal: f64,
+    segments: [Segment; MAX_SEGMENTS],
+    len: u8,
+}
+
+impl Profile {
+    /// An axis at rest at a position.
+    pub fn at_rest(position: f64) -> Self {
+        Self {
+            position,
+            goal: position,
+            ..Default::default()
+        }
+    }
+
+    /// Plans the motion from a position, a velocity and an acceleration to a rest at the goal.
+    /// The acceleration is first brought back to 0 with the max jerk, so replanning during a motion
+    /// keeps the acceleration continuous.
+    pub fn plan(
+        position: f64,
+        velocity: f64,
+        acceleration: f64,
+        goal: f64,
+        limits: &Limits,
+    ) -> Self {
+        let mut profile = Self {
+            position,
+            velocity,
+            goal,
+            ..Default::default()
+        };
+        let mut start = position;
+        let mut speed = velocity;
+
+        // 0 without a jerk limit, the acceleration can change at once.
+        let ramp = acceleration.abs() / limits.jerk;
+        if ramp > 0.0 {
+            profile.push(Segment {
+                duration: ramp,
+                acceleration,
+                jerk: -acceleration.signum() * limits.jerk,
+            });
+            start += speed * ramp + acceleration * ramp * ramp / 3.0;
+            speed += acceleration * ramp / 2.0;
+        }
+
+        // Stop first if we are going the wrong way or too fast to stop at the goal.
+        let distance = goal - start;
+        if speed != 0.0
+            && (speed * distance <= 0.0
+                || velocity_change_distance(speed, 0.0, limits).abs() > distance.abs())
+        {
+            start += velocity_change_distance(speed, 0.0, limits);
+            profile.push_velocity_change(speed, 0.0, limits);
+            speed = 0.0;
+        }
+
+        let distance = goal - start;
+        if distance == 0.0 {
+            return profile;
+        }
+        let direction = distance.signum();
+        let distance = distance.abs();
+        let initial = (speed * direction).max(0.0);
+        let travel = |peak: f64| {
+            (velocity_change_distance(initial, peak, limits)
+                + velocity_change_distance(peak, 0.0, limits))
+            .abs()
+        };
+
+        let peak = if initial >= limits.velocity || travel(limits.velocity) <= distance {
+            limits.velocity
+        } else {
+            // the distance is monotonic with the peak velocity.
+            let (mut low, mut high) = (initial, limits.velocity);
+            for _ in 0..64 {
+                let middle = (low + high) / 2.0;
+                if travel(middle) <= distance {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            low
+        };
+
+        profile.push_velocity_change(initial * direction, peak * direction, limits);
+        let cruise = (distance - travel(peak)).max(0.0);
+        if cruise > 0.0 && peak > 0.0 {
+            profile.push(Segment {
+                duration: cruise / peak,
+                acceleration: 0.0,
+                jerk: 0.0,
+            });
+        }
+        profile.push_velocity_change(peak * direction, 0.0, limits);
+        profile
+    }
+
+    pub fn goal(&self) -> f64 {
+        self.goal
+    }
+
+    /// The total duration of the motion in s.
+    pub fn duration(&self) -> f64 {
+        self.segments().iter().map(|s| s.duration).sum()
+    }
+
+    /// The state at t s after the start of the profile.
+    pub fn sample(&self, t: f64) -> State {
+        let mut state = State {
+            position: self.position,
+            velocity: self.velocity,
+            acceleration: 0.0,
+        };
+        let mut remaining = t.max(0.0);
+        for segment in self.segments() {
+            let dt = remaining.min(segment.duration);
+            let (a, j) = (segment.acceleration, segment.jerk);
+            state.position += state.velocity * dt + a * dt * dt / 2.0 + j * dt * dt * dt / 6.0;
+            state.velocity += a * dt + j * dt * dt / 2.0;
+            state.acceleration = a + j * dt;
+            remaining -= dt;
+            if remaining <= 0.0 {
+                return state;
+            }
+        }
+        // the numerical errors should not leave it short of the goal.
+        State {
+            position: self.goal,
+            velocity: 0.0,
+            acceleration: 0.0,
+        }
+    }
+
+    fn segments(&self) -> &[Segment] {
+        &self.segments[..self.len as usize]
+    }
+
+    fn push(&mut self, segment: Segment) {
+        if segment.duration > 0.0 {
+            self.segments[self.len as usize] = segment;
+            self.len += 1;
+        }
+    }
+
+    /// Changes the velocity with a jerk limited acceleration that is 0 at both ends.
+    fn push_velocity_change(&mut self, from: f64, to: f64, limits: &Limits) {
+        let (jerk_time, constant_time, peak) = velocity_change(from, to, limits);
+        let sign = (to - from).signum();
+        if jerk_time == 0.0 {
+            self.push(Segment {
+                duration: constant_time,
+                acceleration: sign * peak,
+                jerk: 0.0,
+            });
+            return;
+        }
+        self.push(Segment {
+            duration: jerk_time,
+            acceleration: 0.0,
+            jerk: sign * limits.jerk,
+        });
+        self.push(Segment {
+            duration: constant_time,
+            acceleration: sign * peak,
+            jerk: 0.0,
+        });
+        self.push(Segment {
+            duration: jerk_time,
+            acceleration: sign * peak,
+            jerk: -sign * limits.jerk,
+        });
+    }
+}
+
+/// The duration of each jerk phase, of the constant acceleration phase and the peak acceleration
+/// to change the velocity.
+fn velocity_change(from: f64, to: f64, limits: &Limits) -> (f64, f64, f64) {
+    let dv = (to - from).abs();
+    if dv == 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let ramp = limits.acceleration / limits.jerk; // 0 without a jerk limit.
+    if dv >= limits.acceleration * ramp {
+        (ramp, dv / limits.acceleration - ramp, limits.acceleration)
+    } else {
+        let jerk_time = (dv / limits.jerk).sqrt();
+        (jerk_time, 0.0, limits.jerk * jerk_time)
+    }
+}
+
+/// The signed distance travelled while changing the velocity, the profile is symmetric.
+fn velocity_change_distance(from: f64, to: f64, limits: &Limits) -> f64 {
+    let (jerk_time, constant_time, _) = velocity_change(from, to, limits);
+    (from + to) / 2.0 * (2.0 * jerk_time + constant_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRAPEZOIDAL: Limits = Limits {
+        velocity: 1.0,
+        acceleration: 2.0,
+        jerk: f64::INFINITY,
+    };
+    const S_CURVE: Limits = Limits {
+        velocity: 1.0,
+        acceleration: 2.0,
+        jerk: 10.0,
+    };
+
+    /// Samples the profile finely and checks the limits and the continuity.
+    fn check(profile: &Profile, limits: &Limits) {
+        let dt = 1e-4;
+        let mut previous = profile.sample(0.0);
+        let mut t = dt;
+        while t < profile.duration() + 0.1 {
+            let state = profile.sample(t);
+            assert!(
+                state.velocity.abs() <= limits.velocity + 1e-6,
+                "{t} {state:?}"
+            );
+            assert!(
+                state.acceleration.abs() <= limits.acceleration + 1e-6,
+                "{t} {state:?}"
+            );
+            assert!((state.position - previous.position).abs() <= limits.velocity * dt + 1e-6);
+            if limits.jerk.is_finite() {
+                assert!(
+                    (state.acceleration - previous.acceleration).abs() <= limits.jerk * dt + 1e-6,
+                    "{t} {state:?} {previous:?}"
+                );
+            }
+            previous = state;
+            t += dt;
+        }
+        assert_eq!(previous.position, profile.goal());
+    }
+
+    #[test]
+    fn test_trapezoidal() {
+        // 0.5s to accelerate, 1.5s cruising and 0.5s to decelerate.
+        let profile = Profile::plan(0.0, 0.0, 0.0, 2.0, &TRAPEZOIDAL);
+        assert!((profile.duration() - 2.5).abs() < 1e-9);
+        assert!((profile.sample(0.5).velocity - 1.0).abs() < 1e-9);
+        assert!((profile.sample(1.25).position - 1.0).abs() < 1e-9);
+        check(&profile, &TRAPEZOIDAL);
+
+        // too short to reach the max velocity: a triangle.
+        let profile = Profile::plan(0.0, 0.0, 0.0, -0.5, &TRAPEZOIDAL);
+        assert!((profile.duration() - 1.0).abs() < 1e-6);
+        check(&profile, &TRAPEZOIDAL);
+    }
+
+    #[test]
+    fn test_s_curve() {
+        for goal in [3.0, -0.1] {
+            let profile = Profile::plan(1.0, 0.0, 0.0, goal, &S_CURVE);
+            assert!(
+                profile.duration() > Profile::plan(1.0, 0.0, 0.0, goal, &TRAPEZOIDAL).duration()
+            );
+            check(&profile, &S_CURVE);
+        }
+    }
+
+    #[test]
+    fn test_replan_while_moving() {
+        for limits in [TRAPEZOIDAL, S_CURVE] {
+            // goes on, stops before the goal, turns back.
+            for goal in [5.0, 0.1, -1.0] {
+                let profile = Profile::plan(0.0, 1.0, 0.0, goal, &limits);
+                assert_eq!(profile.sample(0.0).velocity, 1.0);
+                check(&profile, &limits);
+            }
+        }
+        assert_eq!(Profile::at_rest(1.0).sample(10.0).position, 1.0);
+    }
+
+    #[test]
+    fn test_replan_while_accelerating() {
+        let profile = Profile::plan(0.0, 0.0, 0.0, 5.0, &S_CURVE);
+        // in the middle of the first jerk phase, the acceleration is ramping up.
+        let state = profile.sample(0.1);
+        assert!((state.acceleration - 1.0).abs() < 1e-9);
+        for goal in [5.0, 0.2, -1.0] {
+            let replanned = Profile::plan(
+                state.position,
+                state.velocity,
+                state.acceleration,
+                goal,
+                &S_CURVE,
+            );
+            let start = replanned.sample(0.0);
+            assert!((start.position - state.position).abs() < 1e-12);
+            assert!((start.velocity - state.velocity).abs() < 1e-12);
+            assert!((start.acceleration - state.acceleration).abs() < 1e-12);
+            check(&replanned, &S_CURVE);
+        }
+    }
+}
@@ -14,10 +14,69 @@ pub struct PIDControlOutputPayload {
     pub i: f32,
     /// Derivative term
     pub d: f32,
-    /// Final output
+    /// Final output, including the feed-forward term
+    /// (see [`PIDController::feed_forward_term`], it is not a field to keep the layout of the logged payloads).
     pub output: f32,
 }
 
+/// Dynamic setpoint of the PID controller, for [`GenericPIDSetpointTask`].
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct PIDSetpointPayload {
+    /// The target value
+    pub setpoint: f32,
+    /// Added as is to the output, for example the command a model of the system predicts for this setpoint.
+    pub feed_forward: f32,
+    /// The value the gain schedule is keyed on if any, instead of the configured one.
+    pub operating_point: Option<f32>,
+}
+
+/// Gains of the PID controller interpolated linearly between operating points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GainSchedule {
+    points: Vec<f32>,
+    kp: Vec<f32>,
+    ki: Vec<f32>,
+    kd: Vec<f32>,
+}
+
+impl GainSchedule {
+    /// The operating points need to be sorted, with one gain of each kind per point.
+    pub fn new(points: Vec<f32>, kp: Vec<f32>, ki: Vec<f32>, kd: Vec<f32>) -> CuResult<Self> {
+        if points.is_empty() {
+            return Err("A gain schedule needs at least one operating point.".into());
+        }
+        if kp.len() != points.len() || ki.len() != points.len() || kd.len() != points.len() {
+            return Err(format!(
+                "A gain schedule needs one gain per operating point: {} points but {} kp, {} ki and {} kd.",
+                points.len(),
+                kp.len(),
+                ki.len(),
+                kd.len()
+            )
+            .into());
+        }
+        if points.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("The operating points of a gain schedule need to be increasing.".into());
+        }
+        Ok(Self { points, kp, ki, kd })
+    }
+
+    /// (kp, ki, kd) at an operating point, the gains are held constant outside of the schedule.
+    pub fn gains_at(&self, operating_point: f32) -> (f32, f32, f32) {
+        let last = self.points.len() - 1;
+        let k = self.points.partition_point(|p| *p <= operating_point);
+        if k == 0 {
+            return (self.kp[0], self.ki[0], self.kd[0]);
+        }
+        if k > last {
+            return (self.kp[last], self.ki[last], self.kd[last]);
+        }
+        let alpha = (operating_point - self.points[k - 1]) / (self.points[k] - self.points[k - 1]);
+        let lerp = |g: &[f32]| g[k - 1] + (g[k] - g[k - 1]) * alpha;
+        (lerp(&self.kp), lerp(&self.ki), lerp(&self.kd))
+    }
+}
+
 /// This is the underlying standard PID controller.
 pub struct PIDController {
     // Configuration
@@ -30,9 +89,17 @@ pub struct PIDController {
     d_limit: f32,
     output_limit: f32,
     sampling: CuDuration,
+    anti_windup_gain: f32,
+    derivative_on_measurement: bool,
+    derivative_filter: f32,
+    feed_forward_gain: f32,
+    feed_forward: f32,
     // Internal state
+    // the accumulated integral term, ki * error * dt, so that it stays continuous when ki changes.
     integral: f32,
     last_error: f32,
+    last_measurement: f32,
+    last_derivative: f32,
     elapsed: CuDuration,
     last_output: PIDControlOutputPayload,
 }
@@ -63,17 +130,71 @@ impl PIDController {
             output_limit,
             elapsed: CuDuration::default(),
             sampling,
+            anti_windup_gain: 0.0,
+            derivative_on_measurement: false,
+            derivative_filter: 0.0,
+            feed_forward_gain: 0.0,
+            feed_forward: 0.0,
+            last_measurement: 0.0,
+            last_derivative: 0.0,
             last_output: PIDControlOutputPayload::default(),
         }
     }
 
+    /// Back-calculation anti-windup: when the output saturates, the integral is driven back
+    /// by gain * (saturated output - unsaturated output). 0 disables it.
+    pub fn set_anti_windup_gain(&mut self, gain: f32) {
+        self.anti_windup_gain = gain;
+    }
+
+    /// Computes the derivative on the measurement instead of the error to avoid kicks on setpoint changes.
+    pub fn set_derivative_on_measurement(&mut self, on_measurement: bool) {
+        self.derivative_on_measurement = on_measurement;
+    }
+
+    /// Time constant of the first order low-pass filter on the derivative, in the same unit as dt. 0 disables it.
+    pub fn set_derivative_filter(&mut self, time_constant: f32) {
+        self.derivative_filter = time_constant;
+    }
+
+    /// The feed-forward term is gain * setpoint + the given feed-forward value.
+    pub fn set_feed_forward_gain(&mut self, gain: f32) {
+        self.feed_forward_gain = gain;
+    }
+
+    pub fn set_feed_forward(&mut self, feed_forward: f32) {
+        self.feed_forward = feed_forward;
+    }
+
+    /// The feed-forward term added to the output.
+    pub fn feed_forward_term(&self) -> f32 {
+        self.feed_forward_gain * self.setpoint + self.feed_forward
+    }
+
+    pub fn setpoint(&self) -> f32 {
+        self.setpoint
+    }
+
+    pub fn set_setpoint(&mut self, setpoint: f32) {
+        self.setpoint = setpoint;
+    }
+
+    /// Changes the gains keeping the integral term continuous: it is held while ki is 0.
+    pub fn set_gains(&mut self, kp: f32, ki: f32, kd: f32) {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
     pub fn reset(&mut self) {
         self.integral = 0.0f32;
         self.last_error = 0.0f32;
+        self.last_derivative = 0.0f32;
     }
 
     pub fn init_measurement(&mut self, measurement: f32) {
         self.last_error = self.setpoint - measurement;
+        self.last_measurement = measurement;
         self.elapsed = self.sampling; // force the computation on the first next_control_output
     }
 
@@ -97,22 +218,38 @@ impl PIDController {
         let p = p_unbounded.clamp(-self.p_limit, self.p_limit);
 
         // Integral term (accumulated over time)
-        self.integral += error * dt;
-        let i_unbounded = self.ki * self.integral;
-        let i = i_unbounded.clamp(-self.i_limit, self.i_limit);
+        self.integral += self.ki * error * dt;
+        let i = self.integral.clamp(-self.i_limit, self.i_limit);
 
         // Derivative term (rate of change)
-        let derivative = (error - self.last_error) / dt;
+        let mut derivative = if self.derivative_on_measurement {
+            -(measurement - self.last_measurement) / dt
+        } else {
+            (error - self.last_error) / dt
+        };
+        if self.derivative_filter > 0.0 {
+            let alpha = self.derivative_filter / (self.derivative_filter + dt);
+            derivative = alpha * self.last_derivative + (1.0 - alpha) * derivative;
+        }
+        self.last_derivative = derivative;
         let d_unbounded = self.kd * derivative;
         let d = d_unbounded.clamp(-self.d_limit, self.d_limit);
 
         // Update last error for next calculation
         self.last_error = error;
+        self.last_measurement = measurement;
+
+        let ff = self.feed_forward_term();
 
-        // Final output: sum of P, I, D with output limit
-        let output_unbounded = p + i + d;
+        // Final output: sum of P, I, D and feed-forward with output limit
+        let output_unbounded = p + i + d + ff;
         let output = output_unbounded.clamp(-self.output_limit, self.output_limit);
 
+        // Back-calculation: unwind the integral by what the output could not apply.
+        if self.anti_windup_gain != 0.0 {
+            self.integral += self.anti_windup_gain * (output - output_unbounded) * dt;
+        }
+
         let output = PIDControlOutputPayload { p, i, d, output };
 
         self.last_output = output.clone();
@@ -121,17 +258,199 @@ impl PIDController {
     }
 }
 
-/// This is the Copper task encapsulating the PID controller.
+/// What the gain schedule is keyed on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScheduleOn {
+    Measurement,
+    Setpoint,
+}
+
+/// The PID controller with its task configuration, shared by the PID tasks.
+struct PIDTaskState {
+    pid: PIDController,
+    first_run: bool,
+    last_tov: CuTime,
+    cutoff: f32,
+    schedule: Option<GainSchedule>,
+    schedule_on: ScheduleOn,
+}
+
+impl PIDTaskState {
+    fn new(config: &ComponentConfig, setpoint: f32) -> CuResult<Self> {
+        debug!("PIDTask config: {:?}", config);
+        let cutoff: f32 = config.get::<f64>("cutoff").ok_or(
+            "'cutoff' not found in config, please set an operating +/- limit on the input.",
+        )? as f32;
+
+        let schedule = match config.get::<Vec<f64>>("schedule_points") {
+            Some(points) => {
+                let gains = |key: &str| -> CuResult<Vec<f32>> {
+                    let gains = config.get::<Vec<f64>>(key).ok_or_else(|| {
+                        CuError::from(format!(
+                            "'{key}' not found in config, it is needed with 'schedule_points'."
+                        ))
+                    })?;
+                    Ok(gains.into_iter().map(|g| g as f32).collect())
+                };
+                Some(GainSchedule::new(
+                    points.into_iter().map(|p| p as f32).collect(),
+                    gains("schedule_kp")?,
+                    gains("schedule_ki")?,
+                    gains("schedule_kd")?,
+                )?)
+            }
+            None => None,
+        };
+        let schedule_on = match config.get::<String>("schedule_on").as_deref() {
+            None | Some("measurement") => ScheduleOn::Measurement,
+            Some("setpoint") => ScheduleOn::Setpoint,
+            Some(other) => {
+                return Err(format!(
+                    "'schedule_on' is \"measurement\" or \"setpoint\", not \"{other}\"."
+                )
+                .into())
+            }
+        };
+
+        // p is mandatory, unless it is scheduled.
+        let kp = if let Some(kp) = config.get::<f64>("kp") {
+            Ok(kp as f32)
+        } else if let Some(schedule) = &schedule {
+            Ok(schedule.kp[0])
+        } else {
+            Err(CuError::from(
+                "'kp' not found in the config. We need at least 'kp' to make the PID algorithm work.",
+            ))
+        }?;
+
+        let p_limit = getcfg(config, "pl", 2.0f32);
+        let ki = getcfg(config, "ki", 0.0f32);
+        let i_limit = getcfg(config, "il", 1.0f32);
+        let kd = getcfg(config, "kd", 0.0f32);
+        let d_limit = getcfg(config, "dl", 2.0f32);
+        let output_limit = getcfg(config, "ol", 1.0f32);
+
+        let sampling = if let Some(value) = config.get::<u32>("sampling_ms") {
+            CuDuration::from(value as u64 * 1_000_000u64)
+        } else {
+            CuDuration::default()
+        };
+
+        let mut pid: PIDController = PIDController::new(
+            kp,
+            ki,
+            kd,
+            setpoint,
+            p_limit,
+            i_limit,
+            d_limit,
+            output_limit,
+            sampling,
+        );
+        pid.set_anti_windup_gain(getcfg(config, "kaw", 0.0f32));
+        pid.set_derivative_on_measurement(config.get::<bool>("d_on_measurement").unwrap_or(false));
+        pid.set_derivative_filter(getcfg(config, "d_filter", 0.0f32));
+        pid.set_feed_forward_gain(getcfg(config, "kff", 0.0f32));
+
+        Ok(Self {
+            pid,
+            first_run: true,
+            last_tov: CuTime::default(),
+            cutoff,
+            schedule,
+            schedule_on,
+        })
+    }
+
+    fn process(
+        &mut self,
+        tov: &Tov,
+        measure: f32,
+        operating_point: Option<f32>,
+        output: &mut CuMsg<PIDControlOutputPayload>,
+    ) -> CuResult<()> {
+        let tov = match tov {
+            Tov::Time(single) => *single,
+            _ => return Err("Unexpected variant for a TOV of PID".into()),
+        };
+
+        if let Some(schedule) = &self.schedule {
+            let operating_point = operating_point.unwrap_or(match self.schedule_on {
+                ScheduleOn::Measurement => measure,
+                ScheduleOn::Setpoint => self.pid.setpoint(),
+            });
+            let (kp, ki, kd) = schedule.gains_at(operating_point);
+            self.pid.set_gains(kp, ki, kd);
+        }
+
+        if self.first_run {
+            self.first_run = false;
+            self.last_tov = tov;
+            self.pid.init_measurement(measure);
+            output.clear_payload();
+            return Ok(());
+        }
+        let dt = tov - self.last_tov;
+        self.last_tov = tov;
+
+        // update the status of the pid.
+        let state = self.pid.next_control_output(measure, dt);
+        // But safety check if the input is within operational margins and cut power if it is not.
+        let setpoint = self.pid.setpoint();
+        if measure > setpoint + self.cutoff {
+            return Err(format!("{} > {} (cutoff)", measure, setpoint + self.cutoff).into());
+        }
+        if measure < setpoint - self.cutoff {
+            return Err(format!("{} < {} (cutoff)", measure, setpoint - self.cutoff).into());
+        }
+        output.metadata.set_status(format!(
+            "{:>5.2} {:>5.2} {:>5.2} {:>5.2}",
+            &state.output, &state.p, &state.i, &state.d
+        ));
+        output.set_payload(state);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.pid.reset();
+        self.first_run = true;
+    }
+
+    /// Breaking change of the frozen state with the anti-windup and feed-forward support: the integral is
+    /// now the integral term (ki included) and the derivative, setpoint and feed-forward state follow,
+    /// so the states frozen by the previous versions can't be thawed.
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.pid.integral, encoder)?;
+        Encode::encode(&self.pid.last_error, encoder)?;
+        Encode::encode(&self.pid.elapsed, encoder)?;
+        Encode::encode(&self.pid.last_output, encoder)?;
+        Encode::encode(&self.pid.last_measurement, encoder)?;
+        Encode::encode(&self.pid.last_derivative, encoder)?;
+        Encode::encode(&self.pid.setpoint, encoder)?;
+        Encode::encode(&self.pid.feed_forward, encoder)?;
+        Ok(())
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.pid.integral = Decode::decode(decoder)?;
+        self.pid.last_error = Decode::decode(decoder)?;
+        self.pid.elapsed = Decode::decode(decoder)?;
+        self.pid.last_output = Decode::decode(decoder)?;
+        self.pid.last_measurement = Decode::decode(decoder)?;
+        self.pid.last_derivative = Decode::decode(decoder)?;
+        self.pid.setpoint = Decode::decode(decoder)?;
+        self.pid.feed_forward = Decode::decode(decoder)?;
+        Ok(())
+    }
+}
+
+/// This is the Copper task encapsulating the PID controller with a fixed setpoint from the config.
 pub struct GenericPIDTask<I>
 where
     f32: for<'a> From<&'a I>,
 {
     _marker: PhantomData<I>,
-    pid: PIDController,
-    first_run: bool,
-    last_tov: CuTime,
-    setpoint: f32,
-    cutoff: f32,
+    state: PIDTaskState,
 }
 
 impl<'cl, I> CuTask<'cl> for GenericPIDTask<I>
@@ -148,57 +467,14 @@ where
     {
         match config {
             Some(config) => {
-                debug!("PIDTask config: {:?}", config);
                 let setpoint: f32 = config
                     .get::<f64>("setpoint")
                     .ok_or("'setpoint' not found in config")?
                     as f32;
 
-                let cutoff: f32 = config.get::<f64>("cutoff").ok_or(
-                    "'cutoff' not found in config, please set an operating +/- limit on the input.",
-                )? as f32;
-
-                // p is mandatory
-                let kp = if let Some(kp) = config.get::<f64>("kp") {
-                    Ok(kp as f32)
-                } else {
-                    Err(CuError::from(
-                        "'kp' not found in the config. We need at least 'kp' to make the PID algorithm work.",
-                    ))
-                }?;
-
-                let p_limit = getcfg(config, "pl", 2.0f32);
-                let ki = getcfg(config, "ki", 0.0f32);
-                let i_limit = getcfg(config, "il", 1.0f32);
-                let kd = getcfg(config, "kd", 0.0f32);
-                let d_limit = getcfg(config, "dl", 2.0f32);
-                let output_limit = getcfg(config, "ol", 1.0f32);
-
-                let sampling = if let Some(value) = config.get::<u32>("sampling_ms") {
-                    CuDuration::from(value as u64 * 1_000_000u64)
-                } else {
-                    CuDuration::default()
-                };
-
-                let pid: PIDController = PIDController::new(
-                    kp,
-                    ki,
-                    kd,
-                    setpoint,
-                    p_limit,
-                    i_limit,
-                    d_limit,
-                    output_limit,
-                    sampling,
-                );
-
                 Ok(Self {
                     _marker: PhantomData,
-                    pid,
-                    first_run: true,
-                    last_tov: CuTime::default(),
-                    setpoint,
-                    cutoff,
+                    state: PIDTaskState::new(config, setpoint)?,
                 })
             }
             None => Err(CuError::from("PIDTask needs a config.")),
@@ -213,41 +489,8 @@ where
     ) -> CuResult<()> {
         match input.payload() {
             Some(payload) => {
-                let tov = match input.metadata.tov {
-                    Tov::Time(single) => single,
-                    _ => return Err("Unexpected variant for a TOV of PID".into()),
-                };
-
-                let measure: f32 = payload.into();
-
-                if self.first_run {
-                    self.first_run = false;
-                    self.last_tov = tov;
-                    self.pid.init_measurement(measure);
-                    output.clear_payload();
-                    return Ok(());
-                }
-                let dt = tov - self.last_tov;
-                self.last_tov = tov;
-
-                // update the status of the pid.
-                let state = self.pid.next_control_output(measure, dt);
-                // But safety check if the input is within operational margins and cut power if it is not.
-                if measure > self.setpoint + self.cutoff {
-                    return Err(
-                        format!("{} > {} (cutoff)", measure, self.setpoint + self.cutoff).into(),
-                    );
-                }
-                if measure < self.setpoint - self.cutoff {
-                    return Err(
-                        format!("{} < {} (cutoff)", measure, self.setpoint - self.cutoff).into(),
-                    );
-                }
-                output.metadata.set_status(format!(
-                    "{:>5.2} {:>5.2} {:>5.2} {:>5.2}",
-                    &state.output, &state.p, &state.i, &state.d
-                ));
-                output.set_payload(state);
+                self.state
+                    .process(&input.metadata.tov, payload.into(), None, output)?
             }
             None => output.clear_payload(),
         };
@@ -255,8 +498,7 @@ where
     }
 
     fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
-        self.pid.reset();
-        self.first_run = true;
+        self.state.stop();
         Ok(())
     }
 }
@@ -267,18 +509,94 @@ where
     f32: for<'a> From<&'a I>,
 {
     fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
-        Encode::encode(&self.pid.integral, encoder)?;
-        Encode::encode(&self.pid.last_error, encoder)?;
-        Encode::encode(&self.pid.elapsed, encoder)?;
-        Encode::encode(&self.pid.last_output, encoder)?;
+        self.state.freeze(encoder)
+    }
+
+    fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+        self.state.thaw(decoder)
+    }
+}
+
+/// The PID controller task with its setpoint and feed-forward coming from a second input.
+/// Until a first setpoint is received, it uses the optional "setpoint" of the config or outputs nothing.
+pub struct GenericPIDSetpointTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    _marker: PhantomData<I>,
+    state: PIDTaskState,
+    has_setpoint: bool,
+    operating_point: Option<f32>,
+}
+
+impl<'cl, I> CuTask<'cl> for GenericPIDSetpointTask<I>
+where
+    f32: for<'a> From<&'a I>,
+    I: CuMsgPayload + 'cl,
+{
+    type Input = input_msg!('cl, I, PIDSetpointPayload);
+    type Output = output_msg!('cl, PIDControlOutputPayload);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let config = config.ok_or_else(|| CuError::from("PIDSetpointTask needs a config."))?;
+        let setpoint = config.get::<f64>("setpoint");
+        Ok(Self {
+            _marker: PhantomData,
+            state: PIDTaskState::new(config, setpoint.unwrap_or_default() as f32)?,
+            has_setpoint: setpoint.is_some(),
+            operating_point: None,
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let (measurement, setpoint) = input;
+        if let Some(setpoint) = setpoint.payload() {
+            self.state.pid.set_setpoint(setpoint.setpoint);
+            self.state.pid.set_feed_forward(setpoint.feed_forward);
+            self.operating_point = setpoint.operating_point;
+            self.has_setpoint = true;
+        }
+        match measurement.payload() {
+            Some(payload) if self.has_setpoint => self.state.process(
+                &measurement.metadata.tov,
+                payload.into(),
+                self.operating_point,
+                output,
+            )?,
+            _ => output.clear_payload(),
+        };
+        Ok(())
+    }
+
+    fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
+        self.state.stop();
         Ok(())
     }
+}
+
+/// Store/Restore the internal state of the PID controller.
+impl<I> Freezable for GenericPIDSetpointTask<I>
+where
+    f32: for<'a> From<&'a I>,
+{
+    fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.state.freeze(encoder)?;
+        Encode::encode(&self.has_setpoint, encoder)?;
+        Encode::encode(&self.operating_point, encoder)
+    }
 
     fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
-        self.pid.integral = Decode::decode(decoder)?;
-        self.pid.last_error = Decode::decode(decoder)?;
-        self.pid.elapsed = Decode::decode(decoder)?;
-        self.pid.last_output = Decode::decode(decoder)?;
+        self.state.thaw(decoder)?;
+        self.has_setpoint = Decode::decode(decoder)?;
+        self.operating_point = Decode::decode(decoder)?;
         Ok(())
     }
 }
@@ -291,3 +609,154 @@ fn getcfg(config: &ComponentConfig, key: &str, default: f32) -> f32 {
         default
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: CuDuration = CuDuration(1_000_000); // 1 in the unit of the controller.
+
+    #[derive(Debug, Default, Clone, Encode, Decode)]
+    struct Measure(f32);
+
+    impl From<&Measure> for f32 {
+        fn from(measure: &Measure) -> f32 {
+            measure.0
+        }
+    }
+
+    fn controller(kp: f32, ki: f32, kd: f32) -> PIDController {
+        PIDController::new(
+            kp,
+            ki,
+            kd,
+            1.0,
+            10.0,
+            10.0,
+            10.0,
+            1.0,
+            CuDuration::default(),
+        )
+    }
+
+    #[test]
+    fn test_anti_windup() {
+        // the output saturates at 1 for a long time, without anti-windup the integral keeps on growing.
+        let mut windup = controller(1.0, 0.01, 0.0);
+        let mut anti_windup = controller(1.0, 0.01, 0.0);
+        anti_windup.set_anti_windup_gain(1.0);
+        for pid in [&mut windup, &mut anti_windup] {
+            pid.init_measurement(-5.0);
+            for _ in 0..100 {
+                pid.next_control_output(-5.0, DT);
+            }
+        }
+        assert!(windup.integral > 5.0);
+        assert!(anti_windup.integral < 1.0);
+        // it recovers faster when the measurement reaches the setpoint.
+        assert!(
+            anti_windup.next_control_output(1.5, DT).output
+                < windup.next_control_output(1.5, DT).output
+        );
+    }
+
+    #[test]
+    fn test_derivative_on_measurement_and_filter() {
+        let mut on_error = controller(0.0, 0.0, 1.0);
+        let mut on_measurement = controller(0.0, 0.0, 1.0);
+        on_measurement.set_derivative_on_measurement(true);
+        for pid in [&mut on_error, &mut on_measurement] {
+            pid.init_measurement(0.0);
+            pid.next_control_output(0.0, DT);
+            pid.set_setpoint(2.0);
+        }
+        // no kick on a setpoint change.
+        assert_eq!(on_error.next_control_output(0.0, DT).d, 1.0);
+        assert_eq!(on_measurement.next_control_output(0.0, DT).d, 0.0);
+
+        let mut filtered = controller(0.0, 0.0, 1.0);
+        filtered.set_derivative_on_measurement(true);
+        filtered.set_derivative_filter(1.0);
+        filtered.init_measurement(0.0);
+        // half of the step with a time constant equal to dt.
+        assert_eq!(filtered.next_control_output(-1.0, DT).d, 0.5);
+        assert_eq!(filtered.next_control_output(-1.0, DT).d, 0.25);
+    }
+
+    #[test]
+    fn test_feed_forward() {
+        let mut pid = controller(0.0, 0.0, 0.0);
+        pid.set_feed_forward_gain(0.5);
+        pid.set_feed_forward(0.1);
+        pid.init_measurement(1.0);
+        let output = pid.next_control_output(1.0, DT);
+        assert!((pid.feed_forward_term() - 0.6).abs() < 1e-6);
+        assert!((output.output - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_set_gains_keeps_the_integral_term() {
+        let mut pid = controller(0.0, 0.5, 0.0);
+        pid.init_measurement(0.0);
+        assert_eq!(pid.next_control_output(0.0, DT).i, 0.5);
+        pid.set_gains(0.0, 1.0, 0.0);
+        // continuous, then integrating with the new ki.
+        assert_eq!(pid.next_control_output(1.0, DT).i, 0.5);
+        assert_eq!(pid.next_control_output(0.0, DT).i, 1.5);
+        // held while ki is 0.
+        pid.set_gains(0.0, 0.0, 0.0);
+        assert_eq!(pid.next_control_output(0.0, DT).i, 1.5);
+        pid.set_gains(0.0, 1.0, 0.0);
+        assert_eq!(pid.next_control_output(1.0, DT).i, 1.5);
+    }
+
+    #[test]
+    fn test_gain_schedule() {
+        let schedule = GainSchedule::new(
+            vec![0.0, 10.0],
+            vec![1.0, 2.0],
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+        )
+        .unwrap();
+        assert_eq!(schedule.gains_at(-1.0), (1.0, 0.0, 0.0));
+        assert_eq!(schedule.gains_at(5.0), (1.5, 0.0, 0.5));
+        assert_eq!(schedule.gains_at(20.0), (2.0, 0.0, 1.0));
+        assert!(
+            GainSchedule::new(vec![1.0, 0.0], vec![1.0; 2], vec![0.0; 2], vec![0.0; 2]).is_err()
+        );
+        assert!(GainSchedule::new(vec![0.0], vec![1.0; 2], vec![0.0], vec![0.0]).is_err());
+
+        let mut config = ComponentConfig::new();
+        config.set("cutoff", 100.0);
+        config.set("ol", 100.0);
+        config.set("pl", 100.0);
+        config.set("schedule_points", vec![0.0, 10.0]);
+        config.set("schedule_kp", vec![1.0, 2.0]);
+        config.set("schedule_ki", vec![0.0, 0.0]);
+        config.set("schedule_kd", vec![0.0, 0.0]);
+        config.set("schedule_on", "setpoint".to_string());
+        let mut task = GenericPIDSetpointTask::<Measure>::new(Some(&config)).unwrap();
+        let clock = RobotClock::new();
+        let mut output = CuMsg::<PIDControlOutputPayload>::default();
+        let measure = |t: u64, value: f32| {
+            let mut msg = CuMsg::new(Some(Measure(value)));
+            msg.metadata.tov = Tov::Time(CuDuration(t * 1_000_000));
+            msg
+        };
+        let setpoint = CuMsg::new(Some(PIDSetpointPayload {
+            setpoint: 5.0,
+            ..Default::default()
+        }));
+        // no setpoint yet.
+        task.process(&clock, (&measure(0, 0.0), &CuMsg::new(None)), &mut output)
+            .unwrap();
+        assert!(output.payload().is_none());
+        task.process(&clock, (&measure(1, 0.0), &setpoint), &mut output)
+            .unwrap();
+        task.process(&clock, (&measure(2, 1.0), &CuMsg::new(None)), &mut output)
+            .unwrap();
+        // kp is 1.5 at the setpoint 5.
+        assert_eq!(output.payload().unwrap().p, 6.0);
+    }
+}
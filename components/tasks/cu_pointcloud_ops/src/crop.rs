@@ -0,0 +1,148 @@
+use crate::{filter_cloud, getcfg};
+use cu29::prelude::*;
+use cu_sensor_payloads::PointCloudSoa;
+
+/// Keeps the points inside an axis aligned box, or outside of it with "negative"
+/// (for example to remove the points hitting the robot itself).
+///
+/// Config: "min_x", "max_x", "min_y", "max_y", "min_z", "max_z" in m (unbounded by default), "negative" (false by default).
+pub struct CropBox<const N: usize> {
+    min: [f32; 3],
+    max: [f32; 3],
+    negative: bool,
+}
+
+impl<const N: usize> Freezable for CropBox<N> {}
+
+impl<'cl, const N: usize> CuTask<'cl> for CropBox<N> {
+    type Input = input_msg!('cl, PointCloudSoa<N>);
+    type Output = output_msg!('cl, PointCloudSoa<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let min = ["min_x", "min_y", "min_z"].map(|k| getcfg(config, k, f32::NEG_INFINITY));
+        let max = ["max_x", "max_y", "max_z"].map(|k| getcfg(config, k, f32::INFINITY));
+        if min.iter().zip(max.iter()).any(|(min, max)| min > max) {
+            return Err("CropBox: a min is greater than its max.".into());
+        }
+        let negative = config
+            .and_then(|c| c.get::<bool>("negative"))
+            .unwrap_or(false);
+        Ok(Self { min, max, negative })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        filter_cloud(input, output, |x, y, z| {
+            let inside = [x, y, z]
+                .iter()
+                .enumerate()
+                .all(|(axis, v)| *v >= self.min[axis] && *v <= self.max[axis]);
+            inside != self.negative
+        });
+        Ok(())
+    }
+}
+
+/// Keeps the points at a distance from the origin of the cloud (the sensor) between a min and a max,
+/// typically to remove the noisy close returns and the sparse far ones.
+///
+/// Config: "min_range" (0 by default) and "max_range" (unbounded by default) in m.
+pub struct RangeFilter<const N: usize> {
+    min_range_sq: f32,
+    max_range_sq: f32,
+}
+
+impl<const N: usize> Freezable for RangeFilter<N> {}
+
+impl<'cl, const N: usize> CuTask<'cl> for RangeFilter<N> {
+    type Input = input_msg!('cl, PointCloudSoa<N>);
+    type Output = output_msg!('cl, PointCloudSoa<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let min_range = getcfg(config, "min_range", 0.0);
+        let max_range = getcfg(config, "max_range", f32::INFINITY);
+        if min_range < 0.0 || min_range > max_range {
+            return Err(format!("RangeFilter: invalid range [{min_range}, {max_range}].").into());
+        }
+        Ok(Self {
+            min_range_sq: min_range * min_range,
+            max_range_sq: max_range * max_range,
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        filter_cloud(input, output, |x, y, z| {
+            let d_sq = x * x + y * y + z * z;
+            d_sq >= self.min_range_sq && d_sq <= self.max_range_sq
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cloud, xyz};
+
+    const POINTS: [(u64, f32, f32, f32); 4] = [
+        (1, 0.5, 0.5, 0.5),
+        (2, 2.0, 0.0, 0.0),
+        (3, 0.0, -3.0, 4.0),
+        (4, 0.1, 0.0, 0.0),
+    ];
+
+    #[test]
+    fn test_crop_box() {
+        let mut config = ComponentConfig::new();
+        config.set("min_x", 0.0);
+        config.set("max_x", 1.0);
+        config.set("min_y", -1.0);
+        config.set("max_y", 1.0);
+        let mut task = CropBox::<8>::new(Some(&config)).unwrap();
+        let input = cloud::<8>(&POINTS);
+        let mut output = CuMsg::<PointCloudSoa<8>>::default();
+        task.process(&RobotClock::new(), &input, &mut output)
+            .unwrap();
+        assert_eq!(xyz(&output), vec![(0.5, 0.5, 0.5), (0.1, 0.0, 0.0)]);
+
+        config.set("negative", true);
+        let mut task = CropBox::<8>::new(Some(&config)).unwrap();
+        task.process(&RobotClock::new(), &input, &mut output)
+            .unwrap();
+        assert_eq!(xyz(&output), vec![(2.0, 0.0, 0.0), (0.0, -3.0, 4.0)]);
+    }
+
+    #[test]
+    fn test_range_filter() {
+        let mut config = ComponentConfig::new();
+        config.set("min_range", 0.5);
+        config.set("max_range", 4.0);
+        let mut task = RangeFilter::<8>::new(Some(&config)).unwrap();
+        let input = cloud::<8>(&POINTS);
+        let mut output = CuMsg::<PointCloudSoa<8>>::default();
+        task.process(&RobotClock::new(), &input, &mut output)
+            .unwrap();
+        assert_eq!(xyz(&output), vec![(0.5, 0.5, 0.5), (2.0, 0.0, 0.0)]);
+
+        // no payload in, no payload out.
+        let empty = CuMsg::<PointCloudSoa<8>>::new(None);
+        task.process(&RobotClock::new(), &empty, &mut output)
+            .unwrap();
+        assert!(output.payload().is_none());
+    }
+}
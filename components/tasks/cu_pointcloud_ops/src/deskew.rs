@@ -0,0 +1,328 @@
+use crate::output_cloud;
+use cu29::prelude::*;
+use cu_aligner::buffers::TimeboundCircularBuffer;
+use cu_sensor_payloads::{
+    Distance, ImuPayload, Odometry, PointCloudSoa, Pose, Quaternion, Transform, Vector3,
+};
+use std::time::Duration;
+
+/// A stream of messages giving the motion of the lidar over time.
+pub trait MotionSample: CuMsgPayload {
+    /// Appends to keys the poses of the lidar at the times of the samples, sorted by time,
+    /// in any frame fixed during the scan.
+    fn key_poses<'a>(
+        samples: impl Iterator<Item = (CuTime, &'a Self)>,
+        keys: &mut Vec<(CuTime, Transform)>,
+    ) where
+        Self: 'a;
+}
+
+/// The pose of the lidar in a fixed frame, for example from a localization.
+impl MotionSample for Pose {
+    fn key_poses<'a>(
+        samples: impl Iterator<Item = (CuTime, &'a Self)>,
+        keys: &mut Vec<(CuTime, Transform)>,
+    ) {
+        keys.extend(samples.map(|(t, pose)| (t, *pose)));
+    }
+}
+
+/// The pose of the odometry, the lidar is assumed at the origin of the robot frame.
+impl MotionSample for Odometry {
+    fn key_poses<'a>(
+        samples: impl Iterator<Item = (CuTime, &'a Self)>,
+        keys: &mut Vec<(CuTime, Transform)>,
+    ) {
+        keys.extend(samples.map(|(t, odometry)| (t, odometry.pose)));
+    }
+}
+
+/// Only the rotation of the lidar, integrated from the gyroscope of an IMU with the same axes,
+/// it is usually the largest part of the distortion.
+impl MotionSample for ImuPayload {
+    fn key_poses<'a>(
+        samples: impl Iterator<Item = (CuTime, &'a Self)>,
+        keys: &mut Vec<(CuTime, Transform)>,
+    ) {
+        let mut last: Option<(CuTime, [f32; 3], Quaternion)> = None;
+        for (t, imu) in samples {
+            let w = imu.angular_velocity.values();
+            let rotation = match last {
+                None => Quaternion::IDENTITY,
+                Some((last_t, last_w, last_rotation)) => {
+                    let dt = t.saturating_sub(last_t).0 as f32 / 1e9;
+                    // trapezoidal integration of the angular velocity, in the body frame.
+                    let delta = [0, 1, 2].map(|i| (w[i] + last_w[i]) / 2.0 * dt);
+                    (last_rotation * Quaternion::from_rotation_vector(delta)).normalize()
+                }
+            };
+            keys.push((t, Transform::new(Vector3::default(), rotation)));
+            last = Some((t, w, rotation));
+        }
+    }
+}
+
+/// Where the deskewed cloud is expressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reference {
+    Start,
+    End,
+}
+
+/// Motion compensation of a lidar scan: the lidar moves while it scans, so all the points are expressed
+/// in the frame of the lidar at the time it measured them.
+/// This task moves them all in the frame of the lidar at the start or the end of the scan,
+/// with the motion interpolated from a stream of timestamped (tov) poses or IMU readings it buffers like cu_aligner.
+///
+/// N is the capacity of the cloud, S the number of motion samples kept, they need to cover the scan duration.
+/// The motion is extrapolated at constant velocity up to "max_extrapolation" past the samples.
+/// Without enough samples the cloud is passed through as is.
+///
+/// Config:
+/// - "reference": "start" or "end" (default) of the scan.
+/// - "max_extrapolation": a duration, "50ms" by default.
+/// - "stale_data_horizon": how long the samples are kept, "1s" by default.
+pub struct Deskew<const N: usize, const S: usize, M: MotionSample> {
+    reference: Reference,
+    max_extrapolation: CuDuration,
+    stale_data_horizon: CuDuration,
+    samples: TimeboundCircularBuffer<S, M>,
+    // one pose per sample, so S at most: it is reused without reallocating.
+    keys: Vec<(CuTime, Transform)>,
+}
+
+/// Deskews with the poses of the lidar.
+pub type PoseDeskew<const N: usize, const S: usize> = Deskew<N, S, Pose>;
+
+/// Deskews the rotation with the gyroscope of an IMU.
+pub type ImuDeskew<const N: usize, const S: usize> = Deskew<N, S, ImuPayload>;
+
+impl<const N: usize, const S: usize, M: MotionSample> Deskew<N, S, M> {
+    /// The interpolated pose at a time, the keys need to have at least 2 poses.
+    fn pose_at(&self, time: CuTime) -> Transform {
+        let first = self.keys[0].0;
+        let last = self.keys[self.keys.len() - 1].0;
+        let time = time.clamp(
+            first.saturating_sub(self.max_extrapolation),
+            last.saturating_add(self.max_extrapolation),
+        );
+        // the segment containing time or the closest one to extrapolate.
+        let k = self
+            .keys
+            .partition_point(|(t, _)| *t <= time)
+            .clamp(1, self.keys.len() - 1);
+        let (t0, pose0) = &self.keys[k - 1];
+        let (t1, pose1) = &self.keys[k];
+        let span = t1.signed_sub(*t0).0;
+        if span <= 0 {
+            return *pose1;
+        }
+        let alpha = time.signed_sub(*t0).0 as f64 / span as f64;
+        pose0.interpolate(pose1, alpha as f32)
+    }
+}
+
+impl<const N: usize, const S: usize, M: MotionSample> Freezable for Deskew<N, S, M> {}
+
+impl<'cl, const N: usize, const S: usize, M: MotionSample + 'cl> CuTask<'cl> for Deskew<N, S, M> {
+    type Input = input_msg!('cl, PointCloudSoa<N>, M);
+    type Output = output_msg!('cl, PointCloudSoa<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let reference = match config.and_then(|c| c.get::<String>("reference")).as_deref() {
+            None | Some("end") => Reference::End,
+            Some("start") => Reference::Start,
+            Some(other) => {
+                return Err(format!(
+                    "Deskew: 'reference' is \"start\" or \"end\", not \"{other}\"."
+                )
+                .into())
+            }
+        };
+        let duration = |key: &str, default: CuDuration| {
+            config
+                .and_then(|c| c.get::<CuDuration>(key))
+                .unwrap_or(default)
+        };
+        Ok(Self {
+            reference,
+            max_extrapolation: duration(
+                "max_extrapolation",
+                CuDuration::from(Duration::from_millis(50)),
+            ),
+            stale_data_horizon: duration(
+                "stale_data_horizon",
+                CuDuration::from(Duration::from_secs(1)),
+            ),
+            samples: TimeboundCircularBuffer::new(),
+            keys: Vec::with_capacity(S),
+        })
+    }
+
+    fn preprocess(&mut self, clock: &RobotClock) -> CuResult<()> {
+        self.samples
+            .purge(clock.now().saturating_sub(self.stale_data_horizon));
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let (cloud_msg, motion_msg) = input;
+        if motion_msg.payload().is_some() {
+            if let Tov::Time(_) = motion_msg.metadata.tov {
+                self.samples.push(motion_msg.clone());
+            }
+        }
+        let Some(cloud) = cloud_msg.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+
+        self.keys.clear();
+        M::key_poses(
+            self.samples
+                .inner
+                .iter()
+                .filter_map(|msg| match msg.metadata.tov {
+                    Tov::Time(t) => msg.payload().map(|p| (t, p)),
+                    _ => None,
+                }),
+            &mut self.keys,
+        );
+
+        let deskewed = output_cloud(output);
+        let tovs = &cloud.tov[..cloud.len];
+        let (Some(&start), Some(&end)) = (tovs.iter().min(), tovs.iter().max()) else {
+            output.metadata.tov = cloud_msg.metadata.tov.clone();
+            return Ok(());
+        };
+        if self.keys.len() < 2 {
+            // not enough motion samples to know how the lidar moved.
+            for i in 0..cloud.len {
+                deskewed.push(cloud.get(i));
+            }
+            output.metadata.tov = cloud_msg.metadata.tov.clone();
+            return Ok(());
+        }
+
+        let reference_time = match self.reference {
+            Reference::Start => start,
+            Reference::End => end,
+        };
+        let to_reference = self.pose_at(reference_time).inverse();
+        for i in 0..cloud.len {
+            let mut point = cloud.get(i);
+            let correction = to_reference.compose(&self.pose_at(point.tov));
+            let p = correction.apply(&Vector3::new(point.x.0, point.y.0, point.z.0));
+            point.x = Distance(p.x);
+            point.y = Distance(p.y);
+            point.z = Distance(p.z);
+            deskewed.push(point);
+        }
+        output.metadata.tov = Tov::Time(reference_time);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cloud, xyz};
+    use cu29::test_utils::msg_at;
+    use cu_sensor_payloads::Twist;
+
+    fn assert_close(a: (f32, f32, f32), b: (f32, f32, f32)) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3 && (a.2 - b.2).abs() < 1e-3,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn test_pose_deskew() {
+        // the lidar moves at 10 m/s along x during a scan of 100ms, it sees a wall 5m in front of it.
+        let mut task = PoseDeskew::<8, 16>::new(None).unwrap();
+        let clock = RobotClock::new();
+        let pose =
+            |x: f32| Transform::new(Vector3::from_values([x, 0.0, 0.0]), Quaternion::IDENTITY);
+        let empty = CuMsg::<PointCloudSoa<8>>::new(None);
+        let mut output = CuMsg::<PointCloudSoa<8>>::default();
+        task.process(
+            &clock,
+            (&empty, &msg_at(CuDuration(0), pose(0.0))),
+            &mut output,
+        )
+        .unwrap();
+        assert!(output.payload().is_none());
+
+        let scan = cloud::<8>(&[
+            (0, 5.0, 1.0, 0.0),
+            (50_000_000, 4.5, 0.0, 0.0),
+            (100_000_000, 4.0, -1.0, 0.0),
+        ]);
+        task.process(
+            &clock,
+            (&scan, &msg_at(CuDuration(100_000_000), pose(1.0))),
+            &mut output,
+        )
+        .unwrap();
+        let points = xyz(&output);
+        // all in the frame of the lidar at the end of the scan.
+        assert_close(points[0], (4.0, 1.0, 0.0));
+        assert_close(points[1], (4.0, 0.0, 0.0));
+        assert_close(points[2], (4.0, -1.0, 0.0));
+        assert_eq!(output.metadata.tov, Tov::Time(CuDuration(100_000_000)));
+    }
+
+    #[test]
+    fn test_imu_deskew() {
+        // the lidar turns at 90 deg/s around z during a scan of 100ms with a single point straight ahead at 2m.
+        let mut config = ComponentConfig::new();
+        config.set("reference", "start".to_string());
+        let mut task = ImuDeskew::<8, 16>::new(Some(&config)).unwrap();
+        let clock = RobotClock::new();
+        let imu = ImuPayload {
+            angular_velocity: Vector3::from_values([0.0, 0.0, std::f32::consts::FRAC_PI_2]),
+            ..Default::default()
+        };
+        let empty = CuMsg::<PointCloudSoa<8>>::new(None);
+        let mut output = CuMsg::<PointCloudSoa<8>>::default();
+        for t in [0, 50_000_000] {
+            task.process(&clock, (&empty, &msg_at(CuDuration(t), imu)), &mut output)
+                .unwrap();
+        }
+        // the last point is extrapolated 50ms after the last sample.
+        let scan = cloud::<8>(&[(0, 2.0, 0.0, 0.0), (100_000_000, 2.0, 0.0, 0.0)]);
+        task.process(&clock, (&scan, &CuMsg::new(None)), &mut output)
+            .unwrap();
+        let points = xyz(&output);
+        assert_close(points[0], (2.0, 0.0, 0.0));
+        // after 100ms the lidar turned by 9 degrees.
+        let angle = std::f32::consts::FRAC_PI_2 * 0.1;
+        assert_close(points[1], (2.0 * angle.cos(), 2.0 * angle.sin(), 0.0));
+    }
+
+    #[test]
+    fn test_no_motion() {
+        let mut task = Deskew::<8, 16, Odometry>::new(None).unwrap();
+        let scan = cloud::<8>(&[(0, 2.0, 0.0, 0.0), (10, 3.0, 0.0, 0.0)]);
+        let mut output = CuMsg::<PointCloudSoa<8>>::default();
+        let odometry = msg_at(
+            CuDuration(0),
+            Odometry {
+                pose: Transform::default(),
+                twist: Twist::default(),
+            },
+        );
+        task.process(&RobotClock::new(), (&scan, &odometry), &mut output)
+            .unwrap();
+        assert_eq!(xyz(&output), xyz(&scan));
+    }
+}
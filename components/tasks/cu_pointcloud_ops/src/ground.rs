@@ -0,0 +1,225 @@
+use crate::{getcfg, output_cloud};
+use cu29::prelude::*;
+use cu_sensor_payloads::PointCloudSoa;
+
+/// The plane z = a x + b y + c.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Plane {
+    a: f32,
+    b: f32,
+    c: f32,
+}
+
+impl Plane {
+    /// Perpendicular distance of a point to the plane, signed positive above it.
+    fn distance(&self, x: f32, y: f32, z: f32) -> f32 {
+        (z - self.a * x - self.b * y - self.c) / (self.a * self.a + self.b * self.b + 1.0).sqrt()
+    }
+}
+
+/// Removes the ground from a point cloud expressed in a frame where z is up (see PointCloudTransform).
+///
+/// It fits a plane on the lowest points of the cloud and refines it a few times on the points close to it,
+/// so it handles a sensor slightly tilted or a gentle slope. The points within "distance_threshold" of the plane
+/// are removed.
+///
+/// Config:
+/// - "distance_threshold": in m, 0.15 by default.
+/// - "seed_count": the number of lowest points estimating the initial ground height, 20 by default.
+/// - "seed_threshold": in m, the points up to this height above the initial ground height seed the first fit, 0.3 by default.
+/// - "iterations": the number of refinements of the plane, 3 by default.
+pub struct GroundRemoval<const N: usize> {
+    distance_threshold: f32,
+    seed_count: usize,
+    seed_threshold: f32,
+    iterations: u32,
+    // scratch buffers with a capacity of N, reused at each process.
+    heights: Vec<f32>,
+    seeds: Vec<u32>,
+    last_plane: Option<Plane>,
+}
+
+impl<const N: usize> GroundRemoval<N> {
+    /// The last estimated ground as (a, b, c) with z = a x + b y + c.
+    pub fn ground_plane(&self) -> Option<(f32, f32, f32)> {
+        self.last_plane.map(|p| (p.a, p.b, p.c))
+    }
+
+    /// Least squares fit of a plane on the seeds, None if they are degenerated.
+    fn fit(&self, cloud: &PointCloudSoa<N>) -> Option<Plane> {
+        if self.seeds.len() < 3 {
+            return None;
+        }
+        let n = self.seeds.len() as f64;
+        let coords = |i: &u32| {
+            let i = *i as usize;
+            (
+                cloud.x[i].0.value as f64,
+                cloud.y[i].0.value as f64,
+                cloud.z[i].0.value as f64,
+            )
+        };
+        let (mut mx, mut my, mut mz) = (0.0, 0.0, 0.0);
+        for (x, y, z) in self.seeds.iter().map(coords) {
+            mx += x;
+            my += y;
+            mz += z;
+        }
+        (mx, my, mz) = (mx / n, my / n, mz / n);
+        // centered normal equations for a and b
+        let (mut sxx, mut sxy, mut syy, mut sxz, mut syz) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (x, y, z) in self.seeds.iter().map(coords) {
+            let (x, y, z) = (x - mx, y - my, z - mz);
+            sxx += x * x;
+            sxy += x * y;
+            syy += y * y;
+            sxz += x * z;
+            syz += y * z;
+        }
+        let det = sxx * syy - sxy * sxy;
+        if det.abs() < 1e-9 {
+            return None;
+        }
+        let a = (sxz * syy - syz * sxy) / det;
+        let b = (syz * sxx - sxz * sxy) / det;
+        Some(Plane {
+            a: a as f32,
+            b: b as f32,
+            c: (mz - a * mx - b * my) as f32,
+        })
+    }
+
+    fn estimate_ground(&mut self, cloud: &PointCloudSoa<N>) -> Option<Plane> {
+        let len = cloud.len;
+        if len == 0 {
+            return None;
+        }
+        // the initial ground height is the mean height of the lowest points.
+        self.heights.clear();
+        self.heights
+            .extend(cloud.z[..len].iter().map(|z| z.0.value));
+        let k = self.seed_count.clamp(1, len);
+        self.heights
+            .select_nth_unstable_by(k - 1, |a, b| a.total_cmp(b));
+        let ground_height = self.heights[..k].iter().sum::<f32>() / k as f32;
+
+        self.seeds.clear();
+        self.seeds.extend(
+            (0..len as u32)
+                .filter(|&i| cloud.z[i as usize].0.value < ground_height + self.seed_threshold),
+        );
+        // a horizontal ground if we cannot do better.
+        let mut plane = Plane {
+            a: 0.0,
+            b: 0.0,
+            c: ground_height,
+        };
+        for _ in 0..=self.iterations {
+            match self.fit(cloud) {
+                Some(fitted) => plane = fitted,
+                None => break,
+            }
+            self.seeds.clear();
+            self.seeds.extend((0..len as u32).filter(|&i| {
+                let i = i as usize;
+                plane
+                    .distance(cloud.x[i].0.value, cloud.y[i].0.value, cloud.z[i].0.value)
+                    .abs()
+                    < self.distance_threshold
+            }));
+        }
+        Some(plane)
+    }
+}
+
+impl<const N: usize> Freezable for GroundRemoval<N> {}
+
+impl<'cl, const N: usize> CuTask<'cl> for GroundRemoval<N> {
+    type Input = input_msg!('cl, PointCloudSoa<N>);
+    type Output = output_msg!('cl, PointCloudSoa<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let distance_threshold = getcfg(config, "distance_threshold", 0.15);
+        if distance_threshold <= 0.0 {
+            return Err("GroundRemoval: 'distance_threshold' must be positive.".into());
+        }
+        Ok(Self {
+            distance_threshold,
+            seed_count: config
+                .and_then(|c| c.get::<u32>("seed_count"))
+                .unwrap_or(20) as usize,
+            seed_threshold: getcfg(config, "seed_threshold", 0.3),
+            iterations: config.and_then(|c| c.get::<u32>("iterations")).unwrap_or(3),
+            heights: Vec::with_capacity(N),
+            seeds: Vec::with_capacity(N),
+            last_plane: None,
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let Some(cloud) = input.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let plane = self.estimate_ground(cloud);
+        self.last_plane = plane;
+        let obstacles = output_cloud(output);
+        for i in 0..cloud.len {
+            let is_ground = plane.is_some_and(|plane| {
+                plane
+                    .distance(cloud.x[i].0.value, cloud.y[i].0.value, cloud.z[i].0.value)
+                    .abs()
+                    < self.distance_threshold
+            });
+            if !is_ground {
+                obstacles.push(cloud.get(i));
+            }
+        }
+        output.metadata.tov = input.metadata.tov.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cloud, xyz};
+
+    #[test]
+    fn test_ground_removal() {
+        // a ground slightly tilted (5%) 1.5m below the sensor with some noise, and a box on it.
+        let mut points = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                let (x, y) = (i as f32 - 5.0, j as f32 - 5.0);
+                let noise = if (i + j) % 2 == 0 { 0.02 } else { -0.02 };
+                points.push((0, x, y, -1.5 + 0.05 * x + noise));
+            }
+        }
+        let obstacles = [
+            (1, 2.0, 2.0, -1.0),
+            (1, 2.0, 2.5, -0.5),
+            (1, -3.0, 1.0, 0.3),
+        ];
+        points.extend_from_slice(&obstacles);
+
+        let mut task = GroundRemoval::<128>::new(None).unwrap();
+        let input = cloud::<128>(&points);
+        let mut output = CuMsg::<PointCloudSoa<128>>::default();
+        task.process(&RobotClock::new(), &input, &mut output)
+            .unwrap();
+
+        let expected: Vec<_> = obstacles.iter().map(|p| (p.1, p.2, p.3)).collect();
+        assert_eq!(xyz(&output), expected);
+        let (a, b, c) = task.ground_plane().unwrap();
+        assert!((a - 0.05).abs() < 0.01 && b.abs() < 0.01 && (c + 1.5).abs() < 0.05);
+    }
+}
@@ -0,0 +1,80 @@
+//! Point cloud processing tasks working directly on the SoA layout of the Copper lidar drivers.
+//! They are all generic on the capacity of the clouds so they can be dropped between a driver and its consumers,
+//! for example `cu_pointcloud_ops::VoxelGrid<320>` after a `cu_hesai::Xt32`.
+
+mod crop;
+mod deskew;
+mod ground;
+mod merge;
+mod transform;
+mod voxel;
+
+pub use crop::{CropBox, RangeFilter};
+pub use deskew::{Deskew, ImuDeskew, MotionSample, PoseDeskew};
+pub use ground::GroundRemoval;
+pub use merge::PointCloudMerge;
+pub use transform::PointCloudTransform;
+pub use voxel::VoxelGrid;
+
+use cu29::prelude::*;
+use cu_sensor_payloads::PointCloudSoa;
+
+/// Gets an f32 from the config with a default value.
+fn getcfg(config: Option<&ComponentConfig>, key: &str, default: f32) -> f32 {
+    config
+        .and_then(|c| c.get::<f64>(key))
+        .map(|v| v as f32)
+        .unwrap_or(default)
+}
+
+/// Reuses the payload of the output if any and empties it.
+fn output_cloud<const N: usize>(output: &mut CuMsg<PointCloudSoa<N>>) -> &mut PointCloudSoa<N> {
+    let cloud = output
+        .payload_mut()
+        .get_or_insert_with(PointCloudSoa::default);
+    cloud.len = 0;
+    cloud
+}
+
+/// Keeps only the points of the input matching the predicate on their (x, y, z) in meters.
+fn filter_cloud<const N: usize>(
+    input: &CuMsg<PointCloudSoa<N>>,
+    output: &mut CuMsg<PointCloudSoa<N>>,
+    mut keep: impl FnMut(f32, f32, f32) -> bool,
+) {
+    let Some(cloud) = input.payload() else {
+        output.clear_payload();
+        return;
+    };
+    let filtered = output_cloud(output);
+    for i in 0..cloud.len {
+        if keep(cloud.x[i].0.value, cloud.y[i].0.value, cloud.z[i].0.value) {
+            filtered.push(cloud.get(i));
+        }
+    }
+    output.metadata.tov = input.metadata.tov.clone();
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+    use cu_sensor_payloads::PointCloud;
+
+    /// A cloud from (tov in ns, x, y, z) tuples.
+    pub fn cloud<const N: usize>(points: &[(u64, f32, f32, f32)]) -> CuMsg<PointCloudSoa<N>> {
+        let mut cloud = PointCloudSoa::<N>::default();
+        for &(tov, x, y, z) in points {
+            cloud.push(PointCloud::new(CuDuration(tov), x, y, z, 0.0, None));
+        }
+        CuMsg::new(Some(cloud))
+    }
+
+    /// The (x, y, z) of the points of a cloud.
+    pub fn xyz<const N: usize>(msg: &CuMsg<PointCloudSoa<N>>) -> Vec<(f32, f32, f32)> {
+        msg.payload()
+            .unwrap()
+            .iter()
+            .map(|p| (p.x.0.value, p.y.0.value, p.z.0.value))
+            .collect()
+    }
+}
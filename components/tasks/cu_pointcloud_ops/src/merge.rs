@@ -0,0 +1,141 @@
+use crate::output_cloud;
+use cu29::prelude::*;
+use cu_sensor_payloads::PointCloudSoa;
+
+/// Merges 2 clouds, for example from 2 lidars already transformed in the same frame and aligned in time,
+/// into one cloud of capacity O sorted by tov.
+/// If only one of the inputs has a payload, it is passed through (sorted).
+/// It fails if the merged cloud doesn't fit in O points.
+pub struct PointCloudMerge<const A: usize, const B: usize, const O: usize> {
+    // the sort orders of the inputs, reused so process doesn't allocate.
+    order_a: Vec<u32>,
+    order_b: Vec<u32>,
+}
+
+impl<const A: usize, const B: usize, const O: usize> Freezable for PointCloudMerge<A, B, O> {}
+
+/// The indices of the points of the cloud sorted by tov.
+fn sort_by_tov<const N: usize>(cloud: Option<&PointCloudSoa<N>>, order: &mut Vec<u32>) {
+    order.clear();
+    if let Some(cloud) = cloud {
+        order.extend(0..cloud.len as u32);
+        // the points with the same tov keep the order of the driver, the unstable sort is in place.
+        order.sort_unstable_by_key(|&i| (cloud.tov[i as usize], i));
+    }
+}
+
+fn tov_of<const N: usize>(cloud: &PointCloudSoa<N>) -> Tov {
+    let tovs = &cloud.tov[..cloud.len];
+    match (tovs.iter().min(), tovs.iter().max()) {
+        (Some(&start), Some(&end)) if start == end => Tov::Time(start),
+        (Some(&start), Some(&end)) => Tov::Range(CuTimeRange { start, end }),
+        _ => Tov::None,
+    }
+}
+
+impl<'cl, const A: usize, const B: usize, const O: usize> CuTask<'cl> for PointCloudMerge<A, B, O> {
+    type Input = input_msg!('cl, PointCloudSoa<A>, PointCloudSoa<B>);
+    type Output = output_msg!('cl, PointCloudSoa<O>);
+
+    fn new(_config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            order_a: Vec::with_capacity(A),
+            order_b: Vec::with_capacity(B),
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let (a, b) = (input.0.payload(), input.1.payload());
+        if a.is_none() && b.is_none() {
+            output.clear_payload();
+            return Ok(());
+        }
+        sort_by_tov(a, &mut self.order_a);
+        sort_by_tov(b, &mut self.order_b);
+        let total = self.order_a.len() + self.order_b.len();
+        if total > O {
+            output.clear_payload();
+            return Err(format!(
+                "PointCloudMerge: {total} points don't fit in the output capacity of {O}."
+            )
+            .into());
+        }
+
+        let merged = output_cloud(output);
+        let (mut ia, mut ib) = (0, 0);
+        while ia < self.order_a.len() || ib < self.order_b.len() {
+            let next_a = self.order_a.get(ia).map(|&i| i as usize);
+            let next_b = self.order_b.get(ib).map(|&i| i as usize);
+            // a wins the ties.
+            let take_a = match (next_a, next_b, a, b) {
+                (Some(i), Some(j), Some(a), Some(b)) => a.tov[i] <= b.tov[j],
+                (Some(_), _, _, _) => true,
+                _ => false,
+            };
+            if take_a {
+                merged.push(a.unwrap().get(next_a.unwrap()));
+                ia += 1;
+            } else {
+                merged.push(b.unwrap().get(next_b.unwrap()));
+                ib += 1;
+            }
+        }
+        let tov = tov_of(merged);
+        output.metadata.tov = tov;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::cloud;
+
+    #[test]
+    fn test_merge() {
+        let mut task = PointCloudMerge::<4, 4, 8>::new(None).unwrap();
+        let a = cloud::<4>(&[(5, 0.0, 0.0, 0.0), (1, 1.0, 0.0, 0.0), (3, 2.0, 0.0, 0.0)]);
+        let b = cloud::<4>(&[
+            (2, 10.0, 0.0, 0.0),
+            (3, 11.0, 0.0, 0.0),
+            (6, 12.0, 0.0, 0.0),
+        ]);
+        let mut output = CuMsg::<PointCloudSoa<8>>::default();
+        task.process(&RobotClock::new(), (&a, &b), &mut output)
+            .unwrap();
+
+        let merged = output.payload().unwrap();
+        let tovs: Vec<u64> = merged.tov[..merged.len].iter().map(|t| t.0).collect();
+        assert_eq!(tovs, vec![1, 2, 3, 3, 5, 6]);
+        let xs: Vec<f32> = merged.x[..merged.len].iter().map(|x| x.0.value).collect();
+        assert_eq!(xs, vec![1.0, 10.0, 2.0, 11.0, 0.0, 12.0]);
+        assert_eq!(
+            output.metadata.tov,
+            Tov::Range(CuTimeRange {
+                start: CuDuration(1),
+                end: CuDuration(6)
+            })
+        );
+
+        // only one input.
+        let none = CuMsg::<PointCloudSoa<4>>::new(None);
+        task.process(&RobotClock::new(), (&a, &none), &mut output)
+            .unwrap();
+        assert_eq!(output.payload().unwrap().len, 3);
+
+        // too many points.
+        let mut task = PointCloudMerge::<4, 4, 5>::new(None).unwrap();
+        let mut output = CuMsg::<PointCloudSoa<5>>::default();
+        assert!(task
+            .process(&RobotClock::new(), (&a, &b), &mut output)
+            .is_err());
+    }
+}
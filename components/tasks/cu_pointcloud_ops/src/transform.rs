@@ -0,0 +1,102 @@
+use crate::{getcfg, output_cloud};
+use cu29::prelude::*;
+use cu_sensor_payloads::{Distance, PointCloudSoa, Quaternion, Transform, Vector3};
+use uom::si::angle::radian;
+use uom::si::f32::{Angle, Length};
+use uom::si::length::meter;
+
+/// Moves a point cloud to another frame of reference, typically from the sensor frame to the robot frame
+/// with the extrinsic calibration of the lidar.
+///
+/// Config: the pose of the sensor in the target frame, "x", "y", "z" in m and "roll", "pitch", "yaw" in rad.
+pub struct PointCloudTransform<const N: usize> {
+    transform: Transform,
+}
+
+impl<const N: usize> PointCloudTransform<N> {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl<const N: usize> Freezable for PointCloudTransform<N> {}
+
+impl<'cl, const N: usize> CuTask<'cl> for PointCloudTransform<N> {
+    type Input = input_msg!('cl, PointCloudSoa<N>);
+    type Output = output_msg!('cl, PointCloudSoa<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let translation = Vector3::new(
+            Length::new::<meter>(getcfg(config, "x", 0.0)),
+            Length::new::<meter>(getcfg(config, "y", 0.0)),
+            Length::new::<meter>(getcfg(config, "z", 0.0)),
+        );
+        let rotation = Quaternion::from_euler(
+            Angle::new::<radian>(getcfg(config, "roll", 0.0)),
+            Angle::new::<radian>(getcfg(config, "pitch", 0.0)),
+            Angle::new::<radian>(getcfg(config, "yaw", 0.0)),
+        );
+        Ok(Self {
+            transform: Transform::new(translation, rotation),
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let Some(cloud) = input.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let transformed = output_cloud(output);
+        for i in 0..cloud.len {
+            let mut point = cloud.get(i);
+            let p = self
+                .transform
+                .apply(&Vector3::new(point.x.0, point.y.0, point.z.0));
+            point.x = Distance(p.x);
+            point.y = Distance(p.y);
+            point.z = Distance(p.z);
+            transformed.push(point);
+        }
+        output.metadata.tov = input.metadata.tov.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cloud, xyz};
+
+    #[test]
+    fn test_transform() {
+        let mut config = ComponentConfig::new();
+        config.set("x", 1.0);
+        config.set("z", 0.5);
+        config.set("yaw", std::f64::consts::FRAC_PI_2);
+        let mut task = PointCloudTransform::<16>::new(Some(&config)).unwrap();
+
+        let input = cloud::<16>(&[(1, 1.0, 0.0, 0.0), (2, 0.0, 2.0, 1.0)]);
+        let mut output = CuMsg::<PointCloudSoa<16>>::default();
+        task.process(&RobotClock::new(), &input, &mut output)
+            .unwrap();
+
+        let points = xyz(&output);
+        let expected = [(1.0, 1.0, 0.5), (-1.0, 0.0, 1.5)];
+        assert_eq!(points.len(), 2);
+        for (p, e) in points.iter().zip(expected) {
+            assert!(
+                (p.0 - e.0).abs() < 1e-5 && (p.1 - e.1).abs() < 1e-5 && (p.2 - e.2).abs() < 1e-5
+            );
+        }
+        // the time of validity of the points is kept.
+        assert_eq!(output.payload().unwrap().tov[1], CuDuration(2));
+    }
+}
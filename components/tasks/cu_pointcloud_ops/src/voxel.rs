@@ -0,0 +1,135 @@
+use crate::{getcfg, output_cloud};
+use cu29::prelude::*;
+use cu_sensor_payloads::{PointCloud, PointCloudSoa};
+use uom::si::f32::{Length, Ratio};
+use uom::si::length::meter;
+use uom::si::ratio::ratio;
+
+type VoxelKey = (i32, i32, i32);
+
+/// Downsamples a point cloud by replacing all the points falling in the same cubic voxel by their centroid.
+/// The centroid gets the earliest tov of the points of its voxel.
+///
+/// Config: "leaf_size", the side of the voxels in m (0.1 by default).
+pub struct VoxelGrid<const N: usize> {
+    leaf_size: f32,
+    // the voxel of each point, with a capacity of N and sorted in place.
+    keys: Vec<(VoxelKey, u32)>,
+}
+
+impl<const N: usize> Freezable for VoxelGrid<N> {}
+
+impl<'cl, const N: usize> CuTask<'cl> for VoxelGrid<N> {
+    type Input = input_msg!('cl, PointCloudSoa<N>);
+    type Output = output_msg!('cl, PointCloudSoa<N>);
+
+    fn new(config: Option<&ComponentConfig>) -> CuResult<Self>
+    where
+        Self: Sized,
+    {
+        let leaf_size = getcfg(config, "leaf_size", 0.1);
+        if leaf_size <= 0.0 {
+            return Err(format!("VoxelGrid: 'leaf_size' must be positive, got {leaf_size}").into());
+        }
+        Ok(Self {
+            leaf_size,
+            keys: Vec::with_capacity(N),
+        })
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        let Some(cloud) = input.payload() else {
+            output.clear_payload();
+            return Ok(());
+        };
+        let voxel = |d: f32| (d / self.leaf_size).floor() as i32;
+        self.keys.clear();
+        self.keys.extend((0..cloud.len).map(|i| {
+            let key = (
+                voxel(cloud.x[i].0.value),
+                voxel(cloud.y[i].0.value),
+                voxel(cloud.z[i].0.value),
+            );
+            (key, i as u32)
+        }));
+        self.keys.sort_unstable();
+
+        let downsampled = output_cloud(output);
+        for group in self.keys.chunk_by(|a, b| a.0 == b.0) {
+            let (mut x, mut y, mut z, mut intensity) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+            let mut tov = CuTime::MAX;
+            let mut return_order = 0;
+            for &(_, i) in group {
+                let i = i as usize;
+                x += cloud.x[i].0.value;
+                y += cloud.y[i].0.value;
+                z += cloud.z[i].0.value;
+                intensity += cloud.i[i].value;
+                if cloud.tov[i] < tov {
+                    tov = cloud.tov[i];
+                    return_order = cloud.return_order[i];
+                }
+            }
+            let n = group.len() as f32;
+            downsampled.push(PointCloud::new_uom(
+                tov,
+                Length::new::<meter>(x / n),
+                Length::new::<meter>(y / n),
+                Length::new::<meter>(z / n),
+                Ratio::new::<ratio>(intensity / n),
+                Some(return_order),
+            ));
+        }
+        output.metadata.tov = input.metadata.tov.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cloud, xyz};
+
+    #[test]
+    fn test_voxel_grid() {
+        let mut config = ComponentConfig::new();
+        config.set("leaf_size", 1.0);
+        let mut task = VoxelGrid::<16>::new(Some(&config)).unwrap();
+
+        let input = cloud::<16>(&[
+            (3, 0.2, 0.2, 0.2),
+            (1, 0.4, 0.6, 0.8),
+            (2, 5.5, 0.5, 0.5),
+            (4, -0.5, 0.5, 0.5), // negative coordinates are in their own voxel
+        ]);
+        let mut output = CuMsg::<PointCloudSoa<16>>::default();
+        task.process(&RobotClock::new(), &input, &mut output)
+            .unwrap();
+
+        let mut points = xyz(&output);
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0], (-0.5, 0.5, 0.5));
+        assert!((points[1].0 - 0.3).abs() < 1e-6 && (points[1].2 - 0.5).abs() < 1e-6);
+        assert_eq!(points[2], (5.5, 0.5, 0.5));
+
+        let merged = output
+            .payload()
+            .unwrap()
+            .iter()
+            .find(|p| p.x.0.value < 1.0 && p.x.0.value > 0.0);
+        assert_eq!(merged.unwrap().tov, CuDuration(1));
+
+        assert!(VoxelGrid::<16>::new(Some(&{
+            let mut c = ComponentConfig::new();
+            c.set("leaf_size", 0.0);
+            c
+        }))
+        .is_err());
+    }
+}
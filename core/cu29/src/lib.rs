@@ -10,6 +10,8 @@ pub use cu29_runtime::monitoring;
 pub use cu29_runtime::output_msg;
 pub use cu29_runtime::payload;
 pub use cu29_runtime::simulation;
+#[cfg(feature = "test-utils")]
+pub use cu29_runtime::test_utils;
 
 pub use bincode;
 pub use cu29_clock as clock;
@@ -20,8 +22,10 @@ pub mod prelude {
     pub use cu29_clock::*;
     pub use cu29_derive::*;
     pub use cu29_intern_strs::*;
+    pub use cu29_log;
     pub use cu29_log::*;
     pub use cu29_log_derive::*;
+    pub use cu29_log_runtime;
     pub use cu29_log_runtime::*;
     pub use cu29_runtime::config::*;
     pub use cu29_runtime::copperlist::*;
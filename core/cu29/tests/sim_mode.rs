@@ -0,0 +1,109 @@
+use cu29::prelude::*;
+use cu29_helpers::basic_copper_setup;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+pub struct Counter {}
+
+impl Freezable for Counter {}
+
+impl<'cl> CuSrcTask<'cl> for Counter {
+    type Output = output_msg!('cl, u32);
+
+    fn new(_config: Option<&ComponentConfig>) -> CuResult<Self> {
+        Ok(Self {})
+    }
+
+    fn process(&mut self, _clock: &RobotClock, _new_msg: Self::Output) -> CuResult<()> {
+        unreachable!("Replaced by a stub in sim mode.")
+    }
+}
+
+pub struct Double {}
+
+impl Freezable for Double {}
+
+impl<'cl> CuTask<'cl> for Double {
+    type Input = input_msg!('cl, u32);
+    type Output = output_msg!('cl, u32);
+
+    fn new(_config: Option<&ComponentConfig>) -> CuResult<Self> {
+        Ok(Self {})
+    }
+
+    fn process(
+        &mut self,
+        _clock: &RobotClock,
+        input: Self::Input,
+        output: Self::Output,
+    ) -> CuResult<()> {
+        if let Some(value) = input.payload() {
+            output.set_payload(value * 2);
+        }
+        Ok(())
+    }
+}
+
+pub struct Recorder {}
+
+impl Freezable for Recorder {}
+
+impl<'cl> CuSinkTask<'cl> for Recorder {
+    type Input = input_msg!('cl, u32);
+
+    fn new(_config: Option<&ComponentConfig>) -> CuResult<Self> {
+        Ok(Self {})
+    }
+
+    fn process(&mut self, _clock: &RobotClock, _input: Self::Input) -> CuResult<()> {
+        unreachable!("Replaced by a stub in sim mode.")
+    }
+}
+
+mod small {
+    use super::*;
+
+    #[copper_runtime(config = "tests/sim_mode_config.ron", sim_mode = true)]
+    struct SmallSim {}
+}
+
+#[test]
+fn test_sim_mode() {
+    use small::{SimStep, SmallSim};
+
+    let tmp_dir = TempDir::new().unwrap();
+    let copper_ctx = basic_copper_setup(&tmp_dir.path().join("sim.copper"), None, true, None)
+        .expect("Failed to setup logger.");
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let mut next = 0u32;
+    let mut sim_callback = {
+        let received = received.clone();
+        move |step: SimStep| match step {
+            SimStep::Src(CuTaskCallbackState::Process(_, output)) => {
+                next += 1;
+                output.set_payload(next);
+                SimOverride::ExecutedBySim
+            }
+            SimStep::Sink(CuTaskCallbackState::Process(input, _)) => {
+                received.lock().unwrap().push(*input.payload().unwrap());
+                SimOverride::ExecutedBySim
+            }
+            SimStep::Src(_) | SimStep::Sink(_) => SimOverride::ExecutedBySim,
+            _ => SimOverride::ExecuteByRuntime,
+        }
+    };
+    let sim_clock = SimClock::new(SimClockMode::FixedStep(CuDuration::from(1_000_000)));
+    let mut app = SmallSim::new_with_sim_clock(
+        sim_clock,
+        copper_ctx.unified_logger.clone(),
+        &mut sim_callback,
+    )
+    .unwrap();
+    app.start_all_tasks(&mut sim_callback).unwrap();
+    for _ in 0..3 {
+        app.run_one_iteration(&mut sim_callback).unwrap();
+    }
+    app.stop_all_tasks(&mut sim_callback).unwrap();
+    assert_eq!(*received.lock().unwrap(), vec![2, 4, 6]);
+    assert_eq!(app.sim_clock().unwrap().now(), CuDuration::from(3_000_000));
+}
@@ -0,0 +1,285 @@
+//! Calibration of the robot clock against an external time base like UTC or PTP.
+//! The robot clock is monotonic from an arbitrary reference, the sensors often timestamp their data
+//! in an external time base: this maintains an offset and drift estimate to convert between the two.
+
+use crate::{CuDuration, CuTime, RobotClock};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The external time bases a robot clock can be calibrated against.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub enum ExternalTimeBase {
+    /// ns since the unix epoch, UTC (with its leap seconds).
+    Utc,
+    /// ns since the PTP epoch, TAI (no leap seconds), as given by a PTP hardware clock.
+    Ptp,
+}
+
+/// A calibration between the robot clock and an external time base:
+/// external = external_ref + (robot - robot_ref) * (1 + drift_ppb / 1e9)
+/// Log it to be able to convert the timestamps in post-processing.
+#[derive(Copy, Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub struct ClockCalibration {
+    pub base: ExternalTimeBase,
+    /// The robot time of the reference point.
+    pub robot_ref: CuTime,
+    /// The external time of the reference point, in ns since the epoch of the base.
+    pub external_ref: u64,
+    /// How much faster the external clock runs compared to the robot clock, in parts per billion.
+    pub drift_ppb: f64,
+}
+
+impl ClockCalibration {
+    /// A calibration from a single pair of matching times, without any drift.
+    pub fn from_reference(base: ExternalTimeBase, robot_ref: CuTime, external_ref: u64) -> Self {
+        Self {
+            base,
+            robot_ref,
+            external_ref,
+            drift_ppb: 0.0,
+        }
+    }
+
+    /// Converts a robot time to the external time base, in ns since its epoch.
+    pub fn to_external(&self, robot_time: CuTime) -> u64 {
+        let elapsed = robot_time.0 as i128 - self.robot_ref.0 as i128;
+        let corrected = elapsed + (elapsed as f64 * self.drift_ppb / 1e9) as i128;
+        (self.external_ref as i128 + corrected).clamp(0, u64::MAX as i128) as u64
+    }
+
+    /// Converts an external time, in ns since the epoch of the base, to the robot time.
+    /// It saturates to 0 for the times before the start of the robot clock.
+    pub fn to_robot(&self, external_time: u64) -> CuTime {
+        let elapsed = external_time as i128 - self.external_ref as i128;
+        let corrected = (elapsed as f64 / (1.0 + self.drift_ppb / 1e9)) as i128;
+        CuDuration((self.robot_ref.0 as i128 + corrected).clamp(0, u64::MAX as i128) as u64)
+    }
+}
+
+impl Display for ClockCalibration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "robot {} = {:?} {} ns, drift {:.1} ppb",
+            self.robot_ref, self.base, self.external_ref, self.drift_ppb
+        )
+    }
+}
+
+/// Estimates a ClockCalibration from pairs of matching (robot, external) times.
+/// It fits a line on the last samples so the offset and the drift follow the slow changes of the clocks.
+#[derive(Debug, Clone)]
+pub struct ClockCalibrator {
+    base: ExternalTimeBase,
+    samples: VecDeque<(CuTime, u64)>,
+    window: usize,
+    calibration: Option<ClockCalibration>,
+}
+
+impl ClockCalibrator {
+    /// window: the number of samples kept for the estimation.
+    pub fn new(base: ExternalTimeBase, window: usize) -> Self {
+        Self {
+            base,
+            samples: VecDeque::with_capacity(window.max(1)),
+            window: window.max(1),
+            calibration: None,
+        }
+    }
+
+    pub fn base(&self) -> ExternalTimeBase {
+        self.base
+    }
+
+    /// Adds a matching pair of times and returns the updated calibration.
+    pub fn add_sample(&mut self, robot_time: CuTime, external_time: u64) -> ClockCalibration {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((robot_time, external_time));
+        let calibration = self.fit();
+        self.calibration = Some(calibration);
+        calibration
+    }
+
+    /// Samples the system UTC time against the robot clock, only valid for the Utc time base.
+    pub fn sample_system_utc(&mut self, clock: &RobotClock) -> ClockCalibration {
+        let (robot_time, utc) = system_utc_sample(clock);
+        self.add_sample(robot_time, utc)
+    }
+
+    /// The current calibration, None until the first sample.
+    pub fn calibration(&self) -> Option<ClockCalibration> {
+        self.calibration
+    }
+
+    /// Least squares fit of the external time against the robot time, relative to the last sample
+    /// to keep the precision in f64.
+    fn fit(&self) -> ClockCalibration {
+        let &(robot_ref, external_ref) = self.samples.back().unwrap();
+        if self.samples.len() < 2 {
+            return ClockCalibration::from_reference(self.base, robot_ref, external_ref);
+        }
+        // x: robot time, y: how much the external clock is ahead of the robot clock since the reference.
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .map(|&(robot, external)| {
+                let x = robot.0 as i128 - robot_ref.0 as i128;
+                let y = external as i128 - external_ref as i128 - x;
+                (x as f64, y as f64)
+            })
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let var_x = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum::<f64>();
+        let slope = if var_x > 0.0 {
+            points
+                .iter()
+                .map(|p| (p.0 - mean_x) * (p.1 - mean_y))
+                .sum::<f64>()
+                / var_x
+        } else {
+            0.0
+        };
+        // The fitted offset at the reference robot time smooths out the jitter of the samples.
+        let offset_at_ref = mean_y - slope * mean_x;
+        ClockCalibration {
+            base: self.base,
+            robot_ref,
+            external_ref: (external_ref as i128 + offset_at_ref as i128).max(0) as u64,
+            drift_ppb: slope * 1e9,
+        }
+    }
+}
+
+/// Reads the system UTC time bracketed by 2 reads of the robot clock to minimize the error.
+fn system_utc_sample(clock: &RobotClock) -> (CuTime, u64) {
+    let before = clock.now();
+    let utc = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The system time is before the unix epoch")
+        .as_nanos() as u64;
+    let after = clock.now();
+    (CuDuration(before.0 + (after.0 - before.0) / 2), utc)
+}
+
+/// A calibrator shared between all the drivers of a process so they convert their timestamps consistently.
+/// Feed it with the samples from your best time source (PTP, GNSS, NTP disciplined system time...)
+/// and the drivers will pick up the updates, see generation.
+#[derive(Debug, Clone)]
+pub struct ClockCalibrationService {
+    calibrator: Arc<RwLock<ClockCalibrator>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl ClockCalibrationService {
+    pub fn new(base: ExternalTimeBase, window: usize) -> Self {
+        Self {
+            calibrator: Arc::new(RwLock::new(ClockCalibrator::new(base, window))),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The process wide service against UTC used by the drivers by default.
+    pub fn global() -> &'static ClockCalibrationService {
+        static GLOBAL: OnceLock<ClockCalibrationService> = OnceLock::new();
+        GLOBAL.get_or_init(|| ClockCalibrationService::new(ExternalTimeBase::Utc, 16))
+    }
+
+    pub fn add_sample(&self, robot_time: CuTime, external_time: u64) -> ClockCalibration {
+        let calibration = self
+            .calibrator
+            .write()
+            .unwrap()
+            .add_sample(robot_time, external_time);
+        self.generation.fetch_add(1, Ordering::Release);
+        calibration
+    }
+
+    /// Samples the system UTC time against the robot clock, see ClockCalibrator::sample_system_utc.
+    pub fn sample_system_utc(&self, clock: &RobotClock) -> ClockCalibration {
+        let (robot_time, utc) = system_utc_sample(clock);
+        self.add_sample(robot_time, utc)
+    }
+
+    pub fn calibration(&self) -> Option<ClockCalibration> {
+        self.calibrator.read().unwrap().calibration()
+    }
+
+    /// The current calibration, or a first one from the system UTC time if nobody fed the service yet.
+    pub fn calibration_or_system_utc(&self, clock: &RobotClock) -> ClockCalibration {
+        match self.calibration() {
+            Some(calibration) => calibration,
+            None => self.sample_system_utc(clock),
+        }
+    }
+
+    /// Incremented at every new sample, it is a cheap way to check if the calibration changed.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let calibration = ClockCalibration {
+            base: ExternalTimeBase::Utc,
+            robot_ref: CuDuration(1_000_000_000),
+            external_ref: 1_700_000_000_000_000_000,
+            drift_ppb: 50_000.0,
+        };
+        let robot = CuDuration(61_000_000_000);
+        let external = calibration.to_external(robot);
+        // 60s later with a 50ppm drift is 3ms more on the external clock.
+        assert_eq!(external, 1_700_000_000_000_000_000 + 60_003_000_000);
+        let back = calibration.to_robot(external);
+        assert!((back.0 as i64 - robot.0 as i64).abs() < 10);
+        // before the start of the robot clock
+        assert_eq!(calibration.to_robot(0), CuDuration(0));
+    }
+
+    #[test]
+    fn test_drift_estimation() {
+        let mut calibrator = ClockCalibrator::new(ExternalTimeBase::Ptp, 8);
+        let external_start = 1_700_000_000_000_000_000u64;
+        // the external clock runs 20ppm faster, with a +-1µs jitter.
+        for i in 0..8u64 {
+            let robot = i * 1_000_000_000;
+            let jitter = if i % 2 == 0 { 1_000 } else { 0 };
+            calibrator.add_sample(
+                CuDuration(robot),
+                external_start + robot + robot / 50_000 + jitter,
+            );
+        }
+        let calibration = calibrator.calibration().unwrap();
+        assert!((calibration.drift_ppb - 20_000.0).abs() < 100.0);
+        let expected = external_start + 10_000_000_000 + 200_000;
+        let estimated = calibration.to_external(CuDuration(10_000_000_000));
+        assert!((estimated as i64 - expected as i64).abs() < 2_000);
+    }
+
+    #[test]
+    fn test_service_generation() {
+        let (clock, mock) = RobotClock::mock();
+        let service = ClockCalibrationService::new(ExternalTimeBase::Utc, 4);
+        assert!(service.calibration().is_none());
+        mock.increment(std::time::Duration::from_secs(1));
+        let calibration = service.calibration_or_system_utc(&clock);
+        assert_eq!(calibration.robot_ref, CuDuration(1_000_000_000));
+        assert_eq!(service.generation(), 1);
+        // already calibrated, no new sample.
+        service.calibration_or_system_utc(&clock);
+        assert_eq!(service.generation(), 1);
+    }
+}
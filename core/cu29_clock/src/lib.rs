@@ -10,16 +10,22 @@ use bincode::{Decode, Encode};
 use core::ops::{Add, Sub};
 pub use quanta::Instant;
 use quanta::{Clock, Mock};
-use serde::{Deserialize, Serialize};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::Into;
 use std::fmt::{Display, Formatter};
-use std::ops::{AddAssign, Div, Mul, SubAssign};
+use std::ops::{AddAssign, Div, Mul, Neg, SubAssign};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+mod calibration;
+pub use calibration::*;
+
 /// For Robot times, the underlying type is a u64 representing nanoseconds.
 /// It is always positive to simplify the reasoning on the user side.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
+/// It serializes as ns and deserializes from ns or a humanized string like "10ms", see FromStr.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct CuDuration(pub u64);
 
 impl CuDuration {
@@ -43,6 +49,201 @@ impl CuDuration {
             p0
         }
     }
+
+    /// None on overflow.
+    pub fn checked_add(self, rhs: CuDuration) -> Option<CuDuration> {
+        self.0.checked_add(rhs.0).map(CuDuration)
+    }
+
+    /// None if rhs is bigger than self.
+    pub fn checked_sub(self, rhs: CuDuration) -> Option<CuDuration> {
+        self.0.checked_sub(rhs.0).map(CuDuration)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<CuDuration> {
+        self.0.checked_mul(rhs).map(CuDuration)
+    }
+
+    pub fn saturating_add(self, rhs: CuDuration) -> CuDuration {
+        CuDuration(self.0.saturating_add(rhs.0))
+    }
+
+    /// Clamps to 0 if rhs is bigger than self.
+    pub fn saturating_sub(self, rhs: CuDuration) -> CuDuration {
+        CuDuration(self.0.saturating_sub(rhs.0))
+    }
+
+    pub fn abs_diff(self, other: CuDuration) -> CuDuration {
+        CuDuration(self.0.abs_diff(other.0))
+    }
+
+    /// self - rhs, negative if rhs is after self.
+    /// Saturates to the i64 range (about 292 years either way).
+    pub fn signed_sub(self, rhs: CuDuration) -> CuSignedDuration {
+        let diff = self.0 as i128 - rhs.0 as i128;
+        CuSignedDuration(diff.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+
+    /// Applies a signed offset, None if the result is negative or overflows.
+    pub fn checked_add_signed(self, rhs: CuSignedDuration) -> Option<CuDuration> {
+        self.0.checked_add_signed(rhs.0).map(CuDuration)
+    }
+}
+
+/// A signed difference between 2 CuTimes, in ns.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+    Default,
+)]
+pub struct CuSignedDuration(pub i64);
+
+impl CuSignedDuration {
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn abs(&self) -> CuDuration {
+        CuDuration(self.0.unsigned_abs())
+    }
+}
+
+impl From<CuSignedDuration> for i64 {
+    fn from(val: CuSignedDuration) -> Self {
+        val.0
+    }
+}
+
+impl Neg for CuSignedDuration {
+    type Output = Self;
+
+    /// Saturates: -i64::MIN is i64::MAX.
+    fn neg(self) -> Self::Output {
+        CuSignedDuration(self.0.saturating_neg())
+    }
+}
+
+impl Display for CuSignedDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_negative() {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.abs())
+    }
+}
+
+/// The error returned when a humanized duration cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDurationError(String);
+
+impl Display for ParseDurationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid duration: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+/// Parses a humanized duration: a number, an optional space and a unit among
+/// ns, us (or µs), ms, s, m (or min), h, d. For example "250us", "1.5s" or "10 ms".
+impl FromStr for CuDuration {
+    type Err = ParseDurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(|| ParseDurationError(format!("{s:?} has no unit")))?;
+        let (value, unit) = s.split_at(split);
+        let ns_per_unit: u64 = match unit.trim_start() {
+            "ns" => 1,
+            "us" | "µs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" | "min" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            "d" => 86_400_000_000_000,
+            unit => {
+                return Err(ParseDurationError(format!(
+                    "unknown unit {unit:?} in {s:?}"
+                )))
+            }
+        };
+        // integers are parsed as such to be exact on the whole u64 range.
+        if let Ok(int_value) = value.parse::<u64>() {
+            return int_value
+                .checked_mul(ns_per_unit)
+                .map(CuDuration)
+                .ok_or_else(|| ParseDurationError(format!("{s:?} overflows")));
+        }
+        let float_value: f64 = value
+            .parse()
+            .map_err(|_| ParseDurationError(format!("{value:?} is not a number in {s:?}")))?;
+        let ns = (float_value * ns_per_unit as f64).round();
+        if ns >= u64::MAX as f64 {
+            return Err(ParseDurationError(format!("{s:?} overflows")));
+        }
+        Ok(CuDuration(ns as u64))
+    }
+}
+
+// The newtype name is used by cu29_value to recognize the CuTimes.
+impl Serialize for CuDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("CuDuration", &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for CuDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CuDurationVisitor;
+
+        impl<'de> Visitor<'de> for CuDurationVisitor {
+            type Value = CuDuration;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "a number of ns or a duration like \"10ms\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<CuDuration, E> {
+                Ok(CuDuration(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<CuDuration, E> {
+                u64::try_from(v)
+                    .map(CuDuration)
+                    .map_err(|_| E::custom("a duration cannot be negative"))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<CuDuration, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<CuDuration, D::Error> {
+                deserializer.deserialize_any(self)
+            }
+
+            // (1000) in RON for example.
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<CuDuration, A::Error> {
+                seq.next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))
+            }
+        }
+
+        deserializer.deserialize_any(CuDurationVisitor)
+    }
 }
 
 /// bridge the API with standard Durations.
@@ -282,21 +483,12 @@ impl From<&[CuTime]> for CuTimeRange {
 }
 
 /// Represents a time range with possible undefined start or end or both.
-#[derive(Copy, Clone, Debug, Encode, Decode, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, Encode, Decode, Serialize, Deserialize)]
 pub struct PartialCuTimeRange {
     pub start: OptionCuTime,
     pub end: OptionCuTime,
 }
 
-impl Default for PartialCuTimeRange {
-    fn default() -> Self {
-        PartialCuTimeRange {
-            start: OptionCuTime::none(),
-            end: OptionCuTime::none(),
-        }
-    }
-}
-
 /// The time of validity of a message can be more than one time but can be a time range of Tovs.
 /// For example a sub scan for a lidar, a set of images etc... can have a range of validity.
 #[derive(Default, Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
@@ -483,6 +675,66 @@ mod tests {
         assert_eq!(d.0, 10);
     }
 
+    #[test]
+    fn test_checked_arithmetics() {
+        let a = CuDuration(10);
+        let b = CuDuration(20);
+        assert_eq!(a.checked_sub(b), None);
+        assert_eq!(a.saturating_sub(b), CuDuration(0));
+        assert_eq!(CuDuration(u64::MAX).checked_add(a), None);
+        assert_eq!(CuDuration(u64::MAX).saturating_add(a), CuDuration(u64::MAX));
+        assert_eq!(a.abs_diff(b), CuDuration(10));
+        let diff = a.signed_sub(b);
+        assert_eq!(diff, CuSignedDuration(-10));
+        assert_eq!(diff.abs(), CuDuration(10));
+        assert_eq!(b.checked_add_signed(diff), Some(a));
+        assert_eq!(a.checked_add_signed(diff), Some(CuDuration(0)));
+        assert_eq!(a.checked_add_signed(CuSignedDuration(-11)), None);
+        assert_eq!(-diff, CuSignedDuration(10));
+        assert_eq!(
+            CuDuration(u64::MAX).signed_sub(CuDuration(0)),
+            CuSignedDuration(i64::MAX)
+        );
+        assert_eq!(
+            CuDuration(0).signed_sub(CuDuration(u64::MAX)),
+            CuSignedDuration(i64::MIN)
+        );
+        assert_eq!(-CuSignedDuration(i64::MIN), CuSignedDuration(i64::MAX));
+    }
+
+    #[test]
+    fn test_parse_durations() {
+        assert_eq!("250us".parse(), Ok(CuDuration(250_000)));
+        assert_eq!("250µs".parse(), Ok(CuDuration(250_000)));
+        assert_eq!("1.5s".parse(), Ok(CuDuration(1_500_000_000)));
+        assert_eq!(" 10 ms".parse(), Ok(CuDuration(10_000_000)));
+        assert_eq!("2min".parse(), Ok(CuDuration(120_000_000_000)));
+        assert_eq!("7ns".parse(), Ok(CuDuration(7)));
+        assert!("10".parse::<CuDuration>().is_err());
+        assert!("10 parsecs".parse::<CuDuration>().is_err());
+        assert!("-1s".parse::<CuDuration>().is_err());
+        assert!("100000000d".parse::<CuDuration>().is_err());
+        // the display format can be parsed back.
+        let d = CuDuration(1_500_000);
+        assert_eq!(d.to_string().parse(), Ok(d));
+    }
+
+    #[test]
+    fn test_serde_durations() {
+        #[derive(Deserialize, Serialize)]
+        struct Config {
+            period: CuDuration,
+            timeout: CuDuration,
+        }
+        let config: Config = ron::from_str(r#"(period: "10ms", timeout: 2000)"#).unwrap();
+        assert_eq!(config.period, CuDuration(10_000_000));
+        assert_eq!(config.timeout, CuDuration(2000));
+        let serialized = ron::to_string(&config).unwrap();
+        assert_eq!(serialized, "(period:(10000000),timeout:(2000))");
+        let config: Config = ron::from_str(&serialized).unwrap();
+        assert_eq!(config.period, CuDuration(10_000_000));
+    }
+
     #[test]
     fn test_build_range_from_slice() {
         let range = CuTimeRange::from(&[20.into(), 10.into(), 30.into()][..]);
@@ -16,6 +16,7 @@ use cu29_runtime::config::CuConfig;
 use cu29_runtime::curuntime::{
     compute_runtime_plan, find_task_type_for_id, CuExecutionLoop, CuExecutionUnit, CuTaskType,
 };
+use cu29_runtime::monitoring::MAX_MONITORS;
 
 #[cfg(feature = "macro_debug")]
 use format::{highlight_rust_code, rustfmt_generated_code};
@@ -124,6 +125,11 @@ fn gen_culist_support(
         pub fn collect_metadata<'a>(culist: &'a CuList) -> [&'a _CuMsgMetadata; #culist_size] {
             [#( &culist.msgs.0.#task_indices.metadata, )*]
         }
+
+        /// The payloads in the same order as collect_metadata.
+        pub fn collect_payloads<'a>(culist: &'a CuList) -> [Option<&'a dyn core::fmt::Debug>; #culist_size] {
+            [#( culist.msgs.0.#task_indices.payload().map(|p| p as &dyn core::fmt::Debug), )*]
+        }
     };
 
     let methods = itertools::multizip((all_tasks_as_struct_member_name, taskid_call_order)).map(
@@ -199,7 +205,9 @@ fn gen_sim_support(runtime_plan: &CuExecutionLoop) -> proc_macro2::TokenStream {
         .collect();
     quote! {
         pub enum SimStep<'cl> {
-            #(#plan_enum),*
+            #(#plan_enum,)*
+            /// The runtime owned SimClock advanced to this time, before any task of the iteration.
+            NewTime(_CuTime),
         }
     }
 }
@@ -301,12 +309,25 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
 
     #[cfg(feature = "macro_debug")]
     eprintln!("[build monitor type]");
-    let monitor_type = if let Some(monitor_config) = copper_config.get_monitor_config() {
-        let monitor_type = parse_str::<Type>(monitor_config.get_type())
-            .expect("Could not transform the monitor type name into a Rust type.");
-        quote! { #monitor_type }
-    } else {
-        quote! { _NoMonitor }
+    let monitor_types: Vec<Type> = copper_config
+        .get_monitor_configs()
+        .iter()
+        .map(|monitor_config| {
+            parse_str::<Type>(monitor_config.get_type())
+                .expect("Could not transform the monitor type name into a Rust type.")
+        })
+        .collect();
+    if monitor_types.len() > MAX_MONITORS {
+        panic!(
+            "{} monitors are configured but at most {MAX_MONITORS} can run at once.",
+            monitor_types.len()
+        );
+    }
+    // several monitors are run as a tuple of monitors, see the composite CuMonitor in cu29::monitoring.
+    let monitor_type = match monitor_types.as_slice() {
+        [] => quote! { _NoMonitor },
+        [monitor_type] => quote! { #monitor_type },
+        monitor_types => quote! { (#(#monitor_types),*,) },
     };
 
     #[cfg(feature = "macro_debug")]
@@ -322,6 +343,15 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         }
     };
 
+    // in sim mode, the runtime can own the simulation clock.
+    let sim_clock_field: Option<Field> = if sim_mode {
+        Some(parse_quote! {
+            sim_clock: Option<_SimClock>
+        })
+    } else {
+        None
+    };
+
     let name = &item_struct.ident;
 
     #[cfg(feature = "macro_debug")]
@@ -329,9 +359,11 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
     match &mut item_struct.fields {
         Named(fields_named) => {
             fields_named.named.push(runtime_field);
+            fields_named.named.extend(sim_clock_field);
         }
         Unnamed(fields_unnamed) => {
             fields_unnamed.unnamed.push(runtime_field);
+            fields_unnamed.unnamed.extend(sim_clock_field);
         }
         Fields::Unit => {
             panic!("This struct is a unit struct, it should have named or unnamed fields. use struct Something {{}} and not struct Something;")
@@ -363,6 +395,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         .enumerate()
         .map(|(index, ty)| {
             let task_index = int2sliceindex(index as u32);
+            let log_task_id = index as u32;
             let task_enum_name = config_id_to_enum(&all_tasks_ids[index]);
             let enum_name = Ident::new(&task_enum_name, proc_macro2::Span::call_site());
             let additional_error_info = format!(
@@ -420,7 +453,10 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                         #call_sim_callback
                         if doit {
                             let task = &mut self.copper_runtime.tasks.#task_index;
-                            if let Err(error) = task.start(&self.copper_runtime.clock) {
+                            _set_log_task_id(Some(#log_task_id));
+                            let maybe_error = task.start(&self.copper_runtime.clock);
+                            _set_log_task_id(None);
+                            if let Err(error) = maybe_error {
                                 #monitoring_action
                             }
                         }
@@ -470,7 +506,10 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                         #call_sim_callback
                         if doit {
                             let task = &mut self.copper_runtime.tasks.#task_index;
-                            if let Err(error) = task.stop(&self.copper_runtime.clock) {
+                            _set_log_task_id(Some(#log_task_id));
+                            let maybe_error = task.stop(&self.copper_runtime.clock);
+                            _set_log_task_id(None);
+                            if let Err(error) = maybe_error {
                                 #monitoring_action
                             }
                         }
@@ -519,7 +558,10 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                         #call_sim_callback
                         if doit {
                             let task = &mut self.copper_runtime.tasks.#task_index;
-                            if let Err(error) = task.preprocess(&self.copper_runtime.clock) {
+                            _set_log_task_id(Some(#log_task_id));
+                            let maybe_error = task.preprocess(&self.copper_runtime.clock);
+                            _set_log_task_id(None);
+                            if let Err(error) = maybe_error {
                                 #monitoring_action
                             }
                         }
@@ -568,7 +610,10 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                         #call_sim_callback
                         if doit {
                             let task = &mut self.copper_runtime.tasks.#task_index;
-                            if let Err(error) = task.postprocess(&self.copper_runtime.clock) {
+                            _set_log_task_id(Some(#log_task_id));
+                            let maybe_error = task.postprocess(&self.copper_runtime.clock);
+                            _set_log_task_id(None);
+                            if let Err(error) = maybe_error {
                                 #monitoring_action
                             }
                         }
@@ -610,6 +655,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                     );
                     let comment_tokens: proc_macro2::TokenStream = parse_str(&comment_str).unwrap();
                     let tid = step.node_id as usize;
+                    let log_task_id = step.node_id;
                     taskid_call_order.push(tid);
 
                     let task_enum_name = config_id_to_enum(&all_tasks_ids[tid]);
@@ -670,7 +716,10 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                                             #call_sim_callback
                                             cumsg_output.metadata.process_time.start = self.copper_runtime.clock.now().into();
                                             let maybe_error = if doit {
-                                                #task_instance.process(&self.copper_runtime.clock, cumsg_output)
+                                                _set_log_task_id(Some(#log_task_id));
+                                                let maybe_error = #task_instance.process(&self.copper_runtime.clock, cumsg_output);
+                                                _set_log_task_id(None);
+                                                maybe_error
                                             } else {
                                                 Ok(())
                                             };
@@ -742,7 +791,14 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                                         let cumsg_output = &mut msgs.#output_culist_index;
                                         #call_sim_callback
                                         cumsg_output.metadata.process_time.start = self.copper_runtime.clock.now().into();
-                                        let maybe_error = if doit {#task_instance.process(&self.copper_runtime.clock, cumsg_input)} else {Ok(())};
+                                        let maybe_error = if doit {
+                                            _set_log_task_id(Some(#log_task_id));
+                                            let maybe_error = #task_instance.process(&self.copper_runtime.clock, cumsg_input);
+                                            _set_log_task_id(None);
+                                            maybe_error
+                                        } else {
+                                            Ok(())
+                                        };
                                         cumsg_output.metadata.process_time.end = self.copper_runtime.clock.now().into();
                                         if let Err(error) = maybe_error {
                                             #monitoring_action
@@ -809,7 +865,14 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                                         let cumsg_output = &mut msgs.#output_culist_index;
                                         #call_sim_callback
                                         cumsg_output.metadata.process_time.start = self.copper_runtime.clock.now().into();
-                                        let maybe_error = if doit {#task_instance.process(&self.copper_runtime.clock, cumsg_input, cumsg_output)} else {Ok(())};
+                                        let maybe_error = if doit {
+                                            _set_log_task_id(Some(#log_task_id));
+                                            let maybe_error = #task_instance.process(&self.copper_runtime.clock, cumsg_input, cumsg_output);
+                                            _set_log_task_id(None);
+                                            maybe_error
+                                        } else {
+                                            Ok(())
+                                        };
                                         cumsg_output.metadata.process_time.end = self.copper_runtime.clock.now().into();
                                         if let Err(error) = maybe_error {
                                             #monitoring_action
@@ -916,15 +979,73 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         None
     };
 
+    let sim_clock_advance = if sim_mode {
+        Some(quote! {
+            if let Some(sim_clock) = self.sim_clock.as_mut() {
+                let Some(now) = sim_clock.advance() else {
+                    return Ok(()); // paused, skip this iteration
+                };
+                if let cu29::simulation::SimOverride::Errored(reason) = sim_callback(SimStep::NewTime(now)) {
+                    return Err(reason.into());
+                }
+            }
+        })
+    } else {
+        None
+    };
+
+    // run() would spin on the skipped iterations otherwise.
+    let sim_clock_wait = if sim_mode {
+        Some(quote! {
+            if let Some(sim_clock) = self.sim_clock.as_ref() {
+                sim_clock.wait_until_running();
+            }
+        })
+    } else {
+        None
+    };
+
+    let sim_clock_init = if sim_mode {
+        Some(quote!(sim_clock: None,))
+    } else {
+        None
+    };
+
+    let sim_clock_methods = if sim_mode {
+        Some(quote! {
+            /// Creates the runtime with a simulation clock it will advance at every iteration.
+            pub fn new_with_sim_clock<F>(sim_clock: _SimClock, unified_logger: _Arc<_Mutex<_UnifiedLoggerWrite>>, sim_callback: &mut F) -> _CuResult<Self>
+            where F: FnMut(SimStep) -> cu29::simulation::SimOverride,
+            {
+                let mut runtime = Self::new(sim_clock.clock(), unified_logger, sim_callback)?;
+                runtime.sim_clock = Some(sim_clock);
+                Ok(runtime)
+            }
+
+            pub fn sim_clock(&self) -> Option<&_SimClock> {
+                self.sim_clock.as_ref()
+            }
+
+            /// To pause, step, schedule events or change the speed of the simulation.
+            pub fn sim_clock_mut(&mut self) -> Option<&mut _SimClock> {
+                self.sim_clock.as_mut()
+            }
+        })
+    } else {
+        None
+    };
+
     #[cfg(feature = "macro_debug")]
     eprintln!("[build the run method]");
     let run_method = quote! {
 
         #run_one_iteration {
+            #sim_clock_advance
             #(#preprocess_calls)*
             {
                 let mut culist: &mut _ = &mut self.copper_runtime.copper_lists_manager.create().expect("Ran out of space for copper lists"); // FIXME: error handling.
                 let id = culist.id;
+                let _culist_log_scope = _CuListLogScope::enter(id);
                 culist.change_state(cu29::copperlist::CopperListState::Processing);
                 {
                     let msgs = &mut culist.msgs.0;
@@ -939,6 +1060,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                 } // drop(md);
 
                 self.copper_runtime.monitor.process_copperlist(&collect_metadata(&culist))?;
+                self.copper_runtime.monitor.process_payloads(&collect_payloads(&culist))?;
                 self.copper_runtime.end_of_processing(id);
 
            }// drop(culist); avoids a double mutable borrow
@@ -961,6 +1083,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         #run {
             self.start_all_tasks(#sim_callback_arg)?;
             let error = loop {
+                #sim_clock_wait
                 let error = self.run_one_iteration(#sim_callback_arg);
                 if error.is_err() {
                     break error;
@@ -1014,6 +1137,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                         #tasks_instanciator,
                         monitor_instanciator,
                         copperlist_stream)?,
+                    #sim_clock_init
                 });
 
                 #sim_callback_on_new
@@ -1025,6 +1149,8 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
                 #copper_config_content.to_string()
             }
 
+            #sim_clock_methods
+
             #run_method
         }
     };
@@ -1043,6 +1169,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         use cu29::clock::RobotClock as _RobotClock;
         use cu29::clock::OptionCuTime as _OptionCuTime;
         use cu29::clock::ClockProvider as _ClockProvider;
+        use cu29::clock::CuTime as _CuTime;
         use cu29::config::CuConfig as _CuConfig;
         use cu29::config::ComponentConfig as _ComponentConfig;
         use cu29::config::MonitorConfig as _MonitorConfig;
@@ -1061,9 +1188,12 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         use cu29::monitoring::NoMonitor as _NoMonitor;
         use cu29::monitoring::CuTaskState as _CuTaskState;
         use cu29::monitoring::Decision as _Decision;
+        use cu29::simulation::SimClock as _SimClock;
         use cu29::prelude::stream_write as _stream_write;
         use cu29::prelude::UnifiedLoggerWrite as _UnifiedLoggerWrite;
         use cu29::prelude::UnifiedLogType as _UnifiedLogType;
+        use cu29::prelude::set_log_task_id as _set_log_task_id;
+        use cu29::prelude::CuListLogScope as _CuListLogScope;
         use std::sync::Arc as _Arc;
         use std::sync::Mutex as _Mutex;
 
@@ -1090,7 +1220,7 @@ pub fn copper_runtime(args: TokenStream, input: TokenStream) -> TokenStream {
         }
 
         fn monitor_instanciator(config: &_CuConfig) -> #monitor_type {
-            #monitor_type::new(config, TASKS_IDS).expect("Failed to create the given monitor.")
+            <#monitor_type as _CuMonitor>::new(config, TASKS_IDS).expect("Failed to create the given monitor.")
         }
 
         pub #item_struct
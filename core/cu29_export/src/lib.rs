@@ -29,8 +29,14 @@ impl Display for ExportFormat {
 pub struct LogReaderCli {
     /// The base path is the name with no _0 _1 et the end.
     /// for example for toto_0.copper, toto_1.copper ... the base name is toto.copper
+    /// It is not read with --listen.
     pub unifiedlog_base: PathBuf,
 
+    /// Read a live stream from a robot instead of a log on disk, for example tcp://0.0.0.0:7878 or udp://0.0.0.0:7878.
+    /// See UnifiedLoggerBuilder::remote_target for the robot side.
+    #[arg(long)]
+    pub listen: Option<RemoteLogTarget>,
+
     #[command(subcommand)]
     pub command: Command,
 }
@@ -38,7 +44,13 @@ pub struct LogReaderCli {
 #[derive(Subcommand)]
 pub enum Command {
     /// Extract logs
-    ExtractLog { log_index: PathBuf },
+    ExtractLog {
+        /// The string index generated at build time, if omitted the copy embedded in the log is used.
+        log_index: Option<PathBuf>,
+        /// Only extract the log lines at this level or above (trace, debug, info, warning, error, critical)
+        #[arg(short, long, default_value_t = CuLogLevel::Trace)]
+        min_level: CuLogLevel,
+    },
     /// Extract copperlists
     ExtractCopperlist {
         #[arg(short, long, default_value_t = ExportFormat::Json)]
@@ -55,6 +67,10 @@ where
     let args = LogReaderCli::parse();
     let unifiedlog_base = args.unifiedlog_base;
 
+    if let Some(target) = args.listen {
+        return run_live::<P>(target, args.command);
+    }
+
     let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
         .file_base_name(&unifiedlog_base)
         .build()
@@ -64,9 +80,18 @@ where
     };
 
     match args.command {
-        Command::ExtractLog { log_index } => {
+        Command::ExtractLog {
+            log_index,
+            min_level,
+        } => {
             let reader = UnifiedLoggerIOReader::new(dl, UnifiedLogType::StructuredLogLine);
-            textlog_dump(reader, &log_index)?;
+            match log_index {
+                Some(log_index) => textlog_dump(reader, &log_index, min_level)?,
+                None => {
+                    let all_strings = read_embedded_interned_strings(&unifiedlog_base)?;
+                    textlog_dump_with_strings(reader, &all_strings, min_level)?
+                }
+            }
         }
         Command::ExtractCopperlist { export_format } => {
             println!("Extracting copperlists with format: {export_format}");
@@ -81,6 +106,34 @@ where
     Ok(())
 }
 
+/// Same as run_cli but from a live stream, it runs until interrupted.
+fn run_live<P>(target: RemoteLogTarget, command: Command) -> CuResult<()>
+where
+    P: CopperListTuple,
+{
+    let reader = RemoteLogReader::listen(target)?;
+    eprintln!("Listening on {target}");
+    match command {
+        Command::ExtractLog {
+            log_index,
+            min_level,
+        } => {
+            let log_index = log_index.ok_or_else(|| {
+                CuError::from("A live stream does not carry the string index, give the one generated at build time.")
+            })?;
+            let reader = UnifiedLoggerIOReader::new(reader, UnifiedLogType::StructuredLogLine);
+            textlog_dump(reader, &log_index, min_level)?;
+        }
+        Command::ExtractCopperlist { .. } => {
+            let mut reader = UnifiedLoggerIOReader::new(reader, UnifiedLogType::CopperList);
+            for entry in copperlists_dump::<P>(&mut reader) {
+                println!("{entry:#?}");
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Extracts the copper lists from a binary representation.
 /// P is the Payload determined by the configuration of the application.
 pub fn copperlists_dump<P: CopperListTuple>(
@@ -109,12 +162,36 @@ pub fn copperlists_dump<P: CopperListTuple>(
     })
 }
 
+/// Reads the interned strings embedded in a unified log by the log lines built with the embed-string-index feature.
+/// This allows to decode the structured logs without the string index generated at build time.
+pub fn read_embedded_interned_strings(unifiedlog_base: &Path) -> CuResult<Vec<String>> {
+    let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+        .file_base_name(unifiedlog_base)
+        .build()
+        .map_err(|e| CuError::new_with_cause("Failed to open the unified log", e))?
+    else {
+        return Err("Failed to open the unified log for reading".into());
+    };
+    read_interned_strings_from_log(UnifiedLoggerIOReader::new(dl, UnifiedLogType::StringIndex))
+}
+
 /// Full dump of the copper structured log from its binary representation.
 /// This rebuilds a textual log.
 /// src: the source of the log data
 /// index: the path to the index file (containing the interned strings constructed at build time)
-pub fn textlog_dump(mut src: impl Read, index: &Path) -> CuResult<()> {
+/// min_level: the log lines below this level are skipped
+pub fn textlog_dump(src: impl Read, index: &Path, min_level: CuLogLevel) -> CuResult<()> {
     let all_strings = read_interned_strings(index)?;
+    textlog_dump_with_strings(src, &all_strings, min_level)
+}
+
+/// Same as textlog_dump but with the interned strings already loaded,
+/// for example from the copy embedded in the log with read_embedded_interned_strings.
+pub fn textlog_dump_with_strings(
+    mut src: impl Read,
+    all_strings: &[String],
+    min_level: CuLogLevel,
+) -> CuResult<()> {
     loop {
         let entry = decode_from_std_read::<CuLogEntry, _, _>(&mut src, standard());
 
@@ -136,13 +213,22 @@ pub fn textlog_dump(mut src: impl Read, index: &Path) -> CuResult<()> {
                 if entry.msg_index == 0 {
                     break;
                 }
+                if entry.level < min_level {
+                    continue;
+                }
 
-                let result = rebuild_logline(&all_strings, &entry);
+                let result = rebuild_logline(all_strings, &entry);
                 if result.is_err() {
                     println!("Failed to rebuild log line: {result:?}");
                     continue;
                 }
-                println!("{}: {}", entry.time, result.unwrap());
+                println!(
+                    "{}: [{}] {}{}",
+                    entry.time,
+                    entry.level,
+                    entry.context_prefix(),
+                    result.unwrap()
+                );
             }
         };
     }
@@ -215,13 +301,18 @@ mod python {
     /// Creates an iterator of CuLogEntries from a unified log file.
     /// This function allows you to easily use python to datamind Copper's structured text logs.
     /// it returns a tuple with the iterator of log entries and the list of interned strings.
+    /// If index_path is omitted, the string index embedded in the log is used.
     #[pyfunction]
+    #[pyo3(signature = (unified_src_path, index_path=None))]
     pub fn struct_log_iterator_unified(
         unified_src_path: &str,
-        index_path: &str,
+        index_path: Option<&str>,
     ) -> PyResult<(PyLogIterator, Vec<String>)> {
-        let all_strings = read_interned_strings(Path::new(index_path))
-            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let all_strings = match index_path {
+            Some(index_path) => read_interned_strings(Path::new(index_path)),
+            None => super::read_embedded_interned_strings(Path::new(unified_src_path)),
+        }
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
 
         let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
             .file_base_name(Path::new(unified_src_path))
@@ -260,6 +351,21 @@ mod python {
             PyDelta::new(py, days, seconds, microseconds, false).unwrap()
         }
 
+        /// Returns the level of the log entry as a string (trace, debug, info, warning, error, critical).
+        pub fn level(&self) -> &'static str {
+            self.inner.level.as_str()
+        }
+
+        /// Returns the index of the task (in the configuration order) that logged this entry, None if outside of a task.
+        pub fn task_id(&self) -> Option<u32> {
+            self.inner.task_id
+        }
+
+        /// Returns the id of the copper list being processed when this entry was logged, None if outside of one.
+        pub fn culist_id(&self) -> Option<u32> {
+            self.inner.culist_id
+        }
+
         /// Returns the index of the message in the vector of interned strings.
         pub fn msg_index(&self) -> u32 {
             self.inner.msg_index
@@ -350,10 +456,10 @@ mod tests {
     fn test_extract_low_level_cu29_log() {
         let temp_dir = TempDir::new().unwrap();
         let temp_path = copy_stringindex_to_temp(&temp_dir);
-        let entry = CuLogEntry::new(3);
+        let entry = CuLogEntry::new(3, CuLogLevel::Debug);
         let bytes = bincode::encode_to_vec(&entry, standard()).unwrap();
         let reader = Cursor::new(bytes.as_slice());
-        textlog_dump(reader, temp_path.as_path()).unwrap();
+        textlog_dump(reader, temp_path.as_path(), CuLogLevel::Trace).unwrap();
     }
 
     #[test]
@@ -378,17 +484,37 @@ mod tests {
             let stream = stream_write(data_logger.clone(), UnifiedLogType::StructuredLogLine, 1024);
             let rt = LoggerRuntime::init(RobotClock::default(), stream, None::<NullLog>);
 
-            let mut entry = CuLogEntry::new(4); // this is a "Just a String {}" log line
+            let mut entry = CuLogEntry::new(4, CuLogLevel::Info); // this is a "Just a String {}" log line
             entry.add_param(0, Value::String("Parameter for the log line".into()));
             log(&mut entry).expect("Failed to log");
-            let mut entry = CuLogEntry::new(2); // this is a "Just a String {}" log line
+            set_log_task_id(Some(1));
+            set_log_culist_id(Some(42));
+            let mut entry = CuLogEntry::new(2, CuLogLevel::Warning); // this is a "Just a String {}" log line
             entry.add_param(0, Value::String("Parameter for the log line".into()));
             log(&mut entry).expect("Failed to log");
+            set_log_task_id(None);
+            set_log_culist_id(None);
 
             // everything is dropped here
             drop(rt);
         }
         // Read back the log
+        let UnifiedLogger::Read(logger) = UnifiedLoggerBuilder::new()
+            .file_base_name(
+                &dir.path()
+                    .join("end_to_end_datalogger_and_structlog_test.copper"),
+            )
+            .build()
+            .expect("Failed to create logger")
+        else {
+            panic!("Failed to create logger")
+        };
+        let mut reader = UnifiedLoggerIOReader::new(logger, UnifiedLogType::StructuredLogLine);
+        let first = decode_from_std_read::<CuLogEntry, _, _>(&mut reader, standard()).unwrap();
+        assert_eq!((first.task_id, first.culist_id), (None, None));
+        let second = decode_from_std_read::<CuLogEntry, _, _>(&mut reader, standard()).unwrap();
+        assert_eq!((second.task_id, second.culist_id), (Some(1), Some(42)));
+
         let UnifiedLogger::Read(logger) = UnifiedLoggerBuilder::new()
             .file_base_name(
                 &dir.path()
@@ -404,6 +530,7 @@ mod tests {
         textlog_dump(
             reader,
             Path::new(copy_stringindex_to_temp(&temp_dir).as_path()),
+            CuLogLevel::Trace,
         )
         .expect("Failed to dump log");
     }
@@ -1,5 +1,5 @@
 use cu29_clock::RobotClock;
-use cu29_log_runtime::LoggerRuntime;
+use cu29_log_runtime::{LoggerRuntime, StringIndexWriter};
 use cu29_traits::{CuResult, UnifiedLogType};
 use cu29_unifiedlog::{stream_write, UnifiedLogger, UnifiedLoggerBuilder, UnifiedLoggerWrite};
 use simplelog::TermLogger;
@@ -71,6 +71,15 @@ pub fn basic_copper_setup(
 
     let clock = clock.unwrap_or_default();
     let structured_logging = LoggerRuntime::init(clock.clone(), structured_stream, extra);
+    // Only used by the log lines built with the embed-string-index feature, the section is allocated on first use.
+    let string_index_logger = unified_logger.clone();
+    structured_logging.embed_string_index(move || {
+        Ok(Box::new(stream_write(
+            string_index_logger.clone(),
+            UnifiedLogType::StringIndex,
+            4096,
+        )) as StringIndexWriter)
+    });
     Ok(CopperContext {
         unified_logger: unified_logger.clone(),
         logger_runtime: structured_logging,
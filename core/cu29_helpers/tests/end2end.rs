@@ -1,7 +1,10 @@
 use cu29_helpers::basic_copper_setup;
+use cu29_intern_strs::read_interned_strings_from_log;
 use cu29_log::CuLogEntry;
 use cu29_log::ANONYMOUS;
-use cu29_log_derive::debug;
+use cu29_log_derive::{critical, debug, error, info, trace, warning};
+use cu29_traits::UnifiedLogType;
+use cu29_unifiedlog::{UnifiedLogger, UnifiedLoggerBuilder, UnifiedLoggerIOReader};
 use cu29_value::to_value;
 
 #[cfg(not(debug_assertions))]
@@ -20,8 +23,9 @@ fn log_derive_end2end() {
     let tmp_dir = TempDir::new("teststructlog").expect("Failed to create temp dir");
     let log_path = tmp_dir.path().join("teststructlog.copper");
 
-    let _ = basic_copper_setup(&log_path, None, true, None).expect("Failed to setup logger.");
-    debug!("Logger created at {}.", log_path);
+    let copper_ctx =
+        basic_copper_setup(&log_path, None, true, None).expect("Failed to setup logger.");
+    debug!("Logger created at {}.", &log_path);
 
     #[derive(Serialize)]
     struct Test {
@@ -37,5 +41,32 @@ fn log_derive_end2end() {
         debug!("mixed named param constants, {} {} {}", a = 3, 54, b = 2);
         debug!("complex tuple", mytuple);
         debug!("Struct", Test { a: 3, b: 4 });
+        debug!(
+            "format specifiers {:>8.3} {:#x} {name:?}",
+            12.34567,
+            255u32,
+            name = "toto"
+        );
+        trace!("trace level {}", 1);
+        info!("info level {}", 2);
+        warning!("warning level {}", 3);
+        error!("error level {}", 4);
+        critical!("critical level {}", 5);
     }
+    drop(copper_ctx);
+
+    // The log lines embedded their strings, the log can be decoded without the build time index.
+    let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+        .file_base_name(&log_path)
+        .build()
+        .expect("Failed to open the log")
+    else {
+        panic!("Failed to open the log")
+    };
+    let all_strings =
+        read_interned_strings_from_log(UnifiedLoggerIOReader::new(dl, UnifiedLogType::StringIndex))
+            .expect("Failed to read the embedded string index.");
+    assert!(all_strings.iter().any(|s| s == "Logger created at {}."));
+    assert!(all_strings.iter().any(|s| s == "critical level {}"));
+    assert!(all_strings.iter().any(|s| s == "name"));
 }
@@ -1,5 +1,10 @@
+use std::io::Read;
 use std::path::Path;
 
+use bincode::config::standard;
+use bincode::decode_from_std_read;
+use bincode::error::DecodeError;
+
 use cu29_traits::{CuError, CuResult};
 use rkv::backend::Lmdb;
 use rkv::{Rkv, StoreOptions};
@@ -34,3 +39,39 @@ pub fn read_interned_strings(index: &Path) -> CuResult<Vec<String>> {
     }
     Ok(all_strings)
 }
+
+/// Rebuild the interned string index in memory from the copy embedded in a log.
+/// src is the content of the `UnifiedLogType::StringIndex` sections of a unified log, ie. a sequence of bincode
+/// encoded (index, string) pairs written by the log lines the first time they were executed.
+pub fn read_interned_strings_from_log(mut src: impl Read) -> CuResult<Vec<String>> {
+    let mut all_strings = Vec::<String>::new();
+    loop {
+        match decode_from_std_read::<(u32, String), _, _>(&mut src, standard()) {
+            Ok((index, s)) => {
+                let index = index as usize;
+                if all_strings.len() <= index {
+                    all_strings.resize(index + 1, String::new());
+                }
+                all_strings[index] = s;
+            }
+            Err(DecodeError::UnexpectedEnd { .. }) => break,
+            Err(DecodeError::Io { inner, .. })
+                if inner.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break
+            }
+            Err(e) => {
+                return Err(CuError::new_with_cause(
+                    "Could not decode the embedded string index",
+                    e,
+                ))
+            }
+        }
+    }
+    if all_strings.is_empty() {
+        return Err(
+            "No string index embedded in this log, was it built with the embed-string-index feature?".into(),
+        );
+    }
+    Ok(all_strings)
+}
@@ -0,0 +1,434 @@
+//! Parsing and application of the format specifiers of the log messages.
+//! The parsing is used at macro expansion time to validate the messages, the application is only done when the text log
+//! is rebuilt so the robot never pays for the formatting.
+//!
+//! The supported syntax is a subset of the one of `std::fmt`:
+//! `{[name][:[[fill]align][sign]['#']['0'][width]['.' precision][type]]}`
+//! with align one of `<`, `^`, `>`, sign `+` and type one of ``, `?`, `x`, `X`, `o`, `b`, `e`, `E`.
+//! Positional arguments (`{0}`) and runtime widths (`{:width$}`) are not supported.
+
+use cu29_traits::{CuError, CuResult};
+use cu29_value::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The conversion requested by a placeholder, ie. the trait used in `std::fmt` terms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FormatKind {
+    #[default]
+    Display,
+    Debug,
+    LowerHex,
+    UpperHex,
+    Octal,
+    Binary,
+    LowerExp,
+    UpperExp,
+}
+
+/// Everything after the `:` in a placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub sign_plus: bool,
+    pub alternate: bool,
+    pub zero_pad: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+    pub kind: FormatKind,
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        FormatSpec {
+            fill: ' ',
+            align: None,
+            sign_plus: false,
+            alternate: false,
+            zero_pad: false,
+            width: None,
+            precision: None,
+            kind: FormatKind::Display,
+        }
+    }
+}
+
+/// A parsed piece of a log message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatPiece {
+    /// Text copied as is (with the `{{` and `}}` escapes resolved).
+    Literal(String),
+    /// A placeholder, the name is None for the anonymous ones (`{}` or `{:x}`).
+    Placeholder {
+        name: Option<String>,
+        spec: FormatSpec,
+    },
+}
+
+/// Splits a log message into its literals and placeholders, validating the format specifiers.
+pub fn parse_format_string(format_str: &str) -> CuResult<Vec<FormatPiece>> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = format_str.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => {
+                return Err(format!("Unmatched '}}' in log message {format_str:?}").into());
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => {
+                            return Err(
+                                format!("Unclosed '{{' in log message {format_str:?}").into()
+                            );
+                        }
+                        Some(c) => placeholder.push(c),
+                    }
+                }
+                if !literal.is_empty() {
+                    pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(parse_placeholder(&placeholder).map_err(|e| {
+                    CuError::from(format!(
+                        "Invalid placeholder {{{placeholder}}} in log message {format_str:?}"
+                    ))
+                    .add_cause(e.to_string().as_str())
+                })?);
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(FormatPiece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+fn parse_placeholder(placeholder: &str) -> CuResult<FormatPiece> {
+    let (name, spec) = match placeholder.split_once(':') {
+        Some((name, spec)) => (name, parse_spec(spec)?),
+        None => (placeholder, FormatSpec::default()),
+    };
+    let name = name.trim();
+    let name = if name.is_empty() {
+        None
+    } else if name.chars().all(|c| c.is_ascii_digit()) {
+        return Err("positional arguments are not supported, use named parameters".into());
+    } else if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        Some(name.to_string())
+    } else {
+        return Err(format!("{name:?} is not a valid parameter name").into());
+    };
+    Ok(FormatPiece::Placeholder { name, spec })
+}
+
+fn parse_align(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    }
+}
+
+fn parse_spec(spec: &str) -> CuResult<FormatSpec> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut result = FormatSpec::default();
+    let mut i = 0;
+
+    // [[fill]align]
+    if chars.len() >= 2 && parse_align(chars[1]).is_some() {
+        result.fill = chars[0];
+        result.align = parse_align(chars[1]);
+        i = 2;
+    } else if let Some(align) = chars.first().and_then(|c| parse_align(*c)) {
+        result.align = Some(align);
+        i = 1;
+    }
+
+    if chars.get(i) == Some(&'+') {
+        result.sign_plus = true;
+        i += 1;
+    } else if chars.get(i) == Some(&'-') {
+        return Err("the '-' sign flag is not supported".into());
+    }
+    if chars.get(i) == Some(&'#') {
+        result.alternate = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        result.zero_pad = true;
+        i += 1;
+    }
+
+    let read_number = |i: &mut usize| -> Option<usize> {
+        let start = *i;
+        while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>().parse().ok()
+    };
+
+    result.width = read_number(&mut i);
+    if chars.get(i) == Some(&'$') {
+        return Err("runtime widths and precisions are not supported".into());
+    }
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        result.precision = read_number(&mut i);
+        if result.precision.is_none() {
+            return Err("expected a number after '.'".into());
+        }
+        if chars.get(i) == Some(&'$') {
+            return Err("runtime widths and precisions are not supported".into());
+        }
+    }
+
+    let kind: String = chars[i..].iter().collect();
+    result.kind = match kind.as_str() {
+        "" => FormatKind::Display,
+        "?" => FormatKind::Debug,
+        "x" => FormatKind::LowerHex,
+        "X" => FormatKind::UpperHex,
+        "o" => FormatKind::Octal,
+        "b" => FormatKind::Binary,
+        "e" => FormatKind::LowerExp,
+        "E" => FormatKind::UpperExp,
+        _ => return Err(format!("unknown format type {kind:?}").into()),
+    };
+    Ok(result)
+}
+
+/// Formats an integer value with the given conversion, returns None if the conversion does not apply to it.
+macro_rules! format_integer {
+    ($v:expr, $kind:expr, $precision:expr) => {
+        match $kind {
+            FormatKind::Display | FormatKind::Debug => Some(format!("{}", $v)),
+            FormatKind::LowerHex => Some(format!("{:x}", $v)),
+            FormatKind::UpperHex => Some(format!("{:X}", $v)),
+            FormatKind::Octal => Some(format!("{:o}", $v)),
+            FormatKind::Binary => Some(format!("{:b}", $v)),
+            FormatKind::LowerExp => Some(match $precision {
+                Some(p) => format!("{:.*e}", p, $v),
+                None => format!("{:e}", $v),
+            }),
+            FormatKind::UpperExp => Some(match $precision {
+                Some(p) => format!("{:.*E}", p, $v),
+                None => format!("{:E}", $v),
+            }),
+        }
+    };
+}
+
+/// Formats a float value with the given conversion, returns None if the conversion does not apply to it.
+macro_rules! format_float {
+    ($v:expr, $kind:expr, $precision:expr) => {
+        match ($kind, $precision) {
+            (FormatKind::Display, Some(p)) => Some(format!("{:.*}", p, $v)),
+            (FormatKind::Display, None) => Some(format!("{}", $v)),
+            (FormatKind::Debug, Some(p)) => Some(format!("{:.*?}", p, $v)),
+            (FormatKind::Debug, None) => Some(format!("{:?}", $v)),
+            (FormatKind::LowerExp, Some(p)) => Some(format!("{:.*e}", p, $v)),
+            (FormatKind::LowerExp, None) => Some(format!("{:e}", $v)),
+            (FormatKind::UpperExp, Some(p)) => Some(format!("{:.*E}", p, $v)),
+            (FormatKind::UpperExp, None) => Some(format!("{:E}", $v)),
+            _ => None,
+        }
+    };
+}
+
+/// Renders a numeric value without any padding, returns None if the value is not a number.
+fn format_number(value: &Value, spec: &FormatSpec) -> CuResult<Option<String>> {
+    let formatted = match value {
+        Value::U8(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::U16(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::U32(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::U64(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::I8(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::I16(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::I32(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::I64(v) => format_integer!(v, spec.kind, spec.precision),
+        Value::F32(v) => format_float!(v, spec.kind, spec.precision),
+        Value::F64(v) => format_float!(v, spec.kind, spec.precision),
+        Value::Newtype(v) => return format_number(v, spec),
+        _ => return Ok(None),
+    };
+    formatted
+        .map(Some)
+        .ok_or_else(|| format!("{:?} cannot be applied to {value}", spec.kind).into())
+}
+
+/// Pads the rendered value to the requested width.
+fn pad(body: &str, spec: &FormatSpec, default_align: Align) -> String {
+    let len = body.chars().count();
+    let width = match spec.width {
+        Some(width) if width > len => width,
+        _ => return body.to_string(),
+    };
+    let padding = width - len;
+    let (before, after) = match spec.align.unwrap_or(default_align) {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+    let fill = |n: usize| std::iter::repeat_n(spec.fill, n).collect::<String>();
+    format!("{}{body}{}", fill(before), fill(after))
+}
+
+/// Applies a format specifier to a value, this is the equivalent of `format!("{:spec}", value)`.
+pub fn format_value(value: &Value, spec: &FormatSpec) -> CuResult<String> {
+    if let Some(number) = format_number(value, spec)? {
+        let (mut sign, digits) = match number.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", number.as_str()),
+        };
+        if sign.is_empty() && spec.sign_plus {
+            sign = "+";
+        }
+        let prefix = match (spec.alternate, spec.kind) {
+            (true, FormatKind::LowerHex | FormatKind::UpperHex) => "0x",
+            (true, FormatKind::Octal) => "0o",
+            (true, FormatKind::Binary) => "0b",
+            _ => "",
+        };
+        if spec.zero_pad {
+            // Like std, the zeros go after the sign and the prefix and the fill/alignment are ignored.
+            let head = format!("{sign}{prefix}");
+            let zero_spec = FormatSpec {
+                fill: '0',
+                align: Some(Align::Right),
+                width: spec.width.map(|w| w.saturating_sub(head.chars().count())),
+                ..spec.clone()
+            };
+            return Ok(format!("{head}{}", pad(digits, &zero_spec, Align::Right)));
+        }
+        return Ok(pad(&format!("{sign}{prefix}{digits}"), spec, Align::Right));
+    }
+
+    let body = match (spec.kind, value) {
+        (FormatKind::Display, Value::String(s)) => match spec.precision {
+            Some(p) => s.chars().take(p).collect(),
+            None => s.clone(),
+        },
+        (FormatKind::Debug, Value::String(s)) => format!("{s:?}"),
+        (FormatKind::Debug, Value::Char(c)) => format!("{c:?}"),
+        (FormatKind::Display | FormatKind::Debug, v) => v.to_string(),
+        (kind, v) => return Err(format!("{kind:?} cannot be applied to {v}").into()),
+    };
+    Ok(pad(&body, spec, Align::Left))
+}
+
+/// Renders the parsed pieces of a message with the given parameters.
+/// The anonymous placeholders consume the parameters in order, the named ones are looked up by name.
+pub fn format_pieces(
+    pieces: &[FormatPiece],
+    params: &[Value],
+    named_params: &HashMap<String, Value>,
+) -> CuResult<String> {
+    let mut result = String::new();
+    let mut anonymous = params.iter();
+    for piece in pieces {
+        match piece {
+            FormatPiece::Literal(literal) => result.push_str(literal),
+            FormatPiece::Placeholder { name: None, spec } => {
+                let value = anonymous
+                    .next()
+                    .ok_or_else(|| CuError::from("Not enough parameters for the log message"))?;
+                result.push_str(&format_value(value, spec)?);
+            }
+            FormatPiece::Placeholder {
+                name: Some(name),
+                spec,
+            } => {
+                let value = named_params.get(name).ok_or_else(|| {
+                    CuError::from(format!(
+                        "Missing named parameter {name:?} for the log message"
+                    ))
+                })?;
+                result.push_str(&format_value(value, spec)?);
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(format_str: &str, params: &[Value]) -> String {
+        let pieces = parse_format_string(format_str).unwrap();
+        format_pieces(&pieces, params, &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_std_compatibility() {
+        assert_eq!(
+            fmt("{:.3}", &[Value::F64(1.23456)]),
+            format!("{:.3}", 1.23456)
+        );
+        assert_eq!(fmt("{:>8}", &[Value::I32(-42)]), format!("{:>8}", -42));
+        assert_eq!(fmt("{:<8}|", &[Value::U8(42)]), format!("{:<8}|", 42));
+        assert_eq!(fmt("{:*^9}", &[Value::String("ab".into())]), "***ab****");
+        assert_eq!(fmt("{:x}", &[Value::U32(0xbeef)]), "beef");
+        assert_eq!(
+            fmt("{:#06X}", &[Value::U16(0xab)]),
+            format!("{:#06X}", 0xabu16)
+        );
+        assert_eq!(
+            fmt("{:08.2}", &[Value::F32(-3.5)]),
+            format!("{:08.2}", -3.5f32)
+        );
+        assert_eq!(fmt("{:+}", &[Value::I64(3)]), "+3");
+        assert_eq!(fmt("{:#b}", &[Value::U8(5)]), "0b101");
+        assert_eq!(
+            fmt("{:.2e}", &[Value::F64(1234.5)]),
+            format!("{:.2e}", 1234.5)
+        );
+        assert_eq!(fmt("{:?}", &[Value::String("a".into())]), "\"a\"");
+        assert_eq!(fmt("{{{}}}", &[Value::Bool(true)]), "{true}");
+    }
+
+    #[test]
+    fn test_named() {
+        let pieces = parse_format_string("{a:>4}/{}/{b:.1}").unwrap();
+        let mut named = HashMap::new();
+        named.insert("a".to_string(), Value::U8(1));
+        named.insert("b".to_string(), Value::F64(2.25));
+        assert_eq!(
+            format_pieces(&pieces, &[Value::Char('c')], &named).unwrap(),
+            "   1/c/2.2"
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse_format_string("{").is_err());
+        assert!(parse_format_string("}").is_err());
+        assert!(parse_format_string("{0}").is_err());
+        assert!(parse_format_string("{:w$}").is_err());
+        assert!(parse_format_string("{:.}").is_err());
+        assert!(parse_format_string("{:z}").is_err());
+        let pieces = parse_format_string("{:x}").unwrap();
+        assert!(format_pieces(&pieces, &[Value::F32(1.0)], &HashMap::new()).is_err());
+    }
+}
@@ -7,7 +7,13 @@ use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::path::{Path, PathBuf};
-use strfmt::strfmt;
+use std::str::FromStr;
+
+mod format;
+
+pub use format::{
+    format_pieces, format_value, parse_format_string, Align, FormatKind, FormatPiece, FormatSpec,
+};
 
 /// The name of the directory where the log index is stored.
 const INDEX_DIR_NAME: &str = "cu29_log_index";
@@ -17,12 +23,101 @@ pub const ANONYMOUS: u32 = 0;
 
 pub const MAX_LOG_PARAMS_ON_STACK: usize = 10;
 
+/// The severity of a log entry, from the most verbose to the most critical.
+/// The ordering is meaningful: a minimum level filters out everything below it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Encode,
+    Decode,
+    Serialize,
+    Deserialize,
+)]
+#[repr(u8)]
+pub enum CuLogLevel {
+    Trace = 0,
+    #[default]
+    Debug = 1,
+    Info = 2,
+    Warning = 3,
+    Error = 4,
+    Critical = 5,
+}
+
+impl CuLogLevel {
+    /// All the levels in increasing order of severity.
+    pub const ALL: [CuLogLevel; 6] = [
+        CuLogLevel::Trace,
+        CuLogLevel::Debug,
+        CuLogLevel::Info,
+        CuLogLevel::Warning,
+        CuLogLevel::Error,
+        CuLogLevel::Critical,
+    ];
+
+    /// Converts back a level from its u8 representation.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    /// The textual name of the level as used in the text logs and the CLI.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CuLogLevel::Trace => "trace",
+            CuLogLevel::Debug => "debug",
+            CuLogLevel::Info => "info",
+            CuLogLevel::Warning => "warning",
+            CuLogLevel::Error => "error",
+            CuLogLevel::Critical => "critical",
+        }
+    }
+}
+
+impl Display for CuLogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CuLogLevel {
+    type Err = CuError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "warn" => Ok(CuLogLevel::Warning),
+            "crit" => Ok(CuLogLevel::Critical),
+            _ => Self::ALL
+                .iter()
+                .find(|level| level.as_str() == lower)
+                .copied()
+                .ok_or_else(|| format!("Unknown log level: {s:?}").into()),
+        }
+    }
+}
+
 /// This is the basic structure for a log entry in Copper.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct CuLogEntry {
     // Approximate time when the log entry was created.
     pub time: CuTime,
 
+    // Severity of the log entry.
+    pub level: CuLogLevel,
+
+    // Index of the task (in the order of the configuration) running when the entry was created, if any.
+    pub task_id: Option<u32>,
+
+    // Id of the copper list being processed when the entry was created, if any.
+    pub culist_id: Option<u32>,
+
     // interned index of the message
     pub msg_index: u32,
 
@@ -39,6 +134,9 @@ impl Encode for CuLogEntry {
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
         self.time.encode(encoder)?;
+        self.level.encode(encoder)?;
+        self.task_id.encode(encoder)?;
+        self.culist_id.encode(encoder)?;
         self.msg_index.encode(encoder)?;
 
         (self.paramname_indexes.len() as u64).encode(encoder)?;
@@ -60,6 +158,9 @@ impl Decode for CuLogEntry {
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
         let time = CuTime::decode(decoder)?;
+        let level = CuLogLevel::decode(decoder)?;
+        let task_id = Option::<u32>::decode(decoder)?;
+        let culist_id = Option::<u32>::decode(decoder)?;
         let msg_index = u32::decode(decoder)?;
 
         let paramname_len = u64::decode(decoder)? as usize;
@@ -76,6 +177,9 @@ impl Decode for CuLogEntry {
 
         Ok(CuLogEntry {
             time,
+            level,
+            task_id,
+            culist_id,
             msg_index,
             paramname_indexes,
             params,
@@ -88,24 +192,38 @@ impl Display for CuLogEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "CuLogEntry {{ msg_index: {}, paramname_indexes: {:?}, params: {:?} }}",
-            self.msg_index, self.paramname_indexes, self.params
+            "CuLogEntry {{ level: {}, task_id: {:?}, culist_id: {:?}, msg_index: {}, paramname_indexes: {:?}, params: {:?} }}",
+            self.level, self.task_id, self.culist_id, self.msg_index, self.paramname_indexes, self.params
         )
     }
 }
 
 impl CuLogEntry {
     /// msg_index is the interned index of the message.
-    pub fn new(msg_index: u32) -> Self {
+    /// level is the severity of this log entry.
+    pub fn new(msg_index: u32, level: CuLogLevel) -> Self {
         CuLogEntry {
             time: 0.into(), // We have no clock at that point it is called from random places
             // the clock will be set at actual log time from clock source provided
+            level,
+            task_id: None, // the context is stamped at actual log time too
+            culist_id: None,
             msg_index,
             paramname_indexes: SmallVec::new(),
             params: SmallVec::new(),
         }
     }
 
+    /// Text prefix showing where the entry comes from, ie. "[task 2 CL 42] ", empty if it has no context.
+    pub fn context_prefix(&self) -> String {
+        match (self.task_id, self.culist_id) {
+            (Some(task_id), Some(culist_id)) => format!("[task {task_id} CL {culist_id}] "),
+            (Some(task_id), None) => format!("[task {task_id}] "),
+            (None, Some(culist_id)) => format!("[CL {culist_id}] "),
+            (None, None) => String::new(),
+        }
+    }
+
     /// Add a parameter to the log entry.
     /// paramname_index is the interned index of the parameter name.
     pub fn add_param(&mut self, paramname_index: u32, param: Value) {
@@ -115,52 +233,51 @@ impl CuLogEntry {
 }
 
 /// Text log line formatter.
+/// params are all the parameters in order, they are consumed by the anonymous placeholders.
+/// named_params are the named ones, they are looked up by name.
+/// The format specifiers (`{:.3}`, `{:>8}`, `{:x}`...) are applied here, ie. only when the text log is rebuilt.
 #[inline]
 pub fn format_logline(
     time: CuTime,
     format_str: &str,
-    params: &[String],
-    named_params: &HashMap<String, String>,
+    params: &[Value],
+    named_params: &HashMap<String, Value>,
 ) -> CuResult<String> {
-    let mut format_str = format_str.to_string();
-
-    for param in params.iter() {
-        format_str = format_str.replacen("{}", param, 1);
-    }
-
-    if named_params.is_empty() {
-        return Ok(format_str);
-    }
-
-    let logline = strfmt(&format_str, named_params).map_err(|e| {
+    let pieces = parse_format_string(format_str)?;
+    let logline = format_pieces(&pieces, params, named_params).map_err(|e| {
         CuError::new_with_cause(
             format!("Failed to format log line: {format_str:?} with variables [{named_params:?}]")
                 .as_str(),
             e,
         )
     })?;
+    if named_params.is_empty() {
+        return Ok(logline);
+    }
     Ok(format!("{time}: {logline}"))
 }
 
 /// Rebuild a log line from the interned strings and the CuLogEntry.
 /// This basically translates the world of copper logs to text logs.
 pub fn rebuild_logline(all_interned_strings: &[String], entry: &CuLogEntry) -> CuResult<String> {
-    let format_string = &all_interned_strings[entry.msg_index as usize];
-    let mut anon_params: Vec<String> = Vec::new();
+    let interned = |index: u32| {
+        all_interned_strings.get(index as usize).ok_or_else(|| {
+            CuError::from(format!(
+                "Unknown interned string #{index}, is the string index matching this log?"
+            ))
+        })
+    };
+    let format_string = interned(entry.msg_index)?;
     let mut named_params = HashMap::new();
 
     for (i, param) in entry.params.iter().enumerate() {
-        let param_as_string = format!("{param}");
-        if entry.paramname_indexes[i] == 0 {
-            // Anonymous parameter
-            anon_params.push(param_as_string);
-        } else {
-            // Named parameter
-            let name = all_interned_strings[entry.paramname_indexes[i] as usize].clone();
-            named_params.insert(name, param_as_string);
+        if entry.paramname_indexes[i] != ANONYMOUS {
+            let name = interned(entry.paramname_indexes[i])?.clone();
+            named_params.insert(name, param.clone());
         }
     }
-    format_logline(entry.time, format_string, &anon_params, &named_params)
+    // The anonymous placeholders take all the parameters in order, the unnamed ones come first.
+    format_logline(entry.time, format_string, &entry.params, &named_params)
 }
 
 fn parent_n_times(path: &Path, n: usize) -> Option<PathBuf> {
@@ -2,55 +2,79 @@ extern crate proc_macro;
 mod index;
 
 use crate::index::intern_string;
+use cu29_log::{parse_format_string, CuLogLevel, FormatPiece};
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::parse::Parser;
-#[cfg(debug_assertions)]
 use syn::spanned::Spanned;
 use syn::Token;
 use syn::{Expr, ExprAssign, ExprLit, Lit};
 
-/// This macro is used to log a message with parameters.
-/// The first parameter is a string literal that represents the message to be logged.
-/// Only `{}` is supported as a placeholder for parameters.
-/// The rest of the parameters are the values to be logged.
-/// The parameters can be named or unnamed.
-/// Named parameters are specified as `name = value`.
-/// Unnamed parameters are specified as `value`.
-/// # Example
-/// ```ignore
-/// use cu29_log_derive::debug;
-/// let a = 1;
-/// let b = 2;
-/// debug!("a = {}, b = {}", my_value = a, b); // named and unnamed parameters
-/// ```
-///
-/// You can retreive this data using the log_reader generated with your project and giving it the
-/// unified .copper log file and the string index file generated at compile time.
-///
-/// Note: In debug mode, the log will also be printed to the console. (ie slooow).
-/// In release mode, the log will be only be written to the unified logger.
-#[proc_macro]
-pub fn debug(input: TokenStream) -> TokenStream {
+/// The minimum level compiled in, selected through the `log-level-*` cargo features.
+/// Everything below this level is compiled out at macro expansion time.
+fn compiled_min_level() -> CuLogLevel {
+    if cfg!(feature = "log-level-critical") {
+        CuLogLevel::Critical
+    } else if cfg!(feature = "log-level-error") {
+        CuLogLevel::Error
+    } else if cfg!(feature = "log-level-warning") {
+        CuLogLevel::Warning
+    } else if cfg!(feature = "log-level-info") {
+        CuLogLevel::Info
+    } else if cfg!(feature = "log-level-debug") {
+        CuLogLevel::Debug
+    } else {
+        CuLogLevel::Trace
+    }
+}
+
+/// Validates the format specifiers of the message against the parameters given to the macro.
+/// Nothing is formatted on the robot, the specifiers are only applied when the text log is rebuilt
+/// so this is the only chance to catch a mistake early.
+fn check_placeholders(msg: &str, unnamed_count: usize, names: &[String]) -> Result<(), String> {
+    let pieces = parse_format_string(msg).map_err(|e| e.to_string())?;
+    let mut anonymous_count = 0;
+    for piece in pieces {
+        match piece {
+            FormatPiece::Literal(_) => {}
+            FormatPiece::Placeholder { name: None, .. } => anonymous_count += 1,
+            FormatPiece::Placeholder {
+                name: Some(name), ..
+            } => {
+                if !names.contains(&name) {
+                    return Err(format!(
+                        "The log message refers to {{{name}}} but there is no `{name} = ...` parameter."
+                    ));
+                }
+            }
+        }
+    }
+    // the anonymous placeholders take the unnamed parameters first then the named ones
+    let params_count = unnamed_count + names.len();
+    if anonymous_count > params_count {
+        return Err(format!(
+            "The log message has {anonymous_count} anonymous placeholders but only {params_count} parameters."
+        ));
+    }
+    Ok(())
+}
+
+/// Builds the code for a log line at the given level, this is the common part for all the macros.
+fn create_log_entry(input: TokenStream, level: CuLogLevel) -> TokenStream {
     let parser = syn::punctuated::Punctuated::<Expr, Token![,]>::parse_terminated;
     let exprs = parser.parse(input).expect("Failed to parse input");
 
     let mut exprs_iter = exprs.iter();
 
     let msg_expr = exprs_iter.next().expect("Expected at least one expression");
-    let (index, _msg) = if let Expr::Lit(ExprLit {
+    let msg = if let Expr::Lit(ExprLit {
         lit: Lit::Str(msg), ..
     }) = msg_expr
     {
-        let msg = msg.value();
-        let index = intern_string(&msg).expect("Failed to insert log string.");
-        (index, msg)
+        msg.value()
     } else {
         panic!("The first parameter of the argument needs to be a string literal.");
     };
-    let prefix = quote! {
-        let mut log_entry = CuLogEntry::new(#index);
-    };
 
     let mut unnamed_params = vec![];
     let mut named_params = vec![];
@@ -63,6 +87,39 @@ pub fn debug(input: TokenStream) -> TokenStream {
         }
     }
 
+    let names: Vec<String> = named_params
+        .iter()
+        .map(|(name, _)| quote!(#name).to_string())
+        .collect();
+    if let Err(e) = check_placeholders(&msg, unnamed_params.len(), &names) {
+        return syn::Error::new(msg_expr.span(), e)
+            .to_compile_error()
+            .into();
+    }
+
+    // Compiled out: we still reference the parameters so the call site does not get unused variables warnings
+    // but nothing is evaluated nor interned.
+    if level < compiled_min_level() {
+        let all_values = unnamed_params
+            .iter()
+            .map(|value| quote!(#value))
+            .chain(named_params.iter().map(|(_, value)| quote!(#value)));
+        return quote! {
+            {
+                let _ = || {
+                    #(let _ = &#all_values;)*
+                };
+            }
+        }
+        .into();
+    }
+
+    let index = intern_string(&msg).expect("Failed to insert log string.");
+    let level_ident = format_ident!("{}", format!("{level:?}"));
+    let prefix = quote! {
+        let mut log_entry = CuLogEntry::new(#index, cu29_log::CuLogLevel::#level_ident);
+    };
+
     let unnamed_prints = unnamed_params.iter().map(|value| {
         quote! {
             let param = to_value(#value).expect("Failed to convert a parameter to a Value");
@@ -70,14 +127,37 @@ pub fn debug(input: TokenStream) -> TokenStream {
         }
     });
 
-    let named_prints = named_params.iter().map(|(name, value)| {
-        let index = intern_string(quote!(#name).to_string().as_str())
-            .expect("Failed to insert log string.");
+    let named_indexes: Vec<_> = named_params
+        .iter()
+        .map(|(name, _)| {
+            intern_string(quote!(#name).to_string().as_str()).expect("Failed to insert log string.")
+        })
+        .collect();
+
+    let named_prints = named_params
+        .iter()
+        .zip(&named_indexes)
+        .map(|((_, value), index)| {
+            quote! {
+                let param = to_value(#value).expect("Failed to convert a parameter to a Value");
+                log_entry.add_param(#index, param);
+            }
+        });
+
+    // The strings of this log line are embedded in the log the first time it is executed,
+    // so the log can be decoded without the string index generated at build time.
+    let describe = if cfg!(feature = "embed-string-index") {
         quote! {
-            let param = to_value(#value).expect("Failed to convert a parameter to a Value");
-            log_entry.add_param(#index, param);
+            static DESCRIBED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+            if !DESCRIBED.load(std::sync::atomic::Ordering::Relaxed)
+                && cu29_log_runtime::describe_strings(&[(#index, #msg) #(, (#named_indexes, #names))*])
+            {
+                DESCRIBED.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
         }
-    });
+    } else {
+        quote! {}
+    };
 
     #[cfg(not(debug_assertions))]
     let log_stmt = quote! {
@@ -95,7 +175,7 @@ pub fn debug(input: TokenStream) -> TokenStream {
             })
             .collect();
         quote! {
-            let r = log_debug_mode(&mut log_entry, #_msg, &[#(#keys),*]);
+            let r = log_debug_mode(&mut log_entry, #msg, &[#(#keys),*]);
         }
     };
 
@@ -110,6 +190,7 @@ pub fn debug(input: TokenStream) -> TokenStream {
 
     let expanded = quote! {
         {
+            #describe
             #prefix
             #(#unnamed_prints)*
             #(#named_prints)*
@@ -119,3 +200,68 @@ pub fn debug(input: TokenStream) -> TokenStream {
 
     expanded.into()
 }
+
+/// This macro is used to log a message with parameters at the [`CuLogLevel::Debug`] level.
+/// The first parameter is a string literal that represents the message to be logged.
+/// The placeholders are `{}` for the parameters in order (the unnamed ones first) and `{name}` for the named ones.
+/// They accept the usual format specifiers like `{:.3}`, `{:>8}`, `{name:#x}` (no positional `{0}` nor `{:width$}`).
+/// They are checked at compile time but only applied when the text log is rebuilt, not on the robot.
+/// The rest of the parameters are the values to be logged.
+/// The parameters can be named or unnamed.
+/// Named parameters are specified as `name = value`.
+/// Unnamed parameters are specified as `value`.
+/// # Example
+/// ```ignore
+/// use cu29_log_derive::debug;
+/// let a = 1;
+/// let b = 2;
+/// debug!("a = {}, b = {}", my_value = a, b); // named and unnamed parameters
+/// debug!("a = {my_value:>4}, b = {:#x}", my_value = a, b); // with format specifiers
+/// ```
+///
+/// You can retreive this data using the log_reader generated with your project and giving it the
+/// unified .copper log file and the string index file generated at compile time.
+///
+/// The other levels are available with [`trace!`], [`info!`], [`warning!`], [`error!`] and [`critical!`].
+/// The `log-level-*` features of this crate compile out everything below the given level.
+///
+/// With the `embed-string-index` feature of this crate, the strings of each log line are also written in the log
+/// the first time the line is executed, so the log can be decoded without the index file, even on another machine.
+/// This puts the strings of the log lines in your binary, which the structured logging otherwise avoids.
+///
+/// Note: In debug mode, the log will also be printed to the console. (ie slooow).
+/// In release mode, the log will be only be written to the unified logger.
+#[proc_macro]
+pub fn debug(input: TokenStream) -> TokenStream {
+    create_log_entry(input, CuLogLevel::Debug)
+}
+
+/// Same as [`debug!`] but at the [`CuLogLevel::Trace`] level, the most verbose one.
+#[proc_macro]
+pub fn trace(input: TokenStream) -> TokenStream {
+    create_log_entry(input, CuLogLevel::Trace)
+}
+
+/// Same as [`debug!`] but at the [`CuLogLevel::Info`] level.
+#[proc_macro]
+pub fn info(input: TokenStream) -> TokenStream {
+    create_log_entry(input, CuLogLevel::Info)
+}
+
+/// Same as [`debug!`] but at the [`CuLogLevel::Warning`] level.
+#[proc_macro]
+pub fn warning(input: TokenStream) -> TokenStream {
+    create_log_entry(input, CuLogLevel::Warning)
+}
+
+/// Same as [`debug!`] but at the [`CuLogLevel::Error`] level.
+#[proc_macro]
+pub fn error(input: TokenStream) -> TokenStream {
+    create_log_entry(input, CuLogLevel::Error)
+}
+
+/// Same as [`debug!`] but at the [`CuLogLevel::Critical`] level, the most severe one.
+#[proc_macro]
+pub fn critical(input: TokenStream) -> TokenStream {
+    create_log_entry(input, CuLogLevel::Critical)
+}
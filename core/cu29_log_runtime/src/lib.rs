@@ -4,22 +4,26 @@ use bincode::enc::Encode;
 use bincode::enc::{Encoder, EncoderImpl};
 use bincode::error::EncodeError;
 use cu29_clock::RobotClock;
-use cu29_log::CuLogEntry;
+use cu29_log::{CuLogEntry, CuLogLevel};
 use cu29_traits::{CuResult, WriteStream};
 use log::Log;
 
 #[cfg(debug_assertions)]
-use cu29_log::format_logline;
+use cu29_log::{format_logline, ANONYMOUS};
+#[cfg(debug_assertions)]
+use cu29_value::Value;
 #[cfg(debug_assertions)]
 use log::Record;
 #[cfg(debug_assertions)]
 use std::collections::HashMap;
 
+use std::cell::{Cell, RefCell};
 use std::fmt::{Debug, Formatter};
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
-use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, Weak};
 
 #[derive(Debug)]
 struct DummyWriteStream;
@@ -30,11 +34,86 @@ impl WriteStream<CuLogEntry> for DummyWriteStream {
         Ok(())
     }
 }
-type LogWriter = Box<dyn WriteStream<CuLogEntry>>;
+pub type LogWriter = Box<dyn WriteStream<CuLogEntry>>;
 type WriterPair = (Mutex<LogWriter>, RobotClock);
 
+/// Builds the destination stream of a thread, see [`LoggerRuntime::init_per_thread`].
+pub type LogWriterFactory = Box<dyn Fn() -> CuResult<LogWriter> + Send + Sync>;
+
 static WRITER: OnceLock<WriterPair> = OnceLock::new();
 
+/// The factory for the per thread mode, it is only used the first time a thread logs.
+static PER_THREAD_FACTORY: RwLock<Option<(LogWriterFactory, RobotClock)>> = RwLock::new(None);
+
+/// Generation of the active per thread mode, 0 if the logger is in the shared mode.
+/// It invalidates the streams of the threads from a previous initialization.
+static PER_THREAD_GENERATION: AtomicUsize = AtomicUsize::new(0);
+static LAST_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The stream of a thread in the per thread mode, tagged with the generation it was created for.
+type ThreadWriter = (usize, Arc<Mutex<LogWriter>>, RobotClock);
+
+/// The streams of all the threads in the per thread mode so they can be flushed from any thread.
+/// The lock of a stream is only contended while it is flushed from another thread.
+static PER_THREAD_WRITERS: Mutex<Vec<Weak<Mutex<LogWriter>>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// The stream of this thread in the per thread mode, tagged with its generation.
+    /// It is flushed when the thread exits.
+    static THREAD_WRITER: RefCell<Option<ThreadWriter>> = const { RefCell::new(None) };
+}
+
+/// The destination of the strings embedded by the log lines, see [`LoggerRuntime::embed_string_index`].
+pub type StringIndexWriter = Box<dyn WriteStream<(u32, &'static str)>>;
+
+/// Builds the destination of the embedded strings, it is only called the first time a string is embedded.
+pub type StringIndexWriterFactory = Box<dyn Fn() -> CuResult<StringIndexWriter> + Send + Sync>;
+
+static STRING_INDEX: Mutex<(Option<StringIndexWriterFactory>, Option<StringIndexWriter>)> =
+    Mutex::new((None, None));
+
+/// Fast path for the log lines when no string index is embedded.
+static STRING_INDEX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Runtime minimum level, everything below it is dropped at log time.
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(CuLogLevel::Trace as u8);
+
+thread_local! {
+    /// The task and copper list being executed on this thread, maintained by the runtime.
+    static LOG_CONTEXT: Cell<(Option<u32>, Option<u32>)> = const { Cell::new((None, None)) };
+}
+
+/// Sets the task currently executed on this thread, it is stamped on every log entry.
+/// This is called by the runtime around each task call, None when outside of any task.
+#[inline(always)]
+pub fn set_log_task_id(task_id: Option<u32>) {
+    LOG_CONTEXT.with(|ctx| ctx.set((task_id, ctx.get().1)));
+}
+
+/// Sets the copper list currently processed on this thread, it is stamped on every log entry.
+/// This is called by the runtime for each copper list, None when outside of any copper list.
+#[inline(always)]
+pub fn set_log_culist_id(culist_id: Option<u32>) {
+    LOG_CONTEXT.with(|ctx| ctx.set((ctx.get().0, culist_id)));
+}
+
+/// Stamps a copper list id on the log entries of this thread until it is dropped,
+/// so the id is reset on every exit path of the processing, early returns included.
+pub struct CuListLogScope(());
+
+impl CuListLogScope {
+    pub fn enter(culist_id: u32) -> Self {
+        set_log_culist_id(Some(culist_id));
+        CuListLogScope(())
+    }
+}
+
+impl Drop for CuListLogScope {
+    fn drop(&mut self) {
+        set_log_culist_id(None);
+    }
+}
+
 #[cfg(debug_assertions)]
 static EXTRA_TEXT_LOGGER: OnceLock<Option<Box<dyn Log>>> = OnceLock::new();
 
@@ -60,6 +139,7 @@ impl LoggerRuntime {
         #[allow(unused_variables)] extra_text_logger: Option<impl Log + 'static>,
     ) -> Self {
         let runtime = LoggerRuntime {};
+        PER_THREAD_GENERATION.store(0, Ordering::Release);
 
         // If WRITER is already initialized, update the inner value.
         // This should only be useful for unit testing.
@@ -78,7 +158,68 @@ impl LoggerRuntime {
         runtime
     }
 
+    /// Same as init but every thread logs in its own stream, created by stream_factory the first time it logs.
+    /// The threads never wait on each other to log, for example with one section of the unified logger each:
+    /// ```ignore
+    /// let rt = LoggerRuntime::init_per_thread(clock, move || {
+    ///     Ok(Box::new(stream_write_preallocated(logger.clone(), UnifiedLogType::StructuredLogLine, 4096 * 10)) as LogWriter)
+    /// }, None::<NullLog>);
+    /// ```
+    /// The stream of a thread is closed when the thread exits, or when the runtime is dropped if the thread
+    /// is still running. flush() flushes the streams of all the threads.
+    pub fn init_per_thread(
+        clock: RobotClock,
+        stream_factory: impl Fn() -> CuResult<LogWriter> + Send + Sync + 'static,
+        #[allow(unused_variables)] extra_text_logger: Option<impl Log + 'static>,
+    ) -> Self {
+        *PER_THREAD_FACTORY.write().unwrap() = Some((Box::new(stream_factory), clock));
+        let generation = LAST_GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        PER_THREAD_GENERATION.store(generation, Ordering::Release);
+
+        #[cfg(debug_assertions)]
+        let _ =
+            EXTRA_TEXT_LOGGER.set(extra_text_logger.map(|logger| Box::new(logger) as Box<dyn Log>));
+
+        LoggerRuntime {}
+    }
+
+    /// Embeds the strings of the log lines in the stream built by stream_factory, so the log can be decoded
+    /// without the string index generated at build time. It only applies to the crates built with the
+    /// `embed-string-index` feature of cu29-log-derive, each log line writes its strings the first time it is executed.
+    /// The stream is only created when the first string is embedded.
+    pub fn embed_string_index(
+        &self,
+        stream_factory: impl Fn() -> CuResult<StringIndexWriter> + Send + Sync + 'static,
+    ) {
+        *STRING_INDEX.lock().unwrap() = (Some(Box::new(stream_factory)), None);
+        STRING_INDEX_ENABLED.store(true, Ordering::Release);
+    }
+
+    /// Sets the minimum level of the log entries recorded from now on.
+    /// This is a runtime filter on top of the compile time one from the `log-level-*` features.
+    pub fn set_min_level(&self, level: CuLogLevel) {
+        MIN_LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+
+    /// Returns the current minimum level of the log entries recorded.
+    pub fn min_level(&self) -> CuLogLevel {
+        current_min_level()
+    }
+
     pub fn flush(&self) {
+        if let Some(writer) = STRING_INDEX.lock().unwrap().1.as_mut() {
+            if let Err(err) = writer.flush() {
+                eprintln!("cu29_log: Failed to flush the string index: {err}");
+            }
+        }
+        if PER_THREAD_GENERATION.load(Ordering::Acquire) != 0 {
+            for writer in live_per_thread_writers() {
+                if let Err(err) = writer.lock().unwrap().flush() {
+                    eprintln!("cu29_log: Failed to flush writer: {err}");
+                }
+            }
+            return;
+        }
         if let Some((writer, _clock)) = WRITER.get() {
             if let Ok(mut writer) = writer.lock() {
                 if let Err(err) = writer.flush() {
@@ -96,6 +237,18 @@ impl LoggerRuntime {
 impl Drop for LoggerRuntime {
     fn drop(&mut self) {
         self.flush();
+        STRING_INDEX_ENABLED.store(false, Ordering::Release);
+        *STRING_INDEX.lock().unwrap() = (None, None);
+        if PER_THREAD_GENERATION.swap(0, Ordering::AcqRel) != 0 {
+            *PER_THREAD_FACTORY.write().unwrap() = None;
+            // closes the streams of the threads still running, they would log in a new one if the runtime
+            // is initialized again.
+            for writer in live_per_thread_writers() {
+                *writer.lock().unwrap() = Box::new(DummyWriteStream);
+            }
+            PER_THREAD_WRITERS.lock().unwrap().clear();
+            return;
+        }
         if let Some((mutex, _clock)) = WRITER.get() {
             if let Ok(mut writer_guard) = mutex.lock() {
                 // Replace the current WriteStream with a DummyWriteStream
@@ -105,16 +258,29 @@ impl Drop for LoggerRuntime {
     }
 }
 
+#[inline(always)]
+fn current_min_level() -> CuLogLevel {
+    CuLogLevel::from_u8(MIN_LEVEL.load(Ordering::Relaxed)).unwrap_or_default()
+}
+
 /// Function called from generated code to log data.
 /// It moves entry by design, it will be absorbed in the queue.
 #[inline(always)]
 pub fn log(entry: &mut CuLogEntry) -> CuResult<()> {
+    if entry.level < current_min_level() {
+        return Ok(());
+    }
+    let generation = PER_THREAD_GENERATION.load(Ordering::Acquire);
+    if generation != 0 {
+        return log_per_thread(entry, generation);
+    }
     let d = WRITER.get().map(|(writer, clock)| (writer, clock));
     if d.is_none() {
         return Err("Logger not initialized.".into());
     }
     let (writer, clock) = d.unwrap();
     entry.time = clock.now();
+    (entry.task_id, entry.culist_id) = LOG_CONTEXT.with(|ctx| ctx.get());
     if let Err(err) = writer.lock().unwrap().log(entry) {
         eprintln!("Failed to log data: {err}");
     }
@@ -128,6 +294,76 @@ pub fn log(entry: &mut CuLogEntry) -> CuResult<()> {
     Ok(())
 }
 
+/// Function called from generated code to embed the strings of a log line with their index.
+/// Returns false if they could not be embedded, for example if the runtime does not embed a string index (yet).
+pub fn describe_strings(strings: &[(u32, &'static str)]) -> bool {
+    if !STRING_INDEX_ENABLED.load(Ordering::Acquire) {
+        return false;
+    }
+    let mut string_index = STRING_INDEX.lock().unwrap();
+    let (factory, writer) = &mut *string_index;
+    if writer.is_none() {
+        let Some(factory) = factory.as_ref() else {
+            return false;
+        };
+        match factory() {
+            Ok(w) => *writer = Some(w),
+            Err(err) => {
+                eprintln!("cu29_log: Failed to create the string index stream: {err}");
+                return false;
+            }
+        }
+    }
+    let writer = writer.as_mut().unwrap();
+    for s in strings {
+        if let Err(err) = writer.log(s) {
+            eprintln!("cu29_log: Failed to embed a string: {err}");
+            return false;
+        }
+    }
+    true
+}
+
+/// The streams of the threads which are still running.
+fn live_per_thread_writers() -> Vec<Arc<Mutex<LogWriter>>> {
+    PER_THREAD_WRITERS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+/// Logs in the stream of the calling thread, creating it on the first call.
+/// After that, the only lock taken is the one of the stream of the thread, nobody else takes it but a flush.
+#[inline(always)]
+fn log_per_thread(entry: &mut CuLogEntry, generation: usize) -> CuResult<()> {
+    THREAD_WRITER.with(|thread_writer| {
+        let mut thread_writer = thread_writer
+            .try_borrow_mut()
+            .map_err(|_| "Recursive log from a log stream.")?;
+        if !matches!(thread_writer.as_ref(), Some((g, _, _)) if *g == generation) {
+            *thread_writer = None; // flushes the stream from a previous initialization first.
+            let factory = PER_THREAD_FACTORY.read().unwrap();
+            let Some((stream_factory, clock)) = factory.as_ref() else {
+                return Err("Logger not initialized.".into());
+            };
+            let writer = Arc::new(Mutex::new(stream_factory()?));
+            let mut writers = PER_THREAD_WRITERS.lock().unwrap();
+            writers.retain(|w| w.strong_count() > 0); // forgets the threads which exited.
+            writers.push(Arc::downgrade(&writer));
+            *thread_writer = Some((generation, writer, clock.clone()));
+        }
+        let (_, writer, clock) = thread_writer.as_mut().unwrap();
+        entry.time = clock.now();
+        (entry.task_id, entry.culist_id) = LOG_CONTEXT.with(|ctx| ctx.get());
+        if let Err(err) = writer.lock().unwrap().log(entry) {
+            eprintln!("Failed to log data: {err}");
+        }
+        Ok(())
+    })
+}
+
 /// This version of log is only compiled in debug mode
 /// This allows a normal logging framework to be bridged.
 #[cfg(debug_assertions)]
@@ -136,6 +372,9 @@ pub fn log_debug_mode(
     format_str: &str, // this is the missing info at runtime.
     param_names: &[&str],
 ) -> CuResult<()> {
+    if entry.level < current_min_level() {
+        return Ok(());
+    }
     log(entry)?;
 
     let guarded_logger = EXTRA_TEXT_LOGGER.get();
@@ -143,24 +382,24 @@ pub fn log_debug_mode(
         return Ok(());
     }
     if let Some(logger) = guarded_logger.unwrap() {
-        let fstr = format_str.to_string();
-        // transform the slice into a hashmap
-        let params: Vec<String> = entry.params.iter().map(|v| v.to_string()).collect();
-        let named_params: Vec<(&str, String)> = param_names
-            .iter()
-            .zip(params.iter())
-            .map(|(name, value)| (*name, value.clone()))
-            .collect();
-        // build hashmap of string, string from named_paramgs
-        let named_params: HashMap<String, String> = named_params
+        // The named parameters are the ones with an interned name, they come in the order of param_names.
+        let named_params: HashMap<String, Value> = param_names
             .iter()
-            .map(|(k, v)| (k.to_string(), v.clone()))
+            .zip(
+                entry
+                    .params
+                    .iter()
+                    .zip(entry.paramname_indexes.iter())
+                    .filter(|(_, index)| **index != ANONYMOUS)
+                    .map(|(v, _)| v.clone()),
+            )
+            .map(|(k, v)| (k.to_string(), v))
             .collect();
-        let logline = format_logline(entry.time, &fstr, params.as_slice(), &named_params)?;
+        let logline = format_logline(entry.time, format_str, &entry.params, &named_params)?;
         logger.log(
             &Record::builder()
-                .args(format_args!("{logline}"))
-                .level(log::Level::Info)
+                .args(format_args!("{}{logline}", entry.context_prefix()))
+                .level(to_log_level(entry.level))
                 .target("cu29_log")
                 .module_path_static(Some("cu29_log"))
                 .file_static(Some("cu29_log"))
@@ -171,6 +410,18 @@ pub fn log_debug_mode(
     Ok(())
 }
 
+/// Maps our levels to the ones of the log crate, it has no critical so it maps to error.
+#[cfg(debug_assertions)]
+fn to_log_level(level: CuLogLevel) -> log::Level {
+    match level {
+        CuLogLevel::Trace => log::Level::Trace,
+        CuLogLevel::Debug => log::Level::Debug,
+        CuLogLevel::Info => log::Level::Info,
+        CuLogLevel::Warning => log::Level::Warn,
+        CuLogLevel::Error | CuLogLevel::Critical => log::Level::Error,
+    }
+}
+
 // This is an adaptation of the Iowriter from bincode.
 pub struct OwningIoWriter<W: Write> {
     writer: BufWriter<W>,
@@ -261,15 +512,79 @@ impl WriteStream<CuLogEntry> for SimpleFileWriter {
 
 #[cfg(test)]
 mod tests {
-    use crate::CuLogEntry;
+    use crate::{log, CuListLogScope, CuLogEntry, LogWriter, LoggerRuntime, NullLog, LOG_CONTEXT};
     use bincode::config::standard;
+    use cu29_clock::RobotClock;
+    use cu29_log::CuLogLevel;
+    use cu29_traits::{CuResult, WriteStream};
     use cu29_value::Value;
     use smallvec::smallvec;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct CountingStream(Arc<AtomicUsize>);
+
+    impl WriteStream<CuLogEntry> for CountingStream {
+        fn log(&mut self, _obj: &CuLogEntry) -> CuResult<()> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_per_thread_streams() {
+        let nb_streams = Arc::new(AtomicUsize::new(0));
+        let nb_entries = Arc::new(AtomicUsize::new(0));
+        let rt = {
+            let nb_streams = nb_streams.clone();
+            let nb_entries = nb_entries.clone();
+            LoggerRuntime::init_per_thread(
+                RobotClock::default(),
+                move || {
+                    nb_streams.fetch_add(1, Ordering::Relaxed);
+                    Ok(Box::new(CountingStream(nb_entries.clone())) as LogWriter)
+                },
+                None::<NullLog>,
+            )
+        };
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        log(&mut CuLogEntry::new(1, CuLogLevel::Info)).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(nb_streams.load(Ordering::Relaxed), 4);
+        assert_eq!(nb_entries.load(Ordering::Relaxed), 400);
+        drop(rt);
+    }
+
+    #[test]
+    fn test_culist_log_scope() {
+        fn process(fail: bool) -> CuResult<()> {
+            let _scope = CuListLogScope::enter(42);
+            assert_eq!(LOG_CONTEXT.with(|ctx| ctx.get()).1, Some(42));
+            if fail {
+                return Err("early return".into());
+            }
+            Ok(())
+        }
+        assert!(process(true).is_err());
+        assert_eq!(LOG_CONTEXT.with(|ctx| ctx.get()).1, None);
+        process(false).unwrap();
+        assert_eq!(LOG_CONTEXT.with(|ctx| ctx.get()).1, None);
+    }
 
     #[test]
     fn test_encode_decode_structured_log() {
         let log_entry = CuLogEntry {
             time: 0.into(),
+            level: CuLogLevel::Warning,
+            task_id: Some(3),
+            culist_id: None,
             msg_index: 1,
             paramname_indexes: smallvec![2, 3],
             params: smallvec![Value::String("test".to_string())],
@@ -3,6 +3,7 @@
 //! The configuration is serialized in the RON format.
 //! The configuration is used to generate the runtime code at compile time.
 
+use cu29_clock::CuDuration;
 use cu29_traits::{CuError, CuResult};
 use petgraph::adj::NodeIndex;
 use petgraph::stable_graph::{EdgeIndex, StableDiGraph};
@@ -100,6 +101,12 @@ impl From<f64> for Value {
     }
 }
 
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value(RonValue::Bool(value))
+    }
+}
+
 impl From<Value> for bool {
     fn from(value: Value) -> Self {
         if let RonValue::Bool(v) = value.0 {
@@ -152,14 +159,11 @@ impl From<Value> for i32 {
     }
 }
 
+/// Integers are accepted too.
 impl From<Value> for f64 {
     fn from(value: Value) -> Self {
         if let RonValue::Number(num) = value.0 {
-            if let Some(f) = num.as_f64() {
-                f
-            } else {
-                panic!("Expected a float value but got {value:?}")
-            }
+            num.into_f64()
         } else {
             panic!("Expected a Number variant but got {value:?}")
         }
@@ -182,6 +186,72 @@ impl From<Value> for String {
     }
 }
 
+impl From<Vec<f64>> for Value {
+    fn from(value: Vec<f64>) -> Self {
+        Value(RonValue::Seq(
+            value
+                .into_iter()
+                .map(|v| RonValue::Number(v.into()))
+                .collect(),
+        ))
+    }
+}
+
+/// From a list of numbers like [1.0, 2, 3.5].
+impl From<Value> for Vec<f64> {
+    fn from(value: Value) -> Self {
+        if let RonValue::Seq(seq) = value.0 {
+            seq.into_iter().map(|v| f64::from(Value(v))).collect()
+        } else {
+            panic!("Expected a Seq variant but got {value:?}")
+        }
+    }
+}
+
+impl From<Vec<Vec<f64>>> for Value {
+    fn from(value: Vec<Vec<f64>>) -> Self {
+        Value(RonValue::Seq(
+            value.into_iter().map(|row| Value::from(row).0).collect(),
+        ))
+    }
+}
+
+/// From a list of lists of numbers, like the rows of a matrix [[1.0, 0.0], [0.0, 1.0]].
+impl From<Value> for Vec<Vec<f64>> {
+    fn from(value: Value) -> Self {
+        if let RonValue::Seq(seq) = value.0 {
+            seq.into_iter()
+                .map(|v| Vec::<f64>::from(Value(v)))
+                .collect()
+        } else {
+            panic!("Expected a Seq variant but got {value:?}")
+        }
+    }
+}
+
+/// Stored as a number of ns.
+impl From<CuDuration> for Value {
+    fn from(value: CuDuration) -> Self {
+        Value(RonValue::Number(value.0.into()))
+    }
+}
+
+/// From a number of ns or a humanized string like "10ms".
+impl From<Value> for CuDuration {
+    fn from(value: Value) -> Self {
+        match value.0 {
+            RonValue::Number(num) => match num.as_i64() {
+                Some(i) if i >= 0 => CuDuration(i as u64),
+                _ => panic!("Expected a positive integer number of ns but got {num:?}"),
+            },
+            RonValue::String(s) => s
+                .parse()
+                .unwrap_or_else(|e| panic!("Expected a duration like \"10ms\": {e}")),
+            _ => panic!("Expected a Number or a String variant for a duration but got {value:?}"),
+        }
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.0 {
@@ -286,7 +356,7 @@ pub struct Cnx {
 pub struct CuConfig {
     // This is not what is directly serialized, see the custom serialization below.
     pub graph: StableDiGraph<Node, Cnx, NodeId>,
-    monitor: Option<MonitorConfig>,
+    monitors: Vec<MonitorConfig>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -314,7 +384,11 @@ impl MonitorConfig {
 struct CuConfigRepresentation {
     tasks: Vec<Node>,
     cnx: Vec<Cnx>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     monitor: Option<MonitorConfig>,
+    /// To run several monitors at once.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    monitors: Vec<MonitorConfig>,
 }
 
 impl<'de> Deserialize<'de> for CuConfig {
@@ -350,7 +424,16 @@ impl<'de> Deserialize<'de> for CuConfig {
                 c.store,
             );
         }
-        cuconfig.monitor = representation.monitor;
+        if representation.monitor.is_some() && !representation.monitors.is_empty() {
+            return Err(serde::de::Error::custom(
+                "monitor and monitors are exclusive, list all the monitors in monitors",
+            ));
+        }
+        cuconfig.monitors = representation
+            .monitor
+            .into_iter()
+            .chain(representation.monitors)
+            .collect();
         Ok(cuconfig)
     }
 }
@@ -373,10 +456,17 @@ impl Serialize for CuConfig {
             .map(|edge| self.graph[edge].clone())
             .collect();
 
+        // a single monitor keeps the simpler monitor: (...) form.
+        let (monitor, monitors) = match self.monitors.as_slice() {
+            [monitor] => (Some(monitor.clone()), Vec::new()),
+            monitors => (None, monitors.to_vec()),
+        };
+
         CuConfigRepresentation {
             tasks,
             cnx,
-            monitor: self.monitor.clone(),
+            monitor,
+            monitors,
         }
         .serialize(serializer)
     }
@@ -386,7 +476,7 @@ impl Default for CuConfig {
     fn default() -> Self {
         CuConfig {
             graph: StableDiGraph::new(),
-            monitor: None,
+            monitors: Vec::new(),
         }
     }
 }
@@ -610,9 +700,25 @@ impl CuConfig {
             .collect()
     }
 
+    /// The config of the monitor, the first one if several are configured.
     #[allow(dead_code)]
     pub fn get_monitor_config(&self) -> Option<&MonitorConfig> {
-        self.monitor.as_ref()
+        self.monitors.first()
+    }
+
+    #[allow(dead_code)]
+    pub fn get_monitor_configs(&self) -> &[MonitorConfig] {
+        &self.monitors
+    }
+
+    /// The configuration as seen by the monitor at index when several monitors run at once:
+    /// its get_monitor_config gives back its own config.
+    #[allow(dead_code)]
+    pub fn for_monitor(&self, index: usize) -> CuConfig {
+        CuConfig {
+            graph: self.graph.clone(),
+            monitors: self.monitors.get(index).cloned().into_iter().collect(),
+        }
     }
 }
 
@@ -668,18 +774,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_duration_params() {
+        let txt = r#"( tasks: [(id: "src", type: "pkg::Src", config: { "period": "10ms", "timeout": 500 })], cnx: [] ) "#;
+        let config = CuConfig::deserialize_ron(txt);
+        let node = config.get_node(0).unwrap();
+        assert_eq!(
+            node.get_param::<CuDuration>("period"),
+            Some(CuDuration(10_000_000))
+        );
+        assert_eq!(
+            node.get_param::<CuDuration>("timeout"),
+            Some(CuDuration(500))
+        );
+    }
+
+    #[test]
+    fn test_list_params() {
+        let txt = r#"( tasks: [(id: "src", type: "pkg::Src", config: { "points": [0, 1.5], "matrix": [[1.0, 0.0], [0.0, 2]] })], cnx: [] ) "#;
+        let config = CuConfig::deserialize_ron(txt);
+        let node = config.get_node(0).unwrap();
+        assert_eq!(node.get_param::<Vec<f64>>("points"), Some(vec![0.0, 1.5]));
+        assert_eq!(
+            node.get_param::<Vec<Vec<f64>>>("matrix"),
+            Some(vec![vec![1.0, 0.0], vec![0.0, 2.0]])
+        );
+    }
+
     #[test]
     fn test_monitor() {
         let txt = r#"( tasks: [], cnx: [], monitor: (type: "ExampleMonitor", ) ) "#;
         let config = CuConfig::deserialize_ron(txt);
-        assert_eq!(config.monitor.as_ref().unwrap().type_, "ExampleMonitor");
+        assert_eq!(config.get_monitor_config().unwrap().type_, "ExampleMonitor");
 
         let txt =
             r#"( tasks: [], cnx: [], monitor: (type: "ExampleMonitor", config: { "toto": 4, } )) "#;
         let config = CuConfig::deserialize_ron(txt);
         assert_eq!(
-            config.monitor.as_ref().unwrap().config.as_ref().unwrap().0["toto"],
+            config
+                .get_monitor_config()
+                .unwrap()
+                .config
+                .as_ref()
+                .unwrap()
+                .0["toto"],
             4.into()
         );
     }
+
+    #[test]
+    fn test_monitors() {
+        let txt = r#"( tasks: [], cnx: [], monitors: [(type: "ConsoleMon"), (type: "MetricsMon", config: { "port": 9000 })] ) "#;
+        let config = CuConfig::deserialize_ron(txt);
+        assert_eq!(config.get_monitor_configs().len(), 2);
+        assert_eq!(
+            config.get_monitor_config().unwrap().get_type(),
+            "ConsoleMon"
+        );
+        let metrics_view = config.for_monitor(1);
+        let metrics_config = metrics_view.get_monitor_config().unwrap();
+        assert_eq!(metrics_config.get_type(), "MetricsMon");
+        assert_eq!(
+            metrics_config.get_config().unwrap().get::<u32>("port"),
+            Some(9000)
+        );
+
+        let deserialized = CuConfig::deserialize_ron(&config.serialize_ron());
+        assert_eq!(deserialized.get_monitor_configs().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "monitor and monitors are exclusive")]
+    fn test_monitor_and_monitors() {
+        let txt = r#"( tasks: [], cnx: [], monitor: (type: "ConsoleMon"), monitors: [(type: "MetricsMon")] ) "#;
+        CuConfig::deserialize_ron(txt);
+    }
 }
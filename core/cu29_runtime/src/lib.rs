@@ -7,3 +7,5 @@ pub mod cutask;
 pub mod monitoring;
 pub mod payload;
 pub mod simulation;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
@@ -21,10 +21,11 @@ use cu29_log_runtime::log_debug_mode;
 use cu29_traits::{CuError, CuResult};
 
 use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::Debug;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// The state of a task.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CuTaskState {
     Start,
     Preprocess,
@@ -34,13 +35,44 @@ pub enum CuTaskState {
 }
 
 /// Monitor decision to be taken when a task errored out.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Decision {
     Abort,    // for a step (stop, start) or a copperlist, just stop trying to process it.
     Ignore, // Ignore this error and try to continue, ie calling the other tasks steps, setting a None return value and continue a copperlist.
     Shutdown, // This is a fatal error, shutdown the copper as cleanly as possible.
 }
 
+impl Decision {
+    fn severity(self) -> u8 {
+        match self {
+            Decision::Ignore => 0,
+            Decision::Abort => 1,
+            Decision::Shutdown => 2,
+        }
+    }
+
+    /// The usual reaction to an error of a task at the given step: a task which cannot start or stop
+    /// shuts down the copper, a failed preprocess aborts the copper list and the other errors are ignored.
+    pub fn default_for(step: CuTaskState) -> Decision {
+        match step {
+            CuTaskState::Start => Decision::Shutdown,
+            CuTaskState::Preprocess => Decision::Abort,
+            CuTaskState::Process => Decision::Ignore,
+            CuTaskState::Postprocess => Decision::Ignore,
+            CuTaskState::Stop => Decision::Shutdown,
+        }
+    }
+
+    /// The most severe of the 2 decisions: Shutdown > Abort > Ignore.
+    pub fn merge(self, other: Decision) -> Decision {
+        if other.severity() > self.severity() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
 /// Trait to implement a monitoring task.
 pub trait CuMonitor: Sized {
     fn new(config: &CuConfig, taskids: &'static [&'static str]) -> CuResult<Self>
@@ -54,6 +86,12 @@ pub trait CuMonitor: Sized {
     /// Callback that will be trigger at the end of every copperlist (before, on or after the serialization).
     fn process_copperlist(&self, msgs: &[&CuMsgMetadata]) -> CuResult<()>;
 
+    /// Callback right after process_copperlist with the payloads of the same messages, None if a message has no payload.
+    /// Formatting them is costly, only do it for the messages you really need.
+    fn process_payloads(&self, _payloads: &[Option<&dyn Debug>]) -> CuResult<()> {
+        Ok(())
+    }
+
     /// Callbacked when a Task errored out. The runtime requires an immediate decision.
     fn process_error(&self, taskid: usize, step: CuTaskState, error: &CuError) -> Decision;
 
@@ -82,6 +120,88 @@ impl CuMonitor for NoMonitor {
     }
 }
 
+/// The maximum number of monitors running at once, see the composite CuMonitor below.
+pub const MAX_MONITORS: usize = 8;
+
+/// Several monitors running at once, configured with `monitors: [...]`.
+/// Each monitor sees its own config with get_monitor_config, all the callbacks are fanned out in order
+/// and the most severe decision wins on errors.
+/// If a monitor fails to start, the ones already started are stopped before returning the error.
+macro_rules! impl_composite_monitor {
+    ($($name:ident: $index:tt),+) => {
+        impl<$($name: CuMonitor),+> CuMonitor for ($($name,)+) {
+            fn new(config: &CuConfig, taskids: &'static [&'static str]) -> CuResult<Self> {
+                Ok(($($name::new(&config.for_monitor($index), taskids)?,)+))
+            }
+
+            fn start(&mut self, clock: &RobotClock) -> CuResult<()> {
+                let mut started = 0;
+                let mut result = Ok(());
+                $(
+                    if result.is_ok() {
+                        result = self.$index.start(clock);
+                        if result.is_ok() {
+                            started = $index + 1;
+                        }
+                    }
+                )+
+                if result.is_err() {
+                    $(
+                        if $index < started {
+                            // the start error is the one reported.
+                            let _ = self.$index.stop(clock);
+                        }
+                    )+
+                }
+                result
+            }
+
+            fn process_copperlist(&self, msgs: &[&CuMsgMetadata]) -> CuResult<()> {
+                // all the monitors see the copperlist even if one of them errors out.
+                let mut result = Ok(());
+                $(
+                    if let Err(e) = self.$index.process_copperlist(msgs) {
+                        result = result.and(Err(e));
+                    }
+                )+
+                result
+            }
+
+            fn process_payloads(&self, payloads: &[Option<&dyn Debug>]) -> CuResult<()> {
+                let mut result = Ok(());
+                $(
+                    if let Err(e) = self.$index.process_payloads(payloads) {
+                        result = result.and(Err(e));
+                    }
+                )+
+                result
+            }
+
+            fn process_error(&self, taskid: usize, step: CuTaskState, error: &CuError) -> Decision {
+                Decision::Ignore$(.merge(self.$index.process_error(taskid, step, error)))+
+            }
+
+            fn stop(&mut self, clock: &RobotClock) -> CuResult<()> {
+                let mut result = Ok(());
+                $(
+                    if let Err(e) = self.$index.stop(clock) {
+                        result = result.and(Err(e));
+                    }
+                )+
+                result
+            }
+        }
+    };
+}
+
+impl_composite_monitor!(M0: 0, M1: 1);
+impl_composite_monitor!(M0: 0, M1: 1, M2: 2);
+impl_composite_monitor!(M0: 0, M1: 1, M2: 2, M3: 3);
+impl_composite_monitor!(M0: 0, M1: 1, M2: 2, M3: 3, M4: 4);
+impl_composite_monitor!(M0: 0, M1: 1, M2: 2, M3: 3, M4: 4, M5: 5);
+impl_composite_monitor!(M0: 0, M1: 1, M2: 2, M3: 3, M4: 4, M5: 5, M6: 6);
+impl_composite_monitor!(M0: 0, M1: 1, M2: 2, M3: 3, M4: 4, M5: 5, M6: 6, M7: 7);
+
 #[global_allocator]
 pub static GLOBAL: CountingAllocator = CountingAllocator::new();
 
@@ -221,6 +341,12 @@ impl LiveStatistics {
         }
     }
 
+    /// The number of recorded values between low and high included.
+    #[inline]
+    pub fn count_between(&self, low: u64, high: u64) -> u64 {
+        self.stats.count_between(low, high)
+    }
+
     #[inline]
     pub fn len(&self) -> u64 {
         self.stats.len()
@@ -290,6 +416,12 @@ impl CuDurationStatistics {
         self.bare.len() == 0
     }
 
+    /// The number of recorded durations lower or equal to value, useful to build histogram buckets.
+    #[inline]
+    pub fn count_up_to(&self, value: CuDuration) -> u64 {
+        self.bare.count_between(0, value.0)
+    }
+
     #[inline]
     pub fn jitter_min(&self) -> CuDuration {
         CuDuration(self.jitter.min())
@@ -341,6 +473,90 @@ impl CuDurationStatistics {
 mod tests {
     use super::*;
 
+    struct TestMonitor {
+        decision: Decision,
+        copperlists: AtomicUsize,
+        started: bool,
+        stopped: bool,
+    }
+
+    impl CuMonitor for TestMonitor {
+        fn new(config: &CuConfig, _taskids: &'static [&'static str]) -> CuResult<Self> {
+            let decision = match config.get_monitor_config().unwrap().get_type() {
+                "shutdown" => Decision::Shutdown,
+                "abort" => Decision::Abort,
+                _ => Decision::Ignore,
+            };
+            Ok(TestMonitor {
+                decision,
+                copperlists: AtomicUsize::new(0),
+                started: false,
+                stopped: false,
+            })
+        }
+
+        fn start(&mut self, _clock: &RobotClock) -> CuResult<()> {
+            if self.decision == Decision::Abort {
+                return Err("failed to start".into());
+            }
+            self.started = true;
+            Ok(())
+        }
+
+        fn process_copperlist(&self, _msgs: &[&CuMsgMetadata]) -> CuResult<()> {
+            self.copperlists.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn process_error(&self, _taskid: usize, _step: CuTaskState, _error: &CuError) -> Decision {
+            self.decision
+        }
+
+        fn stop(&mut self, _clock: &RobotClock) -> CuResult<()> {
+            self.stopped = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_composite_monitor() {
+        let config = CuConfig::deserialize_ron(
+            r#"( tasks: [], cnx: [], monitors: [(type: "ignore"), (type: "shutdown"), (type: "abort")] )"#,
+        );
+        let mut monitors =
+            <(TestMonitor, TestMonitor, TestMonitor)>::new(&config, &["task"]).unwrap();
+        assert_eq!(monitors.1.decision, Decision::Shutdown);
+        monitors.process_copperlist(&[]).unwrap();
+        assert_eq!(monitors.0.copperlists.load(Ordering::SeqCst), 1);
+        assert_eq!(monitors.2.copperlists.load(Ordering::SeqCst), 1);
+        let error = CuError::from("boom");
+        assert_eq!(
+            monitors.process_error(0, CuTaskState::Process, &error),
+            Decision::Shutdown
+        );
+        let (clock, _) = RobotClock::mock();
+        monitors.stop(&clock).unwrap();
+        assert!(monitors.0.stopped && monitors.1.stopped && monitors.2.stopped);
+        assert_eq!(
+            (monitors.0, monitors.2).process_error(0, CuTaskState::Process, &error),
+            Decision::Abort
+        );
+    }
+
+    #[test]
+    fn test_composite_monitor_start_failure() {
+        let config = CuConfig::deserialize_ron(
+            r#"( tasks: [], cnx: [], monitors: [(type: "ignore"), (type: "abort"), (type: "shutdown")] )"#,
+        );
+        let mut monitors =
+            <(TestMonitor, TestMonitor, TestMonitor)>::new(&config, &["task"]).unwrap();
+        let (clock, _) = RobotClock::mock();
+        assert!(monitors.start(&clock).is_err());
+        // the first one is stopped, the last one is never started.
+        assert!(monitors.0.started && monitors.0.stopped);
+        assert!(!monitors.2.started && !monitors.2.stopped);
+    }
+
     #[test]
     fn test_live_statistics() {
         let mut stats = LiveStatistics::new_unbounded();
@@ -374,6 +590,8 @@ mod tests {
         assert_eq!(stats.percentile(0.90), CuDuration(500));
         assert_eq!(stats.percentile(0.99), CuDuration(500));
         assert_eq!(stats.len(), 4);
+        assert_eq!(stats.count_up_to(CuDuration(200)), 2);
+        assert_eq!(stats.count_up_to(CuDuration(1000)), 4);
         assert_eq!(stats.jitter.len(), 3);
         assert_eq!(stats.jitter_min(), CuDuration(100));
         assert_eq!(stats.jitter_max(), CuDuration(300));
@@ -106,14 +106,40 @@
 //!   should be skipped.
 //! - **`ExecuteByRuntime`**: Indicates that the real implementation should proceed as normal.
 //!
+//! ## Simulation Time: `SimClock`
+//!
+//! Instead of driving a `RobotClockMock` by hand from the callbacks, you can give the runtime a `SimClock`
+//! with `new_with_sim_clock`. The runtime then advances it at the beginning of every iteration and notifies
+//! the callback with `SimStep::NewTime(now)` before any task is executed.
+//!
+//! - **`SimClockMode::FixedStep(dt)`**: the time advances by `dt` at every iteration.
+//! - **`SimClockMode::NextEvent { fallback }`**: the time jumps to the next event registered with `schedule`,
+//!   or by `fallback` if there is none.
+//!
+//! By default the simulation runs as fast as possible, `set_speed(Some(1.0))` paces it on the wall clock
+//! (2.0 is twice faster than real time etc.).
+//! `pause` stops the time: the iterations are skipped until `resume`, or `step(n)` lets n iterations go through.
+//! While paused, `run()` blocks until the clock is resumed or stepped, from another thread through a
+//! `SimClockControl` handle (see `SimClock::control`), for example from the UI of the simulator.
+//!
+//! ```rust,ignore
+//! let sim_clock = SimClock::new(SimClockMode::FixedStep(CuDuration::from(Duration::from_millis(1))));
+//! let mut app = MyApp::new_with_sim_clock(sim_clock, logger, &mut sim_callback)?;
+//! app.sim_clock_mut().unwrap().set_speed(Some(10.0)); // 10x faster than real time
+//! ```
+//!
 
 use crate::config::ComponentConfig;
 
 use crate::cutask::{CuMsg, CuMsgPack, CuMsgPayload, CuSinkTask, CuSrcTask, Freezable};
 use crate::{input_msg, output_msg};
-use cu29_clock::RobotClock;
+use cu29_clock::{CuDuration, CuTime, RobotClock, RobotClockMock};
 use cu29_traits::CuResult;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
 /// This is the state that will be passed to the simulation support to hook
 /// into the lifecycle of the tasks.
@@ -200,3 +226,266 @@ impl<'cl, T: CuMsgPayload + 'cl> CuSinkTask<'cl> for CuSimSinkTask<T> {
         unimplemented!("A placeholder for sim was called for a sink, you need answer SimOverride to ExecutedBySim for the Process step.")
     }
 }
+
+/// How the SimClock advances at each iteration of the runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimClockMode {
+    /// Advances by a fixed timestep.
+    FixedStep(CuDuration),
+    /// Jumps to the next scheduled event, or by fallback if nothing is scheduled.
+    NextEvent { fallback: CuDuration },
+}
+
+#[derive(Default)]
+struct PauseState {
+    paused: bool,
+    pending_steps: u64,
+    /// Set by resume so the clock restarts its pacing from the current wall clock time.
+    resumed: bool,
+}
+
+/// A handle to pause, resume or step a SimClock, it can be cloned and used from any thread.
+#[derive(Clone, Default)]
+pub struct SimClockControl {
+    state: Arc<(Mutex<PauseState>, Condvar)>,
+}
+
+impl SimClockControl {
+    pub fn pause(&self) {
+        self.state.0.lock().unwrap().paused = true;
+    }
+
+    pub fn resume(&self) {
+        let (state, changed) = &*self.state;
+        let mut state = state.lock().unwrap();
+        state.paused = false;
+        state.pending_steps = 0;
+        state.resumed = true;
+        changed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.0.lock().unwrap().paused
+    }
+
+    /// While paused, lets the next n iterations go through.
+    pub fn step(&self, n: u64) {
+        let (state, changed) = &*self.state;
+        state.lock().unwrap().pending_steps += n;
+        changed.notify_all();
+    }
+
+    /// Blocks while the clock is paused without any step to go through.
+    pub fn wait_until_running(&self) {
+        let (state, changed) = &*self.state;
+        let _running = changed
+            .wait_while(state.lock().unwrap(), |state| {
+                state.paused && state.pending_steps == 0
+            })
+            .unwrap();
+    }
+}
+
+/// A simulation clock owned and advanced by the runtime, see the module documentation.
+pub struct SimClock {
+    clock: RobotClock,
+    mock: RobotClockMock,
+    mode: SimClockMode,
+    /// None: as fast as possible, Some(factor): paced on the wall clock.
+    speed: Option<f64>,
+    control: SimClockControl,
+    events: BinaryHeap<Reverse<CuTime>>,
+    /// Matching wall clock and sim times used for the pacing.
+    wall_ref: Option<(Instant, CuTime)>,
+}
+
+impl SimClock {
+    pub fn new(mode: SimClockMode) -> Self {
+        let (clock, mock) = RobotClock::mock();
+        Self {
+            clock,
+            mock,
+            mode,
+            speed: None,
+            control: SimClockControl::default(),
+            events: BinaryHeap::new(),
+            wall_ref: None,
+        }
+    }
+
+    /// The clock to give to the runtime and the tasks.
+    pub fn clock(&self) -> RobotClock {
+        self.clock.clone()
+    }
+
+    pub fn now(&self) -> CuTime {
+        self.mock.now()
+    }
+
+    pub fn mode(&self) -> SimClockMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: SimClockMode) {
+        self.mode = mode;
+    }
+
+    /// None runs the simulation as fast as possible, Some(factor) paces it at factor x real time.
+    pub fn set_speed(&mut self, speed: Option<f64>) {
+        self.speed = speed.filter(|s| *s > 0.0);
+        self.wall_ref = None;
+    }
+
+    pub fn speed(&self) -> Option<f64> {
+        self.speed
+    }
+
+    /// A handle to pause, resume or step this clock from another thread.
+    pub fn control(&self) -> SimClockControl {
+        self.control.clone()
+    }
+
+    pub fn pause(&mut self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.control.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    /// While paused, lets the next n iterations go through.
+    pub fn step(&mut self, n: u64) {
+        self.control.step(n);
+    }
+
+    /// Blocks while the clock is paused without any step to go through, see SimClockControl.
+    pub fn wait_until_running(&self) {
+        self.control.wait_until_running();
+    }
+
+    /// Registers a time the clock will stop at in the NextEvent mode. The times in the past are ignored.
+    pub fn schedule(&mut self, at: CuTime) {
+        if at > self.now() {
+            self.events.push(Reverse(at));
+        }
+    }
+
+    /// Advances the clock for a new iteration and returns the new time.
+    /// Returns None if the clock is paused, the iteration should then be skipped.
+    pub fn advance(&mut self) -> Option<CuTime> {
+        let paused = {
+            let mut state = self.control.state.0.lock().unwrap();
+            if state.paused {
+                if state.pending_steps == 0 {
+                    return None;
+                }
+                state.pending_steps -= 1;
+            }
+            if state.resumed {
+                state.resumed = false;
+                self.wall_ref = None;
+            }
+            state.paused
+        };
+        let now = self.now();
+        let next = match self.mode {
+            SimClockMode::FixedStep(dt) => now + dt,
+            SimClockMode::NextEvent { fallback } => {
+                // drop the events we already went past
+                while matches!(self.events.peek(), Some(Reverse(t)) if *t <= now) {
+                    self.events.pop();
+                }
+                match self.events.pop() {
+                    Some(Reverse(t)) => t,
+                    None => now + fallback,
+                }
+            }
+        };
+        if !paused {
+            // stepping is not paced.
+            self.pace(next);
+        }
+        self.mock.set_value(next.0);
+        Some(next)
+    }
+
+    /// Waits until the wall clock catches up with the sim time at the configured speed.
+    fn pace(&mut self, next: CuTime) {
+        let Some(speed) = self.speed else {
+            return;
+        };
+        let (wall_start, sim_start) = *self.wall_ref.get_or_insert((Instant::now(), self.now()));
+        let sim_elapsed = (next.0 - sim_start.0) as f64 / speed;
+        let deadline = wall_start + Duration::from_nanos(sim_elapsed as u64);
+        let wall_now = Instant::now();
+        if deadline > wall_now {
+            std::thread::sleep(deadline - wall_now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sim_clock_modes() {
+        let mut sim_clock = SimClock::new(SimClockMode::FixedStep(CuDuration(10)));
+        let clock = sim_clock.clock();
+        assert_eq!(sim_clock.advance(), Some(CuDuration(10)));
+        assert_eq!(sim_clock.advance(), Some(CuDuration(20)));
+        assert_eq!(clock.now(), CuDuration(20));
+
+        sim_clock.set_mode(SimClockMode::NextEvent {
+            fallback: CuDuration(100),
+        });
+        sim_clock.schedule(CuDuration(50));
+        sim_clock.schedule(CuDuration(30));
+        sim_clock.schedule(CuDuration(5)); // in the past
+        assert_eq!(sim_clock.advance(), Some(CuDuration(30)));
+        assert_eq!(sim_clock.advance(), Some(CuDuration(50)));
+        assert_eq!(sim_clock.advance(), Some(CuDuration(150)));
+        assert_eq!(clock.now(), CuDuration(150));
+    }
+
+    #[test]
+    fn test_sim_clock_pause_and_speed() {
+        let mut sim_clock = SimClock::new(SimClockMode::FixedStep(CuDuration(1_000_000)));
+        sim_clock.pause();
+        assert_eq!(sim_clock.advance(), None);
+        sim_clock.step(2);
+        assert_eq!(sim_clock.advance(), Some(CuDuration(1_000_000)));
+        assert_eq!(sim_clock.advance(), Some(CuDuration(2_000_000)));
+        assert_eq!(sim_clock.advance(), None);
+        sim_clock.resume();
+
+        // 10 x 1ms at 2x real time should take around 5ms.
+        sim_clock.set_speed(Some(2.0));
+        let start = Instant::now();
+        for _ in 0..10 {
+            sim_clock.advance();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        assert_eq!(sim_clock.now(), CuDuration(12_000_000));
+    }
+
+    #[test]
+    fn test_sim_clock_control_from_another_thread() {
+        let sim_clock = SimClock::new(SimClockMode::FixedStep(CuDuration(1)));
+        let control = sim_clock.control();
+        control.pause();
+        assert!(sim_clock.is_paused());
+        let waiter = {
+            let control = sim_clock.control();
+            std::thread::spawn(move || control.wait_until_running())
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!waiter.is_finished());
+        control.step(1);
+        waiter.join().unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+//! Helpers to unit test tasks, enabled by the "test-utils" feature.
+
+use crate::cutask::{CuMsg, CuMsgMetadata, CuMsgPayload, Freezable};
+use bincode::de::read::SliceReader;
+use bincode::de::DecoderImpl;
+use bincode::enc::Encoder;
+use bincode::error::EncodeError;
+use bincode::Encode;
+use cu29_clock::{CuTime, OptionCuTime, PartialCuTimeRange, Tov};
+
+struct Frozen<'a, T: Freezable>(&'a T);
+
+impl<T: Freezable> Encode for Frozen<'_, T> {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.0.freeze(encoder)
+    }
+}
+
+/// Restores the frozen state of a task into another one like a resim would.
+pub fn refreeze<T: Freezable>(from: &T, to: &mut T) {
+    let config = bincode::config::standard();
+    let frozen = bincode::encode_to_vec(Frozen(from), config).expect("Could not freeze the task");
+    let mut decoder = DecoderImpl::new(SliceReader::new(&frozen), config);
+    to.thaw(&mut decoder).expect("Could not thaw the task");
+}
+
+/// A message with the given payload and time of validity, to feed a task.
+pub fn msg_at<P: CuMsgPayload>(tov: CuTime, payload: P) -> CuMsg<P> {
+    let mut msg = CuMsg::new(Some(payload));
+    msg.metadata.tov = Tov::Time(tov);
+    msg
+}
+
+/// The metadata of a message processed between start and end, to feed a monitor.
+pub fn process_metadata(start: CuTime, end: CuTime) -> CuMsgMetadata {
+    CuMsgMetadata {
+        process_time: PartialCuTimeRange {
+            start: OptionCuTime::from(start),
+            end: OptionCuTime::from(end),
+        },
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::de::Decoder;
+    use bincode::error::DecodeError;
+    use bincode::Decode;
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    impl Freezable for Counter {
+        fn freeze<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+            self.0.encode(encoder)
+        }
+
+        fn thaw<D: Decoder>(&mut self, decoder: &mut D) -> Result<(), DecodeError> {
+            self.0 = Decode::decode(decoder)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_refreeze() {
+        let mut restored = Counter::default();
+        refreeze(&Counter(42), &mut restored);
+        assert_eq!(restored.0, 42);
+    }
+}
@@ -65,6 +65,43 @@ use syn::{parse_macro_input, Data, DeriveInput, Fields, Type};
 ///    (a + 1, b + 1.0)
 /// });
 /// ```
+///
+/// The SoA has a capacity of N and tracks how many rows are used in `len`:
+/// ```ignore
+/// let mut soa2: MyStructSoa<8> = MyStructSoa::default();
+/// soa2.push(MyStruct{ a: 1, b: 2.3 });
+/// soa2.truncate(0);
+/// soa2.clear();
+/// ```
+///
+/// The used rows can be read and modified in place through references to their fields:
+/// ```ignore
+/// for row in soa1.iter_mut() {
+///     *row.a += 1;
+/// }
+/// *soa1.row_mut(0).b = 42.0;
+/// let a = *soa1.row(0).a;
+/// ```
+///
+/// And sorted in place by any key computed from a row:
+/// ```ignore
+/// soa1.sort_by_key(|row| *row.a);
+/// ```
+///
+/// For SIMD, the used rows of a field can be read or modified by lanes of L values,
+/// followed by the remainder which doesn't fill a lane:
+/// ```ignore
+/// let (lanes, remainder) = soa1.a_lanes::<4>();
+/// let sum: i32 = lanes.map(|lane| lane.iter().sum::<i32>()).sum::<i32>() + remainder.iter().sum::<i32>();
+///
+/// let (lanes, remainder) = soa1.b_lanes_mut::<4>();
+/// for lane in lanes {
+///     lane.iter_mut().for_each(|b| *b *= 2.0);
+/// }
+/// remainder.iter_mut().for_each(|b| *b *= 2.0);
+/// ```
+///
+/// Only the used rows are encoded by bincode.
 #[proc_macro_derive(Soa)]
 pub fn derive_soa(input: TokenStream) -> TokenStream {
     use syn::TypePath;
@@ -89,6 +126,8 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
     let mut field_names_mut = vec![];
     let mut field_names_range = vec![];
     let mut field_names_range_mut = vec![];
+    let mut field_names_lanes = vec![];
+    let mut field_names_lanes_mut = vec![];
     let mut field_types = vec![];
     let mut unique_imports = vec![];
     let mut unique_import_names = vec![];
@@ -122,6 +161,8 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
         field_names_mut.push(format_ident!("{}_mut", field_name));
         field_names_range.push(format_ident!("{}_range", field_name));
         field_names_range_mut.push(format_ident!("{}_range_mut", field_name));
+        field_names_lanes.push(format_ident!("{}_lanes", field_name));
+        field_names_lanes_mut.push(format_ident!("{}_lanes_mut", field_name));
         field_types.push(field_type);
 
         if let Type::Path(TypePath { path, .. }) = field_type {
@@ -136,6 +177,9 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
     }
 
     let soa_struct_name_iterator = format_ident!("{}Iterator", name);
+    let soa_struct_name_iterator_mut = format_ident!("{}IteratorMut", name);
+    let ref_name = format_ident!("{}Ref", name);
+    let ref_mut_name = format_ident!("{}RefMut", name);
 
     let iterator = quote! {
         pub struct #soa_struct_name_iterator<'a, const N: usize> {
@@ -165,6 +209,32 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
                 }
             }
         }
+
+        pub struct #soa_struct_name_iterator_mut<'a> {
+            #( #field_names: core::slice::IterMut<'a, #field_types>, )*
+        }
+
+        impl<'a> Iterator for #soa_struct_name_iterator_mut<'a> {
+            type Item = #ref_mut_name<'a>;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                Some(#ref_mut_name {
+                    #( #field_names: self.#field_names.next()?, )*
+                })
+            }
+        }
+    };
+
+    let refs = quote! {
+        /// A row of the SoA, borrowed.
+        pub struct #ref_name<'a> {
+            #( pub #field_names: &'a #field_types, )*
+        }
+
+        /// A row of the SoA, mutably borrowed.
+        pub struct #ref_mut_name<'a> {
+            #( pub #field_names: &'a mut #field_types, )*
+        }
     };
 
     let expanded = quote! {
@@ -199,6 +269,21 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
                     self.len == 0
                 }
 
+                pub fn capacity(&self) -> usize {
+                    N
+                }
+
+                pub fn clear(&mut self) {
+                    self.len = 0;
+                }
+
+                /// Keeps only the first len rows, it has no effect if len is greater than the current one.
+                pub fn truncate(&mut self, len: usize) {
+                    if len < self.len {
+                        self.len = len;
+                    }
+                }
+
                 pub fn push(&mut self, value: super::#name) {
                     if self.len < N {
                         #( self.#field_names[self.len] = value.#field_names.clone(); )*
@@ -231,6 +316,57 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
                     }
                 }
 
+                pub fn row(&self, index: usize) -> #ref_name<'_> {
+                    assert!(index < self.len, "Index out of bounds");
+                    #ref_name {
+                        #( #field_names: &self.#field_names[index], )*
+                    }
+                }
+
+                pub fn row_mut(&mut self, index: usize) -> #ref_mut_name<'_> {
+                    assert!(index < self.len, "Index out of bounds");
+                    #ref_mut_name {
+                        #( #field_names: &mut self.#field_names[index], )*
+                    }
+                }
+
+                pub fn swap(&mut self, a: usize, b: usize) {
+                    assert!(a < self.len && b < self.len, "Index out of bounds");
+                    #( self.#field_names.swap(a, b); )*
+                }
+
+                /// Sorts the used rows in place by the key computed from each row.
+                /// The sort is not stable and doesn't allocate (heapsort).
+                pub fn sort_by_key<K, F>(&mut self, mut f: F)
+                where
+                    K: PartialOrd,
+                    F: FnMut(#ref_name<'_>) -> K,
+                {
+                    let len = self.len;
+                    let mut sift_down = |soa: &mut Self, mut root: usize, end: usize| loop {
+                        let mut child = 2 * root + 1;
+                        if child >= end {
+                            break;
+                        }
+                        if child + 1 < end && f(soa.row(child)) < f(soa.row(child + 1)) {
+                            child += 1;
+                        }
+                        if f(soa.row(root)) < f(soa.row(child)) {
+                            soa.swap(root, child);
+                            root = child;
+                        } else {
+                            break;
+                        }
+                    };
+                    for start in (0..len / 2).rev() {
+                        sift_down(self, start, len);
+                    }
+                    for end in (1..len).rev() {
+                        self.swap(0, end);
+                        sift_down(self, 0, end);
+                    }
+                }
+
                 pub fn apply<F>(&mut self, mut f: F)
                 where
                     F: FnMut(#(#field_types),*) -> (#(#field_types),*)
@@ -249,6 +385,13 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
                     #soa_struct_name_iterator::new(self)
                 }
 
+                pub fn iter_mut(&mut self) -> #soa_struct_name_iterator_mut<'_> {
+                    let len = self.len;
+                    #soa_struct_name_iterator_mut {
+                        #( #field_names: self.#field_names[..len].iter_mut(), )*
+                    }
+                }
+
                 #(
                     pub fn #field_names(&self) -> &[#field_types] {
                         &self.#field_names
@@ -265,13 +408,35 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
                     pub fn #field_names_range_mut(&mut self, range: std::ops::Range<usize>) -> &mut [#field_types] {
                         &mut self.#field_names[range]
                     }
+
+                    /// The used rows of the field by lanes of L values, and the remainder shorter than a lane.
+                    pub fn #field_names_lanes<const L: usize>(
+                        &self,
+                    ) -> (impl Iterator<Item = &[#field_types; L]> + '_, &[#field_types]) {
+                        let chunks = self.#field_names[..self.len].chunks_exact(L);
+                        let remainder = chunks.remainder();
+                        (chunks.map(|lane| lane.try_into().unwrap()), remainder)
+                    }
+
+                    /// The used rows of the field by mutable lanes of L values, and the remainder shorter than a lane.
+                    pub fn #field_names_lanes_mut<const L: usize>(
+                        &mut self,
+                    ) -> (impl Iterator<Item = &mut [#field_types; L]> + '_, &mut [#field_types]) {
+                        let len = self.len;
+                        let (lanes, remainder) = self.#field_names[..len].split_at_mut(len - len % L);
+                        (lanes.chunks_exact_mut(L).map(|lane| lane.try_into().unwrap()), remainder)
+                    }
                 )*
             }
 
             impl<const N: usize> Encode for #soa_struct_name<N> {
                 fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
                     self.len.encode(encoder)?;
-                    #( self.#field_names[..self.len].encode(encoder)?; )*
+                    #(
+                        for _idx in 0..self.len {
+                            self.#field_names[_idx].encode(encoder)?;
+                        }
+                    )*
                     Ok(())
                 }
             }
@@ -280,6 +445,12 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
                 fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
                     let mut result = Self::default();
                     result.len = Decode::decode(decoder)?;
+                    if result.len > N {
+                        return Err(DecodeError::ArrayLengthMismatch {
+                            required: N,
+                            found: result.len,
+                        });
+                    }
                     #(
                         for _idx in 0..result.len {
                             result.#field_names[_idx] = Decode::decode(decoder)?;
@@ -314,6 +485,8 @@ pub fn derive_soa(input: TokenStream) -> TokenStream {
 
             #iterator
 
+            #refs
+
         }
         #visibility use #module_name::#soa_struct_name;
     };
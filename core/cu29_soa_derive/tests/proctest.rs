@@ -98,6 +98,123 @@ mod tests {
         assert_eq!(distances[2], 0.0);
     }
 
+    fn xyz(x: f32, i: i32) -> Xyz {
+        Xyz {
+            x,
+            y: 0.0,
+            z: 0.0,
+            i,
+        }
+    }
+
+    #[test]
+    fn test_variable_length() {
+        let mut soa = XyzSoa::<4>::default();
+        assert!(soa.is_empty());
+        assert_eq!(soa.capacity(), 4);
+        for i in 0..3 {
+            soa.push(xyz(i as f32, i));
+        }
+        assert_eq!(soa.len(), 3);
+        soa.truncate(5);
+        assert_eq!(soa.len(), 3);
+        soa.truncate(2);
+        assert_eq!(soa.iter().map(|p| p.i).collect::<Vec<_>>(), vec![0, 1]);
+        soa.clear();
+        assert!(soa.is_empty());
+        assert_eq!(soa.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut soa = XyzSoa::<4>::default();
+        soa.push(xyz(1.0, 1));
+        soa.push(xyz(2.0, 2));
+        for row in soa.iter_mut() {
+            *row.x *= 10.0;
+            *row.i += 1;
+        }
+        // only the used rows are touched.
+        assert_eq!(soa.x(), &[10.0, 20.0, 0.0, 0.0]);
+        *soa.row_mut(1).y = 5.0;
+        assert_eq!(*soa.row(1).y, 5.0);
+        soa.set(0, xyz(7.0, 7));
+        assert_eq!(soa.get(0), xyz(7.0, 7));
+        assert_eq!(
+            soa.get(1),
+            Xyz {
+                x: 20.0,
+                y: 5.0,
+                z: 0.0,
+                i: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_sort_by_key() {
+        let mut soa = XyzSoa::<16>::default();
+        let values = [5, 3, 9, 1, 3, 7, 0, 8, 2];
+        for i in values {
+            soa.push(xyz(i as f32 / 10.0, i));
+        }
+        soa.sort_by_key(|row| *row.i);
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        assert_eq!(soa.iter().map(|p| p.i).collect::<Vec<_>>(), sorted);
+        // all the fields moved with the key.
+        assert!(soa.iter().all(|p| p.x == p.i as f32 / 10.0));
+
+        soa.sort_by_key(|row| -*row.x);
+        assert_eq!(soa.get(0).i, 9);
+        assert_eq!(soa.get(soa.len() - 1).i, 0);
+    }
+
+    #[test]
+    fn test_encode_used_length() {
+        let config = bincode::config::standard();
+        let mut soa = XyzSoa::<64>::default();
+        soa.push(xyz(1.0, 1));
+        soa.push(xyz(2.0, 2));
+        let encoded = bincode::encode_to_vec(&soa, config).unwrap();
+        // len + 2 rows of 3 f32 and a small i32.
+        assert_eq!(encoded.len(), 1 + 2 * (3 * 4 + 1));
+        let (decoded, _): (XyzSoa<64>, usize) =
+            bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded.get(1), xyz(2.0, 2));
+
+        // does not fit.
+        assert!(bincode::decode_from_slice::<XyzSoa<1>, _>(&encoded, config).is_err());
+    }
+
+    #[test]
+    fn test_lanes() {
+        let mut soa = XyzSoa::<16>::default();
+        for i in 0..10 {
+            soa.push(xyz(i as f32, i));
+        }
+        let (lanes, remainder) = soa.i_lanes::<4>();
+        let lanes: Vec<_> = lanes.collect();
+        assert_eq!(lanes, [&[0, 1, 2, 3], &[4, 5, 6, 7]]);
+        assert_eq!(remainder, &[8, 9]);
+
+        let (lanes, remainder) = soa.x_lanes_mut::<4>();
+        for lane in lanes {
+            lane.iter_mut().for_each(|x| *x *= 2.0);
+        }
+        remainder.iter_mut().for_each(|x| *x = -1.0);
+        assert_eq!(soa.x()[..8], [0.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 14.0]);
+        assert_eq!(soa.x()[8..10], [-1.0, -1.0]);
+        // the unused rows are not part of the lanes.
+        assert_eq!(soa.x()[10], 0.0);
+
+        let (lanes, remainder) = soa.y_lanes::<16>();
+        assert_eq!(lanes.count(), 0);
+        assert_eq!(remainder.len(), 10);
+    }
+
+    #[allow(dead_code)]
     #[derive(Debug, Clone, Default, PartialEq, Soa, Encode, Decode)]
     pub struct Color {
         r: f32,
@@ -105,6 +222,7 @@ mod tests {
         b: f32,
     }
 
+    #[allow(dead_code)]
     #[derive(Debug, Default, PartialEq, Soa)]
     pub struct Both {
         xyz: Xyz,
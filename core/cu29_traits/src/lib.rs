@@ -73,6 +73,7 @@ pub enum UnifiedLogType {
     StructuredLogLine, // This is for the structured logs (ie. debug! etc..)
     CopperList,        // This is the actual data log storing activities between tasks.
     LastEntry,         // This is a special entry that is used to signal the end of the log.
+    StringIndex, // The interned strings of the structured logs, to decode them without the build time index.
 }
 
 /// A CopperListTuple needs to be encodable, decodable and fixed size in memory.
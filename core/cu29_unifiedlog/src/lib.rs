@@ -5,8 +5,12 @@ use std::io::Read;
 use std::mem::ManuallyDrop;
 use std::path::{Path, PathBuf};
 use std::slice::from_raw_parts_mut;
+use std::sync::mpsc::{
+    channel, sync_channel, Receiver, SendError, Sender, SyncSender, TrySendError,
+};
 use std::sync::{Arc, Mutex};
-use std::{io, mem};
+use std::thread::JoinHandle;
+use std::{io, mem, thread};
 
 use bincode::config::standard;
 use bincode::decode_from_slice;
@@ -15,16 +19,75 @@ use bincode::error::EncodeError;
 use bincode::{Decode, Encode};
 use cu29_traits::{CuError, CuResult, UnifiedLogType, WriteStream};
 
+mod network;
+use network::RemoteSender;
+pub use network::{RemoteLogReader, RemoteLogTarget};
+
 const MAIN_MAGIC: [u8; 4] = [0xB4, 0xA5, 0x50, 0xFF];
 
 const SECTION_MAGIC: [u8; 2] = [0xFA, 0x57];
 
+/// The version of the layout written by this logger, it is bumped at each incompatible change.
+/// 0: the logs written before the version was introduced, their section headers have no compression.
+/// 1: the section headers have a compression codec and the uncompressed size.
+/// 2: every slab starts with the main header, only the first one did before.
+const FORMAT_VERSION: u16 = 2;
+
 /// The main file header of the datalogger.
 #[derive(Encode, Decode, Debug)]
 struct MainHeader {
     magic: [u8; 4],            // Magic number to identify the file.
     first_section_offset: u16, // This is to align with a page at write time.
     page_size: u16,
+    version: u16, // FORMAT_VERSION at write time, it decodes as 0 from the zeroed page of the older logs.
+}
+
+/// The codec used to compress the content of a section.
+#[derive(Encode, Decode, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+    /// Needs the `zstd` feature to write or read.
+    Zstd,
+}
+
+impl Compression {
+    fn compress(&self, data: &[u8]) -> CuResult<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Lz4 => Ok(lz4_flex::compress(data)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::bulk::compress(data, 0)
+                .map_err(|e| CuError::new_with_cause("Failed to compress a section", e)),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err("zstd compression needs the zstd feature enabled".into()),
+        }
+    }
+
+    fn decompress(&self, data: &[u8], uncompressed_size: usize) -> CuResult<Vec<u8>> {
+        let decompressed = match self {
+            Compression::None => data.to_vec(),
+            Compression::Lz4 => lz4_flex::decompress(data, uncompressed_size)
+                .map_err(|e| CuError::new_with_cause("Failed to decompress a section", e))?,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::bulk::decompress(data, uncompressed_size)
+                .map_err(|e| CuError::new_with_cause("Failed to decompress a section", e))?,
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => {
+                return Err("zstd decompression needs the zstd feature enabled".into())
+            }
+        };
+        if decompressed.len() != uncompressed_size {
+            return Err(format!(
+                "Corrupted section: decompressed {} bytes instead of {}",
+                decompressed.len(),
+                uncompressed_size
+            )
+            .into());
+        }
+        Ok(decompressed)
+    }
 }
 
 /// Each concurrent sublogger is tracked through a section header.
@@ -36,10 +99,36 @@ pub struct SectionHeader {
     entry_type: UnifiedLogType,
     section_size: u32, // offset from the first byte of this header to the first byte of the next header (MAGIC to MAGIC).
     filled_size: u32,  // how much of the section is filled.
+    compression: Compression, // codec of the filled part of the section.
+    uncompressed_size: u32, // size of the filled part once decompressed, equals filled_size if not compressed.
 }
 
 const MAX_HEADER_SIZE: usize = mem::size_of::<SectionHeader>() + 3usize; // 3 == additional worse case scenario for the 3 int variable encoding
 
+/// The section header of the logs in version 0, before the compression was added.
+#[derive(Encode, Decode, Debug)]
+struct SectionHeaderV0 {
+    magic: [u8; 2],
+    entry_type: UnifiedLogType,
+    section_size: u32,
+    filled_size: u32,
+}
+
+const MAX_HEADER_SIZE_V0: usize = mem::size_of::<SectionHeaderV0>() + 3usize;
+
+impl From<SectionHeaderV0> for SectionHeader {
+    fn from(header: SectionHeaderV0) -> Self {
+        Self {
+            magic: header.magic,
+            entry_type: header.entry_type,
+            section_size: header.section_size,
+            filled_size: header.filled_size,
+            compression: Compression::None,
+            uncompressed_size: header.filled_size,
+        }
+    }
+}
+
 impl Default for SectionHeader {
     fn default() -> Self {
         Self {
@@ -47,6 +136,8 @@ impl Default for SectionHeader {
             entry_type: UnifiedLogType::Empty,
             section_size: 0,
             filled_size: 0,
+            compression: Compression::None,
+            uncompressed_size: 0,
         }
     }
 }
@@ -133,13 +224,409 @@ impl Drop for MmapStream {
     }
 }
 
+/// Writes a block of encoded entries as one section, compressed with the given codec.
+/// Falls back to storing it raw if the codec does not make it smaller.
+fn write_compressed_section(
+    logger: &Mutex<UnifiedLoggerWrite>,
+    entry_type: UnifiedLogType,
+    compression: Compression,
+    data: &[u8],
+) {
+    let (compression, payload) = match compression.compress(data) {
+        Ok(compressed) if compressed.len() < data.len() => (compression, compressed),
+        Ok(_) => (Compression::None, data.to_vec()),
+        Err(e) => {
+            eprintln!("Warning: storing a section uncompressed: {e}");
+            (Compression::None, data.to_vec())
+        }
+    };
+    let mut logger_guard = logger.lock().unwrap();
+    let mut section = logger_guard.add_section(entry_type, payload.len() + MAX_HEADER_SIZE);
+    section.get_user_buffer()[..payload.len()].copy_from_slice(&payload);
+    section.used = payload.len() as u32;
+    section.section_header.compression = compression;
+    section.section_header.uncompressed_size = data.len() as u32;
+    logger_guard.flush_section(&mut section);
+}
+
+/// How a BackgroundStream hands over its buffers to its worker thread.
+enum ToWorker {
+    /// Never lose anything, the queue grows if the worker is late.
+    Lossless(Sender<Vec<u8>>),
+    /// Bounded queue, the buffers are dropped if the worker is late so the task thread never waits.
+    Lossy(SyncSender<Vec<u8>>),
+}
+
+/// A stream accumulating the encoded entries in memory and handing them over by blocks to a background thread,
+/// so the heavy lifting (compression, network) is not done on the task thread.
+/// Note: unlike the MmapStream, what is still in memory is lost if the process crashes.
+struct BackgroundStream {
+    entry_type: UnifiedLogType,
+    buffer: Vec<u8>,
+    used: usize,
+    to_worker: Option<ToWorker>,
+    recycled: Mutex<Receiver<Vec<u8>>>,
+    worker: Option<JoinHandle<()>>,
+    dropped_blocks: usize,
+}
+
+impl BackgroundStream {
+    /// The worker is called with every block of encoded entries, in order.
+    fn new(
+        entry_type: UnifiedLogType,
+        block_size: usize,
+        thread_name: String,
+        lossy: bool,
+        mut worker: impl FnMut(&[u8]) + Send + 'static,
+    ) -> Self {
+        let (to_worker, from_stream) = if lossy {
+            let (tx, rx) = sync_channel::<Vec<u8>>(4);
+            (ToWorker::Lossy(tx), rx)
+        } else {
+            let (tx, rx) = channel::<Vec<u8>>();
+            (ToWorker::Lossless(tx), rx)
+        };
+        let (to_stream, recycled) = channel::<Vec<u8>>();
+        let worker = thread::Builder::new()
+            .name(thread_name)
+            .spawn(move || {
+                for buffer in from_stream {
+                    worker(&buffer);
+                    // the stream might be gone already, this is fine.
+                    let _ = to_stream.send(buffer);
+                }
+            })
+            .expect("Failed to spawn a log stream thread");
+        Self {
+            entry_type,
+            buffer: vec![0u8; block_size],
+            used: 0,
+            to_worker: Some(to_worker),
+            recycled: Mutex::new(recycled),
+            worker: Some(worker),
+            dropped_blocks: 0,
+        }
+    }
+
+    /// Hands over the current buffer to the worker thread.
+    fn send_buffer(&mut self) -> CuResult<()> {
+        if self.used == 0 {
+            return Ok(());
+        }
+        let capacity = self.buffer.len();
+        let mut next = match self.recycled.lock().unwrap().try_recv() {
+            Ok(buffer) => buffer,
+            Err(_) => Vec::with_capacity(capacity),
+        };
+        next.resize(capacity, 0);
+        let mut full = mem::replace(&mut self.buffer, next);
+        full.truncate(self.used);
+        self.used = 0;
+        match self.to_worker.as_ref().unwrap() {
+            ToWorker::Lossless(tx) => tx
+                .send(full)
+                .map_err(|e| CuError::new_with_cause("The log stream thread is gone", e)),
+            ToWorker::Lossy(tx) => match tx.try_send(full) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) => {
+                    self.dropped_blocks += 1;
+                    Ok(())
+                }
+                Err(e) => Err(CuError::new_with_cause("The log stream thread is gone", e)),
+            },
+        }
+    }
+}
+
+impl Debug for BackgroundStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "BackgroundStream {{ entry_type: {:?}, used: {}, capacity: {}, dropped_blocks: {} }}",
+            self.entry_type,
+            self.used,
+            self.buffer.len(),
+            self.dropped_blocks
+        )
+    }
+}
+
+impl<E: Encode> WriteStream<E> for BackgroundStream {
+    fn log(&mut self, obj: &E) -> CuResult<()> {
+        match encode_into_slice(obj, &mut self.buffer[self.used..], standard()) {
+            Ok(nb_bytes) => {
+                self.used += nb_bytes;
+                Ok(())
+            }
+            Err(EncodeError::UnexpectedEnd) => {
+                self.send_buffer()?;
+                let nb_bytes = encode_into_slice(obj, &mut self.buffer[..], standard())
+                    .map_err(|e| {
+                        CuError::new_with_cause(
+                            "Failed to encode an object in an empty block, is it larger than the block size?",
+                            e,
+                        )
+                    })?;
+                self.used += nb_bytes;
+                Ok(())
+            }
+            Err(e) => Err(
+                <&str as Into<CuError>>::into("Unexpected error while encoding object.")
+                    .add_cause(e.to_string().as_str()),
+            ),
+        }
+    }
+
+    fn flush(&mut self) -> CuResult<()> {
+        self.send_buffer()
+    }
+}
+
+impl Drop for BackgroundStream {
+    fn drop(&mut self) {
+        if let Err(e) = self.send_buffer() {
+            eprintln!("Error: could not write the last log block: {e}");
+        }
+        drop(self.to_worker.take()); // this ends the worker loop.
+        if let Some(worker) = self.worker.take() {
+            worker.join().expect("A log stream thread panicked");
+        }
+    }
+}
+
+/// A stream writing in memory mapped sections like the MmapStream, but its next section is allocated
+/// ahead of time by a background thread, which also closes the full ones.
+/// So the thread logging in it never takes the logger lock when it moves to a new section.
+struct PreallocatedStream {
+    entry_type: UnifiedLogType,
+    current_section: SectionHandle,
+    spare_sections: Mutex<Receiver<SectionHandle>>,
+    full_sections: Option<Sender<SectionHandle>>,
+    parent_logger: Arc<Mutex<UnifiedLoggerWrite>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl PreallocatedStream {
+    fn new(
+        entry_type: UnifiedLogType,
+        parent_logger: Arc<Mutex<UnifiedLoggerWrite>>,
+        minimum_allocation_amount: usize,
+    ) -> Self {
+        let (current_section, first_spare) = {
+            let mut logger_guard = parent_logger.lock().unwrap();
+            (
+                logger_guard.add_section(entry_type, minimum_allocation_amount),
+                logger_guard.add_section(entry_type, minimum_allocation_amount),
+            )
+        };
+        let (to_stream, spare_sections) = channel::<SectionHandle>();
+        to_stream.send(first_spare).unwrap();
+        let (full_sections, from_stream) = channel::<SectionHandle>();
+        let logger = parent_logger.clone();
+        let worker = thread::Builder::new()
+            .name(format!("cu29_log_{entry_type:?}_allocator"))
+            .spawn(move || {
+                for mut full in from_stream {
+                    let mut logger_guard = logger.lock().unwrap();
+                    logger_guard.flush_section(&mut full);
+                    let spare = logger_guard.add_section(entry_type, minimum_allocation_amount);
+                    if let Err(SendError(mut unused)) = to_stream.send(spare) {
+                        logger_guard.flush_section(&mut unused);
+                    }
+                }
+            })
+            .expect("Failed to spawn a log stream thread");
+        Self {
+            entry_type,
+            current_section,
+            spare_sections: Mutex::new(spare_sections),
+            full_sections: Some(full_sections),
+            parent_logger,
+            worker: Some(worker),
+        }
+    }
+}
+
+impl Debug for PreallocatedStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PreallocatedStream {{ entry_type: {:?}, used: {} }}",
+            self.entry_type, self.current_section.used
+        )
+    }
+}
+
+impl<E: Encode> WriteStream<E> for PreallocatedStream {
+    fn log(&mut self, obj: &E) -> CuResult<()> {
+        match encode_into_slice(obj, self.current_section.get_user_buffer(), standard()) {
+            Ok(nb_bytes) => {
+                self.current_section.used += nb_bytes as u32;
+                Ok(())
+            }
+            Err(EncodeError::UnexpectedEnd) => {
+                // only waits if the allocator thread did not keep up since the last section.
+                let spare =
+                    self.spare_sections.lock().unwrap().recv().map_err(|e| {
+                        CuError::new_with_cause("The log allocator thread is gone", e)
+                    })?;
+                let full = mem::replace(&mut self.current_section, spare);
+                self.full_sections
+                    .as_ref()
+                    .unwrap()
+                    .send(full)
+                    .map_err(|e| CuError::new_with_cause("The log allocator thread is gone", e))?;
+                let nb_bytes =
+                    encode_into_slice(obj, self.current_section.get_user_buffer(), standard())
+                        .map_err(|e| {
+                            CuError::new_with_cause(
+                                "Failed to encode an object in an empty section, is it larger than the section size?",
+                                e,
+                            )
+                        })?;
+                self.current_section.used += nb_bytes as u32;
+                Ok(())
+            }
+            Err(e) => Err(
+                <&str as Into<CuError>>::into("Unexpected error while encoding object.")
+                    .add_cause(e.to_string().as_str()),
+            ),
+        }
+    }
+}
+
+impl Drop for PreallocatedStream {
+    fn drop(&mut self) {
+        drop(self.full_sections.take()); // this ends the worker loop.
+        if let Some(worker) = self.worker.take() {
+            worker.join().expect("A log stream thread panicked");
+        }
+        let mut logger_guard = self.parent_logger.lock().unwrap();
+        logger_guard.flush_section(&mut self.current_section);
+        // the spare sections never used still need to be closed.
+        for mut spare in self.spare_sections.lock().unwrap().try_iter() {
+            logger_guard.flush_section(&mut spare);
+        }
+    }
+}
+
+/// The local side of the streams returned by stream_write depending on the compression set for its type.
+#[derive(Debug)]
+enum LocalStream {
+    Mmap(MmapStream),
+    Preallocated(PreallocatedStream),
+    Compressed(BackgroundStream),
+}
+
+/// The stream returned by stream_write, optionally teed to a remote consumer.
+#[derive(Debug)]
+struct UnifiedLogStream {
+    local: LocalStream,
+    remote: Option<BackgroundStream>,
+}
+
+impl<E: Encode> WriteStream<E> for UnifiedLogStream {
+    fn log(&mut self, obj: &E) -> CuResult<()> {
+        match &mut self.local {
+            LocalStream::Mmap(stream) => stream.log(obj)?,
+            LocalStream::Preallocated(stream) => stream.log(obj)?,
+            LocalStream::Compressed(stream) => stream.log(obj)?,
+        }
+        // the remote side is best effort, it should never prevent the robot from logging on disk.
+        if let Some(remote) = &mut self.remote {
+            if let Err(e) = remote.log(obj) {
+                eprintln!("Warning: could not send a log entry remotely: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> CuResult<()> {
+        match &mut self.local {
+            LocalStream::Mmap(stream) => WriteStream::<E>::flush(stream)?,
+            LocalStream::Preallocated(stream) => WriteStream::<E>::flush(stream)?,
+            LocalStream::Compressed(stream) => WriteStream::<E>::flush(stream)?,
+        }
+        if let Some(remote) = &mut self.remote {
+            WriteStream::<E>::flush(remote)?;
+        }
+        Ok(())
+    }
+}
+
 /// Create a new stream to write to the unifiedlogger.
+/// If a compression has been set for this entry type in the builder, the entries are compressed
+/// by sections of minimum_allocation_amount bytes in a background thread.
+/// If a remote target has been set for this entry type in the builder, the entries are also sent to it
+/// by blocks of minimum_allocation_amount bytes (capped to what fits in a datagram for UDP).
 pub fn stream_write<E: Encode>(
     logger: Arc<Mutex<UnifiedLoggerWrite>>,
     entry_type: UnifiedLogType,
     minimum_allocation_amount: usize,
 ) -> impl WriteStream<E> {
-    MmapStream::new(entry_type, logger.clone(), minimum_allocation_amount)
+    new_stream(logger, entry_type, minimum_allocation_amount, false)
+}
+
+/// Same as stream_write but the next section is always allocated ahead of time by a background thread,
+/// so the thread logging in the stream never waits on the logger lock, even when a section is full.
+/// This is meant for streams written from a hot thread, for example one structured log stream per thread.
+/// It costs a thread and one spare section per stream.
+pub fn stream_write_preallocated<E: Encode>(
+    logger: Arc<Mutex<UnifiedLoggerWrite>>,
+    entry_type: UnifiedLogType,
+    minimum_allocation_amount: usize,
+) -> impl WriteStream<E> {
+    new_stream(logger, entry_type, minimum_allocation_amount, true)
+}
+
+fn new_stream(
+    logger: Arc<Mutex<UnifiedLoggerWrite>>,
+    entry_type: UnifiedLogType,
+    minimum_allocation_amount: usize,
+    preallocated: bool,
+) -> UnifiedLogStream {
+    let (compression, remote_target) = {
+        let logger_guard = logger.lock().unwrap();
+        (
+            logger_guard.compression_for(entry_type),
+            logger_guard.remote_target_for(entry_type),
+        )
+    };
+    let local =
+        match compression {
+            Compression::None if preallocated => LocalStream::Preallocated(
+                PreallocatedStream::new(entry_type, logger.clone(), minimum_allocation_amount),
+            ),
+            Compression::None => LocalStream::Mmap(MmapStream::new(
+                entry_type,
+                logger.clone(),
+                minimum_allocation_amount,
+            )),
+            compression => {
+                let parent_logger = logger.clone();
+                LocalStream::Compressed(BackgroundStream::new(
+                    entry_type,
+                    minimum_allocation_amount,
+                    format!("cu29_log_{entry_type:?}_compression"),
+                    false,
+                    move |block| {
+                        write_compressed_section(&parent_logger, entry_type, compression, block)
+                    },
+                ))
+            }
+        };
+    let remote = remote_target.map(|target| {
+        let block_size = target.max_block_size(minimum_allocation_amount);
+        let mut sender = RemoteSender::new(target);
+        BackgroundStream::new(
+            entry_type,
+            block_size,
+            format!("cu29_log_{entry_type:?}_remote"),
+            true,
+            move |block| sender.send(entry_type, block),
+        )
+    });
+    UnifiedLogStream { local, remote }
 }
 
 /// Holder of the read or write side of the datalogger.
@@ -154,6 +641,10 @@ pub struct UnifiedLoggerBuilder {
     preallocated_size: Option<usize>,
     write: bool,
     create: bool,
+    compressions: Vec<(UnifiedLogType, Compression)>,
+    remote_targets: Vec<(UnifiedLogType, RemoteLogTarget)>,
+    max_slabs: Option<usize>,
+    max_total_size: Option<usize>,
 }
 
 impl Default for UnifiedLoggerBuilder {
@@ -169,6 +660,10 @@ impl UnifiedLoggerBuilder {
             preallocated_size: None,
             write: false,
             create: false, // This is the safest default
+            compressions: Vec::new(),
+            remote_targets: Vec::new(),
+            max_slabs: None,
+            max_total_size: None,
         }
     }
 
@@ -193,14 +688,69 @@ impl UnifiedLoggerBuilder {
         self
     }
 
+    /// Compress the sections of this entry type with the given codec, by default nothing is compressed.
+    /// The compression is done in a background thread per stream, the reader decompresses transparently.
+    pub fn compression(mut self, entry_type: UnifiedLogType, compression: Compression) -> Self {
+        self.compressions.retain(|(t, _)| *t != entry_type);
+        self.compressions.push((entry_type, compression));
+        self
+    }
+
+    /// Also send the entries of this type to a remote consumer, see RemoteLogReader for the other end.
+    /// This is best effort: the blocks are dropped if the network cannot keep up, the disk log is not affected.
+    pub fn remote_target(mut self, entry_type: UnifiedLogType, target: RemoteLogTarget) -> Self {
+        self.remote_targets.retain(|(t, _)| *t != entry_type);
+        self.remote_targets.push((entry_type, target));
+        self
+    }
+
+    /// Retention policy: keep at most this number of slabs on disk, the oldest ones are deleted.
+    /// By default the logger keeps creating slabs until the disk is full.
+    ///
+    /// Note: the slabs still having sections in flight are only deleted once they are closed.
+    /// Note: the sections written at startup (like the embedded string index) are lost with the first slab.
+    pub fn max_slabs(mut self, max_slabs: usize) -> Self {
+        self.max_slabs = Some(max_slabs);
+        self
+    }
+
+    /// Retention policy: keep at most this number of bytes of slabs on disk, the oldest ones are deleted.
+    /// This is rounded down to a number of slabs of preallocated_size, see max_slabs.
+    pub fn max_total_size(mut self, max_total_size: usize) -> Self {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
     pub fn build(self) -> io::Result<UnifiedLogger> {
         let page_size = page_size::get();
 
         if self.write && self.create {
+            let slab_size = self.preallocated_size.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Preallocated size is required")
+            })?;
+            let max_slabs = match (self.max_slabs, self.max_total_size) {
+                (None, None) => None,
+                (max_slabs, max_total_size) => {
+                    let from_size = max_total_size.map(|size| size / slab_size);
+                    let max_slabs = max_slabs
+                        .unwrap_or(usize::MAX)
+                        .min(from_size.unwrap_or(usize::MAX));
+                    if max_slabs == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "The retention policy needs to keep at least one slab",
+                        ));
+                    }
+                    Some(max_slabs)
+                }
+            };
             let ulw = UnifiedLoggerWrite::new(
                 &self.file_base_name.unwrap(),
-                self.preallocated_size.unwrap(),
+                slab_size,
                 page_size,
+                self.compressions,
+                self.remote_targets,
+                max_slabs,
             );
 
             Ok(UnifiedLogger::Write(ulw))
@@ -217,6 +767,7 @@ impl UnifiedLoggerBuilder {
 /// A read side of the datalogger.
 pub struct UnifiedLoggerRead {
     base_file_path: PathBuf,
+    version: u16,
     current_mmap_buffer: Mmap,
     current_file: File,
     current_slab_index: usize,
@@ -224,6 +775,7 @@ pub struct UnifiedLoggerRead {
 }
 
 struct SlabEntry {
+    suffix: usize,
     file: File,
     mmap_buffer: ManuallyDrop<MmapMut>,
     current_global_position: usize,
@@ -252,17 +804,31 @@ pub enum AllocatedSection {
 }
 
 impl SlabEntry {
-    fn new(file: File, page_size: usize) -> Self {
+    fn new(suffix: usize, file: File, page_size: usize) -> Self {
         let mmap_buffer =
             ManuallyDrop::new(unsafe { MmapMut::map_mut(&file).expect("Failed to map file") });
-        Self {
+        let mut slab = Self {
+            suffix,
             file,
             mmap_buffer,
             current_global_position: 0,
             sections_offsets_in_flight: Vec::with_capacity(16),
             flushed_until_offset: 0,
             page_size,
-        }
+        };
+
+        // Every slab starts with the main header so the log can be read from any of them.
+        let main_header = MainHeader {
+            magic: MAIN_MAGIC,
+            first_section_offset: page_size as u16,
+            page_size: page_size as u16,
+            version: FORMAT_VERSION,
+        };
+        let nb_bytes = encode_into_slice(&main_header, &mut slab.mmap_buffer[..], standard())
+            .expect("Failed to encode main header");
+        assert!(nb_bytes < page_size);
+        slab.current_global_position = page_size; // align to the next page
+        slab
     }
 
     /// Unsure the underlying mmap is flush to disk until the given position.
@@ -341,6 +907,8 @@ impl SlabEntry {
             entry_type,
             section_size,
             filled_size: 0u32,
+            compression: Compression::None,
+            uncompressed_size: 0u32,
         };
 
         let nb_bytes = encode_into_slice(
@@ -415,6 +983,9 @@ impl SectionHandle {
             return;
         }
         self.section_header.filled_size = self.used;
+        if self.section_header.compression == Compression::None {
+            self.section_header.uncompressed_size = self.used;
+        }
 
         // FIX ME: This was flushed before and cannot be written back to.
         // let _sz = encode_into_slice(&self.section_header, &mut self.buffer, standard())
@@ -434,6 +1005,14 @@ pub struct UnifiedLoggerWrite {
     slab_size: usize,
     /// current suffix for the backing files.
     front_slab_suffix: usize,
+    /// compression codecs per entry type, the other ones are not compressed.
+    compressions: Vec<(UnifiedLogType, Compression)>,
+    /// remote consumers per entry type.
+    remote_targets: Vec<(UnifiedLogType, RemoteLogTarget)>,
+    /// retention policy, maximum number of slabs to keep on disk.
+    max_slabs: Option<usize>,
+    /// suffix of the oldest slab still on disk.
+    oldest_slab_suffix: usize,
 }
 
 fn build_slab_path(base_file_path: &Path, slab_index: usize) -> PathBuf {
@@ -468,20 +1047,16 @@ impl UnifiedLoggerWrite {
         make_slab_file(&self.base_file_path, self.slab_size, self.front_slab_suffix)
     }
 
-    fn new(base_file_path: &Path, slab_size: usize, page_size: usize) -> Self {
+    fn new(
+        base_file_path: &Path,
+        slab_size: usize,
+        page_size: usize,
+        compressions: Vec<(UnifiedLogType, Compression)>,
+        remote_targets: Vec<(UnifiedLogType, RemoteLogTarget)>,
+        max_slabs: Option<usize>,
+    ) -> Self {
         let file = make_slab_file(base_file_path, slab_size, 0);
-        let mut front_slab = SlabEntry::new(file, page_size);
-
-        // This is the first slab so add the main header.
-        let main_header = MainHeader {
-            magic: MAIN_MAGIC,
-            first_section_offset: page_size as u16,
-            page_size: page_size as u16,
-        };
-        let nb_bytes = encode_into_slice(&main_header, &mut front_slab.mmap_buffer[..], standard())
-            .expect("Failed to encode main header");
-        assert!(nb_bytes < page_size);
-        front_slab.current_global_position = page_size; // align to the next page
+        let front_slab = SlabEntry::new(0, file, page_size);
 
         Self {
             front_slab,
@@ -489,9 +1064,52 @@ impl UnifiedLoggerWrite {
             base_file_path: base_file_path.to_path_buf(),
             slab_size,
             front_slab_suffix: 0,
+            compressions,
+            remote_targets,
+            max_slabs,
+            oldest_slab_suffix: 0,
+        }
+    }
+
+    /// Deletes the oldest slabs beyond the retention policy, if any.
+    fn enforce_retention(&mut self) {
+        let Some(max_slabs) = self.max_slabs else {
+            return;
+        };
+        while self.front_slab_suffix + 1 - self.oldest_slab_suffix > max_slabs {
+            if self
+                .back_slabs
+                .iter()
+                .any(|slab| slab.suffix == self.oldest_slab_suffix)
+            {
+                // still in flight, we will retry at the next section.
+                return;
+            }
+            let path = build_slab_path(&self.base_file_path, self.oldest_slab_suffix);
+            if let Err(e) = std::fs::remove_file(&path) {
+                eprintln!("Warning: could not delete old slab {}: {e}", path.display());
+            }
+            self.oldest_slab_suffix += 1;
         }
     }
 
+    /// The compression codec set in the builder for this entry type.
+    pub fn compression_for(&self, entry_type: UnifiedLogType) -> Compression {
+        self.compressions
+            .iter()
+            .find(|(t, _)| *t == entry_type)
+            .map(|(_, c)| *c)
+            .unwrap_or_default()
+    }
+
+    /// The remote target set in the builder for this entry type, if any.
+    pub fn remote_target_for(&self, entry_type: UnifiedLogType) -> Option<RemoteLogTarget> {
+        self.remote_targets
+            .iter()
+            .find(|(t, _)| *t == entry_type)
+            .map(|(_, target)| *target)
+    }
+
     pub fn flush_section(&mut self, section: &mut SectionHandle) {
         for slab in self.back_slabs.iter_mut() {
             if slab.is_it_my_section(section) {
@@ -514,6 +1132,7 @@ impl UnifiedLoggerWrite {
         requested_section_size: usize,
     ) -> SectionHandle {
         self.garbage_collect_backslabs(); // Take the opportunity to keep up and close stale back slabs.
+        self.enforce_retention();
 
         let maybe_section = self
             .front_slab
@@ -522,7 +1141,11 @@ impl UnifiedLoggerWrite {
         match maybe_section {
             AllocatedSection::NoMoreSpace => {
                 // move the front slab to the back slab.
-                let new_slab = SlabEntry::new(self.next_slab(), self.front_slab.page_size);
+                let new_slab = SlabEntry::new(
+                    self.front_slab_suffix + 1,
+                    self.next_slab(),
+                    self.front_slab.page_size,
+                );
                 // keep the slab until all its sections has been flushed.
                 self.back_slabs
                     .push(mem::replace(&mut self.front_slab, new_slab));
@@ -546,52 +1169,111 @@ impl Drop for UnifiedLoggerWrite {
         let mut section = self.add_section(UnifiedLogType::LastEntry, 80); // TODO: determine that exactly
         self.front_slab.flush_section(&mut section);
         self.garbage_collect_backslabs();
+        self.enforce_retention();
     }
 }
 
-fn open_slab_index(base_file_path: &Path, slab_index: usize) -> io::Result<(File, Mmap, u16)> {
+fn map_slab_index(base_file_path: &Path, slab_index: usize) -> io::Result<(File, Mmap)> {
     let mut options = OpenOptions::new();
     let options = options.read(true);
 
     let file_path = build_slab_path(base_file_path, slab_index);
     let file = options.open(file_path)?;
     let mmap = unsafe { Mmap::map(&file) }?;
-    let mut prolog = 0u16;
-    if slab_index == 0 {
-        let main_header: MainHeader;
-        let _read: usize;
-        (main_header, _read) =
-            decode_from_slice(&mmap[..], standard()).expect("Failed to decode main header");
-        if main_header.magic != MAIN_MAGIC {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid magic number in main header",
-            ));
-        }
-        prolog = main_header.first_section_offset;
+    Ok((file, mmap))
+}
+
+/// Opens a slab starting with a main header.
+fn open_slab_index(
+    base_file_path: &Path,
+    slab_index: usize,
+) -> io::Result<(File, Mmap, MainHeader)> {
+    let (file, mmap) = map_slab_index(base_file_path, slab_index)?;
+    let (main_header, _): (MainHeader, usize) = decode_from_slice(&mmap[..], standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if main_header.magic != MAIN_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid magic number in main header",
+        ));
+    }
+    if main_header.version > FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "This log is in version {} of the format, this reader only knows up to version {FORMAT_VERSION}",
+                main_header.version
+            ),
+        ));
     }
-    Ok((file, mmap, prolog))
+    Ok((file, mmap, main_header))
+}
+
+/// Finds the lowest slab index on disk, it is not 0 if the oldest slabs have been deleted by a retention policy.
+fn find_first_slab_index(base_file_path: &Path) -> io::Result<usize> {
+    let slab_0 = build_slab_path(base_file_path, 0);
+    if slab_0.exists() {
+        return Ok(0);
+    }
+    let slab_0_name = slab_0.file_name().unwrap().to_string_lossy();
+    // "toto_0.copper" -> "toto_" and ".copper"
+    let (prefix, suffix) = slab_0_name
+        .rsplit_once("0.")
+        .map(|(prefix, extension)| (prefix.to_string(), format!(".{extension}")))
+        .unwrap();
+    let directory = match slab_0.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(prefix.as_str()))
+                .and_then(|name| name.strip_suffix(suffix.as_str()))
+                .and_then(|index| index.parse::<usize>().ok())
+        })
+        .min()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No slab found for {}", base_file_path.display()),
+            )
+        })
 }
 
 impl UnifiedLoggerRead {
     pub fn new(base_file_path: &Path) -> io::Result<Self> {
-        let (file, mmap, prolog) = open_slab_index(base_file_path, 0)?;
+        let first_slab_index = find_first_slab_index(base_file_path)?;
+        let (file, mmap, main_header) = open_slab_index(base_file_path, first_slab_index)?;
 
         Ok(Self {
             base_file_path: base_file_path.to_path_buf(),
+            version: main_header.version,
             current_file: file,
             current_mmap_buffer: mmap,
-            current_slab_index: 0,
-            current_reading_position: prolog as usize,
+            current_slab_index: first_slab_index,
+            current_reading_position: main_header.first_section_offset as usize,
         })
     }
 
     fn next_slab(&mut self) -> io::Result<()> {
         self.current_slab_index += 1;
-        let (file, mmap, prolog) = open_slab_index(&self.base_file_path, self.current_slab_index)?;
+        if self.version < 2 {
+            // the sections start right away in the next slabs.
+            let (file, mmap) = map_slab_index(&self.base_file_path, self.current_slab_index)?;
+            self.current_file = file;
+            self.current_mmap_buffer = mmap;
+            self.current_reading_position = 0;
+            return Ok(());
+        }
+        let (file, mmap, main_header) =
+            open_slab_index(&self.base_file_path, self.current_slab_index)?;
         self.current_file = file;
         self.current_mmap_buffer = mmap;
-        self.current_reading_position = prolog as usize;
+        self.current_reading_position = main_header.first_section_offset as usize;
         Ok(())
     }
 
@@ -652,21 +1334,28 @@ impl UnifiedLoggerRead {
         if header.filled_size == 0 {
             eprintln!("Warning: read an empty section");
         }
-        let mut section = vec![0; header.filled_size as usize];
-        let start_of_data = self.current_reading_position + MAX_HEADER_SIZE;
-        section.copy_from_slice(
-            &self.current_mmap_buffer[start_of_data..start_of_data + header.filled_size as usize],
-        );
-
-        Ok(section)
+        let header_size = if self.version == 0 {
+            MAX_HEADER_SIZE_V0
+        } else {
+            MAX_HEADER_SIZE
+        };
+        let start_of_data = self.current_reading_position + header_size;
+        let data =
+            &self.current_mmap_buffer[start_of_data..start_of_data + header.filled_size as usize];
+        match header.compression {
+            Compression::None => Ok(data.to_vec()),
+            compression => compression.decompress(data, header.uncompressed_size as usize),
+        }
     }
 
     fn read_section_header(&mut self) -> CuResult<SectionHeader> {
-        let section_header: SectionHeader;
-        (section_header, _) = decode_from_slice(
-            &self.current_mmap_buffer[self.current_reading_position..],
-            standard(),
-        )
+        let bytes = &self.current_mmap_buffer[self.current_reading_position..];
+        let section_header: SectionHeader = if self.version == 0 {
+            decode_from_slice::<SectionHeaderV0, _>(bytes, standard())
+                .map(|(header, _)| header.into())
+        } else {
+            decode_from_slice(bytes, standard()).map(|(header, _)| header)
+        }
         .expect("Failed to decode section header");
         if section_header.magic != SECTION_MAGIC {
             return Err("Invalid magic number in section header".into());
@@ -675,16 +1364,28 @@ impl UnifiedLoggerRead {
     }
 }
 
-/// This a convience wrapper around the UnifiedLoggerRead to implement the Read trait.
-pub struct UnifiedLoggerIOReader {
-    logger: UnifiedLoggerRead,
+/// A source of sections, a log on disk or a live stream.
+pub trait SectionRead {
+    /// Returns the content of the next section of this type, None if there is no more.
+    fn read_next_section_type(&mut self, datalogtype: UnifiedLogType) -> CuResult<Option<Vec<u8>>>;
+}
+
+impl SectionRead for UnifiedLoggerRead {
+    fn read_next_section_type(&mut self, datalogtype: UnifiedLogType) -> CuResult<Option<Vec<u8>>> {
+        UnifiedLoggerRead::read_next_section_type(self, datalogtype)
+    }
+}
+
+/// This a convience wrapper around the UnifiedLoggerRead (or any other SectionRead) to implement the Read trait.
+pub struct UnifiedLoggerIOReader<R: SectionRead = UnifiedLoggerRead> {
+    logger: R,
     log_type: UnifiedLogType,
     buffer: Vec<u8>,
     buffer_pos: usize,
 }
 
-impl UnifiedLoggerIOReader {
-    pub fn new(logger: UnifiedLoggerRead, log_type: UnifiedLogType) -> Self {
+impl<R: SectionRead> UnifiedLoggerIOReader<R> {
+    pub fn new(logger: R, log_type: UnifiedLogType) -> Self {
         Self {
             logger,
             log_type,
@@ -702,21 +1403,19 @@ impl UnifiedLoggerIOReader {
                 Ok(true)
             }
             Ok(None) => Ok(false), // No more sections of this type
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+            Err(e) => Err(io::Error::other(e.to_string())),
         }
     }
 }
 
-impl Read for UnifiedLoggerIOReader {
+impl<R: SectionRead> Read for UnifiedLoggerIOReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.buffer_pos >= self.buffer.len() && !self.fill_buffer()? {
-            // This means we hit the last section.
-            return Ok(0);
-        }
-
-        // If we still have no data after trying to fill the buffer, we're at EOF
-        if self.buffer_pos >= self.buffer.len() {
-            return Ok(0);
+        // Skips the empty sections, for example the ones allocated ahead of time but never used.
+        while self.buffer_pos >= self.buffer.len() {
+            if !self.fill_buffer()? {
+                // This means we hit the last section.
+                return Ok(0);
+            }
         }
 
         // Copy as much as we can from the buffer to `buf`
@@ -945,6 +1644,148 @@ mod tests {
         assert_eq!(v3, 3);
     }
 
+    #[test]
+    fn test_preallocated_streams_from_threads() {
+        let tmp_dir = TempDir::new().expect("could not create a tmp dir");
+        let (logger, f) = make_a_logger(&tmp_dir, 10 * LARGE_SLAB);
+        std::thread::scope(|s| {
+            for t in 0..2u32 {
+                let logger = logger.clone();
+                s.spawn(move || {
+                    let mut stream =
+                        stream_write_preallocated(logger, UnifiedLogType::StructuredLogLine, 1024);
+                    // large enough so we are sure to roll over a few sections
+                    for i in 0..5000u32 {
+                        stream.log(&(t, i)).unwrap();
+                    }
+                });
+            }
+        });
+        drop(logger);
+
+        let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+            .file_base_name(&f)
+            .build()
+            .expect("Failed to build logger")
+        else {
+            panic!("Failed to build logger");
+        };
+        let mut reader = UnifiedLoggerIOReader::new(dl, UnifiedLogType::StructuredLogLine);
+        let mut next = [0u32; 2];
+        for _ in 0..10000 {
+            let (t, i): (u32, u32) =
+                bincode::decode_from_std_read(&mut reader, standard()).expect("Failed to decode");
+            assert_eq!(i, next[t as usize]);
+            next[t as usize] += 1;
+        }
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_read_version_0_log() {
+        // A log written before the format was versioned, without compression in the section headers.
+        let tmp_dir = TempDir::new().expect("could not create a tmp dir");
+        let f = tmp_dir.path().join("test.bin");
+        let page_size = page_size::get();
+        let mut content = vec![0u8; 3 * page_size];
+        let main_header = (MAIN_MAGIC, page_size as u16, page_size as u16);
+        encode_into_slice(main_header, &mut content[..], standard()).unwrap();
+        let mut data = [0u8; 16];
+        let mut filled_size = 0;
+        for v in [1u32, 2, 3] {
+            filled_size += encode_into_slice(v, &mut data[filled_size..], standard()).unwrap();
+        }
+        let section_header = SectionHeaderV0 {
+            magic: SECTION_MAGIC,
+            entry_type: UnifiedLogType::StructuredLogLine,
+            section_size: page_size as u32,
+            filled_size: filled_size as u32,
+        };
+        encode_into_slice(section_header, &mut content[page_size..], standard()).unwrap();
+        let start_of_data = page_size + MAX_HEADER_SIZE_V0;
+        content[start_of_data..start_of_data + filled_size].copy_from_slice(&data[..filled_size]);
+        let last_entry = SectionHeaderV0 {
+            magic: SECTION_MAGIC,
+            entry_type: UnifiedLogType::LastEntry,
+            section_size: page_size as u32,
+            filled_size: 0,
+        };
+        encode_into_slice(last_entry, &mut content[2 * page_size..], standard()).unwrap();
+        std::fs::write(build_slab_path(&f, 0), content).unwrap();
+
+        let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+            .file_base_name(&f)
+            .build()
+            .expect("Failed to build logger")
+        else {
+            panic!("Failed to build logger");
+        };
+        let mut reader = UnifiedLoggerIOReader::new(dl, UnifiedLogType::StructuredLogLine);
+        for expected in [1u32, 2, 3] {
+            let v: u32 =
+                bincode::decode_from_std_read(&mut reader, standard()).expect("Failed to decode");
+            assert_eq!(v, expected);
+        }
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_read_version_1_multi_slab_log() {
+        // Before version 2, only the first slab started with the main header.
+        let tmp_dir = TempDir::new().expect("could not create a tmp dir");
+        let f = tmp_dir.path().join("test.bin");
+        let page_size = page_size::get();
+        let section = |entry_type, value: u32, buffer: &mut [u8]| {
+            let start_of_data = MAX_HEADER_SIZE;
+            let filled_size =
+                encode_into_slice(value, &mut buffer[start_of_data..], standard()).unwrap();
+            let header = SectionHeader {
+                magic: SECTION_MAGIC,
+                entry_type,
+                section_size: page_size as u32,
+                filled_size: filled_size as u32,
+                compression: Compression::None,
+                uncompressed_size: filled_size as u32,
+            };
+            encode_into_slice(header, buffer, standard()).unwrap();
+        };
+        let mut slab_0 = vec![0u8; 2 * page_size];
+        let main_header = MainHeader {
+            magic: MAIN_MAGIC,
+            first_section_offset: page_size as u16,
+            page_size: page_size as u16,
+            version: 1,
+        };
+        encode_into_slice(main_header, &mut slab_0[..], standard()).unwrap();
+        section(UnifiedLogType::CopperList, 1, &mut slab_0[page_size..]);
+        std::fs::write(build_slab_path(&f, 0), slab_0).unwrap();
+        let mut slab_1 = vec![0u8; 2 * page_size];
+        section(UnifiedLogType::CopperList, 2, &mut slab_1[..]);
+        section(UnifiedLogType::LastEntry, 0, &mut slab_1[page_size..]);
+        std::fs::write(build_slab_path(&f, 1), slab_1).unwrap();
+
+        let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+            .file_base_name(&f)
+            .build()
+            .expect("Failed to build logger")
+        else {
+            panic!("Failed to build logger");
+        };
+        let mut reader = UnifiedLoggerIOReader::new(dl, UnifiedLogType::CopperList);
+        for expected in [1u32, 2] {
+            let v: u32 =
+                bincode::decode_from_std_read(&mut reader, standard()).expect("Failed to decode");
+            assert_eq!(v, expected);
+        }
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+
     /// Mimic a basic CopperList implementation.
 
     #[derive(Debug, Encode, Decode)]
@@ -1048,4 +1889,123 @@ mod tests {
         }
         assert_eq!(total_readback, 10000);
     }
+
+    #[test]
+    fn test_retention_end2end() {
+        let tmp_dir = TempDir::new().expect("could not create a tmp dir");
+        let file_path = tmp_dir.path().join("test.bin");
+        let UnifiedLogger::Write(logger) = UnifiedLoggerBuilder::new()
+            .write(true)
+            .create(true)
+            .file_base_name(&file_path)
+            .preallocated_size(SMALL_SLAB)
+            .max_total_size(3 * SMALL_SLAB + 1)
+            .build()
+            .expect("Failed to create logger")
+        else {
+            panic!("Failed to create logger")
+        };
+        let logger = Arc::new(Mutex::new(logger));
+        {
+            let mut stream = stream_write(logger.clone(), UnifiedLogType::CopperList, 1024);
+            for i in 0..10000u32 {
+                let cl = CopperList {
+                    state: CopperListStateMock::Free,
+                    payload: (i, 2u32, 3u32),
+                };
+                stream.log(&cl).unwrap();
+            }
+        }
+        drop(logger);
+
+        let slabs_on_disk = std::fs::read_dir(tmp_dir.path()).unwrap().count();
+        assert_eq!(slabs_on_disk, 3);
+        assert!(!build_slab_path(&file_path, 0).exists());
+
+        // We should read back the tail of the log, without any gap.
+        let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+            .file_base_name(&file_path)
+            .build()
+            .expect("Failed to build logger")
+        else {
+            panic!("Failed to build logger");
+        };
+        let mut reader = UnifiedLoggerIOReader::new(dl, UnifiedLogType::CopperList);
+        let mut first = None;
+        let mut expected = None;
+        while let Ok(cl) = bincode::decode_from_std_read::<CopperList<(u32, u32, u32)>, _, _>(
+            &mut reader,
+            standard(),
+        ) {
+            first.get_or_insert(cl.payload.0);
+            let expected = expected.get_or_insert(cl.payload.0);
+            assert_eq!(cl.payload.0, *expected);
+            *expected += 1;
+        }
+        assert!(first.unwrap() > 0); // the beginning has been deleted
+        assert_eq!(expected, Some(10000));
+    }
+
+    fn compressed_end2end(compression: Compression) {
+        let tmp_dir = TempDir::new().expect("could not create a tmp dir");
+        let file_path = tmp_dir.path().join("test.bin");
+        let UnifiedLogger::Write(logger) = UnifiedLoggerBuilder::new()
+            .write(true)
+            .create(true)
+            .file_base_name(&file_path)
+            .preallocated_size(LARGE_SLAB)
+            .compression(UnifiedLogType::CopperList, compression)
+            .build()
+            .expect("Failed to create logger")
+        else {
+            panic!("Failed to create logger")
+        };
+        let logger = Arc::new(Mutex::new(logger));
+        {
+            let mut stream = stream_write(logger.clone(), UnifiedLogType::CopperList, 16 * 1024);
+            // large enough so we are sure to create a few sections
+            for i in 0..10000u32 {
+                let cl = CopperList {
+                    state: CopperListStateMock::BeingSerialized,
+                    payload: (i, 2u32, 3u32),
+                };
+                stream.log(&cl).unwrap();
+            }
+        }
+        drop(logger);
+
+        let UnifiedLogger::Read(dl) = UnifiedLoggerBuilder::new()
+            .file_base_name(&file_path)
+            .build()
+            .expect("Failed to build logger")
+        else {
+            panic!("Failed to build logger");
+        };
+        let mut reader = UnifiedLoggerIOReader::new(dl, UnifiedLogType::CopperList);
+        for i in 0..10000u32 {
+            let cl: CopperList<(u32, u32, u32)> =
+                bincode::decode_from_std_read(&mut reader, standard()).expect("Failed to decode");
+            assert_eq!(cl.payload.0, i);
+        }
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+
+        // ~50KB of repetitive data, it should compress well.
+        let written = std::fs::metadata(build_slab_path(&file_path, 0))
+            .unwrap()
+            .len();
+        assert!(written < 10000 * 5);
+    }
+
+    #[test]
+    fn test_lz4_compressed_end2end() {
+        compressed_end2end(Compression::Lz4);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_compressed_end2end() {
+        compressed_end2end(Compression::Zstd);
+    }
 }
@@ -0,0 +1,449 @@
+//! Live streaming of the unified log to a remote consumer, for example to watch the copperlists from a laptop.
+//! The blocks of entries are framed like the sections on disk: a section header padded to MAX_HEADER_SIZE
+//! followed by the content.
+
+use crate::{Compression, SectionHeader, SectionRead, MAX_HEADER_SIZE, SECTION_MAGIC};
+use bincode::config::standard;
+use bincode::{decode_from_slice, encode_into_slice};
+use cu29_traits::{CuError, CuResult, UnifiedLogType};
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Maximum payload of a UDP datagram.
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// The sender does not try to reconnect more often than that, not to slow down the logging.
+const RECONNECT_PERIOD: Duration = Duration::from_secs(1);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A consumer not reading for that long is dropped, so it cannot stall the logger (nor its shutdown).
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Pause of the listener after a receive error, not to spin if it keeps failing.
+const RECV_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Where the robot sends a live log stream, or where the consumer listens for it.
+/// The text form is "tcp://host:port" or "udp://host:port".
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RemoteLogTarget {
+    /// Reliable while connected, the sender reconnects if the consumer goes away.
+    Tcp(SocketAddr),
+    /// Fire and forget, each block of entries is sent as one datagram.
+    Udp(SocketAddr),
+}
+
+impl RemoteLogTarget {
+    /// The size of the blocks of entries sent at once to this target.
+    pub(crate) fn max_block_size(&self, requested: usize) -> usize {
+        match self {
+            RemoteLogTarget::Tcp(_) => requested,
+            RemoteLogTarget::Udp(_) => requested.min(MAX_DATAGRAM_SIZE - MAX_HEADER_SIZE),
+        }
+    }
+}
+
+impl Display for RemoteLogTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteLogTarget::Tcp(addr) => write!(f, "tcp://{addr}"),
+            RemoteLogTarget::Udp(addr) => write!(f, "udp://{addr}"),
+        }
+    }
+}
+
+impl FromStr for RemoteLogTarget {
+    type Err = CuError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (protocol, addr) = s.split_once("://").ok_or_else(|| {
+            CuError::from(format!(
+                "Invalid remote log target {s}, expected tcp://host:port or udp://host:port"
+            ))
+        })?;
+        let addr = addr
+            .to_socket_addrs()
+            .map_err(|e| CuError::new_with_cause(&format!("Could not resolve {addr}"), e))?
+            .next()
+            .ok_or_else(|| CuError::from(format!("No address found for {addr}")))?;
+        match protocol {
+            "tcp" => Ok(RemoteLogTarget::Tcp(addr)),
+            "udp" => Ok(RemoteLogTarget::Udp(addr)),
+            _ => Err(format!("Unknown protocol {protocol}, expected tcp or udp").into()),
+        }
+    }
+}
+
+/// Frames a block of entries like a section on disk.
+fn encode_frame(entry_type: UnifiedLogType, block: &[u8]) -> Vec<u8> {
+    let header = SectionHeader {
+        magic: SECTION_MAGIC,
+        entry_type,
+        section_size: (MAX_HEADER_SIZE + block.len()) as u32,
+        filled_size: block.len() as u32,
+        compression: Compression::None,
+        uncompressed_size: block.len() as u32,
+    };
+    let mut frame = vec![0u8; MAX_HEADER_SIZE + block.len()];
+    encode_into_slice(&header, &mut frame[..MAX_HEADER_SIZE], standard())
+        .expect("Failed to encode section header");
+    frame[MAX_HEADER_SIZE..].copy_from_slice(block);
+    frame
+}
+
+fn decode_frame_header(bytes: &[u8]) -> io::Result<SectionHeader> {
+    let (header, _): (SectionHeader, usize) = decode_from_slice(bytes, standard())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if header.magic != SECTION_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid magic number in section header",
+        ));
+    }
+    Ok(header)
+}
+
+fn decode_frame_content(header: &SectionHeader, content: &[u8]) -> io::Result<Vec<u8>> {
+    header
+        .compression
+        .decompress(content, header.uncompressed_size as usize)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Reads one frame from a byte stream, None if the stream has been closed cleanly.
+fn read_frame(src: &mut impl Read) -> io::Result<Option<(UnifiedLogType, Vec<u8>)>> {
+    let mut header_bytes = [0u8; MAX_HEADER_SIZE];
+    match src.read_exact(&mut header_bytes) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let header = decode_frame_header(&header_bytes)?;
+    let mut content = vec![0u8; header.filled_size as usize];
+    src.read_exact(&mut content)?;
+    Ok(Some((
+        header.entry_type,
+        decode_frame_content(&header, &content)?,
+    )))
+}
+
+/// The sending side, it lives in the background thread of a remote stream.
+pub(crate) struct RemoteSender {
+    target: RemoteLogTarget,
+    tcp: Option<TcpStream>,
+    udp: Option<UdpSocket>,
+    last_attempt: Option<Instant>,
+}
+
+impl RemoteSender {
+    pub(crate) fn new(target: RemoteLogTarget) -> Self {
+        Self {
+            target,
+            tcp: None,
+            udp: None,
+            last_attempt: None,
+        }
+    }
+
+    /// Best effort, the block is lost if the consumer is not there.
+    pub(crate) fn send(&mut self, entry_type: UnifiedLogType, block: &[u8]) {
+        if self.tcp.is_none()
+            && self.udp.is_none()
+            && self
+                .last_attempt
+                .is_some_and(|t| t.elapsed() < RECONNECT_PERIOD)
+        {
+            return;
+        }
+        let frame = encode_frame(entry_type, block);
+        match self.target {
+            RemoteLogTarget::Tcp(addr) => {
+                if self.tcp.is_none() {
+                    self.last_attempt = Some(Instant::now());
+                    self.tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+                        .and_then(|stream| {
+                            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                            Ok(stream)
+                        })
+                        .ok();
+                }
+                if let Some(stream) = &mut self.tcp {
+                    // including a timeout (WouldBlock or TimedOut): the consumer is stalled,
+                    // and what it got of this frame is unusable anyway.
+                    if stream.write_all(&frame).is_err() {
+                        self.tcp = None;
+                    }
+                }
+            }
+            RemoteLogTarget::Udp(addr) => {
+                if self.udp.is_none() {
+                    self.last_attempt = Some(Instant::now());
+                    let local: SocketAddr = if addr.is_ipv4() {
+                        ([0, 0, 0, 0], 0).into()
+                    } else {
+                        ([0u16; 8], 0).into()
+                    };
+                    self.udp = UdpSocket::bind(local).ok();
+                }
+                if let Some(socket) = &self.udp {
+                    let _ = socket.send_to(&frame, addr);
+                }
+            }
+        }
+    }
+}
+
+/// The consumer side of a live log stream, it can be used as a source for UnifiedLoggerIOReader
+/// exactly like a log on disk.
+/// For TCP, several robots streams (ie. the copperlists and the structured logs) can connect at the same time.
+pub struct RemoteLogReader {
+    sections: Receiver<(UnifiedLogType, Vec<u8>)>,
+    local_addr: SocketAddr,
+    timeout: Option<Duration>,
+}
+
+impl RemoteLogReader {
+    /// Starts listening in the background.
+    /// Use port 0 to let the OS pick one, see local_addr.
+    pub fn listen(target: RemoteLogTarget) -> CuResult<Self> {
+        let (tx, sections) = channel();
+        let local_addr = match target {
+            RemoteLogTarget::Tcp(addr) => {
+                let listener = TcpListener::bind(addr).map_err(|e| {
+                    CuError::new_with_cause(&format!("Could not listen on {target}"), e)
+                })?;
+                let local_addr = listener.local_addr().map_err(|e| {
+                    CuError::new_with_cause("Could not get the listening address", e)
+                })?;
+                thread::Builder::new()
+                    .name("cu29_remote_log_listener".to_string())
+                    .spawn(move || accept_loop(listener, tx))
+                    .map_err(|e| CuError::new_with_cause("Could not spawn the listener", e))?;
+                local_addr
+            }
+            RemoteLogTarget::Udp(addr) => {
+                let socket = UdpSocket::bind(addr).map_err(|e| {
+                    CuError::new_with_cause(&format!("Could not listen on {target}"), e)
+                })?;
+                let local_addr = socket.local_addr().map_err(|e| {
+                    CuError::new_with_cause("Could not get the listening address", e)
+                })?;
+                thread::Builder::new()
+                    .name("cu29_remote_log_listener".to_string())
+                    .spawn(move || datagram_loop(socket, tx))
+                    .map_err(|e| CuError::new_with_cause("Could not spawn the listener", e))?;
+                local_addr
+            }
+        };
+        Ok(Self {
+            sections,
+            local_addr,
+            timeout: None,
+        })
+    }
+
+    /// The address actually listened on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Consider the stream over after this duration without any section, by default it waits forever.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl SectionRead for RemoteLogReader {
+    /// Blocks until a section of this type arrives, the sections of other types are discarded.
+    fn read_next_section_type(&mut self, datalogtype: UnifiedLogType) -> CuResult<Option<Vec<u8>>> {
+        loop {
+            let (entry_type, section) = match self.timeout {
+                Some(timeout) => match self.sections.recv_timeout(timeout) {
+                    Ok(section) => section,
+                    Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                        return Ok(None)
+                    }
+                },
+                None => match self.sections.recv() {
+                    Ok(section) => section,
+                    Err(_) => return Ok(None),
+                },
+            };
+            if entry_type == datalogtype {
+                return Ok(Some(section));
+            }
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, tx: Sender<(UnifiedLogType, Vec<u8>)>) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let tx = tx.clone();
+        thread::spawn(move || loop {
+            match read_frame(&mut stream) {
+                Ok(Some(section)) => {
+                    if tx.send(section).is_err() {
+                        return; // nobody is listening anymore.
+                    }
+                }
+                Ok(None) => return,
+                Err(e) => {
+                    eprintln!("Warning: dropping a remote log connection: {e}");
+                    return;
+                }
+            }
+        });
+    }
+}
+
+fn datagram_loop(socket: UdpSocket, tx: Sender<(UnifiedLogType, Vec<u8>)>) {
+    let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let size = match socket.recv(&mut buffer) {
+            Ok(size) => size,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // an ICMP error from a previous datagram on some platforms, or a timeout.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                ) =>
+            {
+                thread::sleep(RECV_ERROR_BACKOFF);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Error: stopping the remote log listener: {e}");
+                return;
+            }
+        };
+        if size < MAX_HEADER_SIZE {
+            continue;
+        }
+        let section = decode_frame_header(&buffer[..size]).and_then(|header| {
+            let end = MAX_HEADER_SIZE + header.filled_size as usize;
+            if end > size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Truncated datagram",
+                ));
+            }
+            Ok((
+                header.entry_type,
+                decode_frame_content(&header, &buffer[MAX_HEADER_SIZE..end])?,
+            ))
+        });
+        match section {
+            Ok(section) => {
+                if tx.send(section).is_err() {
+                    return; // nobody is listening anymore.
+                }
+            }
+            Err(e) => eprintln!("Warning: dropping a remote log datagram: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{stream_write, UnifiedLogger, UnifiedLoggerBuilder, UnifiedLoggerIOReader};
+    use bincode::decode_from_std_read;
+    use cu29_traits::WriteStream;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    fn loopback(target: RemoteLogTarget) {
+        let reader = RemoteLogReader::listen(target)
+            .expect("Failed to listen")
+            .with_timeout(Duration::from_secs(2));
+        let target = match target {
+            RemoteLogTarget::Tcp(_) => RemoteLogTarget::Tcp(reader.local_addr()),
+            RemoteLogTarget::Udp(_) => RemoteLogTarget::Udp(reader.local_addr()),
+        };
+
+        let tmp_dir = TempDir::new().expect("could not create a tmp dir");
+        let UnifiedLogger::Write(logger) = UnifiedLoggerBuilder::new()
+            .write(true)
+            .create(true)
+            .file_base_name(&tmp_dir.path().join("test.bin"))
+            .preallocated_size(100 * 1024)
+            .remote_target(UnifiedLogType::CopperList, target)
+            .build()
+            .expect("Failed to create logger")
+        else {
+            panic!("Failed to create logger")
+        };
+        let logger = Arc::new(Mutex::new(logger));
+        {
+            let mut stream = stream_write(logger.clone(), UnifiedLogType::CopperList, 4096);
+            for i in 0..1000u32 {
+                stream.log(&i).unwrap();
+            }
+        }
+        drop(logger);
+
+        let mut src = UnifiedLoggerIOReader::new(reader, UnifiedLogType::CopperList);
+        for i in 0..1000u32 {
+            let v: u32 = decode_from_std_read(&mut src, standard()).expect("Failed to decode");
+            assert_eq!(v, i);
+        }
+    }
+
+    #[test]
+    fn test_tcp_loopback() {
+        loopback(RemoteLogTarget::Tcp("127.0.0.1:0".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_udp_loopback() {
+        loopback(RemoteLogTarget::Udp("127.0.0.1:0".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_stalled_tcp_consumer_is_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = RemoteLogTarget::Tcp(listener.local_addr().unwrap());
+        // accepts the connection but never reads from it.
+        let consumer = thread::spawn(move || listener.accept().unwrap());
+
+        let mut sender = RemoteSender::new(target);
+        let block = vec![0u8; 1024 * 1024];
+        let start = Instant::now();
+        sender.send(UnifiedLogType::CopperList, &block[..16]);
+        assert!(sender.tcp.is_some());
+        // more than what the socket buffers can hold.
+        for _ in 0..100 {
+            sender.send(UnifiedLogType::CopperList, &block);
+            if sender.tcp.is_none() {
+                break;
+            }
+        }
+        assert!(sender.tcp.is_none());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        drop(consumer.join().unwrap());
+    }
+
+    #[test]
+    fn test_target_parsing() {
+        let target: RemoteLogTarget = "udp://127.0.0.1:7878".parse().unwrap();
+        assert_eq!(
+            target,
+            RemoteLogTarget::Udp("127.0.0.1:7878".parse().unwrap())
+        );
+        assert_eq!(target.to_string(), "udp://127.0.0.1:7878");
+        assert!("http://127.0.0.1:7878".parse::<RemoteLogTarget>().is_err());
+        assert!("127.0.0.1:7878".parse::<RemoteLogTarget>().is_err());
+    }
+}
@@ -25,4 +25,11 @@ for log_entry in log_iterator:
     except IndexError as e:
         formatted_message = f"Error formatting message: {e}"
 
-    print(f"{log_entry.ts()}: {formatted_message}")
+    ids = []
+    if log_entry.task_id() is not None:
+        ids.append(f"task {log_entry.task_id()}")
+    if log_entry.culist_id() is not None:
+        ids.append(f"CL {log_entry.culist_id()}")
+    context = f"[{' '.join(ids)}] " if ids else ""
+
+    print(f"{log_entry.ts()}: [{log_entry.level()}] {context}{formatted_message}")
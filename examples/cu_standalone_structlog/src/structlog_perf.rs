@@ -1,26 +1,109 @@
 use cu29::prelude::*;
-use cu29_clock::{CuTime, RobotClock};
-use std::path::PathBuf;
+use cu29_clock::{CuDuration, RobotClock};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 const LOG_FILE: &str = "./logfile.bin";
+const UNIFIED_LOG_FILE: &str = "./logfile.copper";
+const NB_LOG_LINES: usize = 1_000_000;
+
+/// Logs NB_LOG_LINES split evenly on nb_threads threads and returns how long it took.
+fn log_lines(clock: &RobotClock, nb_threads: usize) -> CuDuration {
+    let bf = clock.now();
+    std::thread::scope(|s| {
+        for t in 0..nb_threads {
+            s.spawn(move || {
+                for i in (t..NB_LOG_LINES).step_by(nb_threads) {
+                    debug!(
+                        "This is the logline {} associated with the log Logging and some more = {}, {}",
+                        i,
+                        i + 2,
+                        i + 3
+                    );
+                }
+            });
+        }
+    });
+    clock.now() - bf
+}
 
 fn main() {
+    // usage: structlog_perf [nb_threads]
+    let nb_threads: usize = std::env::args()
+        .nth(1)
+        .map(|arg| {
+            arg.parse()
+                .expect("The number of threads should be an integer")
+        })
+        .unwrap_or(4);
     let clock = RobotClock::new();
+
+    // Baseline: one thread logging in one stream shared behind a mutex.
     let bf = {
         let writer = SimpleFileWriter::new(&PathBuf::from(LOG_FILE)).unwrap();
         let _log_runtime = LoggerRuntime::init(clock.clone(), writer, None::<NullLog>);
-        let bf: CuTime = clock.now();
-        for i in 0..1_000_000 {
-            debug!(
-                "This is the logline {} associated with the log Logging and some more = {}, {}",
-                i,
-                i + 2,
-                i + 3
-            );
-        }
+        let bf = clock.now();
+        log_lines(&clock, 1);
         bf
     };
     // This will force the flush here for fairness.
     let af = clock.now();
     println!("Total time: {} in {}", af - bf, LOG_FILE);
+
+    // Several threads contending on the same shared stream.
+    let shared = {
+        let writer = SimpleFileWriter::new(&PathBuf::from(LOG_FILE)).unwrap();
+        let _log_runtime = LoggerRuntime::init(clock.clone(), writer, None::<NullLog>);
+        log_lines(&clock, nb_threads)
+    };
+    println!("{nb_threads} threads, shared stream: {shared} in {LOG_FILE}");
+
+    // Several threads logging in their own stream, without any lock.
+    let per_thread = {
+        let next_file = AtomicUsize::new(0);
+        let _log_runtime = LoggerRuntime::init_per_thread(
+            clock.clone(),
+            move || {
+                let path = format!(
+                    "./logfile_{}.bin",
+                    next_file.fetch_add(1, Ordering::Relaxed)
+                );
+                Ok(Box::new(SimpleFileWriter::new(&PathBuf::from(path))?) as LogWriter)
+            },
+            None::<NullLog>,
+        );
+        log_lines(&clock, nb_threads)
+    };
+    println!("{nb_threads} threads, per thread streams: {per_thread} in ./logfile_*.bin");
+
+    // Several threads logging in their own sections of a unified log, allocated ahead of time.
+    let unified = {
+        let UnifiedLogger::Write(logger) = UnifiedLoggerBuilder::new()
+            .write(true)
+            .create(true)
+            .file_base_name(Path::new(UNIFIED_LOG_FILE))
+            .preallocated_size(128 * 1024 * 1024)
+            .build()
+            .expect("Failed to create the unified log")
+        else {
+            panic!("Failed to create the unified log")
+        };
+        let logger = Arc::new(Mutex::new(logger));
+        let _log_runtime = LoggerRuntime::init_per_thread(
+            clock.clone(),
+            move || {
+                Ok(Box::new(stream_write_preallocated(
+                    logger.clone(),
+                    UnifiedLogType::StructuredLogLine,
+                    4096 * 10,
+                )) as LogWriter)
+            },
+            None::<NullLog>,
+        );
+        log_lines(&clock, nb_threads)
+    };
+    println!(
+        "{nb_threads} threads, per thread unified log sections: {unified} in {UNIFIED_LOG_FILE}"
+    );
 }